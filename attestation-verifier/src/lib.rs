@@ -0,0 +1,199 @@
+//! Verification of the attestation reports that enclave programs read from
+//! `/dev/attestation`.
+//!
+//! Reports are in the format of the platform that made them:
+//!
+//! - Keystone: the security monitor's `struct report`, whose signatures chain
+//!   up to the device's key, see [`verify_keystone`];
+//! - SGX: a DCAP quote of the enclave's report, whose signatures chain up to
+//!   Intel's root key, see [`verify_sgx`];
+//! - platforms without a TEE: a simulated report authenticated under a public
+//!   key, see [`verify_simulated`].
+//!
+//! A verifier must still compare the [`Evidence`] with the measurement it
+//! expects and with the data it asked the enclave to bind, such as a nonce.
+
+use core::fmt;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+
+#[cfg(test)]
+mod test;
+
+/// The size of the data bound to a report.
+pub const REPORT_DATA_SIZE: usize = 64;
+
+/// The magic number at the start of a simulated report.
+pub const SIMULATED_MAGIC: [u8; 8] = *b"SIMREPT\0";
+
+/// The public key that simulated reports are authenticated with.
+pub const SIMULATED_KEY: [u8; 32] = *b"simulated platform, not a secret";
+
+const SIMULATED_REPORT_SIZE: usize = 8 + 32 + REPORT_DATA_SIZE + 32;
+
+/// Sizes in the security monitor's `struct report`.
+const KEYSTONE_HASH_SIZE: usize = 64;
+const KEYSTONE_DATA_MAX: usize = 1024;
+const KEYSTONE_KEY_SIZE: usize = 32;
+const KEYSTONE_SIGNATURE_SIZE: usize = 64;
+const KEYSTONE_ENCLAVE_REPORT_SIZE: usize =
+    KEYSTONE_HASH_SIZE + 8 + KEYSTONE_DATA_MAX + KEYSTONE_SIGNATURE_SIZE;
+const KEYSTONE_SM_REPORT_SIZE: usize =
+    KEYSTONE_HASH_SIZE + KEYSTONE_KEY_SIZE + KEYSTONE_SIGNATURE_SIZE;
+pub const KEYSTONE_REPORT_SIZE: usize =
+    KEYSTONE_ENCLAVE_REPORT_SIZE + KEYSTONE_SM_REPORT_SIZE + KEYSTONE_KEY_SIZE;
+
+/// The version of the DCAP quotes, and their attestation key type
+/// (ECDSA-256-with-P-256).
+const SGX_QUOTE_VERSION: u16 = 3;
+const SGX_QUOTE_KEY_TYPE: u16 = 2;
+/// Offsets in `sgx_quote3_t`: the report body follows the header, and the
+/// signature data follows its length.
+const SGX_QUOTE_BODY: usize = 48;
+const SGX_QUOTE_SIGNATURE_LEN: usize = SGX_QUOTE_BODY + 384;
+pub const SGX_QUOTE_MIN_SIZE: usize = SGX_QUOTE_SIGNATURE_LEN + 4;
+/// Offsets in `sgx_report_body_t`.
+const SGX_MR_ENCLAVE: usize = 64;
+const SGX_MR_SIGNER: usize = 128;
+const SGX_REPORT_DATA: usize = 320;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The report does not have the size or layout of its format.
+    Malformed,
+    /// A signature or MAC does not match.
+    BadSignature,
+    /// The report is signed by another device than the trusted one.
+    UntrustedDevice,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Malformed => write!(f, "malformed report"),
+            Error::BadSignature => write!(f, "bad signature"),
+            Error::UntrustedDevice => write!(f, "report signed by an untrusted device"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// What a report says about the enclave.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evidence {
+    /// The measurement of the enclave: its SHA3-512 hash on Keystone, its
+    /// `MRENCLAVE` on SGX, and zeros for simulated reports.
+    pub measurement: Vec<u8>,
+    /// The measurement of what vouches for the enclave: the security
+    /// monitor's hash on Keystone, and the `MRSIGNER` on SGX.
+    pub platform_measurement: Option<Vec<u8>>,
+    /// The data that the enclave bound to the report, padded with zeros.
+    pub report_data: [u8; REPORT_DATA_SIZE],
+}
+
+impl Evidence {
+    /// Whether the report binds `data`, as the enclave pads it.
+    pub fn binds(&self, data: &[u8]) -> bool {
+        data.len() <= REPORT_DATA_SIZE
+            && self.report_data[..data.len()] == *data
+            && self.report_data[data.len()..].iter().all(|&byte| byte == 0)
+    }
+}
+
+fn verify_ed25519(key: &[u8], message: &[u8], signature: &[u8]) -> Result<()> {
+    let key = VerifyingKey::from_bytes(key.try_into().unwrap()).map_err(|_| Error::Malformed)?;
+    let signature = Signature::from_slice(signature).map_err(|_| Error::Malformed)?;
+    key.verify_strict(message, &signature)
+        .map_err(|_| Error::BadSignature)
+}
+
+/// Verify a report of the Keystone security monitor on the device whose
+/// public key is `device_key`.
+///
+/// The device signs the monitor's report, which holds the key that the
+/// monitor signs the enclave's report with.
+pub fn verify_keystone(report: &[u8], device_key: &[u8; KEYSTONE_KEY_SIZE]) -> Result<Evidence> {
+    if report.len() != KEYSTONE_REPORT_SIZE {
+        return Err(Error::Malformed);
+    }
+    let (enclave, rest) = report.split_at(KEYSTONE_ENCLAVE_REPORT_SIZE);
+    let (sm, report_device_key) = rest.split_at(KEYSTONE_SM_REPORT_SIZE);
+    if report_device_key != device_key {
+        return Err(Error::UntrustedDevice);
+    }
+
+    let (sm_signed, sm_signature) = sm.split_at(KEYSTONE_HASH_SIZE + KEYSTONE_KEY_SIZE);
+    verify_ed25519(device_key, sm_signed, sm_signature)?;
+    let (sm_hash, sm_key) = sm_signed.split_at(KEYSTONE_HASH_SIZE);
+
+    // Only the part of the data that is used is signed
+    let data_len = u64::from_le_bytes(enclave[KEYSTONE_HASH_SIZE..][..8].try_into().unwrap());
+    if data_len != REPORT_DATA_SIZE as u64 {
+        return Err(Error::Malformed);
+    }
+    let signed_len = KEYSTONE_HASH_SIZE + 8 + REPORT_DATA_SIZE;
+    let signature = &enclave[KEYSTONE_ENCLAVE_REPORT_SIZE - KEYSTONE_SIGNATURE_SIZE..];
+    verify_ed25519(sm_key, &enclave[..signed_len], signature)?;
+
+    Ok(Evidence {
+        measurement: enclave[..KEYSTONE_HASH_SIZE].to_vec(),
+        platform_measurement: Some(sm_hash.to_vec()),
+        report_data: enclave[KEYSTONE_HASH_SIZE + 8..signed_len]
+            .try_into()
+            .unwrap(),
+    })
+}
+
+/// Verify a DCAP quote of an SGX report.
+///
+/// The signatures of a quote chain up to Intel's root key through the
+/// platform's PCK certificate, and whether the platform is up to date is only
+/// known from Intel's collateral. `verify_quote` must check all of this, e.g.
+/// with Intel's Quote Verification Library (`sgx_qv_verify_quote`), before the
+/// body of the quote is read: nothing in it is authenticated otherwise.
+pub fn verify_sgx(quote: &[u8], verify_quote: impl FnOnce(&[u8]) -> bool) -> Result<Evidence> {
+    if quote.len() < SGX_QUOTE_MIN_SIZE {
+        return Err(Error::Malformed);
+    }
+    let u16_at = |offset: usize| u16::from_le_bytes(quote[offset..][..2].try_into().unwrap());
+    let signature_len =
+        u32::from_le_bytes(quote[SGX_QUOTE_SIGNATURE_LEN..][..4].try_into().unwrap());
+    if u16_at(0) != SGX_QUOTE_VERSION
+        || u16_at(2) != SGX_QUOTE_KEY_TYPE
+        || quote.len() - SGX_QUOTE_MIN_SIZE != signature_len as usize
+    {
+        return Err(Error::Malformed);
+    }
+    if !verify_quote(quote) {
+        return Err(Error::BadSignature);
+    }
+
+    let body = &quote[SGX_QUOTE_BODY..SGX_QUOTE_SIGNATURE_LEN];
+    Ok(Evidence {
+        measurement: body[SGX_MR_ENCLAVE..][..32].to_vec(),
+        platform_measurement: Some(body[SGX_MR_SIGNER..][..32].to_vec()),
+        report_data: body[SGX_REPORT_DATA..][..REPORT_DATA_SIZE]
+            .try_into()
+            .unwrap(),
+    })
+}
+
+/// Verify a simulated report. As its key is public, this only shows that the
+/// report is intact: anyone can make one.
+pub fn verify_simulated(report: &[u8]) -> Result<Evidence> {
+    if report.len() != SIMULATED_REPORT_SIZE || report[..8] != SIMULATED_MAGIC {
+        return Err(Error::Malformed);
+    }
+    let (body, mac) = report.split_at(SIMULATED_REPORT_SIZE - 32);
+    if !crypto::ct_eq(&crypto::hmac::hmac_sha256(&SIMULATED_KEY, body), mac) {
+        return Err(Error::BadSignature);
+    }
+    Ok(Evidence {
+        measurement: body[8..40].to_vec(),
+        platform_measurement: None,
+        report_data: body[40..].try_into().unwrap(),
+    })
+}
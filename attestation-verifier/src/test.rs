@@ -0,0 +1,139 @@
+use ed25519_dalek::{Signer, SigningKey};
+
+use crate::*;
+
+const DEVICE_SECRET: [u8; 32] = [1; 32];
+const SM_SECRET: [u8; 32] = [2; 32];
+
+/// Make a report as the security monitor would, on a device with
+/// `DEVICE_SECRET` as its key.
+fn keystone_report(data: &[u8; REPORT_DATA_SIZE]) -> Vec<u8> {
+    let device = SigningKey::from_bytes(&DEVICE_SECRET);
+    let sm = SigningKey::from_bytes(&SM_SECRET);
+
+    let mut enclave = vec![0xe0; KEYSTONE_HASH_SIZE];
+    enclave.extend((REPORT_DATA_SIZE as u64).to_le_bytes());
+    enclave.extend(data);
+    let signature = sm.sign(&enclave).to_bytes();
+    enclave.resize(KEYSTONE_ENCLAVE_REPORT_SIZE - KEYSTONE_SIGNATURE_SIZE, 0);
+    enclave.extend(signature);
+
+    let mut report = enclave;
+    let mut sm_report = vec![0x5e; KEYSTONE_HASH_SIZE];
+    sm_report.extend(sm.verifying_key().to_bytes());
+    let signature = device.sign(&sm_report).to_bytes();
+    report.extend(sm_report);
+    report.extend(signature);
+    report.extend(device.verifying_key().to_bytes());
+    report
+}
+
+fn device_key() -> [u8; 32] {
+    SigningKey::from_bytes(&DEVICE_SECRET)
+        .verifying_key()
+        .to_bytes()
+}
+
+fn nonce() -> [u8; REPORT_DATA_SIZE] {
+    let mut data = [0; REPORT_DATA_SIZE];
+    data[..5].copy_from_slice(b"nonce");
+    data
+}
+
+#[test]
+fn keystone_reports() {
+    let report = keystone_report(&nonce());
+    assert_eq!(report.len(), KEYSTONE_REPORT_SIZE);
+    let evidence = verify_keystone(&report, &device_key()).unwrap();
+    assert_eq!(evidence.measurement, [0xe0; 64]);
+    assert_eq!(evidence.platform_measurement, Some(vec![0x5e; 64]));
+    assert!(evidence.binds(b"nonce"));
+    assert!(!evidence.binds(b"nonc"));
+    assert!(!evidence.binds(b"other"));
+
+    // The data is signed by the monitor
+    let mut tampered = report.clone();
+    tampered[KEYSTONE_HASH_SIZE + 8] ^= 1;
+    assert_eq!(
+        verify_keystone(&tampered, &device_key()),
+        Err(Error::BadSignature)
+    );
+    // ... and so is the enclave's measurement
+    let mut tampered = report.clone();
+    tampered[0] ^= 1;
+    assert_eq!(
+        verify_keystone(&tampered, &device_key()),
+        Err(Error::BadSignature)
+    );
+    // The monitor is vouched for by the device
+    let mut tampered = report.clone();
+    tampered[KEYSTONE_ENCLAVE_REPORT_SIZE] ^= 1;
+    assert_eq!(
+        verify_keystone(&tampered, &device_key()),
+        Err(Error::BadSignature)
+    );
+
+    let other_device = SigningKey::from_bytes(&[3; 32]).verifying_key().to_bytes();
+    assert_eq!(
+        verify_keystone(&report, &other_device),
+        Err(Error::UntrustedDevice)
+    );
+    assert_eq!(
+        verify_keystone(&report[1..], &device_key()),
+        Err(Error::Malformed)
+    );
+}
+
+/// Make a quote as the quoting enclave would, with a dummy signature.
+fn sgx_quote(data: &[u8; REPORT_DATA_SIZE]) -> Vec<u8> {
+    let mut quote = vec![0; SGX_QUOTE_MIN_SIZE];
+    quote[0..2].copy_from_slice(&SGX_QUOTE_VERSION.to_le_bytes());
+    quote[2..4].copy_from_slice(&SGX_QUOTE_KEY_TYPE.to_le_bytes());
+    quote[SGX_QUOTE_BODY + SGX_MR_ENCLAVE..][..32].fill(0xe0);
+    quote[SGX_QUOTE_BODY + SGX_MR_SIGNER..][..32].fill(0x5e);
+    quote[SGX_QUOTE_BODY + SGX_REPORT_DATA..][..REPORT_DATA_SIZE].copy_from_slice(data);
+    quote[SGX_QUOTE_SIGNATURE_LEN..][..4].copy_from_slice(&16u32.to_le_bytes());
+    quote.extend([0x51; 16]);
+    quote
+}
+
+#[test]
+fn sgx_quotes() {
+    let quote = sgx_quote(&nonce());
+    let evidence = verify_sgx(&quote, |checked| checked == quote).unwrap();
+    assert_eq!(evidence.measurement, [0xe0; 32]);
+    assert_eq!(evidence.platform_measurement, Some(vec![0x5e; 32]));
+    assert!(evidence.binds(b"nonce"));
+
+    // Nothing is read from a quote that does not verify
+    assert_eq!(verify_sgx(&quote, |_| false), Err(Error::BadSignature));
+    let mut tampered = quote.clone();
+    tampered[0] ^= 1;
+    assert_eq!(verify_sgx(&tampered, |_| true), Err(Error::Malformed));
+    assert_eq!(
+        verify_sgx(&quote[..quote.len() - 1], |_| true),
+        Err(Error::Malformed)
+    );
+    assert_eq!(verify_sgx(&quote[..100], |_| true), Err(Error::Malformed));
+}
+
+#[test]
+fn simulated_reports() {
+    let mut report = SIMULATED_MAGIC.to_vec();
+    report.extend([0; 32]);
+    report.extend(nonce());
+    let mac = crypto::hmac::hmac_sha256(&SIMULATED_KEY, &report);
+    report.extend(mac);
+
+    let evidence = verify_simulated(&report).unwrap();
+    assert_eq!(evidence.measurement, [0; 32]);
+    assert_eq!(evidence.platform_measurement, None);
+    assert!(evidence.binds(b"nonce"));
+
+    let mut tampered = report.clone();
+    tampered[40] ^= 1;
+    assert_eq!(verify_simulated(&tampered), Err(Error::BadSignature));
+    let mut tampered = report;
+    tampered[0] ^= 1;
+    assert_eq!(verify_simulated(&tampered), Err(Error::Malformed));
+}
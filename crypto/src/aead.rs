@@ -0,0 +1,41 @@
+//! The ChaCha20-Poly1305 AEAD construction (RFC 8439, section 2.8).
+
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, KeyInit};
+
+use crate::chacha20::{KEY_SIZE, NONCE_SIZE};
+
+pub const TAG_SIZE: usize = 16;
+
+/// The tag does not match: the ciphertext or the associated data has been
+/// tampered with, or the wrong key or nonce is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthenticationFailed;
+
+/// Encrypt `data` in place and return the tag over it and `aad`.
+///
+/// A nonce must never be used twice with the same key.
+pub fn seal(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    data: &mut [u8],
+) -> [u8; TAG_SIZE] {
+    ChaCha20Poly1305::new(key.into())
+        .encrypt_in_place_detached(nonce.into(), aad, data)
+        .expect("data too long for ChaCha20-Poly1305")
+        .into()
+}
+
+/// Verify `tag` and decrypt `data` in place. `data` is left untouched if the
+/// verification fails.
+pub fn open(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    data: &mut [u8],
+    tag: &[u8; TAG_SIZE],
+) -> Result<(), AuthenticationFailed> {
+    ChaCha20Poly1305::new(key.into())
+        .decrypt_in_place_detached(nonce.into(), aad, data, tag.into())
+        .map_err(|_| AuthenticationFailed)
+}
@@ -0,0 +1,30 @@
+//! The ChaCha20 stream cipher (RFC 8439, section 2.4).
+
+use chacha20::{
+    cipher::{KeyIvInit, StreamCipher, StreamCipherSeek},
+    ChaCha20,
+};
+
+pub const KEY_SIZE: usize = 32;
+pub const NONCE_SIZE: usize = 12;
+pub const BLOCK_SIZE: usize = 64;
+
+/// Compute the key stream block at `counter`.
+pub fn block(key: &[u8; KEY_SIZE], counter: u32, nonce: &[u8; NONCE_SIZE]) -> [u8; BLOCK_SIZE] {
+    let mut output = [0; BLOCK_SIZE];
+    apply_keystream(key, counter, nonce, &mut output);
+    output
+}
+
+/// XOR `data` with the key stream starting at block `counter`. This both
+/// encrypts and decrypts.
+pub fn apply_keystream(
+    key: &[u8; KEY_SIZE],
+    counter: u32,
+    nonce: &[u8; NONCE_SIZE],
+    data: &mut [u8],
+) {
+    let mut cipher = ChaCha20::new(key.into(), nonce.into());
+    cipher.seek(counter as u64 * BLOCK_SIZE as u64);
+    cipher.apply_keystream(data);
+}
@@ -0,0 +1,28 @@
+//! HMAC-SHA256 (RFC 2104).
+
+use hmac::{Hmac, Mac};
+
+use crate::sha256::DIGEST_SIZE;
+
+#[derive(Clone)]
+pub struct HmacSha256(Hmac<sha2::Sha256>);
+
+impl HmacSha256 {
+    pub fn new(key: &[u8]) -> HmacSha256 {
+        HmacSha256(Hmac::new_from_slice(key).expect("HMAC takes keys of any size"))
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; DIGEST_SIZE] {
+        self.0.finalize().into_bytes().into()
+    }
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; DIGEST_SIZE] {
+    let mut mac = HmacSha256::new(key);
+    mac.update(data);
+    mac.finalize()
+}
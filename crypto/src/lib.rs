@@ -0,0 +1,23 @@
+//! Cryptographic primitives for the enclave kernel.
+//!
+//! These are thin wrappers around the RustCrypto crates, giving the kernel
+//! fixed-size keys and outputs: ChaCha20-Poly1305 (RFC 8439), SHA-256 and
+//! HMAC-SHA256.
+
+#![no_std]
+
+#[cfg(test)]
+extern crate std;
+
+pub mod aead;
+pub mod chacha20;
+pub mod hmac;
+pub mod sha256;
+
+#[cfg(test)]
+mod test;
+
+/// Compare two byte strings in constant time (with respect to their contents).
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    subtle::ConstantTimeEq::ct_eq(a, b).into()
+}
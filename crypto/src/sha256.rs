@@ -0,0 +1,26 @@
+//! The SHA-256 hash function (FIPS 180-4).
+
+use sha2::Digest;
+
+pub const DIGEST_SIZE: usize = 32;
+
+#[derive(Clone, Default)]
+pub struct Sha256(sha2::Sha256);
+
+impl Sha256 {
+    pub fn new() -> Sha256 {
+        Sha256(sha2::Sha256::new())
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; DIGEST_SIZE] {
+        self.0.finalize().into()
+    }
+}
+
+pub fn sha256(data: &[u8]) -> [u8; DIGEST_SIZE] {
+    sha2::Sha256::digest(data).into()
+}
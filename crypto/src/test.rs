@@ -0,0 +1,97 @@
+use std::vec::Vec;
+
+use crate::{aead, chacha20, hmac::hmac_sha256, sha256::sha256};
+
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+const SUNSCREEN: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+#[test]
+fn chacha20_rfc8439_encryption() {
+    let key: [u8; 32] = core::array::from_fn(|i| i as u8);
+    let nonce = hex("000000000000004a00000000").try_into().unwrap();
+    let mut data = SUNSCREEN.to_vec();
+    chacha20::apply_keystream(&key, 1, &nonce, &mut data);
+    assert_eq!(
+        data,
+        hex(concat!(
+            "6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b",
+            "f91b65c5524733ab8f593dabcd62b3571639d624e65152ab8f530c359f0861d8",
+            "07ca0dbf500d6a6156a38e088a22b65e52bc514d16ccf806818ce91ab7793736",
+            "5af90bbf74a35be6b40b8eedf2785e42874d",
+        ))
+    );
+}
+
+#[test]
+fn aead_rfc8439_seal_and_open() {
+    let key: [u8; 32] = core::array::from_fn(|i| 0x80 + i as u8);
+    let nonce = hex("070000004041424344454647").try_into().unwrap();
+    let aad = hex("50515253c0c1c2c3c4c5c6c7");
+    let expected = hex(concat!(
+        "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6",
+        "3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36",
+        "92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc",
+        "3ff4def08e4b7a9de576d26586cec64b6116",
+        "1ae10b594f09e26a7e902ecbd0600691",
+    ));
+
+    let mut data = SUNSCREEN.to_vec();
+    let tag = aead::seal(&key, &nonce, &aad, &mut data);
+    assert_eq!(data, expected[0..SUNSCREEN.len()]);
+    assert_eq!(tag[..], expected[SUNSCREEN.len()..]);
+
+    aead::open(&key, &nonce, &aad, &mut data, &tag).unwrap();
+    assert_eq!(data, SUNSCREEN);
+
+    aead::seal(&key, &nonce, &aad, &mut data);
+    data[7] ^= 1;
+    assert_eq!(
+        aead::open(&key, &nonce, &aad, &mut data, &tag),
+        Err(aead::AuthenticationFailed)
+    );
+}
+
+#[test]
+fn sha256_known_digests() {
+    for (data, digest) in [
+        (
+            &b""[..],
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        ),
+        (
+            b"abc",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        ),
+        (
+            &[b'a'; 1000],
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3",
+        ),
+    ] {
+        assert_eq!(sha256(data)[..], hex(digest));
+    }
+}
+
+#[test]
+fn hmac_sha256_rfc4231() {
+    assert_eq!(
+        hmac_sha256(b"Jefe", b"what do ya want for nothing?")[..],
+        hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+    );
+    assert_eq!(
+        hmac_sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First"
+        )[..],
+        hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54")
+    );
+}
@@ -1,10 +1,53 @@
 #[cfg(feature = "async")]
 use alloc::boxed::Box;
+use core::{
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{channel::EdgeMemLayout, EdgeCallReq, EdgeCallResp, EdgeError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdgeCallError {
+    /// The request could not be serialized, or the response could not be
+    /// deserialized.
+    Serialization(postcard::Error),
+    /// The payload does not fit into the shared buffer.
+    BufferOverflow { len: usize, capacity: usize },
+    /// The host could not be notified, or did not answer properly.
+    Transport,
+    /// The response does not match the kind of the request.
+    UnexpectedResponse,
+    /// The host failed to perform the edge call.
+    Remote(EdgeError),
+}
 
-use crate::{EdgeCallReq, EdgeCallResp};
+impl EdgeCallError {
+    /// The Linux error number that best describes this error.
+    pub fn errno(&self) -> i32 {
+        match self {
+            EdgeCallError::Remote(err) => err.errno,
+            _ => EdgeError::EIO,
+        }
+    }
+}
+
+impl fmt::Display for EdgeCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdgeCallError::Serialization(err) => write!(f, "serialization error: {}", err),
+            EdgeCallError::BufferOverflow { len, capacity } => write!(
+                f,
+                "buffer overflow: {} bytes do not fit into {} bytes",
+                len, capacity,
+            ),
+            EdgeCallError::Transport => write!(f, "transport error"),
+            EdgeCallError::UnexpectedResponse => write!(f, "unexpected response"),
+            EdgeCallError::Remote(err) => write!(f, "remote error: {}", err),
+        }
+    }
+}
 
-#[derive(Debug)]
-pub struct EdgeCallError;
 pub type Result<T> = core::result::Result<T, EdgeCallError>;
 
 pub trait EdgeCaller {
@@ -13,6 +56,8 @@ pub trait EdgeCaller {
     fn kick(&mut self) -> Result<()>;
     fn read_header(&mut self) -> Result<EdgeCallResp>;
     fn read_data(&mut self) -> Result<&[u8]>;
+    /// Borrow the whole data zone, so that it can be filled in place.
+    fn data_zone_mut(&mut self) -> Result<&mut [u8]>;
 }
 
 #[cfg(feature = "async")]
@@ -23,12 +68,15 @@ pub trait AsyncEdgeCaller {
     async fn kick(&mut self) -> Result<()>;
     async fn read_header(&mut self) -> Result<EdgeCallResp>;
     async fn read_data(&mut self) -> Result<&[u8]>;
+    async fn data_zone_mut(&mut self) -> Result<&mut [u8]>;
 }
 
 #[derive(Clone)]
 pub struct SharedMemCaller {
     header_zone: (*mut u8, usize),
     data_zone: (*mut u8, usize),
+    /// Null if the edge memory only has a single channel without a doorbell.
+    doorbell: *const AtomicU32,
     kick: fn() -> Result<()>,
 }
 
@@ -46,6 +94,27 @@ impl SharedMemCaller {
         SharedMemCaller {
             header_zone: (header_zone_ptr, header_zone_len),
             data_zone: (data_zone_ptr, data_zone_len),
+            doorbell: core::ptr::null(),
+            kick,
+        }
+    }
+
+    /// Create a caller for `channel` of the edge memory at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be 8-byte aligned and point to `layout.total_size()` bytes
+    /// that stay mapped for the lifetime of the caller.
+    pub unsafe fn for_channel(
+        layout: &EdgeMemLayout,
+        base: *mut u8,
+        channel: usize,
+        kick: fn() -> Result<()>,
+    ) -> SharedMemCaller {
+        SharedMemCaller {
+            header_zone: (base.add(layout.header_offset(channel)), layout.header_len()),
+            data_zone: (base.add(layout.data_offset(channel)), layout.buffer_size),
+            doorbell: layout.doorbell(base, channel),
             kick,
         }
     }
@@ -71,28 +140,42 @@ impl EdgeCaller for SharedMemCaller {
     fn write_header(&mut self, header: &EdgeCallReq) -> Result<()> {
         postcard::to_slice(header, self.borrow_header_zone_mut()).map_err(|err| {
             log::error!("Failed to serialize edge call header: {}", err);
-            EdgeCallError
+            EdgeCallError::Serialization(err)
         })?;
         Ok(())
     }
 
     fn write_data(&mut self, data: &[u8]) -> Result<()> {
-        self.borrow_data_zone_mut()[0..data.len()].copy_from_slice(data);
+        let capacity = self.data_zone.1;
+        self.borrow_data_zone_mut()
+            .get_mut(0..data.len())
+            .ok_or(EdgeCallError::BufferOverflow {
+                len: data.len(),
+                capacity,
+            })?
+            .copy_from_slice(data);
         Ok(())
     }
 
     fn kick(&mut self) -> Result<()> {
+        if let Some(doorbell) = unsafe { self.doorbell.as_ref() } {
+            doorbell.store(1, Ordering::SeqCst);
+        }
         (self.kick)()
     }
 
     fn read_header(&mut self) -> Result<EdgeCallResp> {
         postcard::from_bytes(self.borrow_header_zone()).map_err(|err| {
             log::error!("Failed to deserialize edge call header: {}", err);
-            EdgeCallError
+            EdgeCallError::Serialization(err)
         })
     }
 
     fn read_data(&mut self) -> Result<&[u8]> {
         Ok(self.borrow_data_zone())
     }
+
+    fn data_zone_mut(&mut self) -> Result<&mut [u8]> {
+        Ok(self.borrow_data_zone_mut())
+    }
 }
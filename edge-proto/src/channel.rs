@@ -0,0 +1,83 @@
+//! Layout of the edge memory.
+//!
+//! The edge memory is split into channels of equal size, each holding a header
+//! zone followed by a data zone, so that several edge calls can be prepared at
+//! the same time. The last bytes of every header zone form a control area. It
+//! holds the channel's doorbell, which the caller rings before kicking the host
+//! so that the host knows which channel to serve, and, in the first channel,
+//! the [`NotificationArea`].
+
+use core::sync::atomic::AtomicU32;
+
+use crate::notify::{NotificationArea, NOTIFICATION_AREA_SIZE};
+
+/// The size of the control area at the end of every header zone.
+pub const CONTROL_AREA_SIZE: usize = 0x40;
+const DOORBELL_OFFSET: usize = NOTIFICATION_AREA_SIZE;
+
+const _: () = assert!(DOORBELL_OFFSET + core::mem::size_of::<AtomicU32>() <= CONTROL_AREA_SIZE);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdgeMemLayout {
+    pub channels: usize,
+    /// The size of each header zone, including its control area.
+    pub header_size: usize,
+    /// The size of each data zone.
+    pub buffer_size: usize,
+}
+
+impl EdgeMemLayout {
+    pub const fn new(channels: usize, header_size: usize, buffer_size: usize) -> EdgeMemLayout {
+        EdgeMemLayout {
+            channels,
+            header_size,
+            buffer_size,
+        }
+    }
+
+    pub const fn channel_size(&self) -> usize {
+        self.header_size + self.buffer_size
+    }
+
+    pub const fn total_size(&self) -> usize {
+        self.channels * self.channel_size()
+    }
+
+    /// The usable part of a header zone.
+    pub const fn header_len(&self) -> usize {
+        self.header_size - CONTROL_AREA_SIZE
+    }
+
+    pub const fn header_offset(&self, channel: usize) -> usize {
+        assert!(channel < self.channels);
+        channel * self.channel_size()
+    }
+
+    pub const fn data_offset(&self, channel: usize) -> usize {
+        self.header_offset(channel) + self.header_size
+    }
+
+    const fn control_offset(&self, channel: usize) -> usize {
+        self.data_offset(channel) - CONTROL_AREA_SIZE
+    }
+
+    /// Locate the notification area in the edge memory at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be 8-byte aligned and point to `total_size()` bytes that stay
+    /// mapped for `'a`.
+    pub unsafe fn notification_area<'a>(&self, base: *mut u8) -> &'a NotificationArea {
+        NotificationArea::from_ptr(base.add(self.control_offset(0)))
+    }
+
+    /// Locate the doorbell of `channel` in the edge memory at `base`.
+    ///
+    /// # Safety
+    ///
+    /// See [`EdgeMemLayout::notification_area`].
+    pub(crate) unsafe fn doorbell(&self, base: *mut u8, channel: usize) -> *const AtomicU32 {
+        base.add(self.control_offset(channel) + DOORBELL_OFFSET)
+            .cast()
+    }
+}
@@ -3,9 +3,12 @@
 extern crate alloc;
 
 pub mod caller;
+pub mod channel;
+pub mod notify;
 pub mod server;
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
+use core::fmt;
 use enum_as_inner::EnumAsInner;
 use serde::{Deserialize, Serialize};
 
@@ -32,14 +35,52 @@ pub enum EdgeCallReq {
         fd: i32,
         len: u64,
     },
+    /// Read into the given data zone regions (like `readv`).
+    SyscallReadV {
+        pid: i32,
+        fd: i32,
+        regions: Vec<DataRegion>,
+    },
+    /// Write the given data zone regions (like `writev`).
+    SyscallWriteV {
+        pid: i32,
+        fd: i32,
+        regions: Vec<DataRegion>,
+    },
+    /// Read into the given data zone regions at `offset`, leaving the file
+    /// position alone (like `preadv`).
+    SyscallPreadV {
+        pid: i32,
+        fd: i32,
+        regions: Vec<DataRegion>,
+        offset: i64,
+    },
+    /// Write the given data zone regions at `offset`, leaving the file
+    /// position alone (like `pwritev`).
+    SyscallPwriteV {
+        pid: i32,
+        fd: i32,
+        regions: Vec<DataRegion>,
+        offset: i64,
+    },
+    /// Answered with the new file position.
+    SyscallLseek {
+        pid: i32,
+        fd: i32,
+        offset: i64,
+        whence: i32,
+    },
     SyscallClose {
         pid: i32,
         fd: i32,
     },
+    /// Duplicate `src_fd` at `dest_fd`, or at the lowest free fd, with
+    /// `FD_CLOEXEC` set on the new fd if `cloexec` is.
     SyscallDup {
         pid: i32,
         src_fd: i32,
         dest_fd: Option<i32>,
+        cloexec: bool,
     },
     SyscallMkdirAt {
         pid: i32,
@@ -59,16 +100,188 @@ pub enum EdgeCallReq {
         fd: i32,
         len: u64,
     },
+    /// Answered with [`EdgeCallResp::OkWithStat`].
     SyscallFstat {
         pid: i32,
         fd: i32,
     },
+    /// Get the metadata of a path (like `newfstatat`). `flags` may contain
+    /// `AT_SYMLINK_NOFOLLOW`. Answered with [`EdgeCallResp::OkWithStat`].
+    SyscallFstatAt {
+        pid: i32,
+        dir_fd: i32,
+        path: String,
+        flags: i32,
+    },
+    SyscallFaccessAt {
+        pid: i32,
+        dir_fd: i32,
+        path: String,
+        mode: i32,
+    },
+    /// Answered with the target as [`EdgeCallResp::OkWithString`].
+    SyscallReadLinkAt {
+        pid: i32,
+        dir_fd: i32,
+        path: String,
+    },
     SyscallUnlinkAt {
         pid: i32,
         dir_fd: i32,
         path: String,
         flags: i32,
     },
+    /// Rename a path (like `renameat2`). `flags` may contain
+    /// `RENAME_NOREPLACE` or `RENAME_EXCHANGE`.
+    SyscallRenameAt {
+        pid: i32,
+        old_dir_fd: i32,
+        old_path: String,
+        new_dir_fd: i32,
+        new_path: String,
+        flags: u32,
+    },
+    /// Create a hard link (like `linkat`). `flags` may contain
+    /// `AT_SYMLINK_FOLLOW`.
+    SyscallLinkAt {
+        pid: i32,
+        old_dir_fd: i32,
+        old_path: String,
+        new_dir_fd: i32,
+        new_path: String,
+        flags: i32,
+    },
+    SyscallSymlinkAt {
+        pid: i32,
+        target: String,
+        dir_fd: i32,
+        path: String,
+    },
+    SyscallTruncate {
+        pid: i32,
+        path: String,
+        len: i64,
+    },
+    SyscallFtruncate {
+        pid: i32,
+        fd: i32,
+        len: i64,
+    },
+    /// Flush a file to the host's storage, only its data and the metadata
+    /// needed to read it if `data_only` is set (like `fdatasync`).
+    SyscallFsync {
+        pid: i32,
+        fd: i32,
+        data_only: bool,
+    },
+    /// One of the `fcntl` commands on the fd table or on a host fd; `arg` is
+    /// never a pointer.
+    SyscallFcntl {
+        pid: i32,
+        fd: i32,
+        cmd: i32,
+        arg: u64,
+    },
+    /// One of the whitelisted `ioctl` requests that fill a structure of `len`
+    /// bytes, which is returned in the data zone if the call succeeds.
+    SyscallIoctl {
+        pid: i32,
+        fd: i32,
+        request: u32,
+        len: u64,
+    },
+    /// Poll host fds like `poll` with a zero timeout. The returned events of
+    /// each fd are written to the data zone as native-endian `i16`s, with
+    /// `POLLNVAL` for fds that are not open; the result is the number of fds
+    /// that returned events. If `watch` is set and none did, the host notifies
+    /// the enclave once one of them is ready (see
+    /// [`notify::Notification::IoReady`]).
+    SyscallPoll {
+        pid: i32,
+        fds: Vec<PolledFd>,
+        watch: bool,
+    },
+    /// Create a host socket. Failures are returned as [`EdgeCallResp::Error`]
+    /// by this and the other socket calls.
+    SyscallSocket {
+        pid: i32,
+        domain: i32,
+        ty: i32,
+        protocol: i32,
+    },
+    SyscallBind {
+        pid: i32,
+        fd: i32,
+        addr: SockAddr,
+    },
+    SyscallListen {
+        pid: i32,
+        fd: i32,
+        backlog: i32,
+    },
+    /// Accept a connection, returning its fd and the peer's address, or
+    /// [`EdgeCallResp::WouldBlock`] if none is pending on a blocking socket.
+    SyscallAccept {
+        pid: i32,
+        fd: i32,
+        flags: i32,
+    },
+    /// Connect a socket. On a blocking socket, a connection that is still in
+    /// progress is answered with [`EdgeCallResp::WouldBlock`], and its result
+    /// must be taken from `SO_ERROR` once the socket is writable.
+    SyscallConnect {
+        pid: i32,
+        fd: i32,
+        addr: SockAddr,
+    },
+    /// Send the first `len` bytes of the data zone, or answer with
+    /// [`EdgeCallResp::WouldBlock`] if that would block.
+    SyscallSendTo {
+        pid: i32,
+        fd: i32,
+        len: u64,
+        flags: i32,
+        addr: Option<SockAddr>,
+    },
+    /// Receive up to `len` bytes into the data zone, returning the sender's
+    /// address, or answer with [`EdgeCallResp::WouldBlock`] if that would
+    /// block.
+    SyscallRecvFrom {
+        pid: i32,
+        fd: i32,
+        len: u64,
+        flags: i32,
+    },
+    SyscallShutdown {
+        pid: i32,
+        fd: i32,
+        how: i32,
+    },
+    /// Read one of the whitelisted socket options, whose value of at most
+    /// `len` bytes is returned in the data zone.
+    SyscallGetSockOpt {
+        pid: i32,
+        fd: i32,
+        level: i32,
+        name: i32,
+        len: u64,
+    },
+    /// Set one of the whitelisted socket options.
+    SyscallSetSockOpt {
+        pid: i32,
+        fd: i32,
+        level: i32,
+        name: i32,
+        value: Vec<u8>,
+    },
+    SyscallGetSockName {
+        pid: i32,
+        fd: i32,
+    },
+    SyscallGetPeerName {
+        pid: i32,
+        fd: i32,
+    },
     PcbDup {
         from: i32,
         to: i32,
@@ -76,6 +289,17 @@ pub enum EdgeCallReq {
     PcbDrop {
         pid: i32,
     },
+    /// Look up the guest path an fd was opened at.
+    FdPath {
+        pid: i32,
+        fd: i32,
+    },
+    /// Allocate an fd for a file served inside the enclave, so that its number
+    /// does not clash with host files. It is released with `SyscallClose`.
+    FdReserve {
+        pid: i32,
+        cloexec: bool,
+    },
     FileOpen {
         path: String,
     },
@@ -93,9 +317,65 @@ pub enum EdgeCallReq {
     FileClose {
         file_obj: u64,
     },
+    /// Read the host's wall clock, in nanoseconds since the Unix epoch. It is
+    /// only asked for while the enclave's clock is calibrated at boot.
+    WallClock,
+    /// Look up the configuration of the network link that carries the frames
+    /// of the enclave's network stack. It is answered with
+    /// [`EdgeCallResp::OkWithNetConfig`], or an error if the host has none.
+    NetConfig,
+    /// Send the Ethernet frame in the data zone on the network link.
+    NetTransmit {
+        len: u64,
+    },
+    /// Take the next Ethernet frame received on the network link into the
+    /// data zone, answering with its length, or with
+    /// [`EdgeCallResp::WouldBlock`] if none is pending.
+    NetReceive,
     StreamShutdown,
 }
 
+/// The maximum number of data zone regions in a scatter-gather request.
+pub const MAX_DATA_REGIONS: usize = 64;
+
+/// The maximum number of fds in a [`EdgeCallReq::SyscallPoll`] request.
+pub const MAX_POLLED_FDS: usize = 256;
+
+/// An fd polled for `events`, which are `POLL*` flags.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolledFd {
+    pub fd: i32,
+    pub events: i16,
+}
+
+/// A byte range inside the data zone, referenced by scatter-gather requests.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRegion {
+    pub offset: u64,
+    pub len: u64,
+}
+
+impl DataRegion {
+    /// Lay out regions of the given lengths back to back, starting at offset 0.
+    pub fn packed(lens: impl IntoIterator<Item = usize>) -> Vec<DataRegion> {
+        let mut offset = 0;
+        lens.into_iter()
+            .map(|len| {
+                let region = DataRegion {
+                    offset: offset as u64,
+                    len: len as u64,
+                };
+                offset += len;
+                region
+            })
+            .collect()
+    }
+
+    pub fn range(&self) -> core::ops::Range<usize> {
+        self.offset as usize..(self.offset + self.len) as usize
+    }
+}
+
 #[derive(Serialize, Deserialize, EnumAsInner, Debug, Clone, PartialEq, Eq)]
 pub enum EdgeCallResp {
     Invalid,
@@ -103,5 +383,105 @@ pub enum EdgeCallResp {
     Ok,
     OkWithU64(u64),
     OkWithString(String),
-    Error,
+    OkWithStat(Stat),
+    Error(EdgeError),
+    /// The result of a socket call, with the address it returns.
+    OkWithSockAddr {
+        result: i64,
+        addr: SockAddr,
+    },
+    OkWithNetConfig(NetConfig),
+    /// The call would block, as the host file is not ready yet. The host
+    /// notifies the enclave once it is (see [`notify::Notification::IoReady`]).
+    WouldBlock,
+}
+
+impl EdgeCallResp {
+    /// Turn an [`EdgeCallResp::Error`] into an `Err`, leaving any other
+    /// response untouched.
+    pub fn into_result(self) -> caller::Result<EdgeCallResp> {
+        match self {
+            EdgeCallResp::Error(err) => Err(caller::EdgeCallError::Remote(err)),
+            other => Ok(other),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Timespec {
+    pub sec: i64,
+    pub nsec: i64,
+}
+
+/// File metadata, independent of the layout of `struct stat` on either side.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    pub size: i64,
+    pub blksize: i64,
+    pub blocks: i64,
+    pub atime: Timespec,
+    pub mtime: Timespec,
+    pub ctime: Timespec,
+}
+
+/// A socket address, independent of the layout of `struct sockaddr_*` on
+/// either side. Ports and addresses are in host byte order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SockAddr {
+    /// The address of an unbound socket, or one that the guest cannot see.
+    Unnamed,
+    /// A Unix socket, by its guest path.
+    Unix(String),
+    Inet {
+        addr: [u8; 4],
+        port: u16,
+    },
+    Inet6 {
+        addr: [u8; 16],
+        port: u16,
+        flowinfo: u32,
+        scope_id: u32,
+    },
+}
+
+/// The configuration of the network link that the host gives the enclave.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetConfig {
+    pub mac: [u8; 6],
+    pub addr: [u8; 4],
+    pub prefix_len: u8,
+    pub gateway: Option<[u8; 4]>,
+}
+
+/// The reason why the edge responder failed to perform an edge call.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EdgeError {
+    /// A Linux error number (e.g. `ENOENT`).
+    pub errno: i32,
+    /// An optional human-readable description, for diagnostics only.
+    pub message: Option<String>,
+}
+
+impl EdgeError {
+    pub const EIO: i32 = 5;
+
+    pub fn new(errno: i32, message: Option<String>) -> EdgeError {
+        EdgeError { errno, message }
+    }
+}
+
+impl fmt::Display for EdgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{} (errno {})", message, self.errno),
+            None => write!(f, "errno {}", self.errno),
+        }
+    }
 }
@@ -0,0 +1,120 @@
+//! Host-initiated notifications.
+//!
+//! Edge calls are always initiated by the enclave. To let the host report
+//! asynchronous events, the control area of the first edge channel holds a
+//! notification area (see [`crate::channel`]): the host sets bits in it at any
+//! time, and the enclave atomically takes all pending bits whenever it polls.
+
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// The size reserved for [`NotificationArea`] in the control area.
+pub const NOTIFICATION_AREA_SIZE: usize = 0x20;
+
+const EVENT_IO_READY: u32 = 1 << 0;
+const EVENT_TIMER_TICK: u32 = 1 << 1;
+const EVENT_SHUTDOWN: u32 = 1 << 2;
+
+/// The highest signal number that can be posted.
+pub const MAX_SIGNAL: u8 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notification {
+    /// A host file that a read would have blocked on has become ready.
+    IoReady,
+    /// A periodic timer tick.
+    TimerTick,
+    /// The host asks the enclave to shut down.
+    Shutdown,
+    /// A signal (e.g. `SIGINT`) for the enclave's processes.
+    Signal(u8),
+}
+
+/// Pending notifications, shared between the host and the enclave.
+#[repr(C)]
+pub struct NotificationArea {
+    events: AtomicU32,
+    signals: AtomicU64,
+}
+
+const _: () = assert!(core::mem::size_of::<NotificationArea>() <= NOTIFICATION_AREA_SIZE);
+
+impl NotificationArea {
+    /// Interpret shared memory as a notification area.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be 8-byte aligned, and must point to `NOTIFICATION_AREA_SIZE`
+    /// bytes that stay mapped for `'a`.
+    pub unsafe fn from_ptr<'a>(ptr: *mut u8) -> &'a NotificationArea {
+        assert_eq!(ptr as usize % core::mem::align_of::<NotificationArea>(), 0);
+        &*(ptr as *const NotificationArea)
+    }
+
+    pub fn post(&self, notification: Notification) {
+        match notification {
+            Notification::IoReady => self.post_event(EVENT_IO_READY),
+            Notification::TimerTick => self.post_event(EVENT_TIMER_TICK),
+            Notification::Shutdown => self.post_event(EVENT_SHUTDOWN),
+            Notification::Signal(signo) => {
+                assert!(
+                    (1..=MAX_SIGNAL).contains(&signo),
+                    "invalid signal {}",
+                    signo
+                );
+                self.signals.fetch_or(1 << (signo - 1), Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn post_event(&self, event: u32) {
+        self.events.fetch_or(event, Ordering::SeqCst);
+    }
+
+    /// Check whether `notification` has been posted but not taken yet.
+    pub fn is_pending(&self, notification: Notification) -> bool {
+        let pending = PendingNotifications {
+            events: self.events.load(Ordering::SeqCst),
+            signals: self.signals.load(Ordering::SeqCst),
+        };
+        pending.iter().any(|n| n == notification)
+    }
+
+    /// Take all pending notifications, clearing them.
+    pub fn take(&self) -> PendingNotifications {
+        PendingNotifications {
+            events: self.events.swap(0, Ordering::SeqCst),
+            signals: self.signals.swap(0, Ordering::SeqCst),
+        }
+    }
+}
+
+/// A set of notifications taken from a [`NotificationArea`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PendingNotifications {
+    events: u32,
+    signals: u64,
+}
+
+impl PendingNotifications {
+    pub fn is_empty(&self) -> bool {
+        self.events == 0 && self.signals == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Notification> {
+        let events = self.events;
+        let signals = self.signals;
+        [
+            (EVENT_SHUTDOWN, Notification::Shutdown),
+            (EVENT_IO_READY, Notification::IoReady),
+            (EVENT_TIMER_TICK, Notification::TimerTick),
+        ]
+        .into_iter()
+        .filter(move |&(event, _)| events & event != 0)
+        .map(|(_, notification)| notification)
+        .chain(
+            (1..=MAX_SIGNAL)
+                .filter(move |signo| signals & (1 << (signo - 1)) != 0)
+                .map(Notification::Signal),
+        )
+    }
+}
@@ -1,10 +1,34 @@
 #[cfg(feature = "async")]
 use alloc::boxed::Box;
+use core::{
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
-use crate::{EdgeCallReq, EdgeCallResp};
+use crate::{channel::EdgeMemLayout, EdgeCallReq, EdgeCallResp};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdgeCallError {
+    /// The request could not be deserialized, or the response could not be
+    /// serialized.
+    Serialization(postcard::Error),
+    /// The payload does not fit into the shared buffer.
+    BufferOverflow { len: usize, capacity: usize },
+}
+
+impl fmt::Display for EdgeCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdgeCallError::Serialization(err) => write!(f, "serialization error: {}", err),
+            EdgeCallError::BufferOverflow { len, capacity } => write!(
+                f,
+                "buffer overflow: {} bytes do not fit into {} bytes",
+                len, capacity,
+            ),
+        }
+    }
+}
 
-#[derive(Debug)]
-pub struct EdgeCallError;
 pub type Result<T> = core::result::Result<T, EdgeCallError>;
 
 pub trait EdgeStream {
@@ -12,6 +36,8 @@ pub trait EdgeStream {
     fn read_data(&mut self) -> Result<&[u8]>;
     fn write_header(&mut self, header: &EdgeCallResp) -> Result<()>;
     fn write_data(&mut self, data: &[u8]) -> Result<()>;
+    /// Borrow the whole data zone, so that it can be filled in place.
+    fn data_zone_mut(&mut self) -> Result<&mut [u8]>;
 }
 
 #[cfg(feature = "async")]
@@ -21,12 +47,15 @@ pub trait AsyncEdgeStream {
     async fn read_data(&mut self) -> Result<&[u8]>;
     async fn write_header(&mut self, header: &EdgeCallResp) -> Result<()>;
     async fn write_data(&mut self, data: &[u8]) -> Result<()>;
+    async fn data_zone_mut(&mut self) -> Result<&mut [u8]>;
 }
 
 #[derive(Clone)]
 pub struct SharedMemEdgeStream {
     header_zone: (*mut u8, usize),
     data_zone: (*mut u8, usize),
+    /// Null if the edge memory only has a single channel without a doorbell.
+    doorbell: *const AtomicU32,
 }
 
 unsafe impl Send for SharedMemEdgeStream {}
@@ -42,6 +71,34 @@ impl SharedMemEdgeStream {
         SharedMemEdgeStream {
             header_zone: (header_zone_ptr, header_zone_len),
             data_zone: (data_zone_ptr, data_zone_len),
+            doorbell: core::ptr::null(),
+        }
+    }
+
+    /// Create a stream for `channel` of the edge memory at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be 8-byte aligned and point to `layout.total_size()` bytes
+    /// that stay mapped for the lifetime of the stream.
+    pub unsafe fn for_channel(
+        layout: &EdgeMemLayout,
+        base: *mut u8,
+        channel: usize,
+    ) -> SharedMemEdgeStream {
+        SharedMemEdgeStream {
+            header_zone: (base.add(layout.header_offset(channel)), layout.header_len()),
+            data_zone: (base.add(layout.data_offset(channel)), layout.buffer_size),
+            doorbell: layout.doorbell(base, channel),
+        }
+    }
+
+    /// Check whether the caller has kicked the host on this channel, and
+    /// reset the doorbell. Always true for a stream without a doorbell.
+    pub fn take_doorbell(&self) -> bool {
+        match unsafe { self.doorbell.as_ref() } {
+            Some(doorbell) => doorbell.swap(0, Ordering::SeqCst) != 0,
+            None => true,
         }
     }
 
@@ -66,7 +123,7 @@ impl EdgeStream for SharedMemEdgeStream {
     fn read_header(&mut self) -> Result<EdgeCallReq> {
         postcard::from_bytes(self.borrow_header_zone()).map_err(|err| {
             log::error!("Failed to deserialize edge call header: {}", err);
-            EdgeCallError
+            EdgeCallError::Serialization(err)
         })
     }
 
@@ -77,13 +134,24 @@ impl EdgeStream for SharedMemEdgeStream {
     fn write_header(&mut self, header: &EdgeCallResp) -> Result<()> {
         postcard::to_slice(header, self.borrow_header_zone_mut()).map_err(|err| {
             log::error!("Failed to serialize edge call header: {}", err);
-            EdgeCallError
+            EdgeCallError::Serialization(err)
         })?;
         Ok(())
     }
 
     fn write_data(&mut self, data: &[u8]) -> Result<()> {
-        self.borrow_data_zone_mut()[0..data.len()].copy_from_slice(data);
+        let capacity = self.data_zone.1;
+        self.borrow_data_zone_mut()
+            .get_mut(0..data.len())
+            .ok_or(EdgeCallError::BufferOverflow {
+                len: data.len(),
+                capacity,
+            })?
+            .copy_from_slice(data);
         Ok(())
     }
+
+    fn data_zone_mut(&mut self) -> Result<&mut [u8]> {
+        Ok(self.borrow_data_zone_mut())
+    }
 }
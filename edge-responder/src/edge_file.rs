@@ -1,9 +1,14 @@
-use std::{fs::File, mem::ManuallyDrop};
+use std::{fs::File, mem::ManuallyDrop, path::Path};
 
 use anyhow::Context;
+use nix::{fcntl::OFlag, sys::stat::Mode};
+
+use crate::policy;
 
 pub fn edge_open(path: &str) -> Result<u64, anyhow::Error> {
-    let boxed_file = Box::new(File::open(path).context("failed to open edge file")?);
+    let file = policy::open(Path::new(path), OFlag::O_RDONLY, Mode::empty())
+        .context("failed to open edge file")?;
+    let boxed_file = Box::new(file);
     let boxed_file_ptr = Box::into_raw(boxed_file);
     Ok(boxed_file_ptr as u64)
 }
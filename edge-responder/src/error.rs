@@ -6,7 +6,21 @@ pub trait EdgeErrorCompat<T> {
 
 impl<T> EdgeErrorCompat<T> for Result<T, EdgeCallError> {
     fn compat(self) -> anyhow::Result<T> {
-        self.map_err(|_err| anyhow::anyhow!("failed to perform edge call"))
+        self.map_err(|err| anyhow::anyhow!("failed to perform edge call: {}", err))
+    }
+}
+
+/// Find the Linux error number that caused `err`, defaulting to `EIO`.
+pub fn errno_of(err: &anyhow::Error) -> i32 {
+    if let Some(errno) = err.downcast_ref::<nix::Error>() {
+        *errno as i32
+    } else if let Some(errno) = err
+        .downcast_ref::<std::io::Error>()
+        .and_then(std::io::Error::raw_os_error)
+    {
+        errno
+    } else {
+        nix::Error::EIO as i32
     }
 }
 
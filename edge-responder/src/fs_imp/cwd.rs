@@ -1,15 +1,32 @@
 use std::path::{Path, PathBuf};
 
-use crate::error::LinuxResult;
+use nix::{fcntl::OFlag, sys::stat::Mode};
+
+use crate::{error::LinuxResult, sandbox};
 
 use super::TaskFsContext;
 
 impl TaskFsContext {
     pub fn resolve_path(&self, path: &str) -> PathBuf {
+        Self::resolve_path_from(&self.cwd, path)
+    }
+
+    /// Resolve `path` relative to the directory `dir_fd` refers to, like the
+    /// `*at` syscalls do.
+    pub fn resolve_path_at(&self, dir_fd: i32, path: &str) -> LinuxResult<PathBuf> {
+        if dir_fd == nix::libc::AT_FDCWD || path.starts_with('/') {
+            return Ok(self.resolve_path(path));
+        }
+        let dir = self.find_fd(dir_fd)?.lock().unwrap();
+        let dir_path = dir.path().ok_or(nix::Error::ENOTDIR)?;
+        Ok(Self::resolve_path_from(dir_path, path))
+    }
+
+    fn resolve_path_from(base: &Path, path: &str) -> PathBuf {
         let mut cur = if path.starts_with('/') {
             PathBuf::new()
         } else {
-            self.cwd.clone()
+            base.to_path_buf()
         };
         for component in path.split('/') {
             match component {
@@ -29,7 +46,7 @@ impl TaskFsContext {
             cur.push(".");
         }
 
-        log::trace!("Resolve path: {:?}/{:?} -> {:?}", self.cwd, path, cur);
+        log::trace!("Resolve path: {:?}/{:?} -> {:?}", base, path, cur);
         // TODO: distinguish between `xxx/` and `xxx`
         cur
     }
@@ -44,12 +61,9 @@ impl TaskFsContext {
 
     pub fn chdir(&mut self, path: &str) -> LinuxResult<()> {
         let dest_dir = self.resolve_path(path);
-        if dest_dir.is_dir() {
-            // TODO: permission checks, etc.
-            self.cwd = dest_dir;
-            Ok(())
-        } else {
-            Err(nix::Error::ENOTDIR)
-        }
+        // TODO: permission checks, etc.
+        sandbox::open(&dest_dir, OFlag::O_PATH | OFlag::O_DIRECTORY, Mode::empty())?;
+        self.cwd = dest_dir;
+        Ok(())
     }
 }
@@ -1,17 +1,92 @@
-use nix::sys::stat::Mode;
+use std::{os::unix::prelude::AsRawFd, path::Path};
 
-use crate::error::SyscallResult;
+use nix::{
+    fcntl::{OFlag, RenameFlags},
+    sys::stat::Mode,
+    unistd::LinkatFlags,
+};
+
+use crate::{error::SyscallResult, policy, policy::Access, sandbox};
 
 use super::TaskFsContext;
 
 impl TaskFsContext {
     pub fn mkdirat(&self, fd: i32, path: &str, mode: u32) -> SyscallResult<isize> {
-        if fd != nix::libc::AT_FDCWD {
-            return Err(anyhow::anyhow!("only FD_ATCWD is supported").into());
-        }
+        let path = self.resolve_path_at(fd, path)?;
+        let (parent, name) = sandbox::open_parent(&path)?;
+        policy::check_entry(&path, &parent, name)?;
+        nix::sys::stat::mkdirat(parent.as_raw_fd(), name, Mode::from_bits_truncate(mode))?;
+
+        Ok(0)
+    }
+
+    pub fn rename_at(
+        &self,
+        old_dir_fd: i32,
+        old_path: &str,
+        new_dir_fd: i32,
+        new_path: &str,
+        flags: u32,
+    ) -> SyscallResult<isize> {
+        let flags = RenameFlags::from_bits(flags).ok_or(nix::Error::EINVAL)?;
+        let old_path = self.resolve_path_at(old_dir_fd, old_path)?;
+        let new_path = self.resolve_path_at(new_dir_fd, new_path)?;
+        let (old_parent, old_name) = sandbox::open_parent(&old_path)?;
+        let (new_parent, new_name) = sandbox::open_parent(&new_path)?;
+        // Both entries are removed or replaced
+        policy::check_entry(&old_path, &old_parent, old_name)?;
+        policy::check_entry(&new_path, &new_parent, new_name)?;
+        nix::fcntl::renameat2(
+            Some(old_parent.as_raw_fd()),
+            old_name,
+            Some(new_parent.as_raw_fd()),
+            new_name,
+            flags,
+        )?;
+
+        Ok(0)
+    }
+
+    pub fn link_at(
+        &self,
+        old_dir_fd: i32,
+        old_path: &str,
+        new_dir_fd: i32,
+        new_path: &str,
+        flags: i32,
+    ) -> SyscallResult<isize> {
+        let lookup_flags = match flags {
+            0 => OFlag::O_NOFOLLOW,
+            nix::libc::AT_SYMLINK_FOLLOW => OFlag::empty(),
+            other => {
+                log::warn!("linkat: unknown flags: {}", other);
+                return Err(nix::Error::EINVAL.into());
+            }
+        };
+        let old_path = self.resolve_path_at(old_dir_fd, old_path)?;
+        let new_path = self.resolve_path_at(new_dir_fd, new_path)?;
+        // The new name could be used to write to the file
+        let file = policy::lookup(&old_path, lookup_flags, Access::Write)?;
+        let (parent, name) = sandbox::open_parent(&new_path)?;
+        policy::check_entry(&new_path, &parent, name)?;
+        // Link the file the lookup found, without looking it up again
+        nix::unistd::linkat(
+            None,
+            Path::new(&format!("/proc/self/fd/{}", file.as_raw_fd())),
+            Some(parent.as_raw_fd()),
+            Path::new(name),
+            LinkatFlags::SymlinkFollow,
+        )?;
+
+        Ok(0)
+    }
 
-        let path = self.resolve_path(path);
-        nix::unistd::mkdir(&path, Mode::from_bits_truncate(mode))?;
+    pub fn symlink_at(&self, target: &str, dir_fd: i32, path: &str) -> SyscallResult<isize> {
+        let path = self.resolve_path_at(dir_fd, path)?;
+        let (parent, name) = sandbox::open_parent(&path)?;
+        policy::check_entry(&path, &parent, name)?;
+        // The target is resolved beneath the guest root when it is followed
+        nix::unistd::symlinkat(target, Some(parent.as_raw_fd()), name)?;
 
         Ok(0)
     }
@@ -1,25 +1,54 @@
 use std::{
     fs::File,
-    os::unix::prelude::{AsRawFd, FromRawFd},
+    os::unix::prelude::{AsRawFd, OwnedFd},
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
 use nix::{fcntl::OFlag, sys::stat::Mode, unistd::UnlinkatFlags};
 
-use crate::error::LinuxResult;
+use crate::{error::LinuxResult, policy, sandbox};
 
 use super::TaskFsContext;
 
+/// The size of the fd table (like `RLIMIT_NOFILE`).
+const MAX_FDS: i32 = 4096;
+
 pub enum TeeFile {
     Stdio(i32),
-    File(File),
+    File {
+        file: File,
+        /// The guest path the file was opened at.
+        path: PathBuf,
+    },
+    /// A host socket.
+    Socket {
+        socket: OwnedFd,
+        /// The guest path of the Unix socket file it is bound to, which the
+        /// host would report as a path of its own.
+        bound_path: Option<String>,
+    },
+    /// An fd number taken by a file that the enclave serves itself.
+    Reserved,
+}
+
+impl TeeFile {
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            TeeFile::Stdio(_) | TeeFile::Socket { .. } | TeeFile::Reserved => None,
+            TeeFile::File { path, .. } => Some(path),
+        }
+    }
 }
 
 impl AsRawFd for TeeFile {
     fn as_raw_fd(&self) -> std::os::unix::prelude::RawFd {
         match self {
             TeeFile::Stdio(fd) => *fd,
-            TeeFile::File(file) => file.as_raw_fd(),
+            TeeFile::File { file, .. } => file.as_raw_fd(),
+            TeeFile::Socket { socket, .. } => socket.as_raw_fd(),
+            // Fails any host operation with EBADF
+            TeeFile::Reserved => -1,
         }
     }
 }
@@ -29,12 +58,27 @@ impl TaskFsContext {
         self.fd_mappings.get(&fd).ok_or(nix::Error::EBADFD)
     }
 
-    fn find_free_fd(&self) -> i32 {
+    /// Find the guest path of `fd`, as the guest would write it.
+    pub fn fd_path(&self, fd: i32) -> LinuxResult<String> {
+        let file = self.find_fd(fd)?.lock().unwrap();
+        let path = file.path().ok_or(nix::Error::ENOENT)?;
+        Ok(Self::to_kernel_path(path))
+    }
+
+    /// Find the lowest free fd that is at least `min`.
+    fn find_free_fd(&self, min: i32) -> LinuxResult<i32> {
         // naive implementation
-        (0..4096)
-            .filter(|fd| !self.fd_mappings.contains_key(fd))
-            .next()
-            .unwrap()
+        (min..MAX_FDS)
+            .find(|fd| !self.fd_mappings.contains_key(fd))
+            .ok_or(nix::Error::EMFILE)
+    }
+
+    /// Put `file` in the fd table at `fd`, replacing whatever was there.
+    fn install(&mut self, fd: i32, file: Arc<Mutex<TeeFile>>, cloexec: bool) {
+        if self.fd_mappings.insert(fd, file).is_some() {
+            log::debug!("Overwriting fd {}", fd);
+        }
+        self.set_cloexec(fd, cloexec);
     }
 
     pub fn open(
@@ -44,48 +88,81 @@ impl TaskFsContext {
         flags: i32,
         mode: u32,
     ) -> LinuxResult<i32> {
-        let path = self.resolve_path(&path);
-        let dir_fd = dir_fd
-            .map(|fd| {
-                self.fd_mappings
-                    .get(&fd)
-                    .expect("no such fd")
-                    .lock()
-                    .unwrap()
-                    .as_raw_fd()
-            })
-            .unwrap_or(nix::libc::AT_FDCWD);
-        let fd = nix::fcntl::openat(
-            dir_fd,
+        let path = self.resolve_path_at(dir_fd.unwrap_or(nix::libc::AT_FDCWD), path)?;
+        let file = policy::open(
             &path,
             OFlag::from_bits_truncate(flags),
             Mode::from_bits_truncate(mode),
         )?;
-        let file = Arc::new(Mutex::new(TeeFile::File(unsafe { File::from_raw_fd(fd) })));
+        let file = Arc::new(Mutex::new(TeeFile::File { file, path }));
 
-        let dest_fd = self.find_free_fd();
-        self.fd_mappings.insert(dest_fd, file);
+        let dest_fd = self.find_free_fd(0)?;
+        self.install(dest_fd, file, flags & nix::libc::O_CLOEXEC != 0);
         Ok(dest_fd)
     }
 
+    /// Put the host socket `socket` in the lowest free fd.
+    pub fn install_socket(&mut self, socket: OwnedFd, cloexec: bool) -> LinuxResult<i32> {
+        let fd = self.find_free_fd(0)?;
+        let file = TeeFile::Socket {
+            socket,
+            bound_path: None,
+        };
+        self.install(fd, Arc::new(Mutex::new(file)), cloexec);
+        Ok(fd)
+    }
+
+    pub fn reserve_fd(&mut self, cloexec: bool) -> LinuxResult<i32> {
+        let fd = self.find_free_fd(0)?;
+        self.install(fd, Arc::new(Mutex::new(TeeFile::Reserved)), cloexec);
+        Ok(fd)
+    }
+
     pub fn close(&mut self, fd: i32) -> LinuxResult<()> {
         if self.fd_mappings.remove(&fd).is_some() {
+            self.cloexec.remove(&fd);
             Ok(())
         } else {
             Err(nix::Error::EBADFD)
         }
     }
 
-    pub fn dup(&mut self, src_fd: i32, dest_fd: Option<i32>) -> LinuxResult<i32> {
-        let src_fd = Arc::clone(self.fd_mappings.get(&src_fd).ok_or(nix::Error::EBADFD)?);
-        let dest_fd = dest_fd.unwrap_or_else(|| self.find_free_fd());
-        if self.fd_mappings.remove(&dest_fd).is_some() {
-            log::debug!("dup2: overwriting fd {}", dest_fd);
-        }
-        self.fd_mappings.insert(dest_fd, src_fd);
+    pub fn dup(&mut self, src_fd: i32, dest_fd: Option<i32>, cloexec: bool) -> LinuxResult<i32> {
+        let src_fd = Arc::clone(self.find_fd(src_fd)?);
+        let dest_fd = match dest_fd {
+            Some(dest_fd) => dest_fd,
+            None => self.find_free_fd(0)?,
+        };
+        self.install(dest_fd, src_fd, cloexec);
+        Ok(dest_fd)
+    }
+
+    /// Duplicate `src_fd` at the lowest free fd that is at least `min` (like
+    /// `F_DUPFD`).
+    pub fn dup_above(&mut self, src_fd: i32, min: u64, cloexec: bool) -> LinuxResult<i32> {
+        let min = i32::try_from(min)
+            .ok()
+            .filter(|&min| min < MAX_FDS)
+            .ok_or(nix::Error::EINVAL)?;
+        let src_fd = Arc::clone(self.find_fd(src_fd)?);
+        let dest_fd = self.find_free_fd(min)?;
+        self.install(dest_fd, src_fd, cloexec);
         Ok(dest_fd)
     }
 
+    pub fn cloexec(&self, fd: i32) -> LinuxResult<bool> {
+        self.find_fd(fd)?;
+        Ok(self.cloexec.contains(&fd))
+    }
+
+    pub fn set_cloexec(&mut self, fd: i32, cloexec: bool) {
+        if cloexec {
+            self.cloexec.insert(fd);
+        } else {
+            self.cloexec.remove(&fd);
+        }
+    }
+
     pub fn unlink_at(&self, dir_fd: i32, path: &str, flags: i32) -> LinuxResult<()> {
         let flags = match flags {
             0 => UnlinkatFlags::NoRemoveDir,
@@ -95,16 +172,17 @@ impl TaskFsContext {
                 return Err(nix::Error::EINVAL);
             }
         };
-        if dir_fd == nix::libc::AT_FDCWD {
-            let path = self.resolve_path(path);
-            nix::unistd::unlinkat(None, &path, flags)
-        } else {
-            log::debug!("unlinkat: dirfd = {}", dir_fd);
-            let dir_file = self.fd_mappings.get(&dir_fd).ok_or_else(|| {
-                log::warn!("unlinkat: unknown dir fd: {}", dir_fd);
-                nix::Error::EBADFD
-            })?;
-            nix::unistd::unlinkat(Some(dir_file.lock().unwrap().as_raw_fd()), path, flags)
-        }
+        let path = self.resolve_path_at(dir_fd, path)?;
+        let (parent, name) = sandbox::open_parent(&path)?;
+        policy::check_entry(&path, &parent, name)?;
+        nix::unistd::unlinkat(Some(parent.as_raw_fd()), name, flags)
+    }
+
+    pub fn truncate(&self, path: &str, len: i64) -> LinuxResult<()> {
+        let path = self.resolve_path(path);
+        // Without O_NONBLOCK, opening a FIFO would wait for a reader
+        let file = policy::open(&path, OFlag::O_WRONLY | OFlag::O_NONBLOCK, Mode::empty())?;
+        policy::check_size(file.as_raw_fd(), len)?;
+        nix::unistd::ftruncate(file.as_raw_fd(), len)
     }
 }
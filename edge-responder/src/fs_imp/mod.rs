@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::PathBuf,
     sync::{Arc, Mutex},
 };
@@ -7,14 +7,17 @@ use std::{
 mod cwd;
 mod dir;
 mod file;
+mod stat;
 
-use self::file::TeeFile;
+pub use self::file::TeeFile;
 
 /// The file system context of a remote task.
 #[derive(Clone)]
 pub struct TaskFsContext {
     cwd: PathBuf,
     fd_mappings: HashMap<i32, Arc<Mutex<TeeFile>>>,
+    /// The fds with `FD_CLOEXEC` set.
+    cloexec: HashSet<i32>,
 }
 
 impl TaskFsContext {
@@ -28,6 +31,7 @@ impl TaskFsContext {
         TaskFsContext {
             cwd: PathBuf::new(),
             fd_mappings,
+            cloexec: HashSet::new(),
         }
     }
 }
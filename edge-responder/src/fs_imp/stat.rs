@@ -0,0 +1,83 @@
+use std::os::unix::prelude::AsRawFd;
+
+use edge_proto::{Stat, Timespec};
+use nix::{fcntl::OFlag, libc, sys::stat::SFlag, unistd::AccessFlags};
+
+use crate::{error::LinuxResult, policy, policy::Access};
+
+use super::TaskFsContext;
+
+/// Convert the host's `struct stat`.
+// The field types differ between architectures
+#[allow(clippy::unnecessary_cast)]
+fn stat_of(stat: &libc::stat) -> Stat {
+    Stat {
+        dev: stat.st_dev as u64,
+        ino: stat.st_ino as u64,
+        mode: stat.st_mode as u32,
+        nlink: stat.st_nlink as u32,
+        uid: stat.st_uid,
+        gid: stat.st_gid,
+        rdev: stat.st_rdev as u64,
+        size: stat.st_size as i64,
+        blksize: stat.st_blksize as i64,
+        blocks: stat.st_blocks as i64,
+        atime: Timespec {
+            sec: stat.st_atime,
+            nsec: stat.st_atime_nsec,
+        },
+        mtime: Timespec {
+            sec: stat.st_mtime,
+            nsec: stat.st_mtime_nsec,
+        },
+        ctime: Timespec {
+            sec: stat.st_ctime,
+            nsec: stat.st_ctime_nsec,
+        },
+    }
+}
+
+impl TaskFsContext {
+    pub fn fstat(&self, fd: i32) -> LinuxResult<Stat> {
+        let file = self.find_fd(fd)?.lock().unwrap();
+        Ok(stat_of(&nix::sys::stat::fstat(file.as_raw_fd())?))
+    }
+
+    pub fn fstat_at(&self, dir_fd: i32, path: &str, flags: i32) -> LinuxResult<Stat> {
+        let lookup_flags = match flags {
+            0 => OFlag::empty(),
+            libc::AT_SYMLINK_NOFOLLOW => OFlag::O_NOFOLLOW,
+            other => {
+                log::warn!("fstatat: unknown flags: {}", other);
+                return Err(nix::Error::EINVAL);
+            }
+        };
+        let path = self.resolve_path_at(dir_fd, path)?;
+        let file = policy::lookup(&path, lookup_flags, Access::Read)?;
+        Ok(stat_of(&nix::sys::stat::fstat(file.as_raw_fd())?))
+    }
+
+    pub fn access_at(&self, dir_fd: i32, path: &str, mode: i32) -> LinuxResult<()> {
+        let mode = AccessFlags::from_bits(mode).ok_or(nix::Error::EINVAL)?;
+        let access = if mode.contains(AccessFlags::W_OK) {
+            Access::Write
+        } else {
+            Access::Read
+        };
+        let path = self.resolve_path_at(dir_fd, path)?;
+        let file = policy::lookup(&path, OFlag::empty(), access)?;
+        // The permissions are those of the file the lookup found
+        nix::unistd::access(format!("/proc/self/fd/{}", file.as_raw_fd()).as_str(), mode)
+    }
+
+    pub fn readlink_at(&self, dir_fd: i32, path: &str) -> LinuxResult<String> {
+        let path = self.resolve_path_at(dir_fd, path)?;
+        let link = policy::lookup(&path, OFlag::O_NOFOLLOW, Access::Read)?;
+        let mode = nix::sys::stat::fstat(link.as_raw_fd())?.st_mode;
+        if SFlag::from_bits_truncate(mode) & SFlag::S_IFMT != SFlag::S_IFLNK {
+            return Err(nix::Error::EINVAL);
+        }
+        let target = nix::fcntl::readlinkat(link.as_raw_fd(), "")?;
+        Ok(target.to_string_lossy().into_owned())
+    }
+}
@@ -1,13 +1,19 @@
 use anyhow::Context;
-use edge_proto::{server::EdgeStream, EdgeCallReq, EdgeCallResp};
+use edge_proto::{server::EdgeStream, EdgeCallReq, EdgeCallResp, EdgeError};
 use error::{SyscallError, SyscallResult};
+use std::io::Write;
 
 use crate::error::EdgeErrorCompat;
 
 mod edge_file;
 pub mod error;
 mod fs_imp;
+pub mod net;
+pub mod notify;
 mod pcb;
+pub mod policy;
+pub mod sandbox;
+mod socket_imp;
 mod syscall_imp;
 
 pub fn handle_edge_call(stream: &mut dyn EdgeStream) -> anyhow::Result<()> {
@@ -24,9 +30,8 @@ pub fn handle_edge_call_req(
     match header {
         Print { len } => {
             let data = &stream.read_data().compat().context("read data")?[0..len as usize];
-            let text = std::str::from_utf8(data)
-                .expect("the enclave tries to print an invalid UTF-8 string");
-            print!("{}", text);
+            // Output written to /dev/tty need not be valid UTF-8
+            std::io::stdout().write_all(data).context("print")?;
             stream
                 .write_header(&EdgeCallResp::Ok)
                 .compat()
@@ -49,6 +54,50 @@ pub fn handle_edge_call_req(
             let result = syscall_imp::write(stream, pid, fd, len);
             write_syscall_result(stream, result).context("write result")?;
         }
+        SyscallReadV { pid, fd, regions } => match syscall_imp::read_would_block(pid, fd) {
+            Ok(true) => {
+                stream
+                    .write_header(&EdgeCallResp::WouldBlock)
+                    .compat()
+                    .context("write header")?;
+            }
+            would_block => {
+                let result =
+                    would_block.and_then(|_| syscall_imp::readv(stream, pid, fd, regions, None));
+                write_syscall_result(stream, result).context("write result")?;
+            }
+        },
+        SyscallWriteV { pid, fd, regions } => {
+            let result = syscall_imp::writev(stream, pid, fd, regions, None);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallPreadV {
+            pid,
+            fd,
+            regions,
+            offset,
+        } => {
+            let result = syscall_imp::readv(stream, pid, fd, regions, Some(offset));
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallPwriteV {
+            pid,
+            fd,
+            regions,
+            offset,
+        } => {
+            let result = syscall_imp::writev(stream, pid, fd, regions, Some(offset));
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallLseek {
+            pid,
+            fd,
+            offset,
+            whence,
+        } => {
+            let result = syscall_imp::lseek(pid, fd, offset, whence);
+            write_syscall_result(stream, result).context("write result")?;
+        }
         SyscallClose { pid, fd } => {
             let result = syscall_imp::close(stream, pid, fd);
             write_syscall_result(stream, result).context("write result")?;
@@ -57,8 +106,9 @@ pub fn handle_edge_call_req(
             pid,
             src_fd,
             dest_fd,
+            cloexec,
         } => {
-            let result = syscall_imp::dup(stream, pid, src_fd, dest_fd);
+            let result = syscall_imp::dup(stream, pid, src_fd, dest_fd, cloexec);
             write_syscall_result(stream, result).context("write result")?;
         }
         SyscallMkdirAt {
@@ -81,7 +131,30 @@ pub fn handle_edge_call_req(
             syscall_imp::special_getdents64(stream, pid, fd, len)?;
         }
         SyscallFstat { pid, fd } => {
-            syscall_imp::special_fstat(stream, pid, fd)?;
+            let result = syscall_imp::fstat(pid, fd);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallFstatAt {
+            pid,
+            dir_fd,
+            path,
+            flags,
+        } => {
+            let result = syscall_imp::fstatat(pid, dir_fd, path, flags);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallFaccessAt {
+            pid,
+            dir_fd,
+            path,
+            mode,
+        } => {
+            let result = syscall_imp::faccessat(pid, dir_fd, path, mode);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallReadLinkAt { pid, dir_fd, path } => {
+            let result = syscall_imp::readlinkat(pid, dir_fd, path);
+            write_syscall_reply(stream, result).context("write result")?;
         }
         SyscallUnlinkAt {
             pid,
@@ -92,6 +165,144 @@ pub fn handle_edge_call_req(
             let result = syscall_imp::unlinkat(stream, pid, dir_fd, path, flags);
             write_syscall_result(stream, result).context("write result")?;
         }
+        SyscallRenameAt {
+            pid,
+            old_dir_fd,
+            old_path,
+            new_dir_fd,
+            new_path,
+            flags,
+        } => {
+            let result =
+                syscall_imp::renameat(pid, old_dir_fd, old_path, new_dir_fd, new_path, flags);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallLinkAt {
+            pid,
+            old_dir_fd,
+            old_path,
+            new_dir_fd,
+            new_path,
+            flags,
+        } => {
+            let result =
+                syscall_imp::linkat(pid, old_dir_fd, old_path, new_dir_fd, new_path, flags);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallSymlinkAt {
+            pid,
+            target,
+            dir_fd,
+            path,
+        } => {
+            let result = syscall_imp::symlinkat(pid, target, dir_fd, path);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallTruncate { pid, path, len } => {
+            let result = syscall_imp::truncate(pid, path, len);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallFtruncate { pid, fd, len } => {
+            let result = syscall_imp::ftruncate(pid, fd, len);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallFsync { pid, fd, data_only } => {
+            let result = syscall_imp::fsync(pid, fd, data_only);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallFcntl { pid, fd, cmd, arg } => {
+            let result = syscall_imp::fcntl(pid, fd, cmd, arg);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallIoctl {
+            pid,
+            fd,
+            request,
+            len,
+        } => {
+            let result = syscall_imp::ioctl(stream, pid, fd, request, len);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallPoll { pid, fds, watch } => {
+            let result = syscall_imp::poll(stream, pid, fds, watch);
+            write_syscall_result(stream, result).context("write result")?;
+        }
+        SyscallSocket {
+            pid,
+            domain,
+            ty,
+            protocol,
+        } => {
+            let result = socket_imp::socket(pid, domain, ty, protocol);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallBind { pid, fd, addr } => {
+            let result = socket_imp::bind(pid, fd, addr);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallListen { pid, fd, backlog } => {
+            let result = socket_imp::listen(pid, fd, backlog);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallAccept { pid, fd, flags } => {
+            let result = socket_imp::accept(pid, fd, flags);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallConnect { pid, fd, addr } => {
+            let result = socket_imp::connect(pid, fd, addr);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallSendTo {
+            pid,
+            fd,
+            len,
+            flags,
+            addr,
+        } => {
+            let result = socket_imp::sendto(stream, pid, fd, len, flags, addr);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallRecvFrom {
+            pid,
+            fd,
+            len,
+            flags,
+        } => {
+            let result = socket_imp::recvfrom(stream, pid, fd, len, flags);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallShutdown { pid, fd, how } => {
+            let result = socket_imp::shutdown(pid, fd, how);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallGetSockOpt {
+            pid,
+            fd,
+            level,
+            name,
+            len,
+        } => {
+            let result = socket_imp::getsockopt(stream, pid, fd, level, name, len);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallSetSockOpt {
+            pid,
+            fd,
+            level,
+            name,
+            value,
+        } => {
+            let result = socket_imp::setsockopt(pid, fd, level, name, value);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallGetSockName { pid, fd } => {
+            let result = socket_imp::getsockname(pid, fd);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        SyscallGetPeerName { pid, fd } => {
+            let result = socket_imp::getpeername(pid, fd);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
         PcbDup { from, to } => {
             let mut tasks = pcb::TASKS.lock().unwrap();
             let new_pcb = tasks.get(&from).expect("no such PID?!").clone();
@@ -110,6 +321,16 @@ pub fn handle_edge_call_req(
                 .compat()
                 .context("write header")?;
         }
+        FdPath { pid, fd } => {
+            write_anyhow_result(
+                stream,
+                syscall_imp::fd_path(pid, fd).map(EdgeCallResp::OkWithString),
+            )?;
+        }
+        FdReserve { pid, cloexec } => {
+            let result = syscall_imp::reserve_fd(stream, pid, cloexec);
+            write_syscall_result(stream, result).context("write result")?;
+        }
         FileOpen { path } => {
             write_anyhow_result(
                 stream,
@@ -144,6 +365,24 @@ pub fn handle_edge_call_req(
                 .compat()
                 .context("write header")?;
         }
+        WallClock => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .context("the wall clock is set before the Unix epoch")
+                .map(|now| EdgeCallResp::OkWithU64(now.as_nanos() as u64));
+            write_anyhow_result(stream, now)?;
+        }
+        NetConfig => {
+            write_syscall_reply(stream, net::config()).context("write result")?;
+        }
+        NetTransmit { len } => {
+            let result = net::transmit(stream, len);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
+        NetReceive => {
+            let result = net::receive(stream);
+            write_syscall_reply(stream, result).context("write result")?;
+        }
         other @ (Invalid | StreamShutdown) => {
             log::warn!("Invalid edge call {:?}, ignoring", other);
         }
@@ -152,30 +391,49 @@ pub fn handle_edge_call_req(
     Ok(())
 }
 
+/// Log the error of a failed syscall, returning its errno unless it is an
+/// internal error.
+fn syscall_errno(err: SyscallError) -> anyhow::Result<nix::Error> {
+    match err {
+        SyscallError::Linux(errno, None) => {
+            // TODO: display the syscall type and arguments
+            log::warn!("Error handling syscall: {}", errno);
+            Ok(errno)
+        }
+        SyscallError::Linux(errno, Some(err)) => {
+            log::warn!("Error handling syscall: {}, caused by: {:#}", errno, err);
+            Ok(errno)
+        }
+        SyscallError::Internal(err) => Err(err).context("error handling syscall"),
+    }
+}
+
 fn write_syscall_result(
     stream: &mut dyn EdgeStream,
     result: SyscallResult<isize>,
 ) -> anyhow::Result<()> {
     let result_as_isize = match result {
         Ok(r) => r,
-        Err(SyscallError::Linux(errno, None)) => {
-            // TODO: display the syscall type and arguments
-            log::warn!("Error handling syscall: {}", errno);
-            -(errno as isize)
-        }
-        Err(SyscallError::Linux(errno, Some(err))) => {
-            log::warn!("Error handling syscall: {}, caused by: {:#}", errno, err);
-            -(errno as isize)
-        }
-        Err(SyscallError::Internal(err)) => {
-            return Err(err).context("error handling syscall");
-        }
+        Err(err) => -(syscall_errno(err)? as isize),
     };
     stream
         .write_header(&EdgeCallResp::SyscallResp(result_as_isize as i64))
         .compat()
 }
 
+/// Write the reply of a syscall whose result does not fit in an integer,
+/// which is [`EdgeCallResp::Error`] if it fails.
+fn write_syscall_reply(
+    stream: &mut dyn EdgeStream,
+    result: SyscallResult<EdgeCallResp>,
+) -> anyhow::Result<()> {
+    let header = match result {
+        Ok(header) => header,
+        Err(err) => EdgeCallResp::Error(EdgeError::new(syscall_errno(err)? as i32, None)),
+    };
+    stream.write_header(&header).compat()
+}
+
 fn write_anyhow_result(
     stream: &mut dyn EdgeStream,
     result: anyhow::Result<EdgeCallResp>,
@@ -185,7 +443,10 @@ fn write_anyhow_result(
             Ok(header) => header,
             Err(err) => {
                 log::error!("Failed to perform edge call: {:#}", err);
-                EdgeCallResp::Error
+                EdgeCallResp::Error(EdgeError::new(
+                    error::errno_of(&err),
+                    Some(format!("{:#}", err)),
+                ))
             }
         })
         .compat()
@@ -0,0 +1,189 @@
+//! The network link of the enclave's network stack.
+//!
+//! With a network stack inside the enclave, the host only moves Ethernet
+//! frames between the enclave and a link: a TAP device, or one end of a
+//! datagram socket pair whose other end is a peer in the same process, for
+//! tests. Frames are passed as they are, the host never looks into them.
+
+use std::{
+    net::Ipv4Addr,
+    os::unix::{
+        net::UnixDatagram,
+        prelude::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd},
+    },
+    sync::OnceLock,
+};
+
+use anyhow::Context;
+use edge_proto::{server::EdgeStream, EdgeCallResp, NetConfig};
+use nix::{
+    errno::Errno,
+    fcntl::{FcntlArg, OFlag},
+    libc,
+    poll::PollFlags,
+};
+
+use crate::{
+    error::{EdgeErrorCompat, SyscallResult},
+    notify,
+};
+
+/// The environment variable naming the TAP device to use as the link if
+/// [`set_link`] is not called. There is no link if it is not set either.
+pub const TAP_DEVICE_ENV: &str = "EDGE_TAP_DEVICE";
+/// The environment variable with the enclave's address and prefix length on
+/// the link, such as `10.0.0.2/24`, which is the default.
+pub const NET_ADDR_ENV: &str = "EDGE_NET_ADDR";
+/// The environment variable with the address of the default gateway, if any.
+pub const NET_GATEWAY_ENV: &str = "EDGE_NET_GATEWAY";
+/// The environment variable with the enclave's MAC address.
+pub const NET_MAC_ENV: &str = "EDGE_NET_MAC";
+
+/// The configuration of the link if the environment does not set it: a
+/// locally administered MAC address, and the second address of `10.0.0.0/24`.
+pub const DEFAULT_NET_CONFIG: NetConfig = NetConfig {
+    mac: [0x02, 0, 0, 0, 0, 0x01],
+    addr: [10, 0, 0, 2],
+    prefix_len: 24,
+    gateway: None,
+};
+
+const TUNSETIFF: libc::c_ulong = 0x400454ca;
+const IFF_TAP: libc::c_short = 0x0002;
+const IFF_NO_PI: libc::c_short = 0x1000;
+
+static LINK: OnceLock<Option<Link>> = OnceLock::new();
+
+struct Link {
+    fd: OwnedFd,
+    config: NetConfig,
+}
+
+/// Carry the frames of the enclave over `fd`, which must be a TAP device or
+/// a datagram socket. This must be done before the enclave asks for the
+/// link, and only once.
+pub fn set_link(fd: OwnedFd, config: NetConfig) -> anyhow::Result<()> {
+    nix::fcntl::fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK))
+        .context("make the link non-blocking")?;
+    LINK.set(Some(Link { fd, config }))
+        .map_err(|_| anyhow::anyhow!("the network link is already set"))?;
+    log::info!("Network link: {:?}", config);
+    Ok(())
+}
+
+/// Make a link whose other end is returned, for a peer in this process.
+pub fn frame_pipe(config: NetConfig) -> anyhow::Result<UnixDatagram> {
+    let (link, peer) = UnixDatagram::pair().context("create socket pair")?;
+    set_link(unsafe { OwnedFd::from_raw_fd(link.into_raw_fd()) }, config)?;
+    Ok(peer)
+}
+
+/// Open the TAP device `name`, without the packet information header.
+pub fn open_tap(name: &str) -> anyhow::Result<OwnedFd> {
+    #[repr(C)]
+    struct IfReq {
+        name: [u8; libc::IFNAMSIZ],
+        flags: libc::c_short,
+        _pad: [u8; 22],
+    }
+
+    let mut req = IfReq {
+        name: [0; libc::IFNAMSIZ],
+        flags: IFF_TAP | IFF_NO_PI,
+        _pad: [0; 22],
+    };
+    anyhow::ensure!(name.len() < libc::IFNAMSIZ, "TAP device name too long");
+    req.name[0..name.len()].copy_from_slice(name.as_bytes());
+
+    let fd = nix::fcntl::open(
+        "/dev/net/tun",
+        OFlag::O_RDWR | OFlag::O_CLOEXEC,
+        nix::sys::stat::Mode::empty(),
+    )
+    .context("open /dev/net/tun")?;
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    Errno::result(unsafe { libc::ioctl(fd.as_raw_fd(), TUNSETIFF, &mut req) })
+        .with_context(|| format!("attach to TAP device {}", name))?;
+    Ok(fd)
+}
+
+fn config_from_env() -> anyhow::Result<NetConfig> {
+    let mut config = DEFAULT_NET_CONFIG;
+    if let Ok(addr) = std::env::var(NET_ADDR_ENV) {
+        let (addr, prefix_len) = addr
+            .split_once('/')
+            .with_context(|| format!("{} is not an address with a prefix length", addr))?;
+        config.addr = addr.parse::<Ipv4Addr>()?.octets();
+        config.prefix_len = prefix_len.parse()?;
+        anyhow::ensure!(config.prefix_len <= 32, "invalid prefix length");
+    }
+    if let Ok(gateway) = std::env::var(NET_GATEWAY_ENV) {
+        config.gateway = Some(gateway.parse::<Ipv4Addr>()?.octets());
+    }
+    if let Ok(mac) = std::env::var(NET_MAC_ENV) {
+        let bytes = mac
+            .split(':')
+            .map(|byte| u8::from_str_radix(byte, 16))
+            .collect::<Result<Vec<u8>, _>>()?;
+        config.mac = bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("{} is not a MAC address", mac))?;
+    }
+    Ok(config)
+}
+
+fn link() -> Option<&'static Link> {
+    LINK.get_or_init(|| {
+        let name = std::env::var(TAP_DEVICE_ENV).ok()?;
+        let link = open_tap(&name).and_then(|fd| {
+            nix::fcntl::fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK))?;
+            Ok(Link {
+                fd,
+                config: config_from_env()?,
+            })
+        });
+        match link {
+            Ok(link) => {
+                log::info!("Network link: {}, {:?}", name, link.config);
+                Some(link)
+            }
+            Err(err) => {
+                log::error!("No network link: {:#}", err);
+                None
+            }
+        }
+    })
+    .as_ref()
+}
+
+pub fn config() -> SyscallResult<EdgeCallResp> {
+    let link = link().ok_or(Errno::ENODEV)?;
+    Ok(EdgeCallResp::OkWithNetConfig(link.config))
+}
+
+pub fn transmit(stream: &mut dyn EdgeStream, len: u64) -> SyscallResult<EdgeCallResp> {
+    let link = link().ok_or(Errno::ENODEV)?;
+    let data = stream.read_data().compat().context("read data")?;
+    let frame = data.get(0..len as usize).ok_or(Errno::EINVAL)?;
+    match nix::unistd::write(link.fd.as_raw_fd(), frame) {
+        // A frame the link has no room for is dropped, as on a real network
+        Ok(_) | Err(Errno::EAGAIN | Errno::ENOBUFS) => Ok(EdgeCallResp::Ok),
+        Err(errno) => Err(errno.into()),
+    }
+}
+
+pub fn receive(stream: &mut dyn EdgeStream) -> SyscallResult<EdgeCallResp> {
+    let link = link().ok_or(Errno::ENODEV)?;
+    let zone = stream
+        .data_zone_mut()
+        .compat()
+        .context("borrow data zone")?;
+    match nix::unistd::read(link.fd.as_raw_fd(), zone) {
+        Ok(len) => Ok(EdgeCallResp::OkWithU64(len as u64)),
+        Err(Errno::EAGAIN) => {
+            notify::watch(link.fd.as_raw_fd(), PollFlags::POLLIN);
+            Ok(EdgeCallResp::WouldBlock)
+        }
+        Err(errno) => Err(errno.into()),
+    }
+}
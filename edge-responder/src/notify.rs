@@ -0,0 +1,154 @@
+//! Posting host events to the enclave's notification area.
+
+use std::{
+    os::unix::prelude::RawFd,
+    sync::{
+        atomic::{AtomicI32, AtomicPtr, Ordering},
+        Mutex, Once,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use edge_proto::notify::{Notification, NotificationArea};
+use nix::{
+    fcntl::OFlag,
+    poll::{PollFd, PollFlags},
+    sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal},
+};
+
+/// How often timer ticks are posted.
+pub const TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+static NOTIFICATION_AREA: AtomicPtr<NotificationArea> = AtomicPtr::new(std::ptr::null_mut());
+static START_NOTIFIER: Once = Once::new();
+
+/// Host files that a task waits for, with the events it waits for, until they
+/// become ready.
+static WATCHED: Mutex<Vec<(RawFd, PollFlags)>> = Mutex::new(Vec::new());
+/// The write end of a pipe that interrupts the watcher thread when a file is
+/// added to [`WATCHED`], or -1 before the thread is started.
+static WATCHER_WAKE: AtomicI32 = AtomicI32::new(-1);
+
+/// Set the notification area that [`post`] writes to. A null pointer
+/// disables posting, e.g. before the shared memory is unmapped.
+///
+/// # Safety
+///
+/// `area` must be null or stay valid until it is replaced.
+pub unsafe fn set_notification_area(area: *const NotificationArea) {
+    NOTIFICATION_AREA.store(area as *mut _, Ordering::SeqCst);
+}
+
+/// Post `notification` to the enclave, if a notification area is set.
+///
+/// This only does atomic operations, so it may be called from signal handlers.
+pub fn post(notification: Notification) {
+    let area = NOTIFICATION_AREA.load(Ordering::SeqCst);
+    if let Some(area) = unsafe { area.as_ref() } {
+        area.post(notification);
+    }
+}
+
+/// Post [`Notification::IoReady`] once `fd` is ready for one of `events` (or
+/// hung up).
+pub fn watch(fd: RawFd, events: PollFlags) {
+    let mut watched = WATCHED.lock().unwrap();
+    match watched.iter_mut().find(|(watched_fd, _)| *watched_fd == fd) {
+        Some((_, watched_events)) => *watched_events |= events,
+        None => watched.push((fd, events)),
+    }
+    let wake = WATCHER_WAKE.load(Ordering::SeqCst);
+    if wake >= 0 {
+        // A full pipe wakes the watcher up as well
+        let _ = nix::unistd::write(wake, &[0]);
+    }
+}
+
+extern "C" fn forward_signal(signo: nix::libc::c_int) {
+    if signo == nix::libc::SIGTERM {
+        post(Notification::Shutdown);
+    } else {
+        post(Notification::Signal(signo as u8));
+    }
+}
+
+/// Start posting host events: readiness of watched files and timer ticks
+/// from helper threads, `SIGINT` as a signal, and `SIGTERM` as a shutdown
+/// request.
+///
+/// The signal handlers are reset once they have run, so that a second Ctrl-C
+/// still terminates an unresponsive runner. Calling this more than once has no
+/// further effect.
+pub fn start_notifier() -> anyhow::Result<()> {
+    let mut result = Ok(());
+    START_NOTIFIER.call_once(|| {
+        result = start_notifier_once();
+    });
+    result
+}
+
+fn start_notifier_once() -> anyhow::Result<()> {
+    let action = SigAction::new(
+        SigHandler::Handler(forward_signal),
+        SaFlags::SA_RESETHAND | SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    for signal in [Signal::SIGINT, Signal::SIGTERM] {
+        unsafe { nix::sys::signal::sigaction(signal, &action) }
+            .with_context(|| format!("install {} handler", signal))?;
+    }
+
+    std::thread::Builder::new()
+        .name("notify-timer".into())
+        .spawn(|| loop {
+            std::thread::sleep(TICK_INTERVAL);
+            post(Notification::TimerTick);
+        })
+        .context("spawn timer thread")?;
+
+    let (wake_read, wake_write) = nix::unistd::pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK)
+        .context("create the watcher's wake-up pipe")?;
+    WATCHER_WAKE.store(wake_write, Ordering::SeqCst);
+    std::thread::Builder::new()
+        .name("notify-io".into())
+        .spawn(move || {
+            if let Err(err) = watch_files(wake_read) {
+                log::warn!("No longer watching host files: {:#}", err);
+            }
+        })
+        .context("spawn watcher thread")?;
+
+    Ok(())
+}
+
+/// Wait for the watched files to become ready, and report them to the enclave.
+fn watch_files(wake_read: RawFd) -> anyhow::Result<()> {
+    loop {
+        let watched = WATCHED.lock().unwrap().clone();
+        let mut fds: Vec<_> = std::iter::once((wake_read, PollFlags::POLLIN))
+            .chain(watched.iter().copied())
+            .map(|(fd, events)| PollFd::new(fd, events))
+            .collect();
+        nix::poll::poll(&mut fds, -1).context("poll watched files")?;
+
+        let is_ready = |fd: &PollFd| fd.revents().is_some_and(|revents| !revents.is_empty());
+        if is_ready(&fds[0]) {
+            // Drain the pipe, and start over with the new set of files
+            while nix::unistd::read(wake_read, &mut [0; 64]).is_ok_and(|len| len > 0) {}
+        }
+        let ready: Vec<RawFd> = watched
+            .iter()
+            .zip(&fds[1..])
+            .filter(|(_, fd)| is_ready(fd))
+            .map(|(&(fd, _), _)| fd)
+            .collect();
+        if !ready.is_empty() {
+            WATCHED
+                .lock()
+                .unwrap()
+                .retain(|(fd, _)| !ready.contains(fd));
+            post(Notification::IoReady);
+        }
+    }
+}
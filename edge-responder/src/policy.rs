@@ -0,0 +1,298 @@
+//! A host file system policy, restricting what the guest may do beneath the
+//! guest root.
+//!
+//! The policy file has one rule per line; `#` starts a comment:
+//!
+//! ```text
+//! read-only /
+//! writable /tmp
+//! deny /etc/secrets
+//! max-file-size 16777216
+//! ```
+//!
+//! Paths are guest paths. The rule with the longest path that contains the
+//! accessed path applies: `read-only` refuses modifications with `EROFS`,
+//! `deny` refuses any access with `EACCES`, and `writable` lifts both for a
+//! subtree. Paths not covered by any rule are writable. `max-file-size` caps
+//! the size that writes may grow a regular file to, failing with `EFBIG`.
+//!
+//! Rules are checked against the path as the guest wrote it, and against the
+//! path with symlinks resolved. Files are not created through dangling
+//! symlinks while a policy is in effect.
+
+use std::{
+    ffi::OsStr,
+    fs::File,
+    os::unix::prelude::RawFd,
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::Context;
+use nix::{
+    errno::Errno,
+    fcntl::{FcntlArg, OFlag},
+    sys::stat::{Mode, SFlag},
+    unistd::Whence,
+};
+
+use crate::{error::LinuxResult, sandbox};
+
+/// The environment variable naming the policy file if [`set_policy`] is not
+/// called. Without either, the guest may do anything the runner can.
+pub const POLICY_ENV: &str = "EDGE_FS_POLICY";
+
+static POLICY: OnceLock<Option<Policy>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    fn of_open_flags(flags: OFlag) -> Access {
+        // O_TMPFILE includes the bits of O_DIRECTORY, so it is tested on its own
+        if flags & OFlag::O_ACCMODE != OFlag::O_RDONLY
+            || flags.intersects(OFlag::O_CREAT | OFlag::O_TRUNC)
+            || flags.contains(OFlag::O_TMPFILE)
+        {
+            Access::Write
+        } else {
+            Access::Read
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    ReadOnly,
+    Writable,
+    Deny,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Policy {
+    rules: Vec<(PathBuf, Rule)>,
+    max_file_size: Option<u64>,
+}
+
+/// Turn a guest path into a comparable form, relative to the guest root.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+impl Policy {
+    pub fn parse(text: &str) -> anyhow::Result<Policy> {
+        let mut policy = Policy::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            policy
+                .parse_rule(line)
+                .with_context(|| format!("line {}: {:?}", index + 1, line))?;
+        }
+        Ok(policy)
+    }
+
+    fn parse_rule(&mut self, line: &str) -> anyhow::Result<()> {
+        let (keyword, arg) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| anyhow::anyhow!("missing argument"))?;
+        let arg = arg.trim();
+        let rule = match keyword {
+            "read-only" => Rule::ReadOnly,
+            "writable" => Rule::Writable,
+            "deny" => Rule::Deny,
+            "max-file-size" => {
+                self.max_file_size = Some(arg.parse().context("invalid size")?);
+                return Ok(());
+            }
+            other => anyhow::bail!("unknown rule {:?}", other),
+        };
+        let path = Path::new(arg);
+        if !path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+            anyhow::bail!("the path must be absolute and must not contain `..`");
+        }
+        self.rules.push((normalize(path), rule));
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Policy> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("read policy file {}", path.display()))?;
+        Policy::parse(&text).with_context(|| format!("parse policy file {}", path.display()))
+    }
+
+    /// Check whether the guest path `path` may be accessed.
+    pub fn check(&self, path: &Path, access: Access) -> LinuxResult<()> {
+        let path = normalize(path);
+        let rule = self
+            .rules
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.components().count())
+            .map(|&(_, rule)| rule);
+        let result = match (rule, access) {
+            (Some(Rule::Deny), _) => Err(Errno::EACCES),
+            (Some(Rule::ReadOnly), Access::Write) => Err(Errno::EROFS),
+            _ => Ok(()),
+        };
+        if let Err(errno) = result {
+            log::warn!(
+                "Policy denies {:?} access to /{}: {}",
+                access,
+                path.display(),
+                errno
+            );
+        }
+        result
+    }
+
+    /// Check an entry that is about to be created or removed in `parent`.
+    fn check_entry(&self, path: &Path, parent: &File, name: &OsStr) -> LinuxResult<()> {
+        self.check(path, Access::Write)?;
+        self.check(&sandbox::guest_path_of(parent)?.join(name), Access::Write)
+    }
+
+    /// Look up `path` without opening it, checking `access` against both the
+    /// path and where it really leads.
+    fn lookup(&self, path: &Path, flags: OFlag, access: Access) -> LinuxResult<File> {
+        self.check(path, access)?;
+        let target = sandbox::open(path, lookup_flags(flags), Mode::empty())?;
+        self.check(&sandbox::guest_path_of(&target)?, access)?;
+        Ok(target)
+    }
+
+    fn open(&self, path: &Path, flags: OFlag, mode: Mode) -> LinuxResult<File> {
+        match self.lookup(path, flags, Access::of_open_flags(flags)) {
+            Ok(_) if flags.contains(OFlag::O_CREAT | OFlag::O_EXCL) => Err(Errno::EEXIST),
+            Ok(target) => sandbox::reopen(&target, flags),
+            Err(Errno::ENOENT) if flags.contains(OFlag::O_CREAT) => {
+                let (parent, name) = sandbox::open_parent(path)?;
+                self.check_entry(path, &parent, name)?;
+                sandbox::open_in(&parent, name, flags, mode)
+            }
+            Err(errno) => Err(errno),
+        }
+    }
+
+    /// Find how many of `len` bytes may be written to `fd` at `offset`, or
+    /// at its current position, without exceeding the maximum file size.
+    fn write_len(&self, fd: RawFd, offset: Option<i64>, len: usize) -> LinuxResult<usize> {
+        let max = match self.max_file_size {
+            Some(max) => max,
+            None => return Ok(len),
+        };
+        let stat = nix::sys::stat::fstat(fd)?;
+        if SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT != SFlag::S_IFREG {
+            return Ok(len);
+        }
+        let flags = OFlag::from_bits_truncate(nix::fcntl::fcntl(fd, FcntlArg::F_GETFL)?);
+        // Positional writes append as well in append mode
+        let pos = if flags.contains(OFlag::O_APPEND) {
+            stat.st_size as u64
+        } else if let Some(offset) = offset {
+            offset as u64
+        } else {
+            nix::unistd::lseek(fd, 0, Whence::SeekCur)? as u64
+        };
+        let allowed = max.saturating_sub(pos).min(len as u64) as usize;
+        if allowed == 0 && len > 0 {
+            log::warn!("Policy denies growing host fd {} beyond {} bytes", fd, max);
+            return Err(Errno::EFBIG);
+        }
+        Ok(allowed)
+    }
+
+    /// Check that `fd` may be truncated or extended to `len` bytes.
+    fn check_size(&self, fd: RawFd, len: i64) -> LinuxResult<()> {
+        let max = match self.max_file_size {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+        let stat = nix::sys::stat::fstat(fd)?;
+        if SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT != SFlag::S_IFREG {
+            return Ok(());
+        }
+        if len > 0 && len as u64 > max {
+            log::warn!("Policy denies growing host fd {} beyond {} bytes", fd, max);
+            return Err(Errno::EFBIG);
+        }
+        Ok(())
+    }
+}
+
+/// Put `policy` in effect. This must be done before the first edge call that
+/// takes a path, and only once.
+pub fn set_policy(policy: Policy) -> anyhow::Result<()> {
+    POLICY
+        .set(Some(policy))
+        .map_err(|_| anyhow::anyhow!("the file system policy is already set"))
+}
+
+fn policy() -> Option<&'static Policy> {
+    POLICY
+        .get_or_init(|| {
+            let path = std::env::var_os(POLICY_ENV)?;
+            let policy = Policy::load(path.as_ref())
+                .unwrap_or_else(|err| panic!("failed to load file system policy: {:#}", err));
+            log::info!("File system policy: {:?}", path);
+            Some(policy)
+        })
+        .as_ref()
+}
+
+/// The flags of `flags` that apply to an `O_PATH` lookup.
+fn lookup_flags(flags: OFlag) -> OFlag {
+    OFlag::O_PATH | (flags & (OFlag::O_DIRECTORY | OFlag::O_NOFOLLOW))
+}
+
+/// Look up the guest path `path` as an `O_PATH` descriptor, if the policy
+/// allows `access` to it. Only `O_DIRECTORY` and `O_NOFOLLOW` of `flags` are
+/// used.
+pub fn lookup(path: &Path, flags: OFlag, access: Access) -> LinuxResult<File> {
+    match policy() {
+        Some(policy) => policy.lookup(path, flags, access),
+        None => sandbox::open(path, lookup_flags(flags), Mode::empty()),
+    }
+}
+
+/// Open the guest path `path` as allowed by the policy.
+pub fn open(path: &Path, flags: OFlag, mode: Mode) -> LinuxResult<File> {
+    match policy() {
+        Some(policy) => policy.open(path, flags, mode),
+        None => sandbox::open(path, flags, mode),
+    }
+}
+
+/// Check whether the entry `name` in `parent`, whose guest path is `path`,
+/// may be created or removed.
+pub fn check_entry(path: &Path, parent: &File, name: &OsStr) -> LinuxResult<()> {
+    match policy() {
+        Some(policy) => policy.check_entry(path, parent, name),
+        None => Ok(()),
+    }
+}
+
+/// Limit a write of `len` bytes to `fd`, at `offset` if given, to the
+/// maximum file size.
+pub fn write_len(fd: RawFd, offset: Option<i64>, len: usize) -> LinuxResult<usize> {
+    match policy() {
+        Some(policy) => policy.write_len(fd, offset, len),
+        None => Ok(len),
+    }
+}
+
+/// Check that `fd` may be truncated or extended to `len` bytes without
+/// exceeding the maximum file size.
+pub fn check_size(fd: RawFd, len: i64) -> LinuxResult<()> {
+    match policy() {
+        Some(policy) => policy.check_size(fd, len),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,142 @@
+//! Confining guest paths to the guest root directory.
+//!
+//! Guest paths are resolved by the host kernel with `openat2` and
+//! `RESOLVE_IN_ROOT` relative to the guest root, so that neither `..`
+//! components nor symlinks (absolute ones included) can lead outside of it.
+
+use std::{
+    ffi::{CString, OsStr},
+    fs::File,
+    os::unix::prelude::{AsRawFd, FromRawFd, OpenOptionsExt, OsStrExt},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::Context;
+use nix::{errno::Errno, fcntl::OFlag, libc, sys::stat::Mode};
+
+use crate::error::LinuxResult;
+
+/// The environment variable that sets the guest root if [`set_guest_root`]
+/// is not called. The working directory is used if it is not set either.
+pub const GUEST_ROOT_ENV: &str = "EDGE_GUEST_ROOT";
+
+static GUEST_ROOT: OnceLock<GuestRoot> = OnceLock::new();
+
+struct GuestRoot {
+    dir: File,
+    /// Where the guest root is on the host, for mapping host paths back.
+    host_path: PathBuf,
+}
+
+impl GuestRoot {
+    fn open(path: &Path) -> std::io::Result<GuestRoot> {
+        let dir = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_DIRECTORY)
+            .open(path)?;
+        let host_path = host_path_of(&dir)?;
+        Ok(GuestRoot { dir, host_path })
+    }
+}
+
+fn host_path_of(file: &File) -> std::io::Result<PathBuf> {
+    std::fs::read_link(format!("/proc/self/fd/{}", file.as_raw_fd()))
+}
+
+/// Confine the guest to the host directory `path`. This must be done before
+/// the first edge call that takes a path, and only once.
+pub fn set_guest_root(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let root =
+        GuestRoot::open(path).with_context(|| format!("open guest root {}", path.display()))?;
+    GUEST_ROOT
+        .set(root)
+        .map_err(|_| anyhow::anyhow!("the guest root is already set"))?;
+    log::info!("Guest root: {}", path.display());
+    Ok(())
+}
+
+fn guest_root() -> &'static GuestRoot {
+    GUEST_ROOT.get_or_init(|| {
+        let path = std::env::var_os(GUEST_ROOT_ENV).unwrap_or_else(|| ".".into());
+        let root = GuestRoot::open(path.as_ref())
+            .unwrap_or_else(|err| panic!("failed to open guest root {:?}: {}", path, err));
+        log::info!("Guest root: {:?}", path);
+        root
+    })
+}
+
+/// Open `path`, a guest path, beneath the guest root.
+pub fn open(path: &Path, flags: OFlag, mode: Mode) -> LinuxResult<File> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| Errno::EINVAL)?;
+    // `open_how` may grow fields in later versions, which must stay zero
+    let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+    how.flags = (flags | OFlag::O_CLOEXEC).bits() as u64;
+    // openat2 rejects a mode unless a file may be created
+    if flags.contains(OFlag::O_CREAT) || flags.contains(OFlag::O_TMPFILE) {
+        how.mode = mode.bits() as u64;
+    }
+    how.resolve = libc::RESOLVE_IN_ROOT | libc::RESOLVE_NO_MAGICLINKS;
+
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_openat2,
+            guest_root().dir.as_raw_fd(),
+            c_path.as_ptr(),
+            &how as *const libc::open_how,
+            std::mem::size_of::<libc::open_how>(),
+        )
+    };
+    let fd = Errno::result(fd)?;
+    log::trace!("Opened guest path {:?} as host fd {}", path, fd);
+    Ok(unsafe { File::from_raw_fd(fd as i32) })
+}
+
+/// Open the directory containing `path` beneath the guest root, for `*at`
+/// syscalls that must not follow the last component. Returns the directory
+/// and the last component.
+pub fn open_parent(path: &Path) -> LinuxResult<(File, &OsStr)> {
+    let name = path.file_name().ok_or(Errno::EINVAL)?;
+    let parent = match path.parent() {
+        Some(parent) if parent.as_os_str() != "" => parent,
+        _ => Path::new("."),
+    };
+    let dir = open(parent, OFlag::O_PATH | OFlag::O_DIRECTORY, Mode::empty())?;
+    Ok((dir, name))
+}
+
+/// Open `name`, a single path component, in the directory `dir` without
+/// following a symlink there.
+pub fn open_in(dir: &File, name: &OsStr, flags: OFlag, mode: Mode) -> LinuxResult<File> {
+    let fd = nix::fcntl::openat(
+        dir.as_raw_fd(),
+        name,
+        flags | OFlag::O_CLOEXEC | OFlag::O_NOFOLLOW,
+        mode,
+    )?;
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Open the file that `file` (which may be an `O_PATH` descriptor) refers to
+/// again, with different flags.
+pub fn reopen(file: &File, flags: OFlag) -> LinuxResult<File> {
+    let fd = nix::fcntl::open(
+        format!("/proc/self/fd/{}", file.as_raw_fd()).as_str(),
+        (flags | OFlag::O_CLOEXEC) - (OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_NOFOLLOW),
+        Mode::empty(),
+    )?;
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Find the guest path of an open file, with all symlinks resolved.
+pub fn guest_path_of(file: &File) -> LinuxResult<PathBuf> {
+    let host_path = host_path_of(file).map_err(|_| Errno::EACCES)?;
+    host_path
+        .strip_prefix(&guest_root().host_path)
+        .map(Path::to_path_buf)
+        .map_err(|_| {
+            log::warn!("{:?} is not beneath the guest root", host_path);
+            Errno::EACCES
+        })
+}
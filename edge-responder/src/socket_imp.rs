@@ -0,0 +1,531 @@
+//! Host sockets for guest tasks, kept in their fd tables.
+//!
+//! A socket call never blocks the edge responder: the host socket is made
+//! non-blocking for the call, and if the guest expected it to block, the call
+//! is answered with [`EdgeCallResp::WouldBlock`] and the socket is watched, so
+//! that the enclave retries once it is ready.
+
+use std::{
+    mem,
+    os::unix::prelude::{AsRawFd, FromRawFd, OsStrExt, OwnedFd, RawFd},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use edge_proto::{server::EdgeStream, EdgeCallResp, SockAddr};
+use nix::{
+    errno::Errno,
+    fcntl::{FcntlArg, OFlag},
+    libc,
+    poll::PollFlags,
+};
+
+use crate::{
+    error::{EdgeErrorCompat, LinuxResult, SyscallResult},
+    fs_imp::{TaskFsContext, TeeFile},
+    notify,
+    pcb::TASKS,
+    policy, sandbox,
+};
+
+/// The socket types the guest may create. Raw sockets need privileges that
+/// the guest does not have.
+const SOCKET_TYPES: [i32; 3] = [libc::SOCK_STREAM, libc::SOCK_DGRAM, libc::SOCK_SEQPACKET];
+
+/// The flags of `sendto` and `recvfrom` that are passed to the host.
+/// `MSG_WAITALL` is left out, as it could block the edge responder, so the
+/// guest may receive less than it asked for. `MSG_NOSIGNAL` is always added,
+/// as `SIGPIPE` would terminate the edge responder.
+const MSG_FLAGS: i32 = libc::MSG_DONTWAIT
+    | libc::MSG_PEEK
+    | libc::MSG_TRUNC
+    | libc::MSG_OOB
+    | libc::MSG_MORE
+    | libc::MSG_EOR
+    | libc::MSG_NOSIGNAL;
+
+/// The socket options the guest may read and set, by level and name. Linger
+/// and timeouts are left out, as they would make the edge responder block.
+const SOCKET_OPTIONS: &[(i32, i32)] = &[
+    (libc::SOL_SOCKET, libc::SO_REUSEADDR),
+    (libc::SOL_SOCKET, libc::SO_REUSEPORT),
+    (libc::SOL_SOCKET, libc::SO_KEEPALIVE),
+    (libc::SOL_SOCKET, libc::SO_BROADCAST),
+    (libc::SOL_SOCKET, libc::SO_OOBINLINE),
+    (libc::SOL_SOCKET, libc::SO_SNDBUF),
+    (libc::SOL_SOCKET, libc::SO_RCVBUF),
+    (libc::IPPROTO_TCP, libc::TCP_NODELAY),
+    (libc::IPPROTO_TCP, libc::TCP_KEEPIDLE),
+    (libc::IPPROTO_TCP, libc::TCP_KEEPINTVL),
+    (libc::IPPROTO_TCP, libc::TCP_KEEPCNT),
+    (libc::IPPROTO_IP, libc::IP_TTL),
+    (libc::IPPROTO_IPV6, libc::IPV6_V6ONLY),
+];
+
+/// The socket options the guest may only read.
+const READ_ONLY_SOCKET_OPTIONS: &[(i32, i32)] = &[
+    (libc::SOL_SOCKET, libc::SO_ERROR),
+    (libc::SOL_SOCKET, libc::SO_TYPE),
+    (libc::SOL_SOCKET, libc::SO_DOMAIN),
+    (libc::SOL_SOCKET, libc::SO_PROTOCOL),
+    (libc::SOL_SOCKET, libc::SO_ACCEPTCONN),
+];
+
+/// The size of the largest value of an allowed option.
+const MAX_OPTION_LEN: usize = mem::size_of::<libc::c_int>();
+
+/// A socket address laid out for the host.
+struct HostAddr {
+    storage: libc::sockaddr_storage,
+    len: libc::socklen_t,
+    /// The directory of a Unix socket file, which `storage` refers to through
+    /// `/proc/self/fd`.
+    _dir: Option<std::fs::File>,
+}
+
+impl HostAddr {
+    fn empty() -> HostAddr {
+        HostAddr {
+            // SAFETY: All-zero bytes are a valid `sockaddr_storage`
+            storage: unsafe { mem::zeroed() },
+            len: mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t,
+            _dir: None,
+        }
+    }
+
+    fn as_ptr(&self) -> *const libc::sockaddr {
+        &self.storage as *const _ as *const libc::sockaddr
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut libc::sockaddr {
+        &mut self.storage as *mut _ as *mut libc::sockaddr
+    }
+
+    /// Lay `addr` out for the host. The guest path of a Unix socket is
+    /// resolved beneath the guest root, and must be allowed to be created if
+    /// `bind` is set.
+    fn new(pid: i32, addr: &SockAddr, bind: bool) -> SyscallResult<HostAddr> {
+        let mut host_addr = HostAddr::empty();
+        let len = match addr {
+            SockAddr::Unnamed => {
+                host_addr.storage.ss_family = libc::AF_UNSPEC as libc::sa_family_t;
+                mem::size_of::<libc::sa_family_t>()
+            }
+            SockAddr::Inet { addr, port } => {
+                // SAFETY: `sockaddr_storage` is large and aligned enough
+                let sin = unsafe { &mut *(host_addr.as_mut_ptr() as *mut libc::sockaddr_in) };
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = port.to_be();
+                sin.sin_addr.s_addr = u32::from_ne_bytes(*addr);
+                mem::size_of::<libc::sockaddr_in>()
+            }
+            SockAddr::Inet6 {
+                addr,
+                port,
+                flowinfo,
+                scope_id,
+            } => {
+                // SAFETY: `sockaddr_storage` is large and aligned enough
+                let sin6 = unsafe { &mut *(host_addr.as_mut_ptr() as *mut libc::sockaddr_in6) };
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = port.to_be();
+                sin6.sin6_flowinfo = flowinfo.to_be();
+                sin6.sin6_addr.s6_addr = *addr;
+                sin6.sin6_scope_id = *scope_id;
+                mem::size_of::<libc::sockaddr_in6>()
+            }
+            SockAddr::Unix(path) => {
+                if path.is_empty() || path.starts_with('\0') {
+                    // The abstract namespace is shared with the whole host
+                    log::warn!("Abstract Unix socket addresses are refused");
+                    return Err(nix::Error::EACCES.into());
+                }
+                let path = guest_path(pid, path)?;
+                let (dir, name) = sandbox::open_parent(&path)?;
+                if bind {
+                    policy::check_entry(&path, &dir, name)?;
+                }
+                let host_path = format!("/proc/self/fd/{}/", dir.as_raw_fd());
+                let host_path = [host_path.as_bytes(), name.as_bytes()].concat();
+
+                // SAFETY: `sockaddr_storage` is large and aligned enough
+                let sun = unsafe { &mut *(host_addr.as_mut_ptr() as *mut libc::sockaddr_un) };
+                // Keep the terminating NUL
+                if host_path.len() >= sun.sun_path.len() {
+                    return Err(nix::Error::ENAMETOOLONG.into());
+                }
+                sun.sun_family = libc::AF_UNIX as libc::sa_family_t;
+                for (dest, &byte) in sun.sun_path.iter_mut().zip(&host_path) {
+                    *dest = byte as libc::c_char;
+                }
+                host_addr._dir = Some(dir);
+                mem::size_of::<libc::sa_family_t>() + host_path.len() + 1
+            }
+        };
+        host_addr.len = len as libc::socklen_t;
+        Ok(host_addr)
+    }
+
+    /// The address as the guest sees it. Unix socket files are only known by
+    /// their host paths, so they are reported as unnamed.
+    fn to_guest(&self) -> SockAddr {
+        match self.storage.ss_family as i32 {
+            libc::AF_INET => {
+                // SAFETY: The host filled in a `sockaddr_in`
+                let sin = unsafe { &*(self.as_ptr() as *const libc::sockaddr_in) };
+                SockAddr::Inet {
+                    addr: sin.sin_addr.s_addr.to_ne_bytes(),
+                    port: u16::from_be(sin.sin_port),
+                }
+            }
+            libc::AF_INET6 => {
+                // SAFETY: The host filled in a `sockaddr_in6`
+                let sin6 = unsafe { &*(self.as_ptr() as *const libc::sockaddr_in6) };
+                SockAddr::Inet6 {
+                    addr: sin6.sin6_addr.s6_addr,
+                    port: u16::from_be(sin6.sin6_port),
+                    flowinfo: u32::from_be(sin6.sin6_flowinfo),
+                    scope_id: sin6.sin6_scope_id,
+                }
+            }
+            _ => SockAddr::Unnamed,
+        }
+    }
+}
+
+/// Resolve the guest path `path` against the working directory of `pid`.
+fn guest_path(pid: i32, path: &str) -> SyscallResult<std::path::PathBuf> {
+    Ok(TASKS
+        .lock()
+        .unwrap()
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs
+        .resolve_path(path))
+}
+
+fn find_socket(pid: i32, fd: i32) -> SyscallResult<Arc<Mutex<TeeFile>>> {
+    let file = Arc::clone(
+        TASKS
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .ok_or(anyhow::anyhow!("no such process"))?
+            .fs
+            .find_fd(fd)?,
+    );
+    if !matches!(*file.lock().unwrap(), TeeFile::Socket { .. }) {
+        return Err(nix::Error::ENOTSOCK.into());
+    }
+    Ok(file)
+}
+
+/// Run `f` on the host socket `fd` without blocking. If it would block and
+/// the guest expects it to, unless `dont_wait` is set, `fd` is watched for
+/// `events` and `None` is returned.
+fn without_blocking<T>(
+    fd: RawFd,
+    events: PollFlags,
+    dont_wait: bool,
+    f: impl FnOnce() -> LinuxResult<T>,
+) -> LinuxResult<Option<T>> {
+    let flags = OFlag::from_bits_truncate(nix::fcntl::fcntl(fd, FcntlArg::F_GETFL)?);
+    let blocking = !flags.contains(OFlag::O_NONBLOCK);
+    if blocking {
+        nix::fcntl::fcntl(fd, FcntlArg::F_SETFL(flags | OFlag::O_NONBLOCK))?;
+    }
+    let result = f();
+    if blocking {
+        nix::fcntl::fcntl(fd, FcntlArg::F_SETFL(flags))?;
+    }
+    match result {
+        Err(Errno::EAGAIN | Errno::EINPROGRESS) if blocking && !dont_wait => {
+            notify::watch(fd, events);
+            Ok(None)
+        }
+        result => result.map(Some),
+    }
+}
+
+pub fn socket(pid: i32, domain: i32, ty: i32, protocol: i32) -> SyscallResult<EdgeCallResp> {
+    if ![libc::AF_INET, libc::AF_INET6, libc::AF_UNIX].contains(&domain) {
+        log::warn!("socket: Address family {} is not supported", domain);
+        return Err(nix::Error::EAFNOSUPPORT.into());
+    }
+    let kind = ty & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC);
+    if !SOCKET_TYPES.contains(&kind) {
+        log::warn!("socket: Socket type {} is not allowed", kind);
+        return Err(if kind == libc::SOCK_RAW {
+            nix::Error::EPERM
+        } else {
+            nix::Error::EINVAL
+        }
+        .into());
+    }
+
+    let fd = Errno::result(unsafe { libc::socket(domain, ty | libc::SOCK_CLOEXEC, protocol) })?;
+    // SAFETY: The socket was just created, and nothing else owns it
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    let fd = TASKS
+        .lock()
+        .unwrap()
+        .get_mut(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs
+        .install_socket(socket, ty & libc::SOCK_CLOEXEC != 0)?;
+    Ok(EdgeCallResp::SyscallResp(fd as i64))
+}
+
+pub fn bind(pid: i32, fd: i32, addr: SockAddr) -> SyscallResult<EdgeCallResp> {
+    let file = find_socket(pid, fd)?;
+    let mut guard = file.lock().unwrap();
+    let host_addr = HostAddr::new(pid, &addr, true)?;
+    Errno::result(unsafe { libc::bind(guard.as_raw_fd(), host_addr.as_ptr(), host_addr.len) })?;
+    if let (SockAddr::Unix(path), TeeFile::Socket { bound_path, .. }) = (addr, &mut *guard) {
+        *bound_path = Some(TaskFsContext::to_kernel_path(&guest_path(pid, &path)?));
+    }
+    Ok(EdgeCallResp::SyscallResp(0))
+}
+
+pub fn listen(pid: i32, fd: i32, backlog: i32) -> SyscallResult<EdgeCallResp> {
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    Errno::result(unsafe { libc::listen(guard.as_raw_fd(), backlog) })?;
+    Ok(EdgeCallResp::SyscallResp(0))
+}
+
+pub fn accept(pid: i32, fd: i32, flags: i32) -> SyscallResult<EdgeCallResp> {
+    if flags & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC) != 0 {
+        return Err(nix::Error::EINVAL.into());
+    }
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    let mut peer = HostAddr::empty();
+    let host_flags = (flags & libc::SOCK_NONBLOCK) | libc::SOCK_CLOEXEC;
+    let result = without_blocking(guard.as_raw_fd(), PollFlags::POLLIN, false, || {
+        Errno::result(unsafe {
+            libc::accept4(
+                guard.as_raw_fd(),
+                peer.as_mut_ptr(),
+                &mut peer.len,
+                host_flags,
+            )
+        })
+    })?;
+    let fd = match result {
+        Some(fd) => fd,
+        None => return Ok(EdgeCallResp::WouldBlock),
+    };
+
+    // SAFETY: The socket was just accepted, and nothing else owns it
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    let fd = TASKS
+        .lock()
+        .unwrap()
+        .get_mut(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs
+        .install_socket(socket, flags & libc::SOCK_CLOEXEC != 0)?;
+    Ok(EdgeCallResp::OkWithSockAddr {
+        result: fd as i64,
+        addr: peer.to_guest(),
+    })
+}
+
+pub fn connect(pid: i32, fd: i32, addr: SockAddr) -> SyscallResult<EdgeCallResp> {
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    let host_addr = HostAddr::new(pid, &addr, false)?;
+    let result = without_blocking(guard.as_raw_fd(), PollFlags::POLLOUT, false, || {
+        Errno::result(unsafe {
+            libc::connect(guard.as_raw_fd(), host_addr.as_ptr(), host_addr.len)
+        })
+    })?;
+    Ok(match result {
+        Some(_) => EdgeCallResp::SyscallResp(0),
+        None => EdgeCallResp::WouldBlock,
+    })
+}
+
+pub fn sendto(
+    stream: &mut dyn EdgeStream,
+    pid: i32,
+    fd: i32,
+    len: u64,
+    flags: i32,
+    addr: Option<SockAddr>,
+) -> SyscallResult<EdgeCallResp> {
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    let host_addr = addr
+        .map(|addr| HostAddr::new(pid, &addr, false))
+        .transpose()?;
+    let (addr_ptr, addr_len) = host_addr
+        .as_ref()
+        .map_or((std::ptr::null(), 0), |addr| (addr.as_ptr(), addr.len));
+    let data = stream.read_data().compat().context("read data")?;
+    let data = data.get(0..len as usize).ok_or(nix::Error::EINVAL)?;
+
+    let dont_wait = flags & libc::MSG_DONTWAIT != 0;
+    let host_flags = (flags & MSG_FLAGS) | libc::MSG_NOSIGNAL;
+    let result = without_blocking(guard.as_raw_fd(), PollFlags::POLLOUT, dont_wait, || {
+        Errno::result(unsafe {
+            libc::sendto(
+                guard.as_raw_fd(),
+                data.as_ptr() as *const libc::c_void,
+                data.len(),
+                host_flags,
+                addr_ptr,
+                addr_len,
+            )
+        })
+    })?;
+    Ok(match result {
+        Some(sent) => EdgeCallResp::SyscallResp(sent as i64),
+        None => EdgeCallResp::WouldBlock,
+    })
+}
+
+pub fn recvfrom(
+    stream: &mut dyn EdgeStream,
+    pid: i32,
+    fd: i32,
+    len: u64,
+    flags: i32,
+) -> SyscallResult<EdgeCallResp> {
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    let zone = stream
+        .data_zone_mut()
+        .compat()
+        .context("borrow data zone")?;
+    let buf = zone.get_mut(0..len as usize).ok_or(nix::Error::EINVAL)?;
+
+    let mut sender = HostAddr::empty();
+    let dont_wait = flags & libc::MSG_DONTWAIT != 0;
+    let result = without_blocking(guard.as_raw_fd(), PollFlags::POLLIN, dont_wait, || {
+        Errno::result(unsafe {
+            libc::recvfrom(
+                guard.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                flags & MSG_FLAGS,
+                sender.as_mut_ptr(),
+                &mut sender.len,
+            )
+        })
+    })?;
+    Ok(match result {
+        Some(received) => EdgeCallResp::OkWithSockAddr {
+            result: received as i64,
+            // A connected stream socket leaves the address untouched
+            addr: sender.to_guest(),
+        },
+        None => EdgeCallResp::WouldBlock,
+    })
+}
+
+pub fn shutdown(pid: i32, fd: i32, how: i32) -> SyscallResult<EdgeCallResp> {
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    Errno::result(unsafe { libc::shutdown(guard.as_raw_fd(), how) })?;
+    Ok(EdgeCallResp::SyscallResp(0))
+}
+
+fn check_option(level: i32, name: i32, write: bool) -> LinuxResult<()> {
+    let allowed = SOCKET_OPTIONS.contains(&(level, name))
+        || (!write && READ_ONLY_SOCKET_OPTIONS.contains(&(level, name)));
+    if !allowed {
+        log::warn!("Socket option {} at level {} is not allowed", name, level);
+        return Err(nix::Error::ENOPROTOOPT);
+    }
+    Ok(())
+}
+
+pub fn getsockopt(
+    stream: &mut dyn EdgeStream,
+    pid: i32,
+    fd: i32,
+    level: i32,
+    name: i32,
+    len: u64,
+) -> SyscallResult<EdgeCallResp> {
+    check_option(level, name, false)?;
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    let mut value = [0u8; MAX_OPTION_LEN];
+    let mut value_len = (len as usize).min(MAX_OPTION_LEN) as libc::socklen_t;
+    Errno::result(unsafe {
+        libc::getsockopt(
+            guard.as_raw_fd(),
+            level,
+            name,
+            value.as_mut_ptr() as *mut libc::c_void,
+            &mut value_len,
+        )
+    })?;
+    let value = &value[0..value_len as usize];
+    stream
+        .data_zone_mut()
+        .compat()
+        .context("borrow data zone")?
+        .get_mut(0..value.len())
+        .ok_or(anyhow::anyhow!("data zone too small"))?
+        .copy_from_slice(value);
+    Ok(EdgeCallResp::SyscallResp(value.len() as i64))
+}
+
+pub fn setsockopt(
+    pid: i32,
+    fd: i32,
+    level: i32,
+    name: i32,
+    value: Vec<u8>,
+) -> SyscallResult<EdgeCallResp> {
+    check_option(level, name, true)?;
+    if value.len() > MAX_OPTION_LEN {
+        return Err(nix::Error::EINVAL.into());
+    }
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    Errno::result(unsafe {
+        libc::setsockopt(
+            guard.as_raw_fd(),
+            level,
+            name,
+            value.as_ptr() as *const libc::c_void,
+            value.len() as libc::socklen_t,
+        )
+    })?;
+    Ok(EdgeCallResp::SyscallResp(0))
+}
+
+pub fn getsockname(pid: i32, fd: i32) -> SyscallResult<EdgeCallResp> {
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    let mut local = HostAddr::empty();
+    Errno::result(unsafe {
+        libc::getsockname(guard.as_raw_fd(), local.as_mut_ptr(), &mut local.len)
+    })?;
+    let addr = match &*guard {
+        TeeFile::Socket {
+            bound_path: Some(path),
+            ..
+        } => SockAddr::Unix(path.clone()),
+        _ => local.to_guest(),
+    };
+    Ok(EdgeCallResp::OkWithSockAddr { result: 0, addr })
+}
+
+pub fn getpeername(pid: i32, fd: i32) -> SyscallResult<EdgeCallResp> {
+    let file = find_socket(pid, fd)?;
+    let guard = file.lock().unwrap();
+    let mut peer = HostAddr::empty();
+    Errno::result(unsafe {
+        libc::getpeername(guard.as_raw_fd(), peer.as_mut_ptr(), &mut peer.len)
+    })?;
+    Ok(EdgeCallResp::OkWithSockAddr {
+        result: 0,
+        addr: peer.to_guest(),
+    })
+}
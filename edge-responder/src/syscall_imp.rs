@@ -1,11 +1,25 @@
-use std::{os::unix::prelude::AsRawFd, sync::Arc};
+use std::{
+    mem,
+    os::unix::prelude::{AsRawFd, RawFd},
+    sync::Arc,
+};
 
 use anyhow::Context;
-use edge_proto::{server::EdgeStream, EdgeCallResp};
+use edge_proto::{
+    server::EdgeStream, DataRegion, EdgeCallResp, PolledFd, MAX_DATA_REGIONS, MAX_POLLED_FDS,
+};
+use nix::{
+    fcntl::{FcntlArg, OFlag},
+    poll::{PollFd, PollFlags},
+    sys::uio::IoVec,
+    unistd::Whence,
+};
 
 use crate::{
-    error::{EdgeErrorCompat, SyscallResult},
+    error::{EdgeErrorCompat, LinuxResult, SyscallResult},
+    notify,
     pcb::TASKS,
+    policy,
 };
 
 pub fn openat(
@@ -34,6 +48,39 @@ pub fn openat(
         .map_err(Into::into)
 }
 
+/// Check whether reading from the blocking file `fd` (e.g. a pipe, terminal
+/// or socket) would block. If so, the file is watched, so that the enclave is
+/// notified once it becomes readable, and the enclave's thread is not held up
+/// meanwhile. Reads from non-blocking files are left to fail with `EAGAIN`.
+pub fn read_would_block(pid: i32, fd: i32) -> SyscallResult<bool> {
+    let local_file = Arc::clone(
+        TASKS
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .ok_or(anyhow::anyhow!("no such process"))?
+            .fs
+            .find_fd(fd)?,
+    );
+    let guard = local_file.lock().unwrap();
+    Ok(would_block(guard.as_raw_fd())?)
+}
+
+fn would_block(fd: RawFd) -> LinuxResult<bool> {
+    let flags = OFlag::from_bits_truncate(nix::fcntl::fcntl(fd, FcntlArg::F_GETFL)?);
+    if flags.contains(OFlag::O_NONBLOCK) {
+        return Ok(false);
+    }
+    // Regular files are always ready
+    let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+    nix::poll::poll(&mut fds, 0)?;
+    if fds[0].revents().is_some_and(|revents| !revents.is_empty()) {
+        return Ok(false);
+    }
+    notify::watch(fd, PollFlags::POLLIN);
+    Ok(true)
+}
+
 pub fn special_read(
     stream: &mut dyn EdgeStream,
     pid: i32,
@@ -52,18 +99,24 @@ pub fn special_read(
     let guard = local_file.lock().unwrap();
 
     let mut buf = vec![0; len as usize];
-    let result = nix::unistd::read(guard.as_raw_fd(), &mut buf);
+    let result = would_block(guard.as_raw_fd()).and_then(|would_block| {
+        if would_block {
+            Ok(None)
+        } else {
+            nix::unistd::read(guard.as_raw_fd(), &mut buf).map(Some)
+        }
+    });
 
+    let header = match result {
+        Ok(Some(len)) => EdgeCallResp::SyscallResp(len as i64),
+        Ok(None) => EdgeCallResp::WouldBlock,
+        Err(errno) => EdgeCallResp::SyscallResp(-(errno as i64)),
+    };
     stream
-        .write_header(&EdgeCallResp::SyscallResp(
-            result
-                .map(|len| len.try_into().unwrap()) // usize -> isize
-                .map_err(|errno| errno as isize)
-                .unwrap_or_else(std::convert::identity) as i64,
-        ))
+        .write_header(&header)
         .compat()
         .context("write header")?;
-    if let Ok(len) = result {
+    if let Ok(Some(len)) = result {
         stream
             .write_data(&buf[0..len])
             .compat()
@@ -85,15 +138,147 @@ pub fn write(stream: &mut dyn EdgeStream, pid: i32, fd: i32, len: u64) -> Syscal
     );
     let guard = local_file.lock().unwrap();
 
+    let len = policy::write_len(guard.as_raw_fd(), None, len as usize)?;
     let result = nix::unistd::write(
         guard.as_raw_fd(),
-        &stream.read_data().compat().context("read data")?[0..len as usize],
+        &stream.read_data().compat().context("read data")?[0..len],
     )
     .map(|len| len.try_into().unwrap())?; // usize -> isize
 
     Ok(result)
 }
 
+/// Split the data zone into the slices described by `regions`, which must be
+/// in ascending order and must not overlap.
+fn split_regions<'a>(
+    mut zone: &'a mut [u8],
+    regions: &[DataRegion],
+) -> LinuxResult<Vec<&'a mut [u8]>> {
+    if regions.len() > MAX_DATA_REGIONS {
+        return Err(nix::Error::EINVAL);
+    }
+
+    let mut slices = Vec::with_capacity(regions.len());
+    let mut consumed = 0;
+    for region in regions {
+        let skip = (region.offset as usize)
+            .checked_sub(consumed)
+            .ok_or(nix::Error::EINVAL)?;
+        let len = region.len as usize;
+        if skip.checked_add(len).is_none_or(|end| end > zone.len()) {
+            return Err(nix::Error::EINVAL);
+        }
+        let (slice, rest) = std::mem::take(&mut zone)[skip..].split_at_mut(len);
+        slices.push(slice);
+        zone = rest;
+        consumed += skip + len;
+    }
+    Ok(slices)
+}
+
+/// Read into the data zone, at `offset` if given (like `preadv`).
+pub fn readv(
+    stream: &mut dyn EdgeStream,
+    pid: i32,
+    fd: i32,
+    regions: Vec<DataRegion>,
+    offset: Option<i64>,
+) -> SyscallResult<isize> {
+    let local_file = Arc::clone(
+        TASKS
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .ok_or(anyhow::anyhow!("no such process"))?
+            .fs
+            .find_fd(fd)?,
+    );
+    let guard = local_file.lock().unwrap();
+
+    // Read straight into the shared data zone
+    let zone = stream
+        .data_zone_mut()
+        .compat()
+        .context("borrow data zone")?;
+    let mut iov: Vec<_> = split_regions(zone, &regions)?
+        .into_iter()
+        .map(IoVec::from_mut_slice)
+        .collect();
+    let result = match offset {
+        Some(offset) => nix::sys::uio::preadv(guard.as_raw_fd(), &iov, offset),
+        None => nix::sys::uio::readv(guard.as_raw_fd(), &mut iov),
+    }
+    .map(|len| len.try_into().unwrap())?; // usize -> isize
+
+    Ok(result)
+}
+
+/// Write from the data zone, at `offset` if given (like `pwritev`).
+pub fn writev(
+    stream: &mut dyn EdgeStream,
+    pid: i32,
+    fd: i32,
+    regions: Vec<DataRegion>,
+    offset: Option<i64>,
+) -> SyscallResult<isize> {
+    let local_file = Arc::clone(
+        TASKS
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .ok_or(anyhow::anyhow!("no such process"))?
+            .fs
+            .find_fd(fd)?,
+    );
+    let guard = local_file.lock().unwrap();
+
+    // Write straight from the shared data zone
+    let zone = stream
+        .data_zone_mut()
+        .compat()
+        .context("borrow data zone")?;
+    let slices = split_regions(zone, &regions)?;
+    let total = slices.iter().map(|slice| slice.len()).sum();
+    let mut remaining = policy::write_len(guard.as_raw_fd(), offset, total)?;
+    let iov: Vec<_> = slices
+        .into_iter()
+        .map(|slice| {
+            let len = slice.len().min(remaining);
+            remaining -= len;
+            IoVec::from_slice(&slice[0..len])
+        })
+        .collect();
+    let result = match offset {
+        Some(offset) => nix::sys::uio::pwritev(guard.as_raw_fd(), &iov, offset),
+        None => nix::sys::uio::writev(guard.as_raw_fd(), &iov),
+    }
+    .map(|len| len.try_into().unwrap())?; // usize -> isize
+
+    Ok(result)
+}
+
+pub fn lseek(pid: i32, fd: i32, offset: i64, whence: i32) -> SyscallResult<isize> {
+    let whence = match whence {
+        nix::libc::SEEK_SET => Whence::SeekSet,
+        nix::libc::SEEK_CUR => Whence::SeekCur,
+        nix::libc::SEEK_END => Whence::SeekEnd,
+        nix::libc::SEEK_DATA => Whence::SeekData,
+        nix::libc::SEEK_HOLE => Whence::SeekHole,
+        _ => return Err(nix::Error::EINVAL.into()),
+    };
+    let local_file = Arc::clone(
+        TASKS
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .ok_or(anyhow::anyhow!("no such process"))?
+            .fs
+            .find_fd(fd)?,
+    );
+    let guard = local_file.lock().unwrap();
+    Ok(nix::unistd::lseek(guard.as_raw_fd(), offset, whence)? as isize)
+}
+
 pub fn close(_stream: &mut dyn EdgeStream, pid: i32, fd: i32) -> SyscallResult<isize> {
     TASKS
         .lock()
@@ -106,11 +291,22 @@ pub fn close(_stream: &mut dyn EdgeStream, pid: i32, fd: i32) -> SyscallResult<i
         .map_err(Into::into)
 }
 
+pub fn reserve_fd(_stream: &mut dyn EdgeStream, pid: i32, cloexec: bool) -> SyscallResult<isize> {
+    Ok(TASKS
+        .lock()
+        .unwrap()
+        .get_mut(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs
+        .reserve_fd(cloexec)? as isize)
+}
+
 pub fn dup(
     _stream: &mut dyn EdgeStream,
     pid: i32,
     src_fd: i32,
     dest_fd: Option<i32>,
+    cloexec: bool,
 ) -> SyscallResult<isize> {
     TASKS
         .lock()
@@ -118,7 +314,7 @@ pub fn dup(
         .get_mut(&pid)
         .ok_or(anyhow::anyhow!("no such process"))?
         .fs
-        .dup(src_fd, dest_fd)
+        .dup(src_fd, dest_fd, cloexec)
         .map(|fd| fd as isize)
         .map_err(Into::into)
 }
@@ -151,6 +347,15 @@ pub fn chdir(_stream: &mut dyn EdgeStream, pid: i32, path: String) -> SyscallRes
         .map_err(Into::into)
 }
 
+pub fn fd_path(pid: i32, fd: i32) -> anyhow::Result<String> {
+    let tasks = TASKS.lock().unwrap();
+    let fs = &tasks
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs;
+    Ok(fs.fd_path(fd)?)
+}
+
 pub fn special_getcwd(stream: &mut dyn EdgeStream, pid: i32) -> anyhow::Result<()> {
     let cwd = TASKS
         .lock()
@@ -208,36 +413,41 @@ pub fn special_getdents64(
     Ok(())
 }
 
-pub fn special_fstat(stream: &mut dyn EdgeStream, pid: i32, fd: i32) -> anyhow::Result<()> {
-    #[cfg(target_arch = "riscv64")]
-    const STAT_SIZE: usize = 128;
-    #[cfg(target_arch = "x86_64")]
-    const STAT_SIZE: usize = 144;
-    assert_eq!(std::mem::size_of::<nix::libc::stat>(), STAT_SIZE);
-
-    let local_dir = Arc::clone(
-        TASKS
-            .lock()
-            .unwrap()
-            .get(&pid)
-            .ok_or(anyhow::anyhow!("no such process"))?
-            .fs
-            .find_fd(fd as i32)?,
-    );
-    let guard = local_dir.lock().unwrap();
+pub fn fstat(pid: i32, fd: i32) -> SyscallResult<EdgeCallResp> {
+    let tasks = TASKS.lock().unwrap();
+    let fs = &tasks
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs;
+    Ok(EdgeCallResp::OkWithStat(fs.fstat(fd)?))
+}
 
-    let mut buf = [0; STAT_SIZE];
-    let result = unsafe { nix::libc::fstat(guard.as_raw_fd(), buf.as_mut_ptr().cast()) };
+pub fn fstatat(pid: i32, dir_fd: i32, path: String, flags: i32) -> SyscallResult<EdgeCallResp> {
+    let tasks = TASKS.lock().unwrap();
+    let fs = &tasks
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs;
+    Ok(EdgeCallResp::OkWithStat(fs.fstat_at(dir_fd, &path, flags)?))
+}
 
-    stream
-        .write_header(&EdgeCallResp::SyscallResp(result as i64))
-        .compat()
-        .context("write header")?;
-    if result >= 0 {
-        stream.write_data(&buf).compat().context("write data")?;
-    }
+pub fn faccessat(pid: i32, dir_fd: i32, path: String, mode: i32) -> SyscallResult<isize> {
+    let tasks = TASKS.lock().unwrap();
+    let fs = &tasks
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs;
+    fs.access_at(dir_fd, &path, mode)?;
+    Ok(0)
+}
 
-    Ok(())
+pub fn readlinkat(pid: i32, dir_fd: i32, path: String) -> SyscallResult<EdgeCallResp> {
+    let tasks = TASKS.lock().unwrap();
+    let fs = &tasks
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs;
+    Ok(EdgeCallResp::OkWithString(fs.readlink_at(dir_fd, &path)?))
 }
 
 pub fn unlinkat(
@@ -257,3 +467,269 @@ pub fn unlinkat(
         .map(|()| 0)
         .map_err(Into::into)
 }
+
+pub fn renameat(
+    pid: i32,
+    old_dir_fd: i32,
+    old_path: String,
+    new_dir_fd: i32,
+    new_path: String,
+    flags: u32,
+) -> SyscallResult<isize> {
+    TASKS
+        .lock()
+        .unwrap()
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs
+        .rename_at(old_dir_fd, &old_path, new_dir_fd, &new_path, flags)
+}
+
+pub fn linkat(
+    pid: i32,
+    old_dir_fd: i32,
+    old_path: String,
+    new_dir_fd: i32,
+    new_path: String,
+    flags: i32,
+) -> SyscallResult<isize> {
+    TASKS
+        .lock()
+        .unwrap()
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs
+        .link_at(old_dir_fd, &old_path, new_dir_fd, &new_path, flags)
+}
+
+pub fn symlinkat(pid: i32, target: String, dir_fd: i32, path: String) -> SyscallResult<isize> {
+    TASKS
+        .lock()
+        .unwrap()
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs
+        .symlink_at(&target, dir_fd, &path)
+}
+
+pub fn truncate(pid: i32, path: String, len: i64) -> SyscallResult<isize> {
+    TASKS
+        .lock()
+        .unwrap()
+        .get(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs
+        .truncate(&path, len)?;
+    Ok(0)
+}
+
+pub fn ftruncate(pid: i32, fd: i32, len: i64) -> SyscallResult<isize> {
+    let local_file = Arc::clone(
+        TASKS
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .ok_or(anyhow::anyhow!("no such process"))?
+            .fs
+            .find_fd(fd)?,
+    );
+    let guard = local_file.lock().unwrap();
+    policy::check_size(guard.as_raw_fd(), len)?;
+    nix::unistd::ftruncate(guard.as_raw_fd(), len)?;
+    Ok(0)
+}
+
+pub fn fsync(pid: i32, fd: i32, data_only: bool) -> SyscallResult<isize> {
+    let local_file = Arc::clone(
+        TASKS
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .ok_or(anyhow::anyhow!("no such process"))?
+            .fs
+            .find_fd(fd)?,
+    );
+    let guard = local_file.lock().unwrap();
+    if data_only {
+        nix::unistd::fdatasync(guard.as_raw_fd())?;
+    } else {
+        nix::unistd::fsync(guard.as_raw_fd())?;
+    }
+    Ok(0)
+}
+
+/// The file status flags that `F_SETFL` may change on a host fd. `O_ASYNC`
+/// is left out, as its signals would go to the edge responder.
+const SETFL_FLAGS: OFlag = OFlag::O_APPEND.union(OFlag::O_NONBLOCK);
+
+pub fn fcntl(pid: i32, fd: i32, cmd: i32, arg: u64) -> SyscallResult<isize> {
+    let mut tasks = TASKS.lock().unwrap();
+    let fs = &mut tasks
+        .get_mut(&pid)
+        .ok_or(anyhow::anyhow!("no such process"))?
+        .fs;
+    let local_file = match cmd {
+        nix::libc::F_DUPFD | nix::libc::F_DUPFD_CLOEXEC => {
+            let cloexec = cmd == nix::libc::F_DUPFD_CLOEXEC;
+            return Ok(fs.dup_above(fd, arg, cloexec)? as isize);
+        }
+        nix::libc::F_GETFD => {
+            let cloexec = fs.cloexec(fd)?;
+            return Ok(if cloexec { nix::libc::FD_CLOEXEC } else { 0 } as isize);
+        }
+        nix::libc::F_SETFD => {
+            fs.find_fd(fd)?;
+            fs.set_cloexec(fd, arg as i32 & nix::libc::FD_CLOEXEC != 0);
+            return Ok(0);
+        }
+        nix::libc::F_GETFL | nix::libc::F_SETFL => Arc::clone(fs.find_fd(fd)?),
+        _ => {
+            log::warn!("fcntl: unsupported command: {}", cmd);
+            return Err(nix::Error::EINVAL.into());
+        }
+    };
+    drop(tasks);
+
+    let guard = local_file.lock().unwrap();
+    let flags = nix::fcntl::fcntl(guard.as_raw_fd(), FcntlArg::F_GETFL)?;
+    if cmd == nix::libc::F_GETFL {
+        return Ok(flags as isize);
+    }
+    let flags = (OFlag::from_bits_truncate(flags) - SETFL_FLAGS)
+        | (OFlag::from_bits_truncate(arg as i32) & SETFL_FLAGS);
+    nix::fcntl::fcntl(guard.as_raw_fd(), FcntlArg::F_SETFL(flags))?;
+    Ok(0)
+}
+
+/// The size of `struct termios` as `TCGETS` fills it, which is smaller than
+/// the C library's: four flag words, the line discipline and 19 control
+/// characters.
+const KERNEL_TERMIOS_SIZE: usize =
+    4 * mem::size_of::<nix::libc::tcflag_t>() + 20 * mem::size_of::<nix::libc::cc_t>();
+
+/// Run one of the whitelisted `ioctl` requests, which fill a structure of
+/// `len` bytes into the data zone.
+pub fn ioctl(
+    stream: &mut dyn EdgeStream,
+    pid: i32,
+    fd: i32,
+    request: u32,
+    len: u64,
+) -> SyscallResult<isize> {
+    let size = match request as nix::libc::Ioctl {
+        nix::libc::TCGETS => KERNEL_TERMIOS_SIZE,
+        nix::libc::TIOCGWINSZ => mem::size_of::<nix::libc::winsize>(),
+        nix::libc::FIONREAD => mem::size_of::<nix::libc::c_int>(),
+        _ => {
+            log::warn!("ioctl: request {:#x} is not allowed", request);
+            return Err(nix::Error::ENOTTY.into());
+        }
+    };
+    if len != size as u64 {
+        log::warn!(
+            "ioctl: request {:#x} fills {} bytes, not {}",
+            request,
+            size,
+            len
+        );
+        return Err(nix::Error::EINVAL.into());
+    }
+
+    let local_file = Arc::clone(
+        TASKS
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .ok_or(anyhow::anyhow!("no such process"))?
+            .fs
+            .find_fd(fd)?,
+    );
+    let guard = local_file.lock().unwrap();
+
+    let zone = stream
+        .data_zone_mut()
+        .compat()
+        .context("borrow data zone")?;
+    let buf = zone
+        .get_mut(0..size)
+        .ok_or(anyhow::anyhow!("data zone too small"))?;
+    // SAFETY: Each allowed request writes at most `size` bytes to `buf`
+    let result = unsafe {
+        nix::libc::ioctl(
+            guard.as_raw_fd(),
+            request as nix::libc::Ioctl,
+            buf.as_mut_ptr(),
+        )
+    };
+    Ok(nix::errno::Errno::result(result)? as isize)
+}
+
+/// Poll `fds` without blocking, writing the returned events of each to the
+/// data zone. If none is ready and `watch` is set, they are handed to the
+/// watcher, which notifies the enclave once one is.
+pub fn poll(
+    stream: &mut dyn EdgeStream,
+    pid: i32,
+    fds: Vec<PolledFd>,
+    watch: bool,
+) -> SyscallResult<isize> {
+    if fds.len() > MAX_POLLED_FDS {
+        return Err(nix::Error::EINVAL.into());
+    }
+    let raw_fds: Vec<Option<RawFd>> = {
+        let tasks = TASKS.lock().unwrap();
+        let fs = &tasks
+            .get(&pid)
+            .ok_or(anyhow::anyhow!("no such process"))?
+            .fs;
+        fds.iter()
+            .map(|polled| {
+                let file = fs.find_fd(polled.fd).ok()?;
+                let raw_fd = file.lock().unwrap().as_raw_fd();
+                Some(raw_fd)
+            })
+            .collect()
+    };
+    // Fds that are not open are left out, as the host would report its own
+    // file of that number
+    let mut host_fds: Vec<PollFd> = raw_fds
+        .iter()
+        .zip(&fds)
+        .filter_map(|(raw_fd, polled)| {
+            raw_fd.map(|raw_fd| PollFd::new(raw_fd, PollFlags::from_bits_truncate(polled.events)))
+        })
+        .collect();
+    nix::poll::poll(&mut host_fds, 0)?;
+
+    let mut host_revents = host_fds.iter().map(|fd| {
+        fd.revents()
+            .map_or(PollFlags::POLLNVAL.bits(), |revents| revents.bits())
+    });
+    let revents: Vec<i16> = raw_fds
+        .iter()
+        .map(|raw_fd| match raw_fd {
+            Some(_) => host_revents.next().unwrap(),
+            None => PollFlags::POLLNVAL.bits(),
+        })
+        .collect();
+    let ready = revents.iter().filter(|&&revents| revents != 0).count();
+    if ready == 0 && watch {
+        // Every fd is open, or it would have returned `POLLNVAL`
+        for (raw_fd, polled) in raw_fds.iter().flatten().zip(&fds) {
+            notify::watch(*raw_fd, PollFlags::from_bits_truncate(polled.events));
+        }
+    }
+
+    let bytes: Vec<u8> = revents
+        .iter()
+        .flat_map(|revents| revents.to_ne_bytes())
+        .collect();
+    let zone = stream
+        .data_zone_mut()
+        .compat()
+        .context("borrow data zone")?;
+    zone.get_mut(0..bytes.len())
+        .ok_or(anyhow::anyhow!("data zone too small"))?
+        .copy_from_slice(&bytes);
+    Ok(ready as isize)
+}
@@ -1,5 +1,9 @@
 const SBI_EXT_EXPERIMENTAL_KEYSTONE_ENCLAVE: usize = 0x08424b45;
+const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
+const SBI_SM_RANDOM: usize = 3001;
+const SBI_SM_ATTEST_ENCLAVE: usize = 3002;
+const SBI_SM_GET_SEALING_KEY: usize = 3003;
 const SBI_SM_STOP_ENCLAVE: usize = 3004;
 const SBI_SM_EXIT_ENCLAVE: usize = 3006;
 pub const STOP_TIMER_INTERRUPT: usize = 0;
@@ -21,12 +25,99 @@ unsafe fn sbicall(ext: usize, which: usize, arg0: usize, arg1: usize, arg2: usiz
     result
 }
 
+/// Like [`sbicall`], for calls which return a value in `a1` along with the
+/// error in `a0`.
+#[inline]
+unsafe fn sbicall_with_value(
+    ext: usize,
+    which: usize,
+    arg0: usize,
+    arg1: usize,
+    arg2: usize,
+) -> (usize, usize) {
+    let error;
+    let value;
+    core::arch::asm!(
+        "ecall",
+
+        in("a7") ext,
+        in("a6") which,
+        inlateout("a0") arg0 => error,
+        inlateout("a1") arg1 => value,
+        in("a2") arg2,
+    );
+    (error, value)
+}
+
+/// Raise a supervisor timer interrupt once `time` reaches `stime_value`.
+pub fn set_timer(stime_value: u64) {
+    unsafe {
+        sbicall(SBI_SET_TIMER, 0, stime_value as usize, 0, 0);
+    }
+}
+
 pub fn putchar(ch: u8) {
     unsafe {
         sbicall(SBI_CONSOLE_PUTCHAR, 0, ch as usize, 0, 0);
     }
 }
 
+/// A random word from the security monitor's entropy source.
+///
+/// # Panics
+///
+/// Panics if the security monitor fails to provide one, as there is no other
+/// source of entropy that the host cannot observe.
+pub fn random() -> usize {
+    let (error, value) = unsafe {
+        sbicall_with_value(
+            SBI_EXT_EXPERIMENTAL_KEYSTONE_ENCLAVE,
+            SBI_SM_RANDOM,
+            0,
+            0,
+            0,
+        )
+    };
+    if error != 0 {
+        panic!(
+            "The security monitor failed to provide randomness: {}",
+            error
+        );
+    }
+    value
+}
+
+/// Have the security monitor write a report binding `len` bytes of data at
+/// the physical address `data` to the enclave's measurement at the physical
+/// address `report`. Returns zero on success.
+pub fn attest_enclave(report: usize, data: usize, len: usize) -> usize {
+    unsafe {
+        sbicall(
+            SBI_EXT_EXPERIMENTAL_KEYSTONE_ENCLAVE,
+            SBI_SM_ATTEST_ENCLAVE,
+            report,
+            data,
+            len,
+        )
+    }
+}
+
+/// Have the security monitor derive a key, from the device's secret, the
+/// enclave's measurement and the `len` bytes of identifier at the physical
+/// address `ident`, and write it with its signature at the physical address
+/// `key`. Returns zero on success.
+pub fn get_sealing_key(key: usize, ident: usize, len: usize) -> usize {
+    unsafe {
+        sbicall(
+            SBI_EXT_EXPERIMENTAL_KEYSTONE_ENCLAVE,
+            SBI_SM_GET_SEALING_KEY,
+            key,
+            ident,
+            len,
+        )
+    }
+}
+
 pub fn stop_enclave(req: usize) -> usize {
     unsafe {
         sbicall(
@@ -27,6 +27,13 @@ impl HeapPageManager {
     }
 }
 
+/// The physical address of `ptr`, which must point into the kernel's mirror
+/// of the EPM, like everything on the heap. The security monitor runs with
+/// translation off, so it only takes physical addresses.
+pub fn kernel_virt2phys<T>(ptr: *const T) -> usize {
+    HeapPageManager::new().virt2phys(VirtAddr::from_ptr(ptr)).0
+}
+
 impl PageManager for HeapPageManager {
     fn alloc_physical_page(&mut self) -> PhysAddr {
         let addr = VirtAddr::from_ptr(crate::vm::alloc_page());
@@ -0,0 +1,6 @@
+#[repr(C)]
+#[derive(Default)]
+pub struct UserspaceRegs {
+    pub pc: usize,
+    pub sp: usize,
+}
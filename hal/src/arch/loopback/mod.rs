@@ -0,0 +1,63 @@
+//! A host-side platform that connects the edge caller directly to
+//! `edge_responder` in the same process, so that syscall handlers can be
+//! exercised by `cargo test` without booting a TEE.
+
+extern crate std;
+
+use alloc::{boxed::Box, sync::Arc};
+use edge_proto::{notify::Notification, NetConfig};
+use spin::Mutex;
+use std::os::unix::net::UnixDatagram;
+
+use crate::task::Task;
+
+pub mod frame;
+pub mod vm;
+
+/// Allocate the edge memory from the heap and connect the edge caller to an
+/// in-process edge responder.
+pub fn initialize_edge_caller() {
+    crate::sys::edge::initialize_edge_caller();
+}
+
+/// Confine guest paths to the host directory `path`.
+pub fn set_guest_root(path: &str) {
+    edge_responder::sandbox::set_guest_root(path).expect("failed to set the guest root");
+}
+
+/// Put the host file system policy in `text` in effect.
+pub fn set_fs_policy(text: &str) {
+    let policy = edge_responder::policy::Policy::parse(text).expect("invalid file system policy");
+    edge_responder::policy::set_policy(policy).expect("failed to set the file system policy");
+}
+
+/// Make `task` the task returned by [`crate::task::current`].
+pub fn set_current_task(task: &Arc<Mutex<Task>>) {
+    crate::sys::task::set_current_tls(task.lock().tls.as_ref());
+}
+
+/// Run `entry` in `task` once the scheduler switches to it. The task exits
+/// when `entry` returns.
+pub fn set_task_entry(task: &Arc<Mutex<Task>>, entry: impl FnOnce() + Send + 'static) {
+    task.lock()
+        .ktask_ctx
+        .as_mut()
+        .expect("the task is running")
+        .set_entry(Box::new(entry));
+}
+
+/// Carry the frames of the network stack to the returned socket, whose other
+/// end is the host's network link, for a peer in the test.
+pub fn attach_frame_link(config: NetConfig) -> UnixDatagram {
+    edge_responder::net::frame_pipe(config).expect("failed to attach the network link")
+}
+
+/// Post a notification the way a host runner would.
+pub fn post_notification(notification: Notification) {
+    edge_responder::notify::post(notification);
+}
+
+/// Move the clock forward by `ns` nanoseconds.
+pub fn advance_clock(ns: u64) {
+    crate::sys::time::advance_counter(ns);
+}
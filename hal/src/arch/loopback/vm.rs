@@ -0,0 +1,48 @@
+use core::ops::Range;
+
+use crate::vm::{AddressSpace, ClonableAddressSpace};
+
+/// The loopback "user" shares the host process's address space, so there is
+/// nothing to map or unmap.
+#[derive(Debug)]
+pub struct UserAddressSpace;
+
+impl AddressSpace for UserAddressSpace {
+    fn current() -> UserAddressSpace {
+        UserAddressSpace
+    }
+
+    fn set_current(&self) {
+        // no-op
+    }
+
+    fn alloc_map(&mut self, _range: Range<usize>) {
+        panic!("the loopback platform cannot map user memory");
+    }
+
+    fn alloc_map_zeroed(&mut self, _range: Range<usize>) {
+        panic!("the loopback platform cannot map user memory");
+    }
+
+    fn unmap_dealloc(&mut self, _range: Range<usize>) {
+        // no-op
+    }
+
+    fn virt2phys(&self, ptr: *const ()) -> usize {
+        ptr as usize
+    }
+
+    fn phys2virt(&self, addr: usize) -> *const () {
+        addr as *const ()
+    }
+}
+
+impl ClonableAddressSpace for UserAddressSpace {
+    fn create_bare(&self) -> UserAddressSpace {
+        panic!("the loopback platform cannot create address spaces");
+    }
+
+    fn copy_from_current(&mut self, _range: Range<usize>) {
+        panic!("the loopback platform cannot create address spaces");
+    }
+}
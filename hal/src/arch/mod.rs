@@ -7,3 +7,9 @@ pub mod x86_vm;
 
 #[cfg(feature = "sgx")]
 pub mod sgx;
+
+#[cfg(any(feature = "x86-vm", feature = "sgx"))]
+pub mod x86;
+
+#[cfg(feature = "loopback")]
+pub mod loopback;
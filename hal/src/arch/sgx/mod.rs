@@ -1,4 +1,7 @@
-use sgx_types::sgx_status_t;
+use sgx_types::{
+    sgx_attributes_t, sgx_key_128bit_t, sgx_key_request_t, sgx_misc_select_t, sgx_report_data_t,
+    sgx_report_t, sgx_sealed_data_t, sgx_status_t, sgx_target_info_t,
+};
 
 pub mod frame;
 pub mod vm;
@@ -12,4 +15,57 @@ extern "C" {
     pub fn ocall_edge_kick() -> sgx_status_t;
     pub fn ocall_exit(status: i32) -> sgx_status_t;
     pub fn ocall_switch_gs_base(old_gs_base: *mut usize, new_gs_base: usize) -> sgx_status_t;
+    pub fn ocall_get_qe_target_info(
+        retval: *mut sgx_status_t,
+        target_info: *mut sgx_target_info_t,
+    ) -> sgx_status_t;
+    pub fn ocall_get_quote(
+        retval: *mut sgx_status_t,
+        report: *const sgx_report_t,
+        quote: *mut u8,
+        quote_size: u32,
+        quote_len: *mut u32,
+    ) -> sgx_status_t;
+}
+
+// Trusted functions of the SGX SDK (`sgx_tservice`)
+extern "C" {
+    pub fn sgx_create_report(
+        target_info: *const sgx_target_info_t,
+        report_data: *const sgx_report_data_t,
+        report: *mut sgx_report_t,
+    ) -> sgx_status_t;
+    pub fn sgx_self_report() -> *const sgx_report_t;
+    pub fn sgx_get_key(
+        key_request: *const sgx_key_request_t,
+        key: *mut sgx_key_128bit_t,
+    ) -> sgx_status_t;
+    pub fn sgx_calc_sealed_data_size(add_mac_txt_size: u32, txt_encrypt_size: u32) -> u32;
+    pub fn sgx_get_add_mac_txt_len(sealed_data: *const sgx_sealed_data_t) -> u32;
+    pub fn sgx_get_encrypt_txt_len(sealed_data: *const sgx_sealed_data_t) -> u32;
+    pub fn sgx_seal_data_ex(
+        key_policy: u16,
+        attribute_mask: sgx_attributes_t,
+        misc_mask: sgx_misc_select_t,
+        additional_mac_text_length: u32,
+        additional_mac_text: *const u8,
+        text_to_encrypt_length: u32,
+        text_to_encrypt: *const u8,
+        sealed_data_size: u32,
+        sealed_data: *mut sgx_sealed_data_t,
+    ) -> sgx_status_t;
+    pub fn sgx_unseal_data(
+        sealed_data: *const sgx_sealed_data_t,
+        additional_mac_text: *mut u8,
+        additional_mac_text_length: *mut u32,
+        decrypted_text: *mut u8,
+        decrypted_text_length: *mut u32,
+    ) -> sgx_status_t;
+}
+
+// Trusted functions of the SGX SDK (`sgx_tstdc`)
+extern "C" {
+    /// `CPUID` as reported by the host, since the instruction faults inside
+    /// enclaves.
+    pub fn sgx_cpuidex(cpuinfo: *mut [i32; 4], leaf: i32, subleaf: i32) -> sgx_status_t;
 }
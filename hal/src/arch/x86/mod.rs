@@ -0,0 +1,3 @@
+//! Helpers shared by the platforms on x86-64.
+
+pub mod rand;
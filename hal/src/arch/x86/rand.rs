@@ -0,0 +1,34 @@
+use core::arch::x86_64::{_rdrand64_step, _rdseed64_step};
+
+/// How many times an instruction is retried when the CPU has no entropy ready.
+const RETRIES: usize = 128;
+
+#[target_feature(enable = "rdseed")]
+unsafe fn rdseed() -> Option<u64> {
+    let mut value = 0;
+    (0..RETRIES).find_map(|_| (_rdseed64_step(&mut value) == 1).then_some(value))
+}
+
+#[target_feature(enable = "rdrand")]
+unsafe fn rdrand() -> Option<u64> {
+    let mut value = 0;
+    (0..RETRIES).find_map(|_| (_rdrand64_step(&mut value) == 1).then_some(value))
+}
+
+/// Fill `seed` from the CPU's random number generator. `RDSEED` is preferred
+/// if `has_rdseed`, as its output comes straight from the CPU's entropy
+/// source, and `RDRAND` is used when it has nothing left.
+///
+/// # Safety
+///
+/// The CPU must have `RDRAND`, and `RDSEED` too if `has_rdseed`.
+pub unsafe fn fill_seed(seed: &mut [u8], has_rdseed: bool) {
+    for chunk in seed.chunks_mut(8) {
+        let value = has_rdseed
+            .then(|| rdseed())
+            .flatten()
+            .or_else(|| rdrand())
+            .expect("the CPU's random number generator failed");
+        chunk.copy_from_slice(&value.to_ne_bytes()[..chunk.len()]);
+    }
+}
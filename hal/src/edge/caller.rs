@@ -1,6 +1,14 @@
-use edge_proto::caller::EdgeCaller;
+use core::sync::atomic::{AtomicBool, Ordering};
+use edge_proto::{
+    caller::{EdgeCaller, Result, SharedMemCaller},
+    channel::EdgeMemLayout,
+    notify::NotificationArea,
+    EdgeCallReq, EdgeCallResp,
+};
+use spin::{Mutex, MutexGuard};
 
-use crate::sys::edge::with_edge_caller_impl;
+use crate::cfg::{EDGE_BUFFER_SIZE, EDGE_CHANNELS, EDGE_HEADER_SIZE};
+use crate::sys::edge::edge_channels;
 
 pub trait EdgeCallerManager<'c>: Sync {
     type Holder: EdgeCaller + 'c;
@@ -8,10 +16,133 @@ pub trait EdgeCallerManager<'c>: Sync {
     fn acquire(&'c self) -> Self::Holder;
 }
 
-/// Acquire the edge caller and do something with it (usually issuing edge calls).
+/// The layout of the edge memory on this platform.
+pub const EDGE_MEM_LAYOUT: EdgeMemLayout =
+    EdgeMemLayout::new(EDGE_CHANNELS, EDGE_HEADER_SIZE, EDGE_BUFFER_SIZE);
+
+/// How many times [`EdgeChannels::acquire`] looks for a free channel before it
+/// gives up.
+const ACQUIRE_SPINS: usize = 1 << 20;
+
+/// The edge channels carved from the edge memory. Each channel serves one edge
+/// call at a time, so an edge call can be issued while others are in progress.
+///
+/// The last channel may be dedicated to a single user with
+/// [`with_dedicated_edge_caller`], after which it is no longer shared.
+pub struct EdgeChannels {
+    base: *mut u8,
+    channels: [Mutex<SharedMemCaller>; EDGE_CHANNELS],
+    dedicated: AtomicBool,
+}
+
+unsafe impl Send for EdgeChannels {}
+unsafe impl Sync for EdgeChannels {}
+
+impl EdgeChannels {
+    /// # Safety
+    ///
+    /// `base` must point to the edge memory, laid out as [`EDGE_MEM_LAYOUT`],
+    /// and must stay mapped for the lifetime of the channels.
+    pub unsafe fn new(base: *mut u8, kick: fn() -> Result<()>) -> EdgeChannels {
+        EdgeChannels {
+            base,
+            channels: core::array::from_fn(|channel| {
+                Mutex::new(SharedMemCaller::for_channel(
+                    &EDGE_MEM_LAYOUT,
+                    base,
+                    channel,
+                    kick,
+                ))
+            }),
+            dedicated: AtomicBool::new(false),
+        }
+    }
+
+    pub fn notification_area(&self) -> &NotificationArea {
+        unsafe { EDGE_MEM_LAYOUT.notification_area(self.base) }
+    }
+}
+
+/// An edge channel acquired from [`EdgeChannels`], released when dropped.
+pub struct EdgeChannel<'c>(MutexGuard<'c, SharedMemCaller>);
+
+impl EdgeCaller for EdgeChannel<'_> {
+    fn write_header(&mut self, header: &EdgeCallReq) -> Result<()> {
+        self.0.write_header(header)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        self.0.write_data(data)
+    }
+
+    fn kick(&mut self) -> Result<()> {
+        self.0.kick()
+    }
+
+    fn read_header(&mut self) -> Result<EdgeCallResp> {
+        self.0.read_header()
+    }
+
+    fn read_data(&mut self) -> Result<&[u8]> {
+        self.0.read_data()
+    }
+
+    fn data_zone_mut(&mut self) -> Result<&mut [u8]> {
+        self.0.data_zone_mut()
+    }
+}
+
+impl<'c> EdgeCallerManager<'c> for EdgeChannels {
+    type Holder = EdgeChannel<'c>;
+
+    /// Acquire a free channel, spinning for a while until one is released if
+    /// all of them are busy.
+    ///
+    /// # Panics
+    ///
+    /// The kernel runs on a single CPU, so a busy channel is usually held by
+    /// the caller itself, e.g. through nested [`with_edge_caller`] calls, and
+    /// is never released. Panics if no channel is released in time.
+    fn acquire(&'c self) -> EdgeChannel<'c> {
+        let shared = if self.dedicated.load(Ordering::Acquire) {
+            &self.channels[0..EDGE_CHANNELS - 1]
+        } else {
+            &self.channels[..]
+        };
+        for _ in 0..ACQUIRE_SPINS {
+            if let Some(guard) = shared.iter().find_map(Mutex::try_lock) {
+                return EdgeChannel(guard);
+            }
+            core::hint::spin_loop();
+        }
+        panic!(
+            "all {} shared edge channels are held, are edge calls nested too deeply?",
+            shared.len()
+        );
+    }
+}
+
+/// Acquire an edge channel and do something with it (usually issuing edge calls).
 pub fn with_edge_caller<F, R>(f: F) -> R
 where
     F: FnOnce(&mut dyn EdgeCaller) -> R,
 {
-    with_edge_caller_impl(f)
+    let channels = edge_channels().expect("the edge channels are not initialized");
+    f(&mut channels.acquire())
+}
+
+/// Do something with the dedicated edge channel, so that a stream of edge
+/// calls (such as the frames of the network stack) neither waits for nor
+/// holds up the other edge calls.
+pub fn with_dedicated_edge_caller<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut dyn EdgeCaller) -> R,
+{
+    const _: () = assert!(EDGE_CHANNELS > 1, "no edge channel to dedicate");
+    let channels = edge_channels().expect("the edge channels are not initialized");
+    channels.dedicated.store(true, Ordering::Release);
+    // A shared user may still hold it until then
+    f(&mut EdgeChannel(
+        channels.channels[EDGE_CHANNELS - 1].lock(),
+    ))
 }
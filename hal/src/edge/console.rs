@@ -20,7 +20,12 @@ fn print_buffer_once(msg: &[u8]) {
 }
 
 pub fn print_str(msg: &str) {
-    for chunk in msg.as_bytes().chunks(super::EDGE_BUFFER_SIZE) {
+    print_bytes(msg.as_bytes());
+}
+
+/// Print raw bytes, which need not be valid UTF-8.
+pub fn print_bytes(msg: &[u8]) {
+    for chunk in msg.chunks(super::EDGE_BUFFER_SIZE) {
         print_buffer_once(chunk);
     }
 }
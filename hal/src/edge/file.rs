@@ -1,5 +1,8 @@
 use alloc::string::String;
-use edge_proto::EdgeCallReq;
+use edge_proto::{
+    caller::{EdgeCallError, Result},
+    EdgeCallReq,
+};
 
 use super::{with_edge_caller, EDGE_BUFFER_SIZE};
 
@@ -8,105 +11,113 @@ pub struct EdgeFile {
 }
 
 impl EdgeFile {
-    pub fn open(path: &str) -> EdgeFile {
+    pub fn open(path: &str) -> Result<EdgeFile> {
         let file_obj = with_edge_caller(|caller| {
-            caller
-                .write_header(&EdgeCallReq::FileOpen {
-                    path: String::from(path),
-                })
-                .unwrap();
-            caller.kick().unwrap();
+            caller.write_header(&EdgeCallReq::FileOpen {
+                path: String::from(path),
+            })?;
+            caller.kick()?;
 
-            let result = caller.read_header().unwrap();
-            let result = result.into_ok_with_u64().expect("failed to open edge file");
-            result
-        });
-        EdgeFile { file_obj }
+            caller
+                .read_header()?
+                .into_result()?
+                .into_ok_with_u64()
+                .map_err(|_| EdgeCallError::UnexpectedResponse)
+        })?;
+        Ok(EdgeFile { file_obj })
     }
 
-    pub fn size(&self) -> usize {
+    pub fn size(&self) -> Result<usize> {
         with_edge_caller(|caller| {
-            caller
-                .write_header(&EdgeCallReq::FileGetSize {
-                    file_obj: self.file_obj,
-                })
-                .unwrap();
-            caller.kick().unwrap();
+            caller.write_header(&EdgeCallReq::FileGetSize {
+                file_obj: self.file_obj,
+            })?;
+            caller.kick()?;
 
-            let result = caller.read_header().unwrap();
-            let result = result.into_ok_with_u64().expect("failed to stat edge file");
-            result as usize
+            let result = caller
+                .read_header()?
+                .into_result()?
+                .into_ok_with_u64()
+                .map_err(|_| EdgeCallError::UnexpectedResponse)?;
+            Ok(result as usize)
         })
     }
 
-    fn read_once(&mut self, dest: &mut [u8]) -> usize {
+    fn read_once(&mut self, dest: &mut [u8]) -> Result<usize> {
         assert!(dest.len() <= EDGE_BUFFER_SIZE);
         with_edge_caller(|caller| {
-            caller
-                .write_header(&EdgeCallReq::FileRead {
-                    file_obj: self.file_obj,
-                    len: dest.len() as u32,
-                })
-                .unwrap();
-            caller.kick().unwrap();
+            caller.write_header(&EdgeCallReq::FileRead {
+                file_obj: self.file_obj,
+                len: dest.len() as u32,
+            })?;
+            caller.kick()?;
 
-            let result = caller.read_header().unwrap();
-            let len = result.into_ok_with_u64().expect("failed to read edge file") as usize;
-            dest[0..len].copy_from_slice(&caller.read_data().unwrap()[0..len]);
-            len
+            let len = caller
+                .read_header()?
+                .into_result()?
+                .into_ok_with_u64()
+                .map_err(|_| EdgeCallError::UnexpectedResponse)? as usize;
+            if len > dest.len() {
+                return Err(EdgeCallError::BufferOverflow {
+                    len,
+                    capacity: dest.len(),
+                });
+            }
+            dest[0..len].copy_from_slice(&caller.read_data()?[0..len]);
+            Ok(len)
         })
     }
 
-    pub fn read(&mut self, mut dest: &mut [u8]) -> usize {
+    pub fn read(&mut self, mut dest: &mut [u8]) -> Result<usize> {
         let mut bytes_read = 0;
         while dest.len() > EDGE_BUFFER_SIZE {
-            let bytes_read_cur = self.read_once(&mut dest[0..EDGE_BUFFER_SIZE]);
+            let bytes_read_cur = self.read_once(&mut dest[0..EDGE_BUFFER_SIZE])?;
             bytes_read += bytes_read_cur;
             if bytes_read_cur < EDGE_BUFFER_SIZE {
-                return bytes_read_cur;
+                return Ok(bytes_read);
             }
             dest = &mut dest[EDGE_BUFFER_SIZE..];
         }
-        bytes_read += self.read_once(dest);
-        bytes_read
+        bytes_read += self.read_once(dest)?;
+        Ok(bytes_read)
     }
 
-    pub fn seek(&mut self, pos: u64) {
+    pub fn seek(&mut self, pos: u64) -> Result<()> {
         with_edge_caller(|caller| {
-            caller
-                .write_header(&EdgeCallReq::FileSeek {
-                    file_obj: self.file_obj,
-                    pos,
-                })
-                .unwrap();
-            caller.kick().unwrap();
+            caller.write_header(&EdgeCallReq::FileSeek {
+                file_obj: self.file_obj,
+                pos,
+            })?;
+            caller.kick()?;
 
-            let result = caller.read_header().unwrap();
-            assert!(result.is_ok(), "failed to seek edge file");
-        });
+            caller.read_header()?.into_result()?;
+            Ok(())
+        })
     }
 
-    fn close_remote_file(&self) {
+    fn close_remote_file(&self) -> Result<()> {
         with_edge_caller(|caller| {
-            caller
-                .write_header(&EdgeCallReq::FileClose {
-                    file_obj: self.file_obj,
-                })
-                .unwrap();
-            caller.kick().unwrap();
-            assert!(caller.read_header().unwrap().is_ok());
-        });
+            caller.write_header(&EdgeCallReq::FileClose {
+                file_obj: self.file_obj,
+            })?;
+            caller.kick()?;
+
+            caller.read_header()?.into_result()?;
+            Ok(())
+        })
     }
 
-    pub fn close(self) {
+    pub fn close(self) -> Result<()> {
         // prevent drop handler from being called
         let guard = core::mem::ManuallyDrop::new(self);
-        guard.close_remote_file();
+        guard.close_remote_file()
     }
 }
 
 impl Drop for EdgeFile {
     fn drop(&mut self) {
-        self.close_remote_file();
+        if let Err(err) = self.close_remote_file() {
+            log::warn!("Failed to close edge file: {}", err);
+        }
     }
 }
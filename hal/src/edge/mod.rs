@@ -1,9 +1,11 @@
 mod caller;
 mod console;
 mod file;
+mod notify;
 
 pub use caller::*;
 pub use console::*;
 pub use file::*;
+pub use notify::*;
 
 pub const EDGE_BUFFER_SIZE: usize = crate::cfg::EDGE_BUFFER_SIZE;
@@ -0,0 +1,33 @@
+use edge_proto::notify::Notification;
+use spin::Mutex;
+
+use crate::sys::edge::edge_channels;
+
+static NOTIFICATION_HANDLER: Mutex<Option<fn(Notification)>> = Mutex::new(None);
+
+/// Set the function that handles notifications posted by the host.
+pub fn set_notification_handler(handler: fn(Notification)) {
+    *NOTIFICATION_HANDLER.lock() = Some(handler);
+}
+
+/// Take the notifications posted by the host and pass them to the handler.
+///
+/// The scheduler calls this before switching to a task, and while it waits for
+/// a blocked task to be woken up. Notifications taken before a handler is set
+/// are dropped.
+pub fn poll_notifications() {
+    let pending = match edge_channels() {
+        Some(channels) => channels.notification_area().take(),
+        None => return,
+    };
+    if pending.is_empty() {
+        return;
+    }
+
+    // Do not hold the lock while handling, the handler may not return
+    let handler = *NOTIFICATION_HANDLER.lock();
+    match handler {
+        Some(handler) => pending.iter().for_each(handler),
+        None => log::debug!("No notification handler, dropping {:?}", pending),
+    }
+}
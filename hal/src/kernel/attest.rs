@@ -0,0 +1,48 @@
+//! Attestation reports, which prove to a remote verifier that data comes from
+//! an enclave with a given measurement.
+//!
+//! Reports are in the platform's own format: the security monitor's signed
+//! report on Keystone, a DCAP quote made by the host's quoting enclave on SGX,
+//! and a simulated report on the platforms without a TEE (see
+//! [`simulated_report`]).
+
+use alloc::vec::Vec;
+
+/// The size of the data bound to a report. Shorter data is padded with zeros.
+pub const REPORT_DATA_SIZE: usize = 64;
+
+/// The magic number at the start of a simulated report.
+pub const SIMULATED_MAGIC: [u8; 8] = *b"SIMREPT\0";
+
+/// The key that simulated reports are authenticated with. It is public, so a
+/// simulated report only shows that it was not corrupted, not where it comes
+/// from.
+pub const SIMULATED_KEY: [u8; 32] = *b"simulated platform, not a secret";
+
+/// Make a report binding `data` to the measurement of the enclave, or `None`
+/// if the platform fails to.
+///
+/// # Panics
+///
+/// Panics if `data` is longer than [`REPORT_DATA_SIZE`].
+pub fn report(data: &[u8]) -> Option<Vec<u8>> {
+    assert!(data.len() <= REPORT_DATA_SIZE, "report data too long");
+    let mut report_data = [0; REPORT_DATA_SIZE];
+    report_data[..data.len()].copy_from_slice(data);
+    crate::sys::attest::report(&report_data)
+}
+
+/// A report for a platform which cannot measure the enclave, so its
+/// measurement is all zeros: the magic number, the measurement (32 bytes), the
+/// report data and an HMAC-SHA256 of all of them under [`SIMULATED_KEY`].
+#[cfg(any(feature = "x86-vm", feature = "loopback"))]
+pub(crate) fn simulated_report(report_data: &[u8; REPORT_DATA_SIZE]) -> Vec<u8> {
+    let mut report = Vec::with_capacity(8 + 32 + REPORT_DATA_SIZE + 32);
+    report.extend(SIMULATED_MAGIC);
+    report.extend([0; 32]);
+    report.extend(report_data);
+    let mut mac = crypto::hmac::HmacSha256::new(&SIMULATED_KEY);
+    mac.update(&report);
+    report.extend(mac.finalize());
+    report
+}
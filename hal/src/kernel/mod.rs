@@ -1,9 +1,21 @@
+/// Attestation APIs.
+pub mod attest;
+
 /// Memory APIs (e.g. `copy_from_user`).
 pub mod mem;
 
+/// Random number APIs.
+pub mod rand;
+
+/// Sealing APIs.
+pub mod seal;
+
 /// Process APIs.
 pub mod task;
 
+/// Clock and timer APIs.
+pub mod time;
+
 /// Virtual memory APIs.
 pub mod vm;
 
@@ -0,0 +1,80 @@
+//! The kernel's random number generator.
+//!
+//! Random bytes come from a ChaCha20-based CSPRNG, seeded and periodically
+//! reseeded from the platform's entropy source inside the TEE (`RDSEED` and
+//! `RDRAND` on x86, the security monitor on Keystone). The host is never asked
+//! for randomness, as it could make the output predictable.
+//!
+//! After each request the generator replaces its key with fresh keystream
+//! ("fast key erasure"), so its state never reveals earlier output.
+
+use crypto::{
+    chacha20::{self, KEY_SIZE, NONCE_SIZE},
+    sha256::Sha256,
+};
+use spin::Mutex;
+
+use crate::sys::rand::read_seed;
+
+/// How many bytes are generated under one seed before a fresh one is mixed in.
+const RESEED_INTERVAL: usize = 1 << 20;
+
+/// The most bytes generated for one request before the key is replaced, so
+/// that large requests do not hold the lock for long.
+const MAX_REQUEST: usize = 1 << 16;
+
+/// Each key is only used for one request, so the nonce can be fixed.
+const NONCE: [u8; NONCE_SIZE] = [0; NONCE_SIZE];
+
+static RNG: Mutex<Option<Rng>> = Mutex::new(None);
+
+struct Rng {
+    key: [u8; KEY_SIZE],
+    /// Bytes generated since the last reseed.
+    generated: usize,
+}
+
+impl Rng {
+    fn new() -> Rng {
+        let mut key = [0; KEY_SIZE];
+        read_seed(&mut key);
+        Rng { key, generated: 0 }
+    }
+
+    fn reseed(&mut self) {
+        let mut seed = [0; KEY_SIZE];
+        read_seed(&mut seed);
+        let mut hasher = Sha256::new();
+        hasher.update(&self.key);
+        hasher.update(&seed);
+        self.key = hasher.finalize();
+        self.generated = 0;
+    }
+
+    /// Fill `buf`, which must not be larger than [`MAX_REQUEST`], and replace
+    /// the key. The first block of keystream becomes the next key, and the
+    /// following ones are output.
+    fn generate(&mut self, buf: &mut [u8]) {
+        if self.generated >= RESEED_INTERVAL {
+            self.reseed();
+        }
+        buf.fill(0);
+        chacha20::apply_keystream(&self.key, 1, &NONCE, buf);
+        let next = chacha20::block(&self.key, 0, &NONCE);
+        self.key.copy_from_slice(&next[..KEY_SIZE]);
+        self.generated += buf.len();
+    }
+}
+
+/// Fill `buf` with random bytes.
+pub fn fill_bytes(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(MAX_REQUEST) {
+        RNG.lock().get_or_insert_with(Rng::new).generate(chunk);
+    }
+}
+
+pub fn random_u64() -> u64 {
+    let mut bytes = [0; 8];
+    fill_bytes(&mut bytes);
+    u64::from_ne_bytes(bytes)
+}
@@ -0,0 +1,75 @@
+//! Sealing, which encrypts data under a key that only the same enclave can
+//! derive again, so that the host can store it between runs.
+//!
+//! Sealed blobs are bound to the enclave's measurement: on Keystone, the key
+//! is derived by the security monitor, and SGX seals with the `MRENCLAVE`
+//! policy. The x86 VM has no TEE to derive a key from, so it uses a fixed key
+//! (see [`SOFTWARE_KEY`]), which only makes sense for testing.
+
+use alloc::vec::Vec;
+
+/// The key of the platforms without a TEE. It is public, so their blobs are
+/// not confidential.
+#[cfg(any(feature = "x86-vm", feature = "loopback"))]
+pub(crate) const SOFTWARE_KEY: [u8; 32] = *b"software sealing key, not secret";
+
+/// The magic number at the start of a blob sealed by [`seal_with_key`],
+/// followed by the nonce, the ciphertext and the tag.
+#[cfg(any(feature = "keystone", feature = "x86-vm", feature = "loopback"))]
+const MAGIC: [u8; 8] = *b"SEALED1\0";
+
+/// Seal `data`, or return `None` if the platform fails to.
+pub fn seal(data: &[u8]) -> Option<Vec<u8>> {
+    crate::sys::seal::seal(data)
+}
+
+/// Recover the data sealed in `blob`, or return `None` if it was not sealed
+/// by this enclave or has been tampered with.
+pub fn unseal(blob: &[u8]) -> Option<Vec<u8>> {
+    crate::sys::seal::unseal(blob)
+}
+
+/// Derive a key for `label` that only the same enclave can derive again, like
+/// the sealing key, or return `None` if the platform fails to.
+pub fn derive_key(label: &[u8]) -> Option<[u8; 32]> {
+    crate::sys::seal::derive_key(label)
+}
+
+/// Seal `data` with ChaCha20-Poly1305 under `key`, with a random nonce.
+#[cfg(any(feature = "keystone", feature = "x86-vm", feature = "loopback"))]
+pub(crate) fn seal_with_key(key: &[u8; 32], data: &[u8]) -> Vec<u8> {
+    use crypto::{aead::TAG_SIZE, chacha20::NONCE_SIZE};
+
+    let mut nonce = [0; NONCE_SIZE];
+    crate::rand::fill_bytes(&mut nonce);
+    let mut blob = Vec::with_capacity(MAGIC.len() + NONCE_SIZE + data.len() + TAG_SIZE);
+    blob.extend(MAGIC);
+    blob.extend(nonce);
+    let start = blob.len();
+    blob.extend(data);
+    let tag = crypto::aead::seal(key, &nonce, &MAGIC, &mut blob[start..]);
+    blob.extend(tag);
+    blob
+}
+
+#[cfg(any(feature = "keystone", feature = "x86-vm", feature = "loopback"))]
+pub(crate) fn unseal_with_key(key: &[u8; 32], blob: &[u8]) -> Option<Vec<u8>> {
+    use crypto::{aead::TAG_SIZE, chacha20::NONCE_SIZE};
+
+    let rest = blob.strip_prefix(&MAGIC)?;
+    if rest.len() < NONCE_SIZE + TAG_SIZE {
+        return None;
+    }
+    let (nonce, rest) = rest.split_at(NONCE_SIZE);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_SIZE);
+    let mut data = ciphertext.to_vec();
+    crypto::aead::open(
+        key,
+        nonce.try_into().unwrap(),
+        &MAGIC,
+        &mut data,
+        tag.try_into().unwrap(),
+    )
+    .ok()?;
+    Some(data)
+}
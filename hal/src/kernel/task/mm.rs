@@ -1,4 +1,4 @@
-use alloc::collections::BTreeMap;
+use alloc::{collections::BTreeMap, string::String};
 use core::ops::Range;
 
 #[cfg(feature = "multitasking")]
@@ -11,6 +11,8 @@ pub struct TaskMmStruct {
     pub addr_space: UserAddressSpace,
     pub vmas: BTreeMap<usize, VmArea>,
     pub stack_zone: Range<usize>,
+    /// The absolute guest path of the executable, if it is known.
+    pub exe: String,
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +26,7 @@ impl TaskMmStruct {
             addr_space,
             vmas: BTreeMap::new(),
             stack_zone,
+            exe: String::new(),
         }
     }
 
@@ -94,6 +97,7 @@ impl TaskMmStruct {
             addr_space: new_addr_space,
             vmas: self.vmas.clone(),
             stack_zone: self.stack_zone.clone(),
+            exe: self.exe.clone(),
         }
     }
 
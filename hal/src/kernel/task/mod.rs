@@ -1,29 +1,42 @@
 use alloc::{
     boxed::Box,
+    collections::BTreeMap,
     sync::{Arc, Weak},
+    vec::Vec,
 };
 use core::{
     future::Future,
     mem::{ManuallyDrop, MaybeUninit},
     pin::Pin,
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
 };
 use spin::Mutex;
 
 mod mm;
+mod parking;
 mod pid_pool;
+mod sched;
 mod waitqueue;
 
 use crate::kernel::vm::AddressSpace;
 pub use crate::sys::task::*;
 pub use mm::{TaskMmStruct, VmArea};
+pub use parking::ParkingLot;
 pub use pid_pool::PidPool;
+pub use sched::{run_until_idle, spawn, tick, Policy, SchedStats, NICE_MAX, NICE_MIN};
 pub use waitqueue::WaitQueue;
 
 pub type Pid = i32;
 
 pub static PID_POOL: Mutex<PidPool> = Mutex::new(PidPool::new());
 
+/// How many timer ticks a task may run in user mode before it is preempted.
+pub const TIME_SLICE_TICKS: u32 = 2;
+
+/// Every task by PID. Reaped tasks are dropped, and their entries are removed
+/// lazily.
+static TASKS: Mutex<BTreeMap<Pid, Weak<Mutex<Task>>>> = Mutex::new(BTreeMap::new());
+
 pub struct Task {
     pub pid: Pid,
     pub exit_status: Option<i32>,
@@ -31,8 +44,21 @@ pub struct Task {
     pub parent: Weak<Mutex<Task>>,
     /// A simple wait queue, used by wait().
     pub wait_queue: WaitQueue,
-    /// Whether the task is waiting for a child process.
+    /// Whether the task is blocked, e.g. waiting for a child process, a timer
+    /// or a host file.
+    /// A blocked task only runs again once its [`Task::waker`] is called.
     pub waiting: bool,
+    /// Makes the task ready again after it has blocked. Set by the scheduler
+    /// whenever it switches to the task.
+    pub waker: Option<Waker>,
+    /// Timer ticks left in the current time slice, refilled whenever the
+    /// scheduler switches to the task.
+    pub slice_left: u32,
+    /// The nice value, from [`NICE_MIN`] to [`NICE_MAX`]. Lower values get a
+    /// larger share of the CPU.
+    pub nice: i32,
+    pub policy: Policy,
+    pub stats: SchedStats,
 
     /// The kernel thread's TLS (thread local storage), used by the `current!`
     /// macro and `ret_from_fork`. Only a kernel task has a TLS (the scheduler
@@ -80,6 +106,11 @@ impl Task {
             parent: Weak::new(),
             wait_queue: WaitQueue::new(),
             waiting: false,
+            waker: None,
+            slice_left: TIME_SLICE_TICKS,
+            nice: 0,
+            policy: Policy::Normal,
+            stats: SchedStats::default(),
             tls,
             ktask_ctx,
             mm,
@@ -90,6 +121,7 @@ impl Task {
         let pcb_weak_ptr = Arc::downgrade(&task).into_raw();
         // TODO: free `pcb_weak_ptr`, otherwise the `Task` won't be deallocated
         task.lock().tls.set_pcb_weak_ptr(pcb_weak_ptr as usize);
+        TASKS.lock().insert(pid, Arc::downgrade(&task));
         task
     }
 
@@ -122,6 +154,10 @@ impl Future for TaskFuture {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Handle host notifications between tasks, where no edge call is in
+        // progress.
+        crate::edge::poll_notifications();
+
         self.task.lock().mm.addr_space.set_current();
 
         // The scheduler's `KtaskCtx` (write only).
@@ -132,6 +168,8 @@ impl Future for TaskFuture {
             let mut task_guard = self.task.try_lock().unwrap();
             // Set the wait queue's waker.
             task_guard.wait_queue.set_waker(cx.waker().clone());
+            task_guard.waker = Some(cx.waker().clone());
+            task_guard.slice_left = TIME_SLICE_TICKS;
             // Borrow the Task's `KtaskCtx` to ensure exclusive access.
             next_ktask_ctx = task_guard
                 .ktask_ctx
@@ -157,7 +195,7 @@ impl Future for TaskFuture {
             // Terminate the current async task.
             Poll::Ready(())
         } else if task_guard.waiting {
-            // The task is waiting for a child process.
+            // The task is blocked until something calls its waker.
             Poll::Pending
         } else {
             // The task is still in ready state, push it back to the
@@ -210,6 +248,23 @@ pub fn current_pid() -> Pid {
     current().lock().pid
 }
 
+/// Find the task `pid`, unless it does not exist or has been reaped.
+pub fn find(pid: Pid) -> Option<Arc<Mutex<Task>>> {
+    let mut tasks = TASKS.lock();
+    let task = tasks.get(&pid)?.upgrade();
+    if task.is_none() {
+        tasks.remove(&pid);
+    }
+    task
+}
+
+/// The PIDs of the tasks that have not been reaped, in ascending order.
+pub fn pids() -> Vec<Pid> {
+    let mut tasks = TASKS.lock();
+    tasks.retain(|_, task| task.strong_count() > 0);
+    tasks.keys().copied().collect()
+}
+
 /// Print debug message about the current task.
 pub fn dbg_current() {
     let current = current();
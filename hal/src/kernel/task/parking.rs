@@ -0,0 +1,37 @@
+use alloc::vec::Vec;
+use core::task::Waker;
+use spin::Mutex;
+
+/// Tasks blocked until some event happens, which wakes all of them up. Woken
+/// tasks must check whether what they wait for has really happened, and park
+/// again if not.
+pub struct ParkingLot {
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl ParkingLot {
+    pub const fn new() -> ParkingLot {
+        ParkingLot {
+            wakers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Block the current task until [`ParkingLot::unpark_all`] is called.
+    pub fn park(&self) {
+        let current = super::current();
+        {
+            let mut task = current.lock();
+            let waker = task.waker.clone().expect("the task has never been run");
+            self.wakers.lock().push(waker);
+            task.waiting = true;
+        }
+        super::yield_to_sched();
+        current.lock().waiting = false;
+    }
+
+    /// Wake every parked task up.
+    pub fn unpark_all(&self) {
+        let wakers = core::mem::take(&mut *self.wakers.lock());
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
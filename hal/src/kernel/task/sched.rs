@@ -0,0 +1,306 @@
+//! A fair scheduler for kernel tasks, modeled after Linux's CFS.
+//!
+//! Every task accumulates a virtual runtime, which grows more slowly the
+//! higher the task's weight (derived from its nice value) is, and the task with
+//! the least virtual runtime runs next. A task that wakes up after blocking is
+//! placed slightly before the tasks that kept running, so latency-sensitive
+//! services get the CPU ahead of batch workers, but cannot bank the time they
+//! slept to starve everyone else afterwards.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    task::Wake,
+    vec::Vec,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Waker},
+};
+use spin::Mutex;
+
+use super::{current, Pid, Task, TaskFuture};
+
+pub const NICE_MIN: i32 = -20;
+pub const NICE_MAX: i32 = 19;
+
+/// The weight of nice 0.
+const NICE_0_WEIGHT: u64 = 1024;
+
+/// The weight of each nice value from -20 to 19, as on Linux. Each step is
+/// worth about 10% of CPU time.
+#[rustfmt::skip]
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291,
+    29154, 23254, 18705, 14949, 11916,
+    9548,  7620,  6100,  4904,  3906,
+    3121,  2501,  1991,  1586,  1277,
+    1024,  820,   655,   526,   423,
+    335,   272,   215,   172,   137,
+    110,   87,    70,    56,    45,
+    36,    29,    23,    18,    15,
+];
+
+/// The weight of [`Policy::Idle`] tasks, regardless of their nice value.
+const IDLE_WEIGHT: u64 = 3;
+
+/// Runtime is accounted in fractions of a timer tick, so that a task that
+/// blocks before the next tick still pays for being dispatched.
+const UNITS_PER_TICK: u64 = 16;
+
+/// How far (in virtual runtime of a nice 0 task) before the running tasks a
+/// waking [`Policy::Normal`] task is placed.
+const SLEEPER_CREDIT: u64 = super::TIME_SLICE_TICKS as u64 * UNITS_PER_TICK / 2;
+
+/// Timer ticks charged to any task since boot.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+static RUN_QUEUE: Mutex<RunQueue> = Mutex::new(RunQueue::new());
+
+/// Tasks woken since the scheduler last looked. Kept apart from the run queue,
+/// because wakers fire while the scheduler is running a task.
+static WOKEN: Mutex<Vec<Pid>> = Mutex::new(Vec::new());
+
+/// Scheduling policies, all served by the fair scheduler. Real-time policies
+/// are not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// `SCHED_OTHER`, the default.
+    Normal,
+    /// `SCHED_BATCH`, for CPU-bound tasks which gain nothing by waking up
+    /// early: they get no credit for the time they slept.
+    Batch,
+    /// `SCHED_IDLE`, for tasks that should only run when nothing else wants
+    /// to.
+    Idle,
+}
+
+impl Policy {
+    /// The policy with the given Linux number.
+    pub fn from_raw(policy: i32) -> Option<Policy> {
+        match policy {
+            0 => Some(Policy::Normal),
+            3 => Some(Policy::Batch),
+            5 => Some(Policy::Idle),
+            _ => None,
+        }
+    }
+
+    /// The policy's Linux number.
+    pub fn raw(self) -> i32 {
+        match self {
+            Policy::Normal => 0,
+            Policy::Batch => 3,
+            Policy::Idle => 5,
+        }
+    }
+}
+
+/// Runtime statistics of a task.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SchedStats {
+    /// Timer ticks that hit the task while it ran in user mode.
+    pub run_ticks: u64,
+    /// Timer ticks that went by while the task was ready, but waited for
+    /// other tasks to run.
+    pub wait_ticks: u64,
+    /// How often the task gave up the CPU by blocking or yielding.
+    pub voluntary_switches: u64,
+    /// How often the task was preempted at the end of its time slice.
+    pub involuntary_switches: u64,
+    /// Time spent running, in kernel or user mode, in nanoseconds. Does not
+    /// include the current run.
+    pub runtime_ns: u64,
+    /// When the current run started, if the task is running.
+    running_since: Option<u64>,
+}
+
+impl Task {
+    /// CPU time used by the task, including the current run, in nanoseconds.
+    pub fn cpu_time_ns(&self) -> u64 {
+        let running = self
+            .stats
+            .running_since
+            .map_or(0, |since| crate::time::monotonic_ns() - since);
+        self.stats.runtime_ns + running
+    }
+}
+
+fn weight(nice: i32, policy: Policy) -> u64 {
+    match policy {
+        Policy::Idle => IDLE_WEIGHT,
+        _ => NICE_TO_WEIGHT[(nice.clamp(NICE_MIN, NICE_MAX) - NICE_MIN) as usize],
+    }
+}
+
+/// A task known to the scheduler.
+struct Entity {
+    future: TaskFuture,
+    waker: Waker,
+    vruntime: u64,
+    policy: Policy,
+    /// Whether the entity is in the ready queue.
+    queued: bool,
+    /// [`TICKS`] when the entity was queued.
+    queued_at: u64,
+}
+
+struct RunQueue {
+    /// Every task, except the one running.
+    entities: BTreeMap<Pid, Entity>,
+    /// Ready tasks by (virtual runtime, arrival), so that ties are served in
+    /// FIFO order.
+    ready: BTreeSet<(u64, u64, Pid)>,
+    arrivals: u64,
+    /// Never decreases, and follows the least virtual runtime of ready tasks.
+    min_vruntime: u64,
+}
+
+impl RunQueue {
+    const fn new() -> RunQueue {
+        RunQueue {
+            entities: BTreeMap::new(),
+            ready: BTreeSet::new(),
+            arrivals: 0,
+            min_vruntime: 0,
+        }
+    }
+
+    fn enqueue(&mut self, pid: Pid) {
+        let entity = match self.entities.get_mut(&pid) {
+            Some(entity) if !entity.queued => entity,
+            // The task is running or has exited, or is already queued
+            _ => return,
+        };
+        let credit = match entity.policy {
+            Policy::Normal => SLEEPER_CREDIT,
+            Policy::Batch | Policy::Idle => 0,
+        };
+        entity.vruntime = entity
+            .vruntime
+            .max(self.min_vruntime.saturating_sub(credit));
+        entity.queued = true;
+        entity.queued_at = TICKS.load(Ordering::Relaxed);
+        self.arrivals += 1;
+        self.ready.insert((entity.vruntime, self.arrivals, pid));
+    }
+
+    fn drain_woken(&mut self) {
+        let woken = core::mem::take(&mut *WOKEN.lock());
+        for pid in woken {
+            self.enqueue(pid);
+        }
+    }
+
+    /// Take the ready task with the least virtual runtime out of the queue.
+    fn pick_next(&mut self) -> Option<(Pid, Entity)> {
+        let (vruntime, _, pid) = self.ready.pop_first()?;
+        self.min_vruntime = self.min_vruntime.max(vruntime);
+        let mut entity = self.entities.remove(&pid).expect("ready task is unknown");
+        entity.queued = false;
+        Some((pid, entity))
+    }
+}
+
+struct TaskWaker(Pid);
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        WOKEN.lock().push(self.0);
+    }
+}
+
+/// Hand a task over to the scheduler. It becomes ready immediately.
+pub fn spawn(future: TaskFuture) {
+    let (pid, policy) = {
+        let task = future.task.lock();
+        (task.pid, task.policy)
+    };
+    let mut rq = RUN_QUEUE.lock();
+    let entity = Entity {
+        future,
+        waker: Waker::from(Arc::new(TaskWaker(pid))),
+        vruntime: rq.min_vruntime,
+        policy,
+        queued: false,
+        queued_at: 0,
+    };
+    rq.entities.insert(pid, entity);
+    rq.enqueue(pid);
+}
+
+/// Run tasks until all of them have exited. While every task is blocked, wait
+/// for a timer to expire or the host to post a notification.
+pub fn run_until_idle() {
+    loop {
+        crate::time::wake_expired();
+        let (pid, mut entity) = {
+            let mut rq = RUN_QUEUE.lock();
+            rq.drain_woken();
+            match rq.pick_next() {
+                Some(next) => next,
+                None if !rq.entities.is_empty() => {
+                    drop(rq);
+                    crate::edge::poll_notifications();
+                    core::hint::spin_loop();
+                    continue;
+                }
+                None => break,
+            }
+        };
+
+        let run_ticks = {
+            let mut task = entity.future.task.lock();
+            task.stats.wait_ticks += TICKS.load(Ordering::Relaxed) - entity.queued_at;
+            task.stats.running_since = Some(crate::time::monotonic_ns());
+            task.stats.run_ticks
+        };
+        let waker = entity.waker.clone();
+        let poll = Pin::new(&mut entity.future).poll(&mut Context::from_waker(&waker));
+        if poll.is_ready() {
+            log::debug!("PID {} has left the scheduler", pid);
+            continue;
+        }
+
+        // Charge the runtime at the task's current weight
+        let (ticks, weight) = {
+            let mut task = entity.future.task.lock();
+            task.stats.runtime_ns = task.cpu_time_ns();
+            task.stats.running_since = None;
+            if task.slice_left == 0 {
+                task.stats.involuntary_switches += 1;
+            } else {
+                task.stats.voluntary_switches += 1;
+            }
+            entity.policy = task.policy;
+            (
+                task.stats.run_ticks - run_ticks,
+                weight(task.nice, task.policy),
+            )
+        };
+        let runtime = (ticks * UNITS_PER_TICK).max(1);
+        entity.vruntime += (runtime * NICE_0_WEIGHT + weight - 1) / weight;
+
+        // A task that is still ready has woken itself up already
+        RUN_QUEUE.lock().entities.insert(pid, entity);
+    }
+}
+
+/// Charge a timer tick to the current task, which was interrupted in user
+/// mode. Returns whether its time slice has run out, in which case the caller
+/// should [`yield_to_sched`](super::yield_to_sched) before returning to user
+/// mode.
+pub fn tick() -> bool {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    let current = current();
+    let mut task = current.lock();
+    task.stats.run_ticks += 1;
+    task.slice_left = task.slice_left.saturating_sub(1);
+    task.slice_left == 0
+}
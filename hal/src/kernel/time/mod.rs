@@ -0,0 +1,128 @@
+//! The enclave's clock, driven by the platform's cycle counter (TSC or the
+//! `time` CSR).
+//!
+//! The host is asked for the wall clock once at boot, to find the epoch and,
+//! where the platform does not define it, the counter frequency. From then on,
+//! time is derived from the counter alone, so the host cannot make the
+//! monotonic clock jump or run backwards. The exception is an SGX1 enclave,
+//! where `RDTSC` faults: there the counter is the host's wall clock, which is
+//! only kept from running backwards.
+
+use edge_proto::{caller::EdgeCallError, EdgeCallReq};
+use spin::{Mutex, Once};
+
+use crate::{edge::with_edge_caller, sys::time::read_counter};
+use wheel::TimerWheel;
+
+mod wheel;
+
+pub const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+/// How long the counter is measured against the host's wall clock when its
+/// frequency has to be calibrated.
+const CALIBRATION_NS: u64 = 20_000_000;
+
+static CLOCK: Once<Clock> = Once::new();
+
+static TIMERS: Mutex<TimerWheel> = Mutex::new(TimerWheel::new());
+
+struct Clock {
+    /// Counter ticks per second.
+    freq: u64,
+    /// The counter when the clock was calibrated.
+    boot_counter: u64,
+    /// The wall clock when the clock was calibrated, in nanoseconds since the
+    /// Unix epoch.
+    boot_realtime: u64,
+}
+
+pub(crate) fn host_realtime_ns() -> u64 {
+    with_edge_caller(|caller| {
+        caller.write_header(&EdgeCallReq::WallClock)?;
+        caller.kick()?;
+
+        caller
+            .read_header()?
+            .into_result()?
+            .into_ok_with_u64()
+            .map_err(|_| EdgeCallError::UnexpectedResponse)
+    })
+    .expect("failed to read the host's wall clock")
+}
+
+/// Calibrate the clock. Must be called once at boot, before any time is read.
+pub fn init() {
+    CLOCK.call_once(|| {
+        let (start_realtime, start_counter) = (host_realtime_ns(), read_counter());
+        let (realtime, counter, freq) = match crate::sys::time::counter_freq() {
+            Some(freq) => (start_realtime, start_counter, freq),
+            None => loop {
+                let (realtime, counter) = (host_realtime_ns(), read_counter());
+                let elapsed = realtime.saturating_sub(start_realtime);
+                if elapsed >= CALIBRATION_NS {
+                    let cycles = (counter - start_counter) as u128;
+                    let freq = (cycles * NSEC_PER_SEC as u128 / elapsed as u128) as u64;
+                    break (realtime, counter, freq);
+                }
+            },
+        };
+        assert_ne!(freq, 0, "the counter does not advance");
+        log::debug!("Clock calibrated, the counter runs at {} Hz", freq);
+        Clock {
+            freq,
+            boot_counter: counter,
+            boot_realtime: realtime,
+        }
+    });
+}
+
+fn clock() -> &'static Clock {
+    CLOCK.get().expect("the clock has not been calibrated")
+}
+
+/// Nanoseconds since the clock was calibrated. Never goes backwards.
+pub fn monotonic_ns() -> u64 {
+    let clock = clock();
+    let cycles = read_counter().saturating_sub(clock.boot_counter) as u128;
+    (cycles * NSEC_PER_SEC as u128 / clock.freq as u128) as u64
+}
+
+/// Nanoseconds since the Unix epoch.
+pub fn realtime_ns() -> u64 {
+    clock().boot_realtime + monotonic_ns()
+}
+
+/// Block the current task until [`monotonic_ns`] reaches `deadline`.
+pub fn sleep_until(deadline: u64) {
+    let current = crate::task::current();
+    while monotonic_ns() < deadline {
+        {
+            let mut task = current.lock();
+            let waker = task.waker.clone().expect("the task has never been run");
+            TIMERS.lock().insert(deadline, waker);
+            task.waiting = true;
+        }
+        crate::task::yield_to_sched();
+        current.lock().waiting = false;
+    }
+}
+
+/// Wake the current task once [`monotonic_ns`] reaches `deadline`, without
+/// blocking it. This bounds how long it then waits for something else; the
+/// wakeup may come after it has stopped waiting, so it must check why it woke.
+pub fn wake_at(deadline: u64) {
+    let waker = crate::task::current()
+        .lock()
+        .waker
+        .clone()
+        .expect("the task has never been run");
+    TIMERS.lock().insert(deadline, waker);
+}
+
+/// Wake the tasks whose timers have expired. Called by the scheduler.
+pub(crate) fn wake_expired() {
+    let mut timers = TIMERS.lock();
+    if !timers.is_empty() {
+        timers.advance(monotonic_ns());
+    }
+}
@@ -0,0 +1,63 @@
+use alloc::vec::Vec;
+use core::task::Waker;
+
+/// The time covered by a slot of the wheel, in nanoseconds.
+const SLOT_NS: u64 = 1_000_000;
+const SLOTS: usize = 256;
+
+/// A hashed timing wheel. A timer sits in the slot of its deadline modulo the
+/// wheel's size, so a slot may hold timers that expire in later rounds.
+pub struct TimerWheel {
+    slots: [Vec<(u64, Waker)>; SLOTS],
+    /// The slot that was last looked at, counted from time 0.
+    last_slot: u64,
+    len: usize,
+}
+
+impl TimerWheel {
+    pub const fn new() -> TimerWheel {
+        const EMPTY: Vec<(u64, Waker)> = Vec::new();
+        TimerWheel {
+            slots: [EMPTY; SLOTS],
+            last_slot: 0,
+            len: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Call `waker` once the time reaches `deadline`.
+    pub fn insert(&mut self, deadline: u64, waker: Waker) {
+        let slot = deadline / SLOT_NS;
+        if slot < self.last_slot {
+            // The wheel has already turned past the deadline
+            waker.wake();
+            return;
+        }
+        self.slots[slot as usize % SLOTS].push((deadline, waker));
+        self.len += 1;
+    }
+
+    /// Turn the wheel to `now`, waking the timers that have expired.
+    pub fn advance(&mut self, now: u64) {
+        let now_slot = now / SLOT_NS;
+        // The last slot is looked at again, as it may hold timers that expire
+        // later within the slot. After a full round, every slot has been seen.
+        let slots = (now_slot.saturating_sub(self.last_slot) + 1).min(SLOTS as u64);
+        for slot in self.last_slot..self.last_slot + slots {
+            let timers = &mut self.slots[slot as usize % SLOTS];
+            let mut i = 0;
+            while i < timers.len() {
+                if timers[i].0 <= now {
+                    timers.swap_remove(i).1.wake();
+                    self.len -= 1;
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        self.last_slot = self.last_slot.max(now_slot);
+    }
+}
@@ -0,0 +1,31 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    arch::keystone::{sbi, vm::kernel_virt2phys},
+    kernel::attest::REPORT_DATA_SIZE,
+};
+
+/// The size of `struct report` of the security monitor: the enclave's report
+/// (measurement, data length, up to 1024 bytes of data and signature), the
+/// monitor's (measurement, public key and signature) and the device's public
+/// key.
+const REPORT_SIZE: usize = (64 + 8 + 1024 + 64) + (64 + 32 + 64) + 32;
+
+pub fn report(report_data: &[u8; REPORT_DATA_SIZE]) -> Option<Vec<u8>> {
+    // Both buffers must be on the heap, so that they are in the EPM mirror
+    let report_data = report_data.to_vec();
+    let mut report = vec![0; REPORT_SIZE];
+    let result = sbi::attest_enclave(
+        kernel_virt2phys(report.as_mut_ptr()),
+        kernel_virt2phys(report_data.as_ptr()),
+        REPORT_DATA_SIZE,
+    );
+    if result != 0 {
+        log::warn!(
+            "The security monitor failed to attest the enclave: {}",
+            result
+        );
+        return None;
+    }
+    Some(report)
+}
@@ -1,27 +1,19 @@
-use edge_proto::caller::{EdgeCaller, SharedMemCaller};
-use kconfig::{EDGE_BUFFER_SIZE, KERNEL_UTM_BASE};
-use spin::Mutex;
+use kconfig::KERNEL_UTM_BASE;
+use spin::Once;
 
-use crate::arch::keystone::sbi;
+use crate::{arch::keystone::sbi, edge::EdgeChannels};
 
-static GLOBAL_EDGE_CALLER: Mutex<SharedMemCaller> = Mutex::new(SharedMemCaller::new(
-    KERNEL_UTM_BASE as *mut u8,
-    0x1_000,
-    (KERNEL_UTM_BASE + 0x1_000) as *mut u8,
-    EDGE_BUFFER_SIZE,
-    edge_call_kick,
-));
+static EDGE_CHANNELS: Once<EdgeChannels> = Once::new();
 
 fn edge_call_kick() -> edge_proto::caller::Result<()> {
     sbi::stop_enclave(sbi::STOP_EDGE_CALL_HOST);
     Ok(())
 }
 
-pub fn with_edge_caller_impl<F, R>(f: F) -> R
-where
-    F: FnOnce(&mut dyn EdgeCaller) -> R,
-{
-    f(&mut *GLOBAL_EDGE_CALLER
-        .try_lock()
-        .expect("the edge caller is not reentrant"))
+pub fn edge_channels() -> Option<&'static EdgeChannels> {
+    // The UTM is always mapped at `KERNEL_UTM_BASE`
+    Some(
+        EDGE_CHANNELS
+            .call_once(|| unsafe { EdgeChannels::new(KERNEL_UTM_BASE as *mut u8, edge_call_kick) }),
+    )
 }
@@ -1,12 +1,24 @@
+/// Attestation reports signed by the security monitor.
+pub mod attest;
+
 /// Implementations of edge caller.
 pub mod edge;
 
 /// Implementations of `copy_from_user` and `copy_to_user`.
 pub mod mem;
 
+/// Entropy from the security monitor.
+pub mod rand;
+
+/// Sealing keys derived by the security monitor.
+pub mod seal;
+
 /// Implementations of process data structures and operations.
 pub mod task;
 
+/// The `time` CSR, which counts at the timebase frequency.
+pub mod time;
+
 /// Virtual memory abstractions.
 pub mod vm;
 
@@ -0,0 +1,41 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    arch::keystone::{sbi, vm::kernel_virt2phys},
+    kernel::seal::{seal_with_key, unseal_with_key},
+};
+
+/// Distinguishes the key from others that the enclave may derive.
+const KEY_IDENT: &[u8] = b"hal::seal";
+
+/// The size of `struct sealing_key` of the security monitor: the key and the
+/// monitor's signature over it.
+const SEALING_KEY_SIZE: usize = 128 + 64;
+
+/// Keys are derived from the monitor's key for [`KEY_IDENT`].
+pub fn derive_key(label: &[u8]) -> Option<[u8; 32]> {
+    // Both buffers must be on the heap, so that they are in the EPM mirror
+    let ident = KEY_IDENT.to_vec();
+    let mut sealing_key = vec![0; SEALING_KEY_SIZE];
+    let result = sbi::get_sealing_key(
+        kernel_virt2phys(sealing_key.as_mut_ptr()),
+        kernel_virt2phys(ident.as_ptr()),
+        ident.len(),
+    );
+    if result != 0 {
+        log::warn!(
+            "The security monitor failed to derive a sealing key: {}",
+            result
+        );
+        return None;
+    }
+    Some(crypto::hmac::hmac_sha256(&sealing_key[..128], label))
+}
+
+pub fn seal(data: &[u8]) -> Option<Vec<u8>> {
+    Some(seal_with_key(&derive_key(b"seal")?, data))
+}
+
+pub fn unseal(blob: &[u8]) -> Option<Vec<u8>> {
+    unseal_with_key(&derive_key(b"seal")?, blob)
+}
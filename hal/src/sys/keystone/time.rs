@@ -0,0 +1,11 @@
+use riscv::register::time;
+
+pub fn read_counter() -> u64 {
+    time::read() as u64
+}
+
+/// The timebase frequency is fixed by the platform, so the counter needs no
+/// calibration.
+pub fn counter_freq() -> Option<u64> {
+    Some(crate::cfg::TIMEBASE_FREQ as u64)
+}
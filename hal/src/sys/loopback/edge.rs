@@ -0,0 +1,46 @@
+use alloc::vec;
+use edge_proto::{caller::EdgeCallError, server::SharedMemEdgeStream};
+use spin::{Mutex, Once};
+
+use crate::edge::{EdgeChannels, EDGE_MEM_LAYOUT};
+
+static EDGE_CHANNELS: Once<EdgeChannels> = Once::new();
+
+// The responder's view of the same memory.
+static EDGE_STREAMS: Once<[Mutex<SharedMemEdgeStream>; kconfig::EDGE_CHANNELS]> = Once::new();
+
+pub fn initialize_edge_caller() {
+    // The edge memory lives as long as the process does.
+    let edge_mem = vec![0u8; kconfig::UTM_SIZE].leak().as_mut_ptr();
+    unsafe {
+        EDGE_STREAMS.call_once(|| {
+            core::array::from_fn(|channel| {
+                Mutex::new(SharedMemEdgeStream::for_channel(
+                    &EDGE_MEM_LAYOUT,
+                    edge_mem,
+                    channel,
+                ))
+            })
+        });
+        let channels = EDGE_CHANNELS.call_once(|| EdgeChannels::new(edge_mem, edge_call_kick));
+        edge_responder::notify::set_notification_area(channels.notification_area());
+    }
+}
+
+fn edge_call_kick() -> edge_proto::caller::Result<()> {
+    // Serve the channel that rang its doorbell, like a host runner would.
+    for stream in EDGE_STREAMS.get().unwrap() {
+        let mut stream = stream.try_lock().unwrap();
+        if stream.take_doorbell() {
+            edge_responder::handle_edge_call(&mut *stream).map_err(|err| {
+                log::error!("Failed to handle edge call: {:#}", err);
+                EdgeCallError::Transport
+            })?;
+        }
+    }
+    Ok(())
+}
+
+pub fn edge_channels() -> Option<&'static EdgeChannels> {
+    EDGE_CHANNELS.get()
+}
@@ -0,0 +1,12 @@
+pub mod attest;
+pub mod edge;
+pub mod mem;
+pub mod rand;
+pub mod seal;
+pub mod task;
+pub mod time;
+pub mod vm;
+
+pub fn exit_enclave(retval: usize) {
+    panic!("the loopback enclave exited with status {}", retval);
+}
@@ -0,0 +1,17 @@
+use alloc::vec::Vec;
+
+use crate::kernel::seal::{seal_with_key, unseal_with_key, SOFTWARE_KEY};
+
+/// There is no TEE to derive a key from, so blobs are sealed under a fixed
+/// key.
+pub fn seal(data: &[u8]) -> Option<Vec<u8>> {
+    Some(seal_with_key(&SOFTWARE_KEY, data))
+}
+
+pub fn unseal(blob: &[u8]) -> Option<Vec<u8>> {
+    unseal_with_key(&SOFTWARE_KEY, blob)
+}
+
+pub fn derive_key(label: &[u8]) -> Option<[u8; 32]> {
+    Some(crypto::hmac::hmac_sha256(&SOFTWARE_KEY, label))
+}
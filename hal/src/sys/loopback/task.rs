@@ -0,0 +1,159 @@
+extern crate std;
+
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    any::Any,
+    cell::RefCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::sync::{Condvar, Mutex};
+
+pub use crate::arch::loopback::frame::UserspaceRegs;
+
+#[repr(C)]
+#[derive(Default)]
+pub struct KtaskTls {
+    pub pcb_weak_ptr: usize,
+}
+
+/// Each kernel task runs on a host thread of its own, started the first time
+/// the scheduler switches to the task. The scheduler and the tasks hand a
+/// [`Baton`] to each other, so only one of them runs at a time.
+#[derive(Default)]
+pub struct KtaskCtx {
+    tls: usize,
+    entry: Option<Box<dyn FnOnce() + Send>>,
+    baton: Option<Arc<Baton>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum Turn {
+    #[default]
+    Scheduler,
+    Task,
+}
+
+#[derive(Default)]
+struct BatonState {
+    turn: Turn,
+    /// Why the task's thread has panicked, to be raised in the scheduler.
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+#[derive(Default)]
+struct Baton {
+    state: Mutex<BatonState>,
+    passed: Condvar,
+}
+
+impl Baton {
+    fn pass(&self, to: Turn) {
+        self.state.lock().unwrap().turn = to;
+        self.passed.notify_all();
+    }
+
+    fn wait_for(&self, turn: Turn) {
+        let mut state = self.state.lock().unwrap();
+        while state.turn != turn {
+            state = self.passed.wait(state).unwrap();
+        }
+    }
+}
+
+std::thread_local! {
+    // The baton of the task running on this host thread.
+    static TASK_BATON: RefCell<Option<Arc<Baton>>> = const { RefCell::new(None) };
+}
+
+impl KtaskTls {
+    pub fn new() -> KtaskTls {
+        KtaskTls {
+            ..Default::default()
+        }
+    }
+
+    pub fn set_pcb_weak_ptr(&mut self, new: usize) {
+        self.pcb_weak_ptr = new;
+    }
+}
+
+impl KtaskCtx {
+    pub fn allocate_for(thread_ctx: *const KtaskTls, _userspace_regs: &[u8]) -> KtaskCtx {
+        KtaskCtx {
+            tls: thread_ctx as usize,
+            ..Default::default()
+        }
+    }
+
+    /// Run `entry` the next time the scheduler switches to the task, on a new
+    /// host thread. The task exits once `entry` returns.
+    pub fn set_entry(&mut self, entry: Box<dyn FnOnce() + Send>) {
+        self.entry = Some(entry);
+        self.baton = None;
+    }
+
+    fn start(&mut self) -> Arc<Baton> {
+        let entry = self
+            .entry
+            .take()
+            .expect("the loopback task has no entry, see `hal::arch::loopback::set_task_entry`");
+        let baton = Arc::new(Baton::default());
+        let task_baton = Arc::clone(&baton);
+        std::thread::spawn(move || {
+            TASK_BATON.with(|cell| *cell.borrow_mut() = Some(Arc::clone(&task_baton)));
+            task_baton.wait_for(Turn::Task);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(entry));
+            match result {
+                Ok(()) => {
+                    crate::task::current().lock().exit_status.get_or_insert(0);
+                }
+                Err(panic) => task_baton.state.lock().unwrap().panic = Some(panic),
+            }
+            task_baton.pass(Turn::Scheduler);
+        });
+        self.baton = Some(Arc::clone(&baton));
+        baton
+    }
+}
+
+pub unsafe fn ktask_enter(_from: *mut KtaskCtx, to: *mut KtaskCtx) {
+    let to = &mut *to;
+    let baton = match &to.baton {
+        Some(baton) => Arc::clone(baton),
+        None => to.start(),
+    };
+
+    let prev_tls = CURRENT_TLS.swap(to.tls, Ordering::SeqCst);
+    baton.pass(Turn::Task);
+    baton.wait_for(Turn::Scheduler);
+    CURRENT_TLS.store(prev_tls, Ordering::SeqCst);
+
+    let panic = baton.state.lock().unwrap().panic.take();
+    if let Some(panic) = panic {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+pub unsafe fn ktask_leave() {
+    let baton = TASK_BATON
+        .with(|cell| cell.borrow().clone())
+        .expect("only a loopback task's thread can switch back to the scheduler");
+    baton.pass(Turn::Scheduler);
+    baton.wait_for(Turn::Task);
+}
+
+// Address of the current `KtaskTls`, set by the test harness and while the
+// scheduler runs a task.
+static CURRENT_TLS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_current_tls(tls: *const KtaskTls) {
+    CURRENT_TLS.store(tls as usize, Ordering::SeqCst);
+}
+
+pub fn current_pcb_weak() -> usize {
+    let tls = CURRENT_TLS.load(Ordering::SeqCst);
+    assert_ne!(tls, 0);
+
+    let tls = tls as *const KtaskTls;
+    unsafe { (*tls).pcb_weak_ptr }
+}
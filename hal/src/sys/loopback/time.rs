@@ -0,0 +1,17 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// A counter of nanoseconds, which only moves when the test harness advances
+/// it, so that tests do not depend on the host's clock.
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+pub fn read_counter() -> u64 {
+    COUNTER.load(Ordering::SeqCst)
+}
+
+pub fn counter_freq() -> Option<u64> {
+    Some(1_000_000_000)
+}
+
+pub fn advance_counter(ns: u64) {
+    COUNTER.fetch_add(ns, Ordering::SeqCst);
+}
@@ -0,0 +1 @@
+pub use crate::arch::loopback::vm::*;
@@ -0,0 +1,55 @@
+use alloc::{vec, vec::Vec};
+
+use sgx_types::{sgx_report_data_t, sgx_report_t, sgx_status_t, sgx_target_info_t};
+
+use crate::arch::sgx::{ocall_get_qe_target_info, ocall_get_quote, sgx_create_report};
+use crate::kernel::attest::REPORT_DATA_SIZE;
+
+/// The largest quote that the host may return, including the certification
+/// data of the quoting enclave.
+const QUOTE_MAX_SIZE: usize = 8192;
+
+/// Make a report for the quoting enclave on the host, which turns it into a
+/// quote that a remote verifier can check.
+pub fn report(report_data: &[u8; REPORT_DATA_SIZE]) -> Option<Vec<u8>> {
+    let mut target_info = sgx_target_info_t::default();
+    let mut retval = sgx_status_t::SGX_SUCCESS;
+    let status = unsafe { ocall_get_qe_target_info(&mut retval, &mut target_info) };
+    check(status, retval, "get the quoting enclave's target info")?;
+
+    let report_data = sgx_report_data_t { d: *report_data };
+    let mut report = sgx_report_t::default();
+    let status = unsafe { sgx_create_report(&target_info, &report_data, &mut report) };
+    check(status, sgx_status_t::SGX_SUCCESS, "create a report")?;
+
+    let mut quote = vec![0; QUOTE_MAX_SIZE];
+    let mut quote_len = 0;
+    let status = unsafe {
+        ocall_get_quote(
+            &mut retval,
+            &report,
+            quote.as_mut_ptr(),
+            QUOTE_MAX_SIZE as u32,
+            &mut quote_len,
+        )
+    };
+    check(status, retval, "get a quote")?;
+    // The host picks the length
+    if quote_len as usize > QUOTE_MAX_SIZE {
+        log::warn!("The host returned a quote of {} bytes", quote_len);
+        return None;
+    }
+    quote.truncate(quote_len as usize);
+    Some(quote)
+}
+
+/// Check the status of an OCALL and the value that it returned.
+fn check(status: sgx_status_t, retval: sgx_status_t, what: &str) -> Option<()> {
+    for status in [status, retval] {
+        if status != sgx_status_t::SGX_SUCCESS {
+            log::warn!("Failed to {}: {}", what, status);
+            return None;
+        }
+    }
+    Some(())
+}
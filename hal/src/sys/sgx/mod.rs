@@ -1,6 +1,10 @@
+pub mod attest;
 pub mod edge;
 pub mod mem;
+pub mod rand;
+pub mod seal;
 pub mod task;
+pub mod time;
 pub mod vm;
 
 pub fn exit_enclave(retval: usize) {
@@ -0,0 +1,5 @@
+/// `CPUID` faults inside enclaves, but every CPU with SGX has both `RDSEED`
+/// and `RDRAND`.
+pub fn read_seed(seed: &mut [u8]) {
+    unsafe { crate::arch::x86::rand::fill_seed(seed, true) }
+}
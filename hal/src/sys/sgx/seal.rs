@@ -0,0 +1,102 @@
+use alloc::{vec, vec::Vec};
+use core::{mem::size_of, ptr};
+
+use sgx_types::{sgx_attributes_t, sgx_key_request_t, sgx_sealed_data_t, sgx_status_t};
+
+use crate::arch::sgx::{
+    sgx_calc_sealed_data_size, sgx_get_add_mac_txt_len, sgx_get_encrypt_txt_len, sgx_get_key,
+    sgx_seal_data_ex, sgx_self_report, sgx_unseal_data,
+};
+
+/// Derive the key from `MRENCLAVE`, so that only the same enclave can unseal.
+const SGX_KEYPOLICY_MRENCLAVE: u16 = 0x0001;
+/// The seal key, in a key request.
+const SGX_KEYSELECT_SEAL: u16 = 0x0004;
+
+/// The attributes and `MISCSELECT` bits that the key depends on, as
+/// `TSEAL_DEFAULT_FLAGSMASK` and `TSEAL_DEFAULT_MISCMASK` of the SDK.
+const FLAGS_MASK: u64 = !(0x00ff_ffff_ffff_ffc0 | 0x04 | 0x10 | 0x20);
+const MISC_MASK: u32 = !0x0fff_ffff;
+
+pub fn seal(data: &[u8]) -> Option<Vec<u8>> {
+    let len = u32::try_from(data.len()).ok()?;
+    let size = unsafe { sgx_calc_sealed_data_size(0, len) };
+    if size == u32::MAX {
+        return None;
+    }
+    let mut blob = vec![0u8; size as usize];
+    let attribute_mask = sgx_attributes_t {
+        flags: FLAGS_MASK,
+        xfrm: 0,
+    };
+    let status = unsafe {
+        sgx_seal_data_ex(
+            SGX_KEYPOLICY_MRENCLAVE,
+            attribute_mask,
+            MISC_MASK,
+            0,
+            ptr::null(),
+            len,
+            data.as_ptr(),
+            size,
+            blob.as_mut_ptr() as *mut sgx_sealed_data_t,
+        )
+    };
+    if status != sgx_status_t::SGX_SUCCESS {
+        log::warn!("Failed to seal data: {}", status);
+        return None;
+    }
+    Some(blob)
+}
+
+pub fn unseal(blob: &[u8]) -> Option<Vec<u8>> {
+    // The SDK trusts the lengths in the blob, so they are checked first
+    if blob.len() < size_of::<sgx_sealed_data_t>() {
+        return None;
+    }
+    let sealed = blob.as_ptr() as *const sgx_sealed_data_t;
+    let (mac_len, mut len) = unsafe {
+        (
+            sgx_get_add_mac_txt_len(sealed),
+            sgx_get_encrypt_txt_len(sealed),
+        )
+    };
+    if mac_len != 0 || unsafe { sgx_calc_sealed_data_size(mac_len, len) } as usize != blob.len() {
+        return None;
+    }
+    let mut data = vec![0u8; len as usize];
+    let status =
+        unsafe { sgx_unseal_data(sealed, ptr::null_mut(), &mut 0, data.as_mut_ptr(), &mut len) };
+    if status != sgx_status_t::SGX_SUCCESS {
+        log::warn!("Failed to unseal data: {}", status);
+        return None;
+    }
+    data.truncate(len as usize);
+    Some(data)
+}
+
+/// Keys are derived from the CPU's seal key under the `MRENCLAVE` policy, with
+/// a fixed key ID so that the same enclave gets the same key again.
+pub fn derive_key(label: &[u8]) -> Option<[u8; 32]> {
+    let body = unsafe { &(*sgx_self_report()).body };
+    let request = sgx_key_request_t {
+        key_name: SGX_KEYSELECT_SEAL,
+        key_policy: SGX_KEYPOLICY_MRENCLAVE,
+        isv_svn: body.isv_svn,
+        cpu_svn: body.cpu_svn,
+        config_svn: body.config_svn,
+        attribute_mask: sgx_attributes_t {
+            flags: FLAGS_MASK,
+            xfrm: 0,
+        },
+        misc_mask: MISC_MASK,
+        ..Default::default()
+    };
+    let mut key = [0; 16];
+    let status = unsafe { sgx_get_key(&request, &mut key) };
+    if status != sgx_status_t::SGX_SUCCESS {
+        log::warn!("Failed to derive a key: {}", status);
+        return None;
+    }
+    Some(crypto::hmac::hmac_sha256(&key, label))
+}
@@ -0,0 +1,50 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use sgx_types::sgx_status_t;
+use spin::Once;
+
+use crate::time::{host_realtime_ns, NSEC_PER_SEC};
+
+/// Whether `RDTSC` can run inside the enclave, which SGX2 allows and SGX1
+/// does not: it raises #UD there.
+static HAS_TSC: Once<bool> = Once::new();
+
+/// The last value of the fallback counter, so it never goes backwards.
+static HOST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn has_tsc() -> bool {
+    *HAS_TSC.call_once(|| {
+        let mut cpuinfo = [0; 4];
+        let result = unsafe { crate::arch::sgx::sgx_cpuidex(&mut cpuinfo, 0x12, 0) };
+        let sgx2 = result == sgx_status_t::SGX_SUCCESS && cpuinfo[0] & (1 << 1) != 0;
+        if !sgx2 {
+            log::warn!(
+                "The CPU does not support SGX2, so RDTSC faults inside the enclave. \
+                 The clock falls back to the host's wall clock, which the host can \
+                 slow down or speed up."
+            );
+        }
+        sgx2
+    })
+}
+
+/// The TSC where the CPU allows `RDTSC` inside enclaves (SGX2). Otherwise, the
+/// host's wall clock in nanoseconds, which is only kept from going backwards.
+pub fn read_counter() -> u64 {
+    if has_tsc() {
+        unsafe { core::arch::x86_64::_rdtsc() }
+    } else {
+        let now = host_realtime_ns();
+        HOST_COUNTER.fetch_max(now, Ordering::SeqCst).max(now)
+    }
+}
+
+/// The TSC frequency is not known in advance, so it is calibrated at boot. The
+/// fallback counter counts nanoseconds.
+pub fn counter_freq() -> Option<u64> {
+    if has_tsc() {
+        None
+    } else {
+        Some(NSEC_PER_SEC)
+    }
+}
@@ -1,38 +1,29 @@
-use edge_proto::caller::{EdgeCaller, SharedMemCaller};
+use edge_proto::caller::EdgeCallError;
 use kconfig::KERNEL_MIRROR_BASE;
-use spin::{Mutex, MutexGuard};
+use spin::{MutexGuard, Once};
 use uart_16550::SerialPort;
 
-// Null initialized
-static GLOBAL_EDGE_CALLER: Mutex<SharedMemCaller> = Mutex::new(SharedMemCaller::new(
-    core::ptr::null_mut(),
-    0,
-    core::ptr::null_mut(),
-    0,
-    edge_call_kick,
-));
+use crate::edge::EdgeChannels;
+
+static EDGE_CHANNELS: Once<EdgeChannels> = Once::new();
 
 fn borrow_serial_port() -> MutexGuard<'static, SerialPort> {
-    crate::arch::x86_vm::qemu::SERIAL_EDGE.try_lock().unwrap()
+    // Kicks from different edge channels may race
+    crate::arch::x86_vm::qemu::SERIAL_EDGE.lock()
 }
 
 fn edge_call_kick() -> edge_proto::caller::Result<()> {
     let mut serial = borrow_serial_port();
     serial.send_raw(0xCC);
-    assert_eq!(serial.receive(), 0xCC);
-    Ok(())
+    if serial.receive() == 0xCC {
+        Ok(())
+    } else {
+        Err(EdgeCallError::Transport)
+    }
 }
 
 pub unsafe fn initialize_edge_caller(edge_mem: *mut u8) {
-    let mut caller = GLOBAL_EDGE_CALLER.try_lock().unwrap();
-    // Initialize SharedMemCaller
-    *caller = SharedMemCaller::new(
-        edge_mem,
-        0x1_000,
-        edge_mem.add(0x1_000),
-        kconfig::EDGE_BUFFER_SIZE,
-        edge_call_kick,
-    );
+    EDGE_CHANNELS.call_once(|| EdgeChannels::new(edge_mem, edge_call_kick));
 
     let mut serial = borrow_serial_port();
     // Write DMA address to the serial port
@@ -41,11 +32,6 @@ pub unsafe fn initialize_edge_caller(edge_mem: *mut u8) {
     }
 }
 
-pub fn with_edge_caller_impl<F, R>(f: F) -> R
-where
-    F: FnOnce(&mut dyn EdgeCaller) -> R,
-{
-    f(&mut *GLOBAL_EDGE_CALLER
-        .try_lock()
-        .expect("the edge caller is not reentrant"))
+pub fn edge_channels() -> Option<&'static EdgeChannels> {
+    EDGE_CHANNELS.get()
 }
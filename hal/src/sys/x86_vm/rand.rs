@@ -0,0 +1,8 @@
+use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+pub fn read_seed(seed: &mut [u8]) {
+    let has_rdseed = __cpuid_count(7, 0).ebx & (1 << 18) != 0;
+    let has_rdrand = __cpuid(1).ecx & (1 << 30) != 0;
+    assert!(has_rdrand, "the CPU has no RDRAND instruction");
+    unsafe { crate::arch::x86::rand::fill_seed(seed, has_rdseed) }
+}
@@ -0,0 +1,8 @@
+pub fn read_counter() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// The TSC frequency is not known in advance, so it is calibrated at boot.
+pub fn counter_freq() -> Option<u64> {
+    None
+}
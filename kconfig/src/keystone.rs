@@ -13,9 +13,18 @@ pub const USER_STACK_SIZE: usize = 0x1_000;
 
 pub const KERNEL_STACK_SIZE: usize = 0x4_000;
 
+// QEMU's virt machine counts `time` at 10 MHz
+pub const TIMEBASE_FREQ: usize = 10_000_000;
+// Each timer interrupt is a scheduler tick
+pub const TIMER_HZ: usize = 100;
+
 pub const EPM_SIZE: usize = 0x100_000;
-pub const UTM_SIZE: usize = 0x4_000;
-pub const EDGE_BUFFER_SIZE: usize = 0x3_000;
+// The UTM is split into edge channels, each holding a header zone followed by
+// a data zone
+pub const EDGE_CHANNELS: usize = 2;
+pub const EDGE_HEADER_SIZE: usize = 0x1_000;
+pub const EDGE_BUFFER_SIZE: usize = 0x10_000;
+pub const UTM_SIZE: usize = EDGE_CHANNELS * (EDGE_HEADER_SIZE + EDGE_BUFFER_SIZE);
 pub const KERNEL_EPM_OFFSET: usize = 0x4_000;
 
 // KERNEL_EPM_OFFSET + KERNEL_SIZE must be *smaller* than EPM_SIZE
@@ -12,6 +12,9 @@ cfg_if::cfg_if! {
     } else if #[cfg(feature = "sgx")] {
         mod sgx;
         pub use sgx::*;
+    } else if #[cfg(feature = "loopback")] {
+        mod loopback;
+        pub use loopback::*;
     } else {
         compile_error!("unsupported platform configuration");
     }
@@ -0,0 +1,11 @@
+// The loopback platform runs the kernel side as an ordinary host process, so
+// most of the memory layout constants only need to be plausible.
+pub const PAGE_SIZE: usize = 0x1_000;
+
+pub const USER_STACK_END: usize = 0x8000_0000_0000;
+pub const USER_STACK_SIZE: usize = 0x1_000;
+
+pub const EDGE_CHANNELS: usize = 2;
+pub const EDGE_HEADER_SIZE: usize = 0x1_000;
+pub const EDGE_BUFFER_SIZE: usize = 0x100_000;
+pub const UTM_SIZE: usize = EDGE_CHANNELS * (EDGE_HEADER_SIZE + EDGE_BUFFER_SIZE);
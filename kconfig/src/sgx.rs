@@ -1,4 +1,8 @@
 pub const PAGE_SIZE: usize = 0x1_000;
 
-pub const EDGE_MEM_SIZE: usize = 0x4_000;
-pub const EDGE_BUFFER_SIZE: usize = 0x3_000;
+// The edge memory is split into edge channels, each holding a header zone
+// followed by a data zone
+pub const EDGE_CHANNELS: usize = 4;
+pub const EDGE_HEADER_SIZE: usize = 0x1_000;
+pub const EDGE_BUFFER_SIZE: usize = 0x100_000;
+pub const EDGE_MEM_SIZE: usize = EDGE_CHANNELS * (EDGE_HEADER_SIZE + EDGE_BUFFER_SIZE);
@@ -15,5 +15,12 @@ pub const USER_STACK_SIZE: usize = 0x1_000;
 
 pub const KERNEL_STACK_SIZE: usize = 0x4_000;
 
-pub const UTM_SIZE: usize = 0x4_000;
-pub const EDGE_BUFFER_SIZE: usize = 0x3_000;
+// The rate the PIT is programmed to; each timer interrupt is a scheduler tick
+pub const TIMER_HZ: usize = 100;
+
+// The UTM is split into edge channels, each holding a header zone followed by
+// a data zone
+pub const EDGE_CHANNELS: usize = 4;
+pub const EDGE_HEADER_SIZE: usize = 0x1_000;
+pub const EDGE_BUFFER_SIZE: usize = 0x100_000;
+pub const UTM_SIZE: usize = EDGE_CHANNELS * (EDGE_HEADER_SIZE + EDGE_BUFFER_SIZE);
@@ -2,7 +2,7 @@ use std::{collections::HashMap, fs::File, io::Read};
 
 mod keystone;
 
-use edge_proto::server::SharedMemEdgeStream;
+use edge_proto::{channel::EdgeMemLayout, server::SharedMemEdgeStream};
 use kconfig::*;
 use keystone::{EnclaveStatus, KeystoneDev};
 use riscv_sv39::*;
@@ -168,12 +168,15 @@ fn main() {
 
     let edge_mem =
         unsafe { enclave.map_mem(0, UTM_SIZE) }.expect("failed to map untrusted memory") as *mut u8;
-    let mut edge_stream = SharedMemEdgeStream::new(
-        edge_mem,
-        0x1_000,
-        unsafe { edge_mem.add(0x1_000) },
-        EDGE_BUFFER_SIZE,
-    );
+    let layout = EdgeMemLayout::new(EDGE_CHANNELS, EDGE_HEADER_SIZE, EDGE_BUFFER_SIZE);
+    let mut edge_streams: Vec<_> = (0..EDGE_CHANNELS)
+        .map(|channel| unsafe { SharedMemEdgeStream::for_channel(&layout, edge_mem, channel) })
+        .collect();
+    // The UTM stays mapped until the enclave is destroyed
+    unsafe {
+        edge_responder::notify::set_notification_area(layout.notification_area(edge_mem));
+    }
+    edge_responder::notify::start_notifier().expect("failed to start notifier");
 
     let mut status = enclave.run().expect("failed to run enclave");
     loop {
@@ -182,15 +185,21 @@ fn main() {
                 println!("Enclave exited with status {}", code);
                 break;
             }
+            // The runtime lets the host run at each of its timer ticks
             EnclaveStatus::Interrupted => (),
             EnclaveStatus::EdgeCallHost => {
-                edge_responder::handle_edge_call(&mut edge_stream)
-                    .expect("failed to handle edge call");
+                for edge_stream in edge_streams.iter_mut().filter(|s| s.take_doorbell()) {
+                    edge_responder::handle_edge_call(edge_stream)
+                        .expect("failed to handle edge call");
+                }
             }
             _ => panic!("Unexpected enclave status: {:?}", status),
         }
         status = enclave.resume().expect("failed to resume enclave");
     }
 
+    unsafe {
+        edge_responder::notify::set_notification_area(std::ptr::null());
+    }
     enclave.destroy().expect("failed to destroy enclave");
 }
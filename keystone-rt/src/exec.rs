@@ -1,11 +1,12 @@
 use alloc::{string::String, vec::Vec};
+use edge_proto::caller::EdgeCallError;
 use hal::{
     task::UserspaceRegs,
     vm::{AddressSpace, ClonableAddressSpace, UserAddressSpace},
 };
 use riscv_sv39::{PhysAddr, VirtAddr};
 
-pub fn do_execve(path: String, argv: Vec<String>, envp: Vec<String>) {
+pub fn do_execve(path: String, argv: Vec<String>, envp: Vec<String>) -> Result<(), EdgeCallError> {
     log::debug!(
         "execve: Replacing current task with {}, argv = {:?}, envp = {:?}",
         path,
@@ -29,7 +30,7 @@ pub fn do_execve(path: String, argv: Vec<String>, envp: Vec<String>) {
                 );
             }
         },
-    );
+    )?;
 
     let userspace_regs = UserspaceRegs {
         sp: exec_data.user_sp,
@@ -40,4 +41,5 @@ pub fn do_execve(path: String, argv: Vec<String>, envp: Vec<String>) {
     hal::task::current()
         .lock()
         .replace(exec_data.mm, &userspace_regs);
+    Ok(())
 }
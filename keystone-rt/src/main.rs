@@ -18,6 +18,7 @@ mod frame;
 mod klog;
 mod panic;
 mod syscall;
+mod timer;
 mod trap;
 mod uart;
 mod vm;
@@ -35,13 +36,18 @@ extern "C" fn rt_main(vm_info: &vm::VmInfo) -> ! {
         ALLOC.init(vm_info.free_virt as *mut u8, vm_info.free_size);
     }
     log::debug!("It did not crash!");
+    hal::edge::set_notification_handler(linux_abi::notify::handle_notification);
+    hal::time::init();
+    linux_abi::fs::init();
+    #[cfg(feature = "netstack")]
+    linux_abi::net::init();
 
     // load U-mode program
     log::debug!("Run keystone-init as init process");
     let addr_space = UserAddressSpace::current();
     let exec_data = linux_abi::elf::exec_within(
         addr_space,
-        "keystone-init",
+        &linux_abi::exec::init_path("keystone-init"),
         linux_abi::exec::INIT_ARGV,
         linux_abi::exec::INIT_ENVP,
         elf_loader::arch::RiscV,
@@ -54,7 +60,8 @@ extern "C" fn rt_main(vm_info: &vm::VmInfo) -> ! {
                 );
             }
         },
-    );
+    )
+    .expect("failed to load keystone-init");
 
     let userspace_regs = UserspaceRegs {
         sp: exec_data.user_sp,
@@ -70,8 +77,10 @@ extern "C" fn rt_main(vm_info: &vm::VmInfo) -> ! {
         // in S-mode
         riscv::register::sstatus::set_spp(riscv::register::sstatus::SPP::User);
     }
-    executor::spawn(task_future);
-    executor::run_until_idle();
+    // Tasks are preempted from now on
+    timer::init();
+    hal::task::spawn(task_future);
+    hal::task::run_until_idle();
 
     log::debug!("All kernel tasks have exited");
     hal::exit_enclave(0);
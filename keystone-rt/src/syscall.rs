@@ -33,6 +33,9 @@ pub unsafe fn handle_syscall(frame: *mut TrapFrame) {
         Some(SyscallHandler::Syscall4(f)) => {
             result = f(arg0, arg1, arg2, arg3);
         }
+        Some(SyscallHandler::Syscall5(f)) => {
+            result = f(arg0, arg1, arg2, arg3, arg4);
+        }
         Some(SyscallHandler::Syscall6(f)) => {
             result = f(arg0, arg1, arg2, arg3, arg4, arg5);
         }
@@ -41,12 +44,17 @@ pub unsafe fn handle_syscall(frame: *mut TrapFrame) {
         }
         Some(SyscallHandler::SyscallExecvePre(f)) => {
             match f(arg0, arg1, arg2) {
-                Ok((path, argv, envp)) => {
-                    crate::exec::do_execve(path, argv, envp);
-                    hal::task::yield_to_sched();
-                    unreachable!()
-                    // no need to update result value
-                }
+                Ok((path, argv, envp)) => match crate::exec::do_execve(path, argv, envp) {
+                    Ok(()) => {
+                        hal::task::yield_to_sched();
+                        unreachable!()
+                        // no need to update result value
+                    }
+                    Err(err) => {
+                        log::warn!("execve: Failed to load the executable: {}", err);
+                        result = -(err.errno() as isize);
+                    }
+                },
                 Err(err) => {
                     // execve failed, return error code
                     result = err;
@@ -0,0 +1,19 @@
+//! The supervisor timer, whose interrupts are the scheduler's ticks.
+
+use hal::{
+    arch::keystone::sbi,
+    cfg::{TIMEBASE_FREQ, TIMER_HZ},
+};
+use riscv::register::{sie, time};
+
+/// Arm the timer for the next tick.
+pub fn arm() {
+    sbi::set_timer((time::read() + TIMEBASE_FREQ / TIMER_HZ) as u64);
+}
+
+pub fn init() {
+    unsafe {
+        sie::set_stimer();
+    }
+    arm();
+}
@@ -1,4 +1,6 @@
-use crate::{frame::TrapFrame, syscall::handle_syscall, uart_println};
+use hal::arch::keystone::sbi;
+
+use crate::{frame::TrapFrame, syscall::handle_syscall, timer, uart_println};
 
 core::arch::global_asm!(include_str!("asm/trap.S"));
 
@@ -16,10 +18,66 @@ unsafe extern "C" fn trap_handler(frame: *mut TrapFrame) {
             crate::vm::handle_page_fault_at(addr);
             // now just redo the errornous instruction
         }
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            // Let the host run as well, which sees the enclave interrupted
+            sbi::stop_enclave(sbi::STOP_TIMER_INTERRUPT);
+            timer::arm();
+            if hal::task::tick() {
+                preempt();
+            }
+        }
         _ => unknown_trap(),
     }
 }
 
+/// The user's floating-point registers, which other tasks overwrite while
+/// the current one is switched out.
+#[derive(Default)]
+struct FpRegs {
+    f: [u64; 32],
+    fcsr: usize,
+}
+
+impl FpRegs {
+    unsafe fn save(&mut self) {
+        core::arch::asm!(
+            ".irp i, 0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31",
+            "fsd f\\i, \\i*8({f})",
+            ".endr",
+            "frcsr {fcsr}",
+            f = in(reg) self.f.as_mut_ptr(),
+            fcsr = out(reg) self.fcsr,
+        );
+    }
+
+    unsafe fn restore(&self) {
+        core::arch::asm!(
+            ".irp i, 0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31",
+            "fld f\\i, \\i*8({f})",
+            ".endr",
+            "fscsr {fcsr}",
+            f = in(reg) self.f.as_ptr(),
+            fcsr = in(reg) self.fcsr,
+        );
+    }
+}
+
+/// Switch out the current task, whose time slice has run out. Its integer
+/// registers are in the trap frame on its kernel stack.
+unsafe fn preempt() {
+    use riscv::register::sstatus::{self, FS};
+
+    let mut fp_regs = FpRegs::default();
+    let uses_fp = sstatus::read().fs() != FS::Off;
+    if uses_fp {
+        fp_regs.save();
+    }
+    hal::task::yield_to_sched();
+    if uses_fp {
+        fp_regs.restore();
+    }
+}
+
 fn unknown_trap() -> ! {
     use riscv::register::*;
 
@@ -0,0 +1,15 @@
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    // Embed the archive named by INITRAMFS, or an empty one
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let image = out_dir.join("initramfs.cpio");
+    println!("cargo:rerun-if-env-changed=INITRAMFS");
+    match env::var("INITRAMFS") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={}", path);
+            fs::copy(&path, &image).unwrap_or_else(|e| panic!("Cannot read {}: {}", path, e));
+        }
+        _ => fs::write(&image, []).unwrap(),
+    }
+}
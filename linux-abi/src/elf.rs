@@ -1,28 +1,74 @@
+use alloc::boxed::Box;
+use edge_proto::{caller::EdgeCallError, EdgeError};
 use elf_loader::ElfReader;
+use hal::edge::EdgeFile;
+#[cfg(feature = "multitasking")]
 use hal::{
-    edge::EdgeFile,
     task::{TaskMmStruct, VmArea},
     vm::{AddressSpace, UserAddressSpace},
 };
 
-pub struct EdgeElfFile(pub EdgeFile);
+use crate::fs::vfs::{self, SeekFrom};
 
-impl ElfReader for EdgeElfFile {
+/// An executable, either on the host or served inside the enclave.
+pub enum ExecFile {
+    Edge(EdgeFile),
+    Vfs(Box<dyn vfs::File>),
+}
+
+impl ExecFile {
+    /// Open `path`. Absolute paths are looked up in the enclave's file
+    /// systems first.
+    pub fn open(path: &str) -> Result<ExecFile, EdgeCallError> {
+        if path.starts_with('/') {
+            if let Some(result) = vfs::open(path, crate::fs::host::O_RDONLY) {
+                return result.map(ExecFile::Vfs).map_err(errno_error);
+            }
+        }
+        Ok(ExecFile::Edge(EdgeFile::open(path)?))
+    }
+
+    pub fn close(self) -> Result<(), EdgeCallError> {
+        match self {
+            ExecFile::Edge(file) => file.close(),
+            ExecFile::Vfs(file) => file.release().map_err(errno_error),
+        }
+    }
+}
+
+fn errno_error(errno: isize) -> EdgeCallError {
+    EdgeCallError::Remote(EdgeError::new(-errno as i32, None))
+}
+
+impl ElfReader for ExecFile {
     fn read(&mut self, buf: &mut [u8]) -> usize {
-        self.0.read(buf)
+        match self {
+            ExecFile::Edge(file) => file.read(buf).expect("failed to read ELF file"),
+            ExecFile::Vfs(file) => file.read(buf).expect("failed to read ELF file"),
+        }
     }
 
     fn seek(&mut self, pos: u64) {
-        self.0.seek(pos);
+        match self {
+            ExecFile::Edge(file) => {
+                file.seek(pos).expect("failed to seek ELF file");
+            }
+            ExecFile::Vfs(file) => {
+                file.seek(SeekFrom::Start(pos))
+                    .expect("failed to seek ELF file");
+            }
+        }
     }
 }
 
+#[cfg(feature = "multitasking")]
 pub struct ExecData {
     pub mm: TaskMmStruct,
     pub entry: usize,
     pub user_sp: usize,
 }
 
+#[cfg(feature = "multitasking")]
 pub fn exec_within<S>(
     addr_space: UserAddressSpace,
     path: &str,
@@ -30,7 +76,7 @@ pub fn exec_within<S>(
     envp: &[S],
     arch: impl elf_loader::arch::ElfArch,
     mut mapper: impl FnMut(&mut TaskMmStruct, *const (), usize, usize),
-) -> ExecData
+) -> Result<ExecData, EdgeCallError>
 where
     S: AsRef<str>,
 {
@@ -38,9 +84,12 @@ where
         addr_space,
         hal::cfg::USER_STACK_END - hal::cfg::USER_STACK_SIZE..hal::cfg::USER_STACK_END,
     );
+    // execve passes absolute paths; init is the only program loaded by a
+    // relative path, which is relative to the guest root
+    mm.exe = crate::fs::normalize_path(path);
 
     // open ELF file
-    let mut elf_file = EdgeElfFile(EdgeFile::open(path));
+    let mut elf_file = ExecFile::open(path)?;
     // load & map ELF file
     let elf = elf_loader::ElfFile::new(&mut elf_file, arch);
     elf.load_mapped(&mut elf_file, |from, size, to| {
@@ -60,7 +109,7 @@ where
             },
         );
     });
-    elf_file.0.close();
+    elf_file.close()?;
 
     let entry = elf.entry() as usize;
 
@@ -76,5 +125,5 @@ where
         hal::mem::copy_to_user(&user_stack_data, user_sp as *mut u8);
     }
 
-    ExecData { mm, entry, user_sp }
+    Ok(ExecData { mm, entry, user_sp })
 }
@@ -1,19 +1,39 @@
-use alloc::vec::Vec;
+use alloc::{format, string::String, vec::Vec};
+
+use crate::fs::initramfs;
 
 pub const INIT_ARGV: &[&str] = &["init"];
 pub const INIT_ENVP: &[&str] = &["HOME=/", "TERM=linux"];
 
+/// The path that the init program `name` is loaded from: the embedded
+/// initramfs if there is one, or the host otherwise.
+pub fn init_path(name: &str) -> String {
+    if initramfs::IMAGE.is_empty() {
+        name.into()
+    } else {
+        format!("{}/{}", initramfs::INITRAMFS_MOUNT, name)
+    }
+}
+
+const AT_NULL: usize = 0;
+/// The address of random bytes, which the C library uses for stack canaries
+/// and pointer mangling.
+const AT_RANDOM: usize = 25;
+const AT_RANDOM_SIZE: usize = 16;
+/// The number of words in the auxiliary vector, including its terminator.
+const AUXV_LEN: usize = 4;
+
 pub fn prepare_user_stack_data<S>(user_stack_end: usize, argv: &[S], envp: &[S]) -> (Vec<u8>, usize)
 where
     S: AsRef<str>,
 {
     // TODO: ensure that none of argv and envp contains '\0'
 
-    let num_ptrs = 1 + (argv.len() + 1) + (envp.len() + 1);
+    let num_ptrs = 1 + (argv.len() + 1) + (envp.len() + 1) + AUXV_LEN;
     let ptrs_size = num_ptrs * core::mem::size_of::<usize>();
     let argv_size: usize = argv.iter().map(|x| x.as_ref().len() + 1).sum();
     let envp_size: usize = envp.iter().map(|x| x.as_ref().len() + 1).sum();
-    let data_size = ptrs_size + argv_size + envp_size;
+    let data_size = ptrs_size + AT_RANDOM_SIZE + argv_size + envp_size;
     // Align to 16-byte boundary
     let data_offset = (user_stack_end - data_size) & !0xF;
 
@@ -22,7 +42,8 @@ where
     // argc
     result.extend(argv.len().to_ne_bytes());
     // argv array
-    let mut cur = data_offset + ptrs_size;
+    let random_ptr = data_offset + ptrs_size;
+    let mut cur = random_ptr + AT_RANDOM_SIZE;
     for arg in argv {
         result.extend(cur.to_ne_bytes());
         cur += arg.as_ref().len() + 1;
@@ -34,8 +55,15 @@ where
         cur += env.as_ref().len() + 1;
     }
     result.extend(0usize.to_ne_bytes());
+    // auxv array
+    for value in [AT_RANDOM, random_ptr, AT_NULL, 0] {
+        result.extend(value.to_ne_bytes());
+    }
 
     // Append actual data
+    let mut random = [0u8; AT_RANDOM_SIZE];
+    hal::rand::fill_bytes(&mut random);
+    result.extend(random);
     for arg in argv {
         result.extend(arg.as_ref().as_bytes());
         result.push(0);
@@ -0,0 +1,299 @@
+//! The `/dev` file system, whose devices are implemented inside the enclave
+//! rather than opened on the host.
+
+use alloc::{boxed::Box, vec::Vec};
+use spin::Mutex;
+
+use super::{
+    host::{O_ACCMODE, O_CREAT, O_DIRECTORY, O_RDONLY},
+    stat::{Stat, S_IFCHR, S_IFDIR},
+    vfs::{self, File, FileSystem, SeekFrom, DT_CHR, DT_DIR},
+};
+use crate::Errno;
+
+/// Where the file system is mounted by [`super::init`].
+pub const DEVFS_MOUNT: &str = "/dev";
+
+/// The device number reported for the `/dev` directory.
+const DEVFS_DEV: u64 = 0x81;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Device {
+    Null,
+    Zero,
+    /// Both `/dev/random` and `/dev/urandom`, which never block, as the
+    /// kernel's generator is seeded inside the enclave at its first use.
+    Urandom,
+    /// The enclave's console: output is printed by the host, and there is no
+    /// input.
+    Tty,
+    /// Attestation reports of the data written, see [`TransformFile`].
+    Attestation,
+    /// Blobs sealed from the data written.
+    Seal,
+    /// The data sealed in the blob written.
+    Unseal,
+}
+
+impl Device {
+    /// What the device does with what is written to it, and the most that may
+    /// be written, if it is read like a [`TransformFile`].
+    fn transform(self) -> Option<(Transform, usize)> {
+        match self {
+            Device::Attestation => Some((attestation_report, hal::attest::REPORT_DATA_SIZE)),
+            Device::Seal => Some((seal, MAX_SEAL_INPUT)),
+            // Leave room for the metadata of the blob
+            Device::Unseal => Some((unseal, MAX_SEAL_INPUT + 0x1000)),
+            _ => None,
+        }
+    }
+}
+
+type Transform = fn(&[u8]) -> Result<Vec<u8>, isize>;
+
+/// The most data that can be sealed into one blob.
+const MAX_SEAL_INPUT: usize = 1 << 20;
+
+fn attestation_report(data: &[u8]) -> Result<Vec<u8>, isize> {
+    hal::attest::report(data).ok_or(Errno::EIO.as_neg_isize())
+}
+
+fn seal(data: &[u8]) -> Result<Vec<u8>, isize> {
+    hal::seal::seal(data).ok_or(Errno::EIO.as_neg_isize())
+}
+
+fn unseal(blob: &[u8]) -> Result<Vec<u8>, isize> {
+    hal::seal::unseal(blob).ok_or(Errno::EBADMSG.as_neg_isize())
+}
+
+/// Each device with its name, and major and minor numbers.
+const DEVICES: &[(&str, Device, u64, u64)] = &[
+    ("attestation", Device::Attestation, 10, 240),
+    ("null", Device::Null, 1, 3),
+    ("random", Device::Urandom, 1, 8),
+    ("seal", Device::Seal, 10, 241),
+    ("tty", Device::Tty, 5, 0),
+    ("unseal", Device::Unseal, 10, 242),
+    ("urandom", Device::Urandom, 1, 9),
+    ("zero", Device::Zero, 1, 5),
+];
+
+fn makedev(major: u64, minor: u64) -> u64 {
+    (major << 8) | minor
+}
+
+pub struct DevFs;
+
+impl DevFs {
+    /// Find the device at `path`, or `None` for the directory itself.
+    fn lookup(path: &str) -> Result<Option<usize>, isize> {
+        if path.is_empty() {
+            return Ok(None);
+        }
+        DEVICES
+            .iter()
+            .position(|&(name, ..)| name == path)
+            .map(Some)
+            .ok_or(Errno::ENOENT.as_neg_isize())
+    }
+}
+
+impl FileSystem for DevFs {
+    fn open(&self, path: &str, flags: i32, _mode: u32) -> Result<Box<dyn File>, isize> {
+        let index = match Self::lookup(path) {
+            // Devices cannot be created
+            Err(_) if flags & O_CREAT != 0 => return Err(Errno::EACCES.as_neg_isize()),
+            result => result?,
+        };
+        match index {
+            Some(_) if flags & O_DIRECTORY != 0 => Err(Errno::ENOTDIR.as_neg_isize()),
+            Some(index) => match DEVICES[index].1.transform() {
+                Some((transform, max_input)) => Ok(Box::new(TransformFile {
+                    index,
+                    transform,
+                    max_input,
+                    state: Mutex::new(TransformState::default()),
+                })),
+                None => Ok(Box::new(DevFile { index })),
+            },
+            None if flags & O_ACCMODE != O_RDONLY => Err(Errno::EISDIR.as_neg_isize()),
+            None => Ok(Box::new(DevDir { pos: Mutex::new(0) })),
+        }
+    }
+
+    fn mkdir(&self, path: &str, _mode: u32) -> Result<(), isize> {
+        match Self::lookup(path) {
+            Ok(_) => Err(Errno::EEXIST.as_neg_isize()),
+            Err(_) => Err(Errno::EACCES.as_neg_isize()),
+        }
+    }
+
+    fn unlink(&self, path: &str, _remove_dir: bool) -> Result<(), isize> {
+        Self::lookup(path)?;
+        Err(Errno::EACCES.as_neg_isize())
+    }
+}
+
+fn device_stat(index: usize) -> Stat {
+    let (_, _, major, minor) = DEVICES[index];
+    Stat {
+        dev: DEVFS_DEV,
+        ino: index as u64 + 2,
+        mode: S_IFCHR | 0o666,
+        nlink: 1,
+        rdev: makedev(major, minor),
+        blksize: 0x1000,
+        ..Default::default()
+    }
+}
+
+struct DevFile {
+    index: usize,
+}
+
+impl File for DevFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        match DEVICES[self.index].1 {
+            Device::Null | Device::Tty => Ok(0),
+            // These are opened as `TransformFile`s
+            Device::Attestation | Device::Seal | Device::Unseal => unreachable!(),
+            Device::Zero => {
+                buf.fill(0);
+                Ok(buf.len())
+            }
+            Device::Urandom => {
+                hal::rand::fill_bytes(buf);
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, isize> {
+        if DEVICES[self.index].1 == Device::Tty {
+            hal::edge::print_bytes(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(device_stat(self.index))
+    }
+
+    fn read_at(&self, buf: &mut [u8], _offset: usize) -> Result<usize, isize> {
+        match DEVICES[self.index].1 {
+            Device::Tty => Err(Errno::ESPIPE.as_neg_isize()),
+            _ => self.read(buf),
+        }
+    }
+
+    fn write_at(&self, buf: &[u8], _offset: usize) -> Result<usize, isize> {
+        match DEVICES[self.index].1 {
+            Device::Tty => Err(Errno::ESPIPE.as_neg_isize()),
+            _ => self.write(buf),
+        }
+    }
+
+    fn seek(&self, _pos: SeekFrom) -> Result<usize, isize> {
+        // Seeking a character device succeeds but has no effect
+        Ok(0)
+    }
+}
+
+/// A device which turns what is written to an open file into what is read
+/// from it. Writes append to the input, and the first read after them makes
+/// the output, which is then read like a file. A write after a read starts a
+/// new input, and a read before any write makes the output of no input.
+struct TransformFile {
+    index: usize,
+    transform: Transform,
+    max_input: usize,
+    state: Mutex<TransformState>,
+}
+
+#[derive(Default)]
+struct TransformState {
+    input: Vec<u8>,
+    output: Option<Vec<u8>>,
+    /// The offset of the next read in the output.
+    pos: usize,
+}
+
+impl File for TransformFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let mut state = self.state.lock();
+        let TransformState { input, output, pos } = &mut *state;
+        let output = match output {
+            Some(output) => output,
+            output => output.insert((self.transform)(input)?),
+        };
+        let rest = output.get(*pos..).unwrap_or_default();
+        let len = buf.len().min(rest.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        *pos += len;
+        Ok(len)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, isize> {
+        let mut state = self.state.lock();
+        if state.output.is_some() {
+            *state = TransformState::default();
+        }
+        if state.input.len() + buf.len() > self.max_input {
+            return Err(Errno::EINVAL.as_neg_isize());
+        }
+        state.input.extend(buf);
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(device_stat(self.index))
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize, isize> {
+        let mut state = self.state.lock();
+        let len = state.output.as_ref().map_or(0, Vec::len);
+        pos.apply(&mut state.pos, len)
+    }
+}
+
+struct DevDir {
+    /// The index of the next entry.
+    pos: Mutex<usize>,
+}
+
+impl File for DevDir {
+    fn read(&self, _buf: &mut [u8]) -> Result<usize, isize> {
+        Err(Errno::EISDIR.as_neg_isize())
+    }
+
+    fn write(&self, _buf: &[u8]) -> Result<usize, isize> {
+        Err(Errno::EISDIR.as_neg_isize())
+    }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(Stat {
+            dev: DEVFS_DEV,
+            ino: 1,
+            mode: S_IFDIR | 0o755,
+            nlink: 2,
+            blksize: 0x1000,
+            ..Default::default()
+        })
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize, isize> {
+        if pos != SeekFrom::Start(0) {
+            return Err(Errno::EINVAL.as_neg_isize());
+        }
+        pos.apply(&mut self.pos.lock(), 0)
+    }
+
+    fn getdents64(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let dots = [(1, DT_DIR, "."), (1, DT_DIR, "..")];
+        let devices = DEVICES
+            .iter()
+            .enumerate()
+            .map(|(index, &(name, ..))| (index as u64 + 2, DT_CHR, name));
+        vfs::fill_dirents(buf, &mut self.pos.lock(), dots.into_iter().chain(devices))
+    }
+}
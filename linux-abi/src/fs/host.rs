@@ -0,0 +1,397 @@
+//! Host file operations on kernel buffers, for file systems that keep their
+//! data in host files.
+//!
+//! Results are returned as `Err(-errno)` on failure, like syscalls do.
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use edge_proto::{
+    caller::EdgeCallError, DataRegion, EdgeCallReq, EdgeCallResp, PolledFd, MAX_POLLED_FDS,
+};
+use hal::{edge::EDGE_BUFFER_SIZE, task::ParkingLot};
+
+use super::stat::Stat;
+use crate::Errno;
+
+pub const AT_FDCWD: i32 = -100;
+pub const O_RDONLY: i32 = 0;
+pub const O_WRONLY: i32 = 0o1;
+pub const O_RDWR: i32 = 0o2;
+pub const O_ACCMODE: i32 = 0o3;
+pub const O_CREAT: i32 = 0o100;
+pub const O_EXCL: i32 = 0o200;
+pub const O_NOCTTY: i32 = 0o400;
+pub const O_TRUNC: i32 = 0o1000;
+pub const O_APPEND: i32 = 0o2000;
+pub const O_NONBLOCK: i32 = 0o4000;
+pub const O_DIRECTORY: i32 = 0o200000;
+pub const O_NOFOLLOW: i32 = 0o400000;
+pub const O_CLOEXEC: i32 = 0o2000000;
+pub const AT_SYMLINK_NOFOLLOW: i32 = 0x100;
+pub const AT_REMOVEDIR: i32 = 0x200;
+pub const AT_SYMLINK_FOLLOW: i32 = 0x400;
+pub const AT_EMPTY_PATH: i32 = 0x1000;
+pub const W_OK: i32 = 0o2;
+
+/// Tasks whose reads would have blocked on host files.
+static IO_WAITERS: ParkingLot = ParkingLot::new();
+
+/// Block the current task until the host reports that a file it watches
+/// became ready, after a read was answered with [`EdgeCallResp::WouldBlock`].
+/// The read must be retried, as the file may not be the one the task waits
+/// for.
+pub fn wait_for_io() {
+    IO_WAITERS.park();
+}
+
+/// Wake up the tasks waiting for host files, on [`Notification::IoReady`].
+///
+/// [`Notification::IoReady`]: edge_proto::notify::Notification::IoReady
+pub fn io_ready() {
+    IO_WAITERS.unpark_all();
+}
+
+fn syscall_result(result: isize) -> Result<usize, isize> {
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok(result as usize)
+    }
+}
+
+/// Make an edge call that is answered with a syscall result.
+fn syscall_call(req: EdgeCallReq) -> Result<usize, isize> {
+    let result = hal::edge::with_edge_caller(|caller| {
+        caller.write_header(&req).unwrap();
+        caller.kick().unwrap();
+        caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
+    });
+    syscall_result(result)
+}
+
+pub fn open(path: &str, flags: i32, mode: u32) -> Result<i32, isize> {
+    let result = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallOpenAt {
+                pid: hal::task::current_pid(),
+                dir_fd: AT_FDCWD,
+                path: path.to_owned(),
+                flags,
+                mode,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
+    });
+    syscall_result(result).map(|fd| fd as i32)
+}
+
+/// Take an fd number for a file served inside the enclave, with `FD_CLOEXEC`
+/// set if `cloexec` is.
+pub fn reserve_fd(cloexec: bool) -> Result<i32, isize> {
+    let result = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::FdReserve {
+                pid: hal::task::current_pid(),
+                cloexec,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
+    });
+    syscall_result(result).map(|fd| fd as i32)
+}
+
+/// Turn the error of an edge call into a negative errno.
+pub fn errno_of(err: EdgeCallError) -> isize {
+    match err {
+        EdgeCallError::Remote(err) => -(err.errno as isize),
+        err => panic!("edge call failed: {:?}", err),
+    }
+}
+
+fn stat_call(req: EdgeCallReq) -> Result<Stat, isize> {
+    hal::edge::with_edge_caller(|caller| {
+        caller.write_header(&req).unwrap();
+        caller.kick().unwrap();
+        let resp = caller
+            .read_header()
+            .unwrap()
+            .into_result()
+            .map_err(errno_of)?;
+        Ok(resp.into_ok_with_stat().unwrap())
+    })
+}
+
+pub fn fstat(fd: i32) -> Result<Stat, isize> {
+    stat_call(EdgeCallReq::SyscallFstat {
+        pid: hal::task::current_pid(),
+        fd,
+    })
+}
+
+/// Get the metadata of `path`, relative to `dir_fd`. `flags` may contain
+/// `AT_SYMLINK_NOFOLLOW`.
+pub fn fstatat(dir_fd: i32, path: &str, flags: i32) -> Result<Stat, isize> {
+    stat_call(EdgeCallReq::SyscallFstatAt {
+        pid: hal::task::current_pid(),
+        dir_fd,
+        path: path.to_owned(),
+        flags,
+    })
+}
+
+/// Get the metadata of the file at `path`.
+pub fn stat(path: &str) -> Result<Stat, isize> {
+    fstatat(AT_FDCWD, path, 0)
+}
+
+/// Check whether `path`, relative to `dir_fd`, may be accessed with `mode`
+/// (a combination of `R_OK`, `W_OK` and `X_OK`).
+pub fn access(dir_fd: i32, path: &str, mode: i32) -> Result<(), isize> {
+    let result = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallFaccessAt {
+                pid: hal::task::current_pid(),
+                dir_fd,
+                path: path.to_owned(),
+                mode,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
+    });
+    syscall_result(result).map(|_| ())
+}
+
+/// Read the target of the symbolic link at `path`, relative to `dir_fd`.
+pub fn readlink(dir_fd: i32, path: &str) -> Result<String, isize> {
+    hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallReadLinkAt {
+                pid: hal::task::current_pid(),
+                dir_fd,
+                path: path.to_owned(),
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        let resp = caller
+            .read_header()
+            .unwrap()
+            .into_result()
+            .map_err(errno_of)?;
+        Ok(resp.into_ok_with_string().unwrap())
+    })
+}
+
+pub fn unlinkat(dir_fd: i32, path: &str, flags: i32) -> Result<(), isize> {
+    syscall_call(EdgeCallReq::SyscallUnlinkAt {
+        pid: hal::task::current_pid(),
+        dir_fd,
+        path: path.to_owned(),
+        flags,
+    })
+    .map(|_| ())
+}
+
+/// Rename a path like `renameat2`.
+pub fn renameat(
+    old_dir_fd: i32,
+    old_path: &str,
+    new_dir_fd: i32,
+    new_path: &str,
+    flags: u32,
+) -> Result<(), isize> {
+    syscall_call(EdgeCallReq::SyscallRenameAt {
+        pid: hal::task::current_pid(),
+        old_dir_fd,
+        old_path: old_path.to_owned(),
+        new_dir_fd,
+        new_path: new_path.to_owned(),
+        flags,
+    })
+    .map(|_| ())
+}
+
+/// Create a hard link like `linkat`. `flags` may contain
+/// `AT_SYMLINK_FOLLOW`.
+pub fn linkat(
+    old_dir_fd: i32,
+    old_path: &str,
+    new_dir_fd: i32,
+    new_path: &str,
+    flags: i32,
+) -> Result<(), isize> {
+    syscall_call(EdgeCallReq::SyscallLinkAt {
+        pid: hal::task::current_pid(),
+        old_dir_fd,
+        old_path: old_path.to_owned(),
+        new_dir_fd,
+        new_path: new_path.to_owned(),
+        flags,
+    })
+    .map(|_| ())
+}
+
+pub fn symlinkat(target: &str, dir_fd: i32, path: &str) -> Result<(), isize> {
+    syscall_call(EdgeCallReq::SyscallSymlinkAt {
+        pid: hal::task::current_pid(),
+        target: target.to_owned(),
+        dir_fd,
+        path: path.to_owned(),
+    })
+    .map(|_| ())
+}
+
+pub fn truncate(path: &str, len: i64) -> Result<(), isize> {
+    syscall_call(EdgeCallReq::SyscallTruncate {
+        pid: hal::task::current_pid(),
+        path: path.to_owned(),
+        len,
+    })
+    .map(|_| ())
+}
+
+pub fn ftruncate(fd: i32, len: i64) -> Result<(), isize> {
+    syscall_call(EdgeCallReq::SyscallFtruncate {
+        pid: hal::task::current_pid(),
+        fd,
+        len,
+    })
+    .map(|_| ())
+}
+
+/// Flush `fd` to the host's storage, only its data if `data_only` is set.
+pub fn fsync(fd: i32, data_only: bool) -> Result<(), isize> {
+    syscall_call(EdgeCallReq::SyscallFsync {
+        pid: hal::task::current_pid(),
+        fd,
+        data_only,
+    })
+    .map(|_| ())
+}
+
+pub fn close(fd: i32) -> Result<(), isize> {
+    let result = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallClose {
+                pid: hal::task::current_pid(),
+                fd,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
+    });
+    syscall_result(result).map(|_| ())
+}
+
+/// Read from the current position of `fd` until the end of the file.
+pub fn read_to_end(fd: i32) -> Result<Vec<u8>, isize> {
+    let mut data = Vec::new();
+    loop {
+        let eof = hal::edge::with_edge_caller(|caller| {
+            caller
+                .write_header(&EdgeCallReq::SyscallReadV {
+                    pid: hal::task::current_pid(),
+                    fd,
+                    regions: DataRegion::packed([EDGE_BUFFER_SIZE]),
+                })
+                .unwrap();
+            caller.kick().unwrap();
+            let len = match caller.read_header().unwrap() {
+                EdgeCallResp::WouldBlock => return Ok(None),
+                resp => syscall_result(resp.into_syscall_resp().unwrap() as isize)?,
+            };
+            data.extend_from_slice(&caller.read_data().unwrap()[0..len]);
+            Ok::<_, isize>(Some(len == 0))
+        })?;
+        match eof {
+            Some(true) => return Ok(data),
+            Some(false) => {}
+            None => wait_for_io(),
+        }
+    }
+}
+
+/// Write all of `data` at the current position of `fd`.
+pub fn write_all(fd: i32, mut data: &[u8]) -> Result<(), isize> {
+    while !data.is_empty() {
+        let chunk = data.len().min(EDGE_BUFFER_SIZE);
+        let written = hal::edge::with_edge_caller(|caller| {
+            caller.data_zone_mut().unwrap()[0..chunk].copy_from_slice(&data[0..chunk]);
+            caller
+                .write_header(&EdgeCallReq::SyscallWriteV {
+                    pid: hal::task::current_pid(),
+                    fd,
+                    regions: DataRegion::packed([chunk]),
+                })
+                .unwrap();
+            caller.kick().unwrap();
+            syscall_result(caller.read_header().unwrap().into_syscall_resp().unwrap() as isize)
+        })?;
+        if written == 0 {
+            return Err(Errno::EIO.as_neg_isize());
+        }
+        data = &data[written..];
+    }
+    Ok(())
+}
+
+/// Poll host fds without blocking, returning the events each is ready for.
+/// If none is and `watch` is set, [`wait_for_io`] returns once one of them may
+/// be.
+pub fn poll(fds: &[PolledFd], watch: bool) -> Result<Vec<i16>, isize> {
+    let mut revents = Vec::with_capacity(fds.len());
+    for chunk in fds.chunks(MAX_POLLED_FDS) {
+        hal::edge::with_edge_caller(|caller| {
+            caller
+                .write_header(&EdgeCallReq::SyscallPoll {
+                    pid: hal::task::current_pid(),
+                    fds: chunk.to_vec(),
+                    watch,
+                })
+                .unwrap();
+            caller.kick().unwrap();
+            syscall_result(caller.read_header().unwrap().into_syscall_resp().unwrap() as isize)?;
+            let data = caller.read_data().unwrap();
+            let data = data
+                .get(0..chunk.len() * 2)
+                .ok_or(Errno::EIO.as_neg_isize())?;
+            revents.extend(
+                (0..chunk.len()).map(|i| i16::from_ne_bytes([data[2 * i], data[2 * i + 1]])),
+            );
+            Ok::<_, isize>(())
+        })?;
+    }
+    Ok(revents)
+}
+
+pub fn getcwd() -> String {
+    hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallGetCwd {
+                pid: hal::task::current_pid(),
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        caller.read_header().unwrap().into_ok_with_string().unwrap()
+    })
+}
+
+/// Find the guest path `fd` was opened at.
+pub fn fd_path(fd: i32) -> Result<String, isize> {
+    hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::FdPath {
+                pid: hal::task::current_pid(),
+                fd,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        let resp = caller
+            .read_header()
+            .unwrap()
+            .into_result()
+            .map_err(errno_of)?;
+        Ok(resp.into_ok_with_string().unwrap())
+    })
+}
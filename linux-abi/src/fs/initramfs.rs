@@ -0,0 +1,404 @@
+//! A read-only file system served from a `newc` cpio archive in enclave
+//! memory, such as the one embedded into the kernel.
+//!
+//! The archive is embedded at build time from the file named by the
+//! `INITRAMFS` environment variable, so that it is measured with the kernel.
+//! Files are served straight from the archive. Entries other than regular
+//! files and directories (e.g. symlinks) are skipped.
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use core::fmt;
+use spin::Mutex;
+
+use super::{
+    host::{O_ACCMODE, O_CREAT, O_DIRECTORY, O_RDONLY, O_TRUNC, W_OK},
+    stat::{Stat, Timespec, S_IFDIR, S_IFMT, S_IFREG},
+    vfs::{self, File, FileSystem, SeekFrom, DT_DIR, DT_REG},
+};
+use crate::Errno;
+
+/// The archive embedded into the kernel, empty if there is none.
+pub static IMAGE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/initramfs.cpio"));
+
+/// Where the embedded archive is mounted by [`super::init`].
+pub const INITRAMFS_MOUNT: &str = "/initramfs";
+
+/// The device number reported for initramfs files.
+const INITRAMFS_DEV: u64 = 0x7f;
+const MAGIC: &[u8] = b"070701";
+const HEADER_SIZE: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveError {
+    Truncated,
+    BadMagic { offset: usize },
+    BadHeader { offset: usize },
+    BadName { offset: usize },
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Truncated => write!(f, "the archive is truncated"),
+            ArchiveError::BadMagic { offset } => write!(f, "no cpio header at {:#X}", offset),
+            ArchiveError::BadHeader { offset } => {
+                write!(f, "malformed cpio header at {:#X}", offset)
+            }
+            ArchiveError::BadName { offset } => {
+                write!(f, "invalid file name in the entry at {:#X}", offset)
+            }
+        }
+    }
+}
+
+enum Content {
+    File(&'static [u8]),
+    Dir(BTreeMap<&'static str, Arc<Inode>>),
+}
+
+struct Inode {
+    ino: u64,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: i64,
+    content: Content,
+}
+
+impl Inode {
+    fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    fn stat(&self) -> Stat {
+        let (size, nlink) = match &self.content {
+            Content::File(data) => (data.len() as i64, 1),
+            Content::Dir(entries) => {
+                let subdirs = entries.values().filter(|inode| inode.is_dir()).count();
+                (0, 2 + subdirs as u32)
+            }
+        };
+        let time = Timespec {
+            sec: self.mtime,
+            nsec: 0,
+        };
+        Stat {
+            dev: INITRAMFS_DEV,
+            ino: self.ino,
+            mode: self.mode,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+            size,
+            blksize: 0x1000,
+            blocks: (size + 511) / 512,
+            atime: time,
+            mtime: time,
+            ctime: time,
+            ..Default::default()
+        }
+    }
+}
+
+/// An entry of the archive, as it is being read.
+struct Entry {
+    ino: u64,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: i64,
+    name: &'static str,
+    data: &'static [u8],
+}
+
+fn parse_hex(field: &[u8]) -> Option<u32> {
+    u32::from_str_radix(core::str::from_utf8(field).ok()?, 16).ok()
+}
+
+/// Read the entry at `offset`, returning it and the offset of the next one.
+fn read_entry(image: &'static [u8], offset: usize) -> Result<(Entry, usize), ArchiveError> {
+    let header = image
+        .get(offset..offset + HEADER_SIZE)
+        .ok_or(ArchiveError::Truncated)?;
+    if &header[0..6] != MAGIC {
+        return Err(ArchiveError::BadMagic { offset });
+    }
+    let field = |index: usize| {
+        let start = 6 + index * 8;
+        parse_hex(&header[start..start + 8]).ok_or(ArchiveError::BadHeader { offset })
+    };
+    let name_size = field(11)? as usize;
+    let file_size = field(6)? as usize;
+
+    let name_start = offset + HEADER_SIZE;
+    let name = image
+        .get(name_start..name_start + name_size)
+        .ok_or(ArchiveError::Truncated)?;
+    let name = name
+        .strip_suffix(&[0])
+        .and_then(|name| core::str::from_utf8(name).ok())
+        .ok_or(ArchiveError::BadName { offset })?;
+    let data_start = (name_start + name_size).next_multiple_of(4);
+    let data = image
+        .get(data_start..data_start + file_size)
+        .ok_or(ArchiveError::Truncated)?;
+
+    let entry = Entry {
+        ino: field(0)?.into(),
+        mode: field(1)?,
+        uid: field(2)?,
+        gid: field(3)?,
+        mtime: field(5)?.into(),
+        name,
+        data,
+    };
+    Ok((entry, (data_start + file_size).next_multiple_of(4)))
+}
+
+/// A dir being built, before its inode is frozen.
+#[derive(Default)]
+struct DirBuilder {
+    ino: u64,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: i64,
+    dirs: BTreeMap<&'static str, DirBuilder>,
+    files: BTreeMap<&'static str, Arc<Inode>>,
+}
+
+impl DirBuilder {
+    fn dir(&mut self, path: &[&'static str]) -> &mut DirBuilder {
+        path.iter().fold(self, |dir, name| {
+            dir.dirs.entry(name).or_insert_with(|| DirBuilder {
+                mode: S_IFDIR | 0o755,
+                ..Default::default()
+            })
+        })
+    }
+
+    fn build(self, next_ino: &mut u64) -> Arc<Inode> {
+        let mut entries = self.files;
+        for (name, dir) in self.dirs {
+            entries.insert(name, dir.build(next_ino));
+        }
+        let ino = if self.ino != 0 {
+            self.ino
+        } else {
+            *next_ino += 1;
+            *next_ino
+        };
+        Arc::new(Inode {
+            ino,
+            mode: self.mode,
+            uid: self.uid,
+            gid: self.gid,
+            mtime: self.mtime,
+            content: Content::Dir(entries),
+        })
+    }
+}
+
+pub struct InitramFs {
+    root: Arc<Inode>,
+}
+
+impl InitramFs {
+    /// Index the archive `image`.
+    pub fn new(image: &'static [u8]) -> Result<InitramFs, ArchiveError> {
+        let mut root = DirBuilder {
+            mode: S_IFDIR | 0o755,
+            ..Default::default()
+        };
+        let mut max_ino = 0;
+        let mut offset = 0;
+        loop {
+            let (entry, next) = read_entry(image, offset)?;
+            if entry.name == TRAILER {
+                break;
+            }
+            max_ino = max_ino.max(entry.ino);
+
+            let mut path: Vec<_> = entry
+                .name
+                .split('/')
+                .filter(|name| !name.is_empty() && *name != ".")
+                .collect();
+            if path.contains(&"..") {
+                return Err(ArchiveError::BadName { offset });
+            }
+            offset = next;
+            match entry.mode & S_IFMT {
+                S_IFDIR => {
+                    let dir = root.dir(&path);
+                    dir.ino = entry.ino;
+                    dir.mode = entry.mode;
+                    dir.uid = entry.uid;
+                    dir.gid = entry.gid;
+                    dir.mtime = entry.mtime;
+                }
+                S_IFREG if !path.is_empty() => {
+                    let name = path.pop().unwrap();
+                    let inode = Inode {
+                        ino: entry.ino,
+                        mode: entry.mode,
+                        uid: entry.uid,
+                        gid: entry.gid,
+                        mtime: entry.mtime,
+                        content: Content::File(entry.data),
+                    };
+                    root.dir(&path).files.insert(name, Arc::new(inode));
+                }
+                _ => log::warn!(
+                    "initramfs: Skipping {:?} of mode {:#o}",
+                    entry.name,
+                    entry.mode
+                ),
+            }
+        }
+        // Directories missing from the archive get inode numbers of their own
+        let mut next_ino = max_ino;
+        Ok(InitramFs {
+            root: root.build(&mut next_ino),
+        })
+    }
+
+    fn lookup(&self, path: &str) -> Result<Arc<Inode>, isize> {
+        let mut inode = Arc::clone(&self.root);
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let child = match &inode.content {
+                Content::Dir(entries) => entries.get(name).cloned(),
+                Content::File(_) => return Err(Errno::ENOTDIR.as_neg_isize()),
+            };
+            inode = child.ok_or(Errno::ENOENT.as_neg_isize())?;
+        }
+        Ok(inode)
+    }
+}
+
+impl FileSystem for InitramFs {
+    fn open(&self, path: &str, flags: i32, _mode: u32) -> Result<Box<dyn File>, isize> {
+        let inode = match self.lookup(path) {
+            Err(errno) if errno == Errno::ENOENT.as_neg_isize() && flags & O_CREAT != 0 => {
+                return Err(Errno::EROFS.as_neg_isize())
+            }
+            result => result?,
+        };
+        if flags & O_ACCMODE != O_RDONLY || flags & O_TRUNC != 0 {
+            return Err(if inode.is_dir() {
+                Errno::EISDIR.as_neg_isize()
+            } else {
+                Errno::EROFS.as_neg_isize()
+            });
+        }
+        if flags & O_DIRECTORY != 0 && !inode.is_dir() {
+            return Err(Errno::ENOTDIR.as_neg_isize());
+        }
+        Ok(Box::new(InitramFile {
+            inode,
+            pos: Mutex::new(0),
+        }))
+    }
+
+    fn mkdir(&self, path: &str, _mode: u32) -> Result<(), isize> {
+        match self.lookup(path) {
+            Ok(_) => Err(Errno::EEXIST.as_neg_isize()),
+            Err(_) => Err(Errno::EROFS.as_neg_isize()),
+        }
+    }
+
+    fn unlink(&self, path: &str, _remove_dir: bool) -> Result<(), isize> {
+        self.lookup(path)?;
+        Err(Errno::EROFS.as_neg_isize())
+    }
+
+    fn rename(&self, old: &str, _new: &str, _flags: u32) -> Result<(), isize> {
+        self.lookup(old)?;
+        Err(Errno::EROFS.as_neg_isize())
+    }
+
+    fn link(&self, old: &str, _new: &str) -> Result<(), isize> {
+        self.lookup(old)?;
+        Err(Errno::EROFS.as_neg_isize())
+    }
+
+    fn symlink(&self, _target: &str, path: &str) -> Result<(), isize> {
+        match self.lookup(path) {
+            Ok(_) => Err(Errno::EEXIST.as_neg_isize()),
+            Err(_) => Err(Errno::EROFS.as_neg_isize()),
+        }
+    }
+
+    fn stat(&self, path: &str) -> Result<Stat, isize> {
+        Ok(self.lookup(path)?.stat())
+    }
+
+    fn access(&self, path: &str, mode: i32) -> Result<(), isize> {
+        let stat = self.lookup(path)?.stat();
+        if mode & W_OK != 0 {
+            return Err(Errno::EROFS.as_neg_isize());
+        }
+        vfs::check_access(&stat, mode)
+    }
+}
+
+struct InitramFile {
+    inode: Arc<Inode>,
+    /// The offset in a file, or the index of the next entry in a directory.
+    pos: Mutex<usize>,
+}
+
+impl File for InitramFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let mut pos = self.pos.lock();
+        let len = self.read_at(buf, *pos)?;
+        *pos += len;
+        Ok(len)
+    }
+
+    fn write(&self, _buf: &[u8]) -> Result<usize, isize> {
+        // Files can only be opened for reading
+        Err(Errno::EBADF.as_neg_isize())
+    }
+
+    fn read_at(&self, buf: &mut [u8], offset: usize) -> Result<usize, isize> {
+        let data = match self.inode.content {
+            Content::File(data) => data,
+            Content::Dir(_) => return Err(Errno::EISDIR.as_neg_isize()),
+        };
+        let start = offset.min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[0..len].copy_from_slice(&data[start..start + len]);
+        Ok(len)
+    }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(self.inode.stat())
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize, isize> {
+        let len = match self.inode.content {
+            Content::File(data) => data.len(),
+            Content::Dir(_) if pos == SeekFrom::Start(0) => 0,
+            Content::Dir(_) => return Err(Errno::EINVAL.as_neg_isize()),
+        };
+        pos.apply(&mut self.pos.lock(), len)
+    }
+
+    fn getdents64(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let entries = match &self.inode.content {
+            Content::Dir(entries) => entries,
+            Content::File(_) => return Err(Errno::ENOTDIR.as_neg_isize()),
+        };
+        let dots = [
+            (self.inode.ino, DT_DIR, "."),
+            (self.inode.ino, DT_DIR, ".."),
+        ];
+        let children = entries.iter().map(|(&name, inode)| {
+            let kind = if inode.is_dir() { DT_DIR } else { DT_REG };
+            (inode.ino, kind, name)
+        });
+        vfs::fill_dirents(buf, &mut self.pos.lock(), dots.into_iter().chain(children))
+    }
+}
@@ -0,0 +1,90 @@
+//! File systems implemented inside the enclave, and the host files reached
+//! through edge calls.
+
+pub mod devfs;
+pub mod host;
+pub mod initramfs;
+pub mod poll;
+pub mod procfs;
+pub mod protected;
+pub mod stat;
+pub mod tmpfs;
+pub mod vfs;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::{limits::ENCLAVE_FILE_MAX, Errno};
+
+/// The paths that an in-memory file system is mounted at by [`init`].
+pub const TMPFS_MOUNTS: &[&str] = &["/tmp"];
+
+/// The directories whose files are protected by [`init`] (see [`protected`]).
+pub const PROTECTED_DIRS: &[&str] = &["/protected"];
+
+/// Mount the file systems served inside the enclave.
+pub fn init() {
+    vfs::mount(procfs::PROCFS_MOUNT, Arc::new(procfs::ProcFs));
+    vfs::mount(devfs::DEVFS_MOUNT, Arc::new(devfs::DevFs));
+    for prefix in TMPFS_MOUNTS {
+        vfs::mount(prefix, Arc::new(tmpfs::TmpFs::new()));
+    }
+    protected::init(PROTECTED_DIRS);
+    if !initramfs::IMAGE.is_empty() {
+        let fs = initramfs::InitramFs::new(initramfs::IMAGE)
+            .unwrap_or_else(|e| panic!("The embedded initramfs is invalid: {}", e));
+        vfs::mount(initramfs::INITRAMFS_MOUNT, Arc::new(fs));
+    }
+}
+
+/// Resize the content of a file kept in enclave memory to `len`, padding it
+/// with zeros. Fails with `EFBIG` above [`ENCLAVE_FILE_MAX`], and with `ENOSPC`
+/// if the enclave runs out of memory.
+pub(crate) fn resize_enclave_file(data: &mut Vec<u8>, len: usize) -> Result<(), isize> {
+    if len > ENCLAVE_FILE_MAX {
+        return Err(Errno::EFBIG.as_neg_isize());
+    }
+    if let Some(additional) = len.checked_sub(data.len()) {
+        data.try_reserve_exact(additional)
+            .map_err(|_| Errno::ENOSPC.as_neg_isize())?;
+    }
+    data.resize(len, 0);
+    Ok(())
+}
+
+/// Normalize an absolute path lexically, removing `.` and `..` components.
+pub fn normalize_path(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    let mut normalized = String::new();
+    for component in components {
+        normalized.push('/');
+        normalized.push_str(component);
+    }
+    if normalized.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// Find the absolute guest path that `path` refers to, resolving it against
+/// `dir_fd` like the `*at` syscalls do.
+pub fn absolute_path(dir_fd: i32, path: &str) -> Result<String, isize> {
+    let base = if path.starts_with('/') {
+        String::new()
+    } else if dir_fd == host::AT_FDCWD {
+        host::getcwd()
+    } else if let Some(path) = vfs::fd_path(dir_fd) {
+        path
+    } else {
+        host::fd_path(dir_fd)?
+    };
+    Ok(normalize_path(&alloc::format!("{}/{}", base, path)))
+}
@@ -0,0 +1,82 @@
+//! Readiness of fds, for the syscalls that wait on several of them.
+//!
+//! Files served inside the enclave are asked directly, and host files in a
+//! single edge call, in which the edge responder polls the host fds they are
+//! mapped to.
+
+use alloc::vec::Vec;
+use edge_proto::PolledFd;
+
+use super::{host, vfs};
+
+pub const POLLIN: i16 = 0x1;
+pub const POLLPRI: i16 = 0x2;
+pub const POLLOUT: i16 = 0x4;
+pub const POLLERR: i16 = 0x8;
+pub const POLLHUP: i16 = 0x10;
+pub const POLLNVAL: i16 = 0x20;
+pub const POLLRDNORM: i16 = 0x40;
+pub const POLLWRNORM: i16 = 0x100;
+
+/// The events that are returned whether they were asked for or not.
+const ALWAYS_POLLED: i16 = POLLERR | POLLHUP | POLLNVAL;
+
+/// Find the events each of `fds` is ready for, without blocking. Negative fds
+/// are ignored. If none is ready and `watch` is set, the host files are
+/// watched, so that [`host::wait_for_io`] returns once one of them may be.
+///
+/// Returns the number of fds that returned events.
+fn poll_once(fds: &[PolledFd], revents: &mut [i16], watch: bool) -> Result<usize, isize> {
+    let mut host_fds = Vec::new();
+    let mut host_indices = Vec::new();
+    for (i, polled) in fds.iter().enumerate() {
+        revents[i] = 0;
+        if polled.fd < 0 {
+            continue;
+        }
+        match vfs::poll(polled.fd) {
+            Some(ready) => revents[i] = ready & (polled.events | ALWAYS_POLLED),
+            None => {
+                host_fds.push(*polled);
+                host_indices.push(i);
+            }
+        }
+    }
+    let local_ready = revents.iter().filter(|&&revents| revents != 0).count();
+    if !host_fds.is_empty() {
+        let host_revents = host::poll(&host_fds, watch && local_ready == 0)?;
+        for (i, host_revents) in host_indices.into_iter().zip(host_revents) {
+            revents[i] = host_revents;
+        }
+    }
+    Ok(revents.iter().filter(|&&revents| revents != 0).count())
+}
+
+/// Wait until one of `fds` is ready, or the monotonic clock reaches
+/// `deadline`, storing the events each is ready for in `revents`.
+///
+/// Returns the number of fds that returned events, which is 0 on timeout.
+pub fn wait(fds: &[PolledFd], revents: &mut [i16], deadline: Option<u64>) -> Result<usize, isize> {
+    loop {
+        let expired = deadline.is_some_and(|deadline| hal::time::monotonic_ns() >= deadline);
+        let ready = poll_once(fds, revents, !expired)?;
+        if ready > 0 || expired {
+            return Ok(ready);
+        }
+        if let Some(deadline) = deadline {
+            hal::time::wake_at(deadline);
+        }
+        #[cfg(feature = "netstack")]
+        crate::net::wake_for_timers();
+        host::wait_for_io();
+    }
+}
+
+/// Whether `fd` is open, inside the enclave or on the host.
+pub fn is_open(fd: i32) -> Result<bool, isize> {
+    if vfs::poll(fd).is_some() {
+        return Ok(true);
+    }
+    let revents = host::poll(&[PolledFd { fd, events: 0 }], false)?;
+    Ok(revents[0] & POLLNVAL == 0)
+}
@@ -0,0 +1,332 @@
+//! The `/proc` file system, generated from the kernel's own task state so
+//! that the host's `/proc` is neither consulted nor revealed.
+//!
+//! Files are generated when they are opened, and read as a snapshot.
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+use hal::task::Pid;
+use spin::Mutex;
+
+use super::{
+    host::{O_ACCMODE, O_DIRECTORY, O_RDONLY},
+    stat::{Stat, S_IFDIR, S_IFLNK, S_IFREG},
+    vfs::{self, File, FileSystem, SeekFrom, DT_DIR, DT_LNK, DT_REG},
+};
+use crate::Errno;
+
+/// Where the file system is mounted by [`super::init`].
+pub const PROCFS_MOUNT: &str = "/proc";
+
+/// The device number reported for `/proc` files.
+const PROCFS_DEV: u64 = 0x80;
+
+#[cfg(target_arch = "x86_64")]
+const CPUINFO: &str = "processor\t: 0\nvendor_id\t: unknown\nmodel name\t: x86_64\n\n";
+#[cfg(target_arch = "riscv64")]
+const CPUINFO: &str = "processor\t: 0\nhart\t\t: 0\nisa\t\t: rv64imafdc\nmmu\t\t: sv39\n\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Node {
+    Root,
+    CpuInfo,
+    /// `/proc/self`, a link to the directory of the current process.
+    SelfLink(Pid),
+    PidDir(Pid),
+    Exe(Pid),
+    Maps(Pid),
+    Status(Pid),
+}
+
+impl Node {
+    fn parse(path: &str) -> Result<Node, isize> {
+        let mut components = path.split('/').filter(|name| !name.is_empty());
+        let node = match components.next() {
+            None => return Ok(Node::Root),
+            Some("cpuinfo") => Node::CpuInfo,
+            Some(name) => {
+                let (pid, is_self) = match name {
+                    "self" => (hal::task::current_pid(), true),
+                    _ => (
+                        name.parse().map_err(|_| Errno::ENOENT.as_neg_isize())?,
+                        false,
+                    ),
+                };
+                hal::task::find(pid).ok_or(Errno::ENOENT.as_neg_isize())?;
+                match components.next() {
+                    None if is_self => Node::SelfLink(pid),
+                    None => Node::PidDir(pid),
+                    Some("exe") => Node::Exe(pid),
+                    Some("maps") => Node::Maps(pid),
+                    Some("status") => Node::Status(pid),
+                    Some(_) => return Err(Errno::ENOENT.as_neg_isize()),
+                }
+            }
+        };
+        if components.next().is_some() {
+            return Err(Errno::ENOTDIR.as_neg_isize());
+        }
+        Ok(node)
+    }
+
+    fn ino(self) -> u64 {
+        let pid_ino = |pid: Pid, index: u64| ((pid as u64) << 4) | index;
+        match self {
+            Node::Root => 1,
+            Node::CpuInfo => 2,
+            Node::SelfLink(_) => 3,
+            Node::PidDir(pid) => pid_ino(pid, 1),
+            Node::Exe(pid) => pid_ino(pid, 2),
+            Node::Maps(pid) => pid_ino(pid, 3),
+            Node::Status(pid) => pid_ino(pid, 4),
+        }
+    }
+
+    fn stat(self) -> Stat {
+        let (mode, nlink) = match self {
+            Node::Root | Node::PidDir(_) => (S_IFDIR | 0o555, 2),
+            Node::SelfLink(_) | Node::Exe(_) => (S_IFLNK | 0o777, 1),
+            _ => (S_IFREG | 0o444, 1),
+        };
+        Stat {
+            dev: PROCFS_DEV,
+            ino: self.ino(),
+            mode,
+            nlink,
+            blksize: 0x1000,
+            ..Default::default()
+        }
+    }
+
+    fn readlink(self) -> Result<String, isize> {
+        match self {
+            Node::SelfLink(pid) => Ok(format!("{}/{}", PROCFS_MOUNT, pid)),
+            Node::Exe(pid) => {
+                let task = hal::task::find(pid).ok_or(Errno::ENOENT.as_neg_isize())?;
+                let exe = task.lock().mm.exe.clone();
+                if exe.is_empty() {
+                    Err(Errno::ENOENT.as_neg_isize())
+                } else {
+                    Ok(exe)
+                }
+            }
+            _ => Err(Errno::EINVAL.as_neg_isize()),
+        }
+    }
+
+    /// List a directory as (inode number, type, name).
+    fn entries(self) -> Vec<(u64, u8, String)> {
+        // `..` of the root is outside the file system, like on tmpfs
+        let ino = self.ino();
+        let mut entries = vec![(ino, DT_DIR, ".".into()), (ino, DT_DIR, "..".into())];
+        match self {
+            Node::Root => {
+                entries.push((Node::CpuInfo.ino(), DT_REG, "cpuinfo".into()));
+                entries.push((Node::SelfLink(0).ino(), DT_LNK, "self".into()));
+                for pid in hal::task::pids() {
+                    entries.push((Node::PidDir(pid).ino(), DT_DIR, pid.to_string()));
+                }
+            }
+            Node::PidDir(pid) => {
+                entries.push((Node::Exe(pid).ino(), DT_LNK, "exe".into()));
+                entries.push((Node::Maps(pid).ino(), DT_REG, "maps".into()));
+                entries.push((Node::Status(pid).ino(), DT_REG, "status".into()));
+            }
+            _ => unreachable!("{:?} is not a directory", self),
+        }
+        entries
+    }
+
+    /// Generate the content of a file.
+    fn content(self) -> Result<Vec<u8>, isize> {
+        let task = |pid| hal::task::find(pid).ok_or(Errno::ESRCH.as_neg_isize());
+        let text = match self {
+            Node::CpuInfo => CPUINFO.into(),
+            Node::Maps(pid) => maps(&task(pid)?.lock().mm),
+            Node::Status(pid) => status(&task(pid)?.lock()),
+            _ => unreachable!("{:?} is not a regular file", self),
+        };
+        Ok(text.into_bytes())
+    }
+}
+
+fn maps(mm: &hal::task::TaskMmStruct) -> String {
+    // Access rights of VMAs are not tracked, so every mapping reads as rwx
+    let mut text = String::new();
+    let mut line = |range: &core::ops::Range<usize>, name: &str| {
+        let start = text.len();
+        let _ = write!(
+            text,
+            "{:08x}-{:08x} rwxp 00000000 00:00 0",
+            range.start, range.end
+        );
+        if !name.is_empty() {
+            // Names start at the same column as on Linux
+            let width = text.len() - start;
+            text.extend(core::iter::repeat_n(' ', 73usize.saturating_sub(width)));
+            text.push_str(name);
+        }
+        text.push('\n');
+    };
+    for vma in mm.vmas.values() {
+        line(&vma.range, "");
+    }
+    if !mm.stack_zone.is_empty() {
+        line(&mm.stack_zone, "[stack]");
+    }
+    text
+}
+
+fn status(task: &hal::task::Task) -> String {
+    let name = task.mm.exe.rsplit('/').next().unwrap_or_default();
+    let state = if task.exit_status.is_some() {
+        "Z (zombie)"
+    } else if task.waiting {
+        "S (sleeping)"
+    } else {
+        "R (running)"
+    };
+    let ppid = task.parent.upgrade().map_or(0, |parent| parent.lock().pid);
+    let vm_size: usize = task
+        .mm
+        .vmas
+        .values()
+        .map(|vma| vma.range.len())
+        .sum::<usize>()
+        + task.mm.stack_zone.len();
+    format!(
+        "Name:\t{}\nState:\t{}\nTgid:\t{}\nPid:\t{}\nPPid:\t{}\n\
+         Uid:\t0\t0\t0\t0\nGid:\t0\t0\t0\t0\nVmSize:\t{} kB\nThreads:\t1\n\
+         voluntary_ctxt_switches:\t{}\nnonvoluntary_ctxt_switches:\t{}\n",
+        // Linux truncates the name to 15 bytes
+        name.get(..15).unwrap_or(name),
+        state,
+        task.pid,
+        task.pid,
+        ppid,
+        vm_size / 1024,
+        task.stats.voluntary_switches,
+        task.stats.involuntary_switches,
+    )
+}
+
+pub struct ProcFs;
+
+impl FileSystem for ProcFs {
+    fn open(&self, path: &str, flags: i32, _mode: u32) -> Result<Box<dyn File>, isize> {
+        let node = Node::parse(path)?;
+        if matches!(node, Node::SelfLink(_) | Node::Exe(_)) {
+            // Links are followed by the caller, unless they are dangling
+            return Err(node.readlink().err().unwrap_or(Errno::ELOOP.as_neg_isize()));
+        }
+        let is_dir = matches!(node, Node::Root | Node::PidDir(_));
+        if flags & O_ACCMODE != O_RDONLY {
+            return Err(if is_dir {
+                Errno::EISDIR.as_neg_isize()
+            } else {
+                Errno::EACCES.as_neg_isize()
+            });
+        }
+        if flags & O_DIRECTORY != 0 && !is_dir {
+            return Err(Errno::ENOTDIR.as_neg_isize());
+        }
+        let content = if is_dir {
+            Content::Dir(node.entries())
+        } else {
+            Content::File(node.content()?)
+        };
+        Ok(Box::new(ProcFile {
+            node,
+            content,
+            pos: Mutex::new(0),
+        }))
+    }
+
+    fn mkdir(&self, path: &str, _mode: u32) -> Result<(), isize> {
+        match Node::parse(path) {
+            Ok(_) => Err(Errno::EEXIST.as_neg_isize()),
+            Err(_) => Err(Errno::EACCES.as_neg_isize()),
+        }
+    }
+
+    fn unlink(&self, path: &str, _remove_dir: bool) -> Result<(), isize> {
+        Node::parse(path)?;
+        Err(Errno::EACCES.as_neg_isize())
+    }
+
+    fn readlink(&self, path: &str) -> Result<String, isize> {
+        Node::parse(path)?.readlink()
+    }
+
+    fn stat(&self, path: &str) -> Result<Stat, isize> {
+        Ok(Node::parse(path)?.stat())
+    }
+}
+
+enum Content {
+    File(Vec<u8>),
+    Dir(Vec<(u64, u8, String)>),
+}
+
+/// A snapshot of a `/proc` file or directory.
+struct ProcFile {
+    node: Node,
+    content: Content,
+    /// The offset in a file, or the index of the next entry in a directory.
+    pos: Mutex<usize>,
+}
+
+impl File for ProcFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let mut pos = self.pos.lock();
+        let len = self.read_at(buf, *pos)?;
+        *pos += len;
+        Ok(len)
+    }
+
+    fn write(&self, _buf: &[u8]) -> Result<usize, isize> {
+        // Files can only be opened for reading
+        Err(Errno::EBADF.as_neg_isize())
+    }
+
+    fn read_at(&self, buf: &mut [u8], offset: usize) -> Result<usize, isize> {
+        let data = match &self.content {
+            Content::File(data) => data,
+            Content::Dir(_) => return Err(Errno::EISDIR.as_neg_isize()),
+        };
+        let start = offset.min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[0..len].copy_from_slice(&data[start..start + len]);
+        Ok(len)
+    }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(self.node.stat())
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize, isize> {
+        let len = match &self.content {
+            Content::File(data) => data.len(),
+            Content::Dir(_) if pos == SeekFrom::Start(0) => 0,
+            Content::Dir(_) => return Err(Errno::EINVAL.as_neg_isize()),
+        };
+        pos.apply(&mut self.pos.lock(), len)
+    }
+
+    fn getdents64(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let entries = match &self.content {
+            Content::Dir(entries) => entries,
+            Content::File(_) => return Err(Errno::ENOTDIR.as_neg_isize()),
+        };
+        let entries = entries
+            .iter()
+            .map(|(ino, kind, name)| (*ino, *kind, name.as_str()));
+        vfs::fill_dirents(buf, &mut self.pos.lock(), entries)
+    }
+}
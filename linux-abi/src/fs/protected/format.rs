@@ -0,0 +1,202 @@
+//! The layout of protected files in host storage.
+//!
+//! ```text
+//! header:  magic (8) | plaintext size (8) | Merkle root (32) | MAC (32)
+//! blocks:  nonce (12) | ciphertext (BLOCK_SIZE) | tag (16), for every block
+//! tree:    the Merkle tree nodes, level by level from the leaves up (32 each)
+//! ```
+//!
+//! Each block is encrypted with ChaCha20-Poly1305 under a key derived from the
+//! enclave key and the file's path, so files cannot be swapped. The nonce is
+//! derived from the block index and plaintext (as in SIV), so that it never
+//! repeats for different contents without requiring a random source. The
+//! leaves of the Merkle tree are the hashes of the encrypted blocks, and the
+//! header MAC covers the size and the root.
+
+use alloc::vec::Vec;
+use crypto::{
+    aead,
+    hmac::HmacSha256,
+    sha256::{Sha256, DIGEST_SIZE},
+};
+
+pub const BLOCK_SIZE: usize = 0x1000;
+
+const MAGIC: [u8; 8] = *b"TEEPFS\x00\x01";
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+const HEADER_SIZE: usize = 8 + 8 + DIGEST_SIZE + DIGEST_SIZE;
+const RECORD_SIZE: usize = NONCE_SIZE + BLOCK_SIZE + TAG_SIZE;
+
+pub type Hash = [u8; DIGEST_SIZE];
+
+/// The stored file is malformed, or has been modified outside the enclave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Corrupted;
+
+/// The keys protecting one file.
+pub struct FileKeys {
+    enc: [u8; 32],
+    siv: [u8; 32],
+    mac: [u8; 32],
+}
+
+impl FileKeys {
+    pub fn derive(enclave_key: &[u8; 32], path: &str) -> FileKeys {
+        let derive = |label: &[u8]| {
+            let mut mac = HmacSha256::new(enclave_key);
+            mac.update(label);
+            mac.update(path.as_bytes());
+            mac.finalize()
+        };
+        FileKeys {
+            enc: derive(b"pfs-enc\0"),
+            siv: derive(b"pfs-siv\0"),
+            mac: derive(b"pfs-mac\0"),
+        }
+    }
+
+    fn header_mac(&self, size: u64, root: &Hash) -> Hash {
+        let mut mac = HmacSha256::new(&self.mac);
+        mac.update(&MAGIC);
+        mac.update(&size.to_le_bytes());
+        mac.update(root);
+        mac.finalize()
+    }
+}
+
+fn leaf_hash(record: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(&[0]);
+    hasher.update(record);
+    hasher.finalize()
+}
+
+fn node_hash(left: &Hash, right: Option<&Hash>) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(&[1]);
+    hasher.update(left);
+    if let Some(right) = right {
+        hasher.update(right);
+    }
+    hasher.finalize()
+}
+
+/// Build the Merkle tree over `leaves`, returning all its nodes level by
+/// level. The last node is the root; an empty tree has no nodes.
+fn merkle_tree(leaves: Vec<Hash>) -> Vec<Hash> {
+    let mut nodes = leaves;
+    let mut level = 0..nodes.len();
+    while level.len() > 1 {
+        let start = nodes.len();
+        for pair in level.clone().step_by(2) {
+            let right = (pair + 1 < level.end).then(|| nodes[pair + 1]);
+            let parent = node_hash(&nodes[pair], right.as_ref());
+            nodes.push(parent);
+        }
+        level = start..nodes.len();
+    }
+    nodes
+}
+
+fn merkle_root(tree: &[Hash]) -> Hash {
+    tree.last().copied().unwrap_or([0; DIGEST_SIZE])
+}
+
+fn block_count(size: usize) -> usize {
+    size.div_ceil(BLOCK_SIZE)
+}
+
+/// Encrypt `plaintext`, returning what to store and the Merkle root.
+pub fn encode(keys: &FileKeys, plaintext: &[u8]) -> (Vec<u8>, Hash) {
+    let blocks = block_count(plaintext.len());
+    let mut records = Vec::with_capacity(blocks * RECORD_SIZE);
+    let mut leaves = Vec::with_capacity(blocks);
+    for (index, chunk) in plaintext.chunks(BLOCK_SIZE).enumerate() {
+        let mut block = [0; BLOCK_SIZE];
+        block[0..chunk.len()].copy_from_slice(chunk);
+        let aad = (index as u64).to_le_bytes();
+
+        let mut siv = HmacSha256::new(&keys.siv);
+        siv.update(&aad);
+        siv.update(&block);
+        let nonce: [u8; NONCE_SIZE] = siv.finalize()[0..NONCE_SIZE].try_into().unwrap();
+        let tag = aead::seal(&keys.enc, &nonce, &aad, &mut block);
+
+        let start = records.len();
+        records.extend_from_slice(&nonce);
+        records.extend_from_slice(&block);
+        records.extend_from_slice(&tag);
+        leaves.push(leaf_hash(&records[start..]));
+    }
+
+    let tree = merkle_tree(leaves);
+    let root = merkle_root(&tree);
+    let size = plaintext.len() as u64;
+
+    let mut data = Vec::with_capacity(HEADER_SIZE + records.len() + tree.len() * DIGEST_SIZE);
+    data.extend_from_slice(&MAGIC);
+    data.extend_from_slice(&size.to_le_bytes());
+    data.extend_from_slice(&root);
+    data.extend_from_slice(&keys.header_mac(size, &root));
+    data.extend_from_slice(&records);
+    for node in &tree {
+        data.extend_from_slice(node);
+    }
+    (data, root)
+}
+
+/// Verify and decrypt stored data, returning the plaintext and the Merkle
+/// root.
+pub fn decode(keys: &FileKeys, data: &[u8]) -> Result<(Vec<u8>, Hash), Corrupted> {
+    if data.len() < HEADER_SIZE || data[0..8] != MAGIC {
+        return Err(Corrupted);
+    }
+    let size = u64::from_le_bytes(data[8..16].try_into().unwrap());
+    let root: Hash = data[16..48].try_into().unwrap();
+    if !crypto::ct_eq(&keys.header_mac(size, &root), &data[48..HEADER_SIZE]) {
+        return Err(Corrupted);
+    }
+
+    let size = usize::try_from(size).map_err(|_| Corrupted)?;
+    let blocks = block_count(size);
+    let records = &data[HEADER_SIZE..];
+    let records_len = blocks.checked_mul(RECORD_SIZE).ok_or(Corrupted)?;
+    if records.len() < records_len {
+        return Err(Corrupted);
+    }
+    let (records, stored_tree) = records.split_at(records_len);
+
+    let tree = merkle_tree(records.chunks(RECORD_SIZE).map(leaf_hash).collect());
+    let tree_matches = stored_tree.len() == tree.len() * DIGEST_SIZE
+        && stored_tree
+            .chunks(DIGEST_SIZE)
+            .zip(&tree)
+            .all(|(stored, node)| stored == node);
+    if !tree_matches || merkle_root(&tree) != root {
+        return Err(Corrupted);
+    }
+
+    let mut plaintext = Vec::with_capacity(blocks * BLOCK_SIZE);
+    for (index, record) in records.chunks(RECORD_SIZE).enumerate() {
+        let (nonce, rest) = record.split_at(NONCE_SIZE);
+        let (block, tag) = rest.split_at(BLOCK_SIZE);
+        let start = plaintext.len();
+        plaintext.extend_from_slice(block);
+        aead::open(
+            &keys.enc,
+            nonce.try_into().unwrap(),
+            &(index as u64).to_le_bytes(),
+            &mut plaintext[start..],
+            tag.try_into().unwrap(),
+        )
+        .map_err(|_| Corrupted)?;
+    }
+    plaintext.truncate(size);
+    Ok((plaintext, root))
+}
+
+/// The Merkle root of an empty file.
+pub fn empty_root() -> Hash {
+    merkle_root(&[])
+}
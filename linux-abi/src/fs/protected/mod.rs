@@ -0,0 +1,450 @@
+//! Protected files: files in selected directories are encrypted and
+//! integrity-protected inside the enclave, and only reach the host as
+//! ciphertext (see [`format`]).
+//!
+//! A protected file is read and verified as a whole when it is first opened,
+//! kept in enclave memory while it is open, and written back when its last
+//! descriptor is closed. The Merkle root of every file written or read since
+//! the enclave started is remembered, so the host cannot roll a file back to
+//! an older version while the enclave runs. Directory entries (file names and
+//! sizes of the stored files) are not hidden from the host.
+//!
+//! As the keys of a file are bound to its path, a renamed file is encrypted
+//! again, and hard and symbolic links are not supported.
+//!
+//! The keys are derived from the platform's sealing key, so on Keystone and
+//! SGX only the same enclave can read the files. The x86 VM and loopback have
+//! no TEE and derive them from a fixed, public key, which protects nothing
+//! from the host and is only good for testing.
+
+pub mod format;
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::{Mutex, Once};
+
+use self::format::{FileKeys, Hash};
+use super::{
+    host::{
+        self, AT_FDCWD, AT_SYMLINK_NOFOLLOW, O_ACCMODE, O_APPEND, O_CREAT, O_DIRECTORY, O_EXCL,
+        O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY,
+    },
+    resize_enclave_file,
+    stat::{Stat, S_IFMT, S_IFREG},
+    vfs::{self, File, SeekFrom},
+};
+use crate::Errno;
+
+const RENAME_EXCHANGE: u32 = 2;
+
+static ENCLAVE_KEY: Once<[u8; 32]> = Once::new();
+static PROTECTED_DIRS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// The latest known Merkle root of each protected file.
+static ROOTS: Mutex<BTreeMap<String, Hash>> = Mutex::new(BTreeMap::new());
+static OPEN_FILES: Mutex<BTreeMap<String, Weak<Mutex<ProtectedFile>>>> =
+    Mutex::new(BTreeMap::new());
+
+/// What the enclave key is derived for, so that it differs from other keys of
+/// the enclave.
+const KEY_LABEL: &[u8] = b"linux_abi::fs::protected";
+
+/// Derive the per-enclave key that protected files are encrypted with, which
+/// only the same enclave can derive again, and protect the files beneath the
+/// absolute guest paths `dirs`.
+pub fn init(dirs: &[&str]) {
+    match hal::seal::derive_key(KEY_LABEL) {
+        Some(key) => {
+            ENCLAVE_KEY.call_once(|| key);
+        }
+        None => log::error!("Cannot derive the enclave key, protected files cannot be opened"),
+    }
+    for dir in dirs {
+        add_protected_dir(dir);
+    }
+}
+
+/// Protect the files beneath the absolute guest path `dir`.
+pub fn add_protected_dir(dir: &str) {
+    PROTECTED_DIRS.lock().push(super::normalize_path(dir));
+}
+
+/// Find the absolute path of `path` (resolved against `dir_fd`) if it is
+/// protected.
+pub fn protected_path(dir_fd: i32, path: &str) -> Result<Option<String>, isize> {
+    if PROTECTED_DIRS.lock().is_empty() {
+        return Ok(None);
+    }
+    let path = super::absolute_path(dir_fd, path)?;
+    Ok(is_protected(&path).then_some(path))
+}
+
+/// Check whether the normalized absolute path `path` is a protected file.
+fn is_protected(path: &str) -> bool {
+    PROTECTED_DIRS.lock().iter().any(|dir| {
+        path.strip_prefix(dir.as_str())
+            .is_some_and(|rest| rest.starts_with('/') || (dir == "/" && !rest.is_empty()))
+    })
+}
+
+/// Derive the keys of the protected file at `path`.
+fn keys_for(path: &str) -> Result<FileKeys, isize> {
+    let key = ENCLAVE_KEY.get().ok_or_else(|| {
+        log::warn!("No enclave key is set, cannot use protected files");
+        Errno::EACCES.as_neg_isize()
+    })?;
+    Ok(FileKeys::derive(key, path))
+}
+
+struct ProtectedFile {
+    path: String,
+    data: Vec<u8>,
+    dirty: bool,
+    /// The file has been unlinked or replaced, so it is not written back.
+    removed: bool,
+}
+
+impl ProtectedFile {
+    /// Load the file from the host through `fd`, checking it against the
+    /// latest known version. The stored file may only be empty if it has just
+    /// been `created`, as the host could otherwise truncate any file whose
+    /// version is not known yet.
+    fn load(path: String, fd: i32, created: bool) -> Result<ProtectedFile, isize> {
+        let mut file = ProtectedFile {
+            path,
+            data: Vec::new(),
+            dirty: false,
+            removed: false,
+        };
+        let keys = keys_for(&file.path)?;
+        let stored = host::read_to_end(fd)?;
+        let known_root = ROOTS.lock().get(&file.path).copied();
+
+        let root = if stored.is_empty() {
+            if !created {
+                log::error!("Protected file {} has been truncated", file.path);
+                return Err(Errno::EIO.as_neg_isize());
+            }
+            file.dirty = true;
+            format::empty_root()
+        } else {
+            let (data, root) = format::decode(&keys, &stored).map_err(|_| {
+                log::error!("Protected file {} is corrupted", file.path);
+                Errno::EIO.as_neg_isize()
+            })?;
+            file.data = data;
+            root
+        };
+        if known_root.is_some_and(|known| known != root) {
+            log::error!("Protected file {} has been rolled back", file.path);
+            return Err(Errno::EIO.as_neg_isize());
+        }
+        ROOTS.lock().insert(file.path.clone(), root);
+        Ok(file)
+    }
+
+    fn set_len(&mut self, len: usize) -> Result<(), isize> {
+        resize_enclave_file(&mut self.data, len)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), isize> {
+        if !self.dirty || self.removed {
+            return Ok(());
+        }
+        let (stored, root) = format::encode(&keys_for(&self.path)?, &self.data);
+        let fd = host::open(&self.path, O_WRONLY | O_TRUNC, 0)?;
+        let result = host::write_all(fd, &stored);
+        host::close(fd)?;
+        result?;
+        ROOTS.lock().insert(self.path.clone(), root);
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// An open file description of a protected file.
+struct ProtectedFileDesc {
+    file: Arc<Mutex<ProtectedFile>>,
+    pos: Mutex<usize>,
+    append: AtomicBool,
+}
+
+impl ProtectedFileDesc {
+    /// Write `buf` at `offset`, or at the end if the file is in append mode,
+    /// returning where the write ended.
+    fn write_data(&self, buf: &[u8], offset: usize) -> Result<usize, isize> {
+        let mut file = self.file.lock();
+        let start = if self.append.load(Ordering::Relaxed) {
+            file.data.len()
+        } else {
+            offset
+        };
+        let end = start
+            .checked_add(buf.len())
+            .ok_or(Errno::EFBIG.as_neg_isize())?;
+        if file.data.len() < end {
+            resize_enclave_file(&mut file.data, end)?;
+        }
+        file.data[start..end].copy_from_slice(buf);
+        file.dirty = true;
+        Ok(end)
+    }
+}
+
+impl File for ProtectedFileDesc {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let mut pos = self.pos.lock();
+        let len = self.read_at(buf, *pos)?;
+        *pos += len;
+        Ok(len)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, isize> {
+        let mut pos = self.pos.lock();
+        *pos = self.write_data(buf, *pos)?;
+        Ok(buf.len())
+    }
+
+    fn read_at(&self, buf: &mut [u8], offset: usize) -> Result<usize, isize> {
+        let file = self.file.lock();
+        let start = offset.min(file.data.len());
+        let len = buf.len().min(file.data.len() - start);
+        buf[0..len].copy_from_slice(&file.data[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, buf: &[u8], offset: usize) -> Result<usize, isize> {
+        self.write_data(buf, offset)?;
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        let file = self.file.lock();
+        // The host only knows the size of the encrypted file
+        let mut stat = host::stat(&file.path)?;
+        stat.size = file.data.len() as i64;
+        Ok(stat)
+    }
+
+    fn truncate(&self, len: usize) -> Result<(), isize> {
+        self.file.lock().set_len(len)
+    }
+
+    fn sync(&self) -> Result<(), isize> {
+        let mut file = self.file.lock();
+        if file.removed {
+            return Ok(());
+        }
+        file.flush()?;
+        // Any fd of the stored file flushes it as a whole
+        let fd = host::open(&file.path, O_RDONLY, 0)?;
+        let result = host::fsync(fd, false);
+        host::close(fd)?;
+        result
+    }
+
+    fn set_append(&self, append: bool) {
+        self.append.store(append, Ordering::Relaxed);
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize, isize> {
+        let len = self.file.lock().data.len();
+        pos.apply(&mut self.pos.lock(), len)
+    }
+
+    /// Write the file back if this was its last open file description.
+    fn release(&self) -> Result<(), isize> {
+        let mut open_files = OPEN_FILES.lock();
+        if Arc::strong_count(&self.file) > 1 {
+            return Ok(());
+        }
+        let mut file = self.file.lock();
+        // A removed file has already been forgotten, and its path may have
+        // been taken since
+        if !file.removed {
+            open_files.remove(&file.path);
+        }
+        drop(open_files);
+        file.flush()
+    }
+}
+
+/// Open the protected file at `path`, returning the new fd.
+pub fn open(path: &str, flags: i32, mode: u32) -> Result<i32, isize> {
+    // The host fd only serves to reserve an fd number and to read the
+    // stored file; all writes go through `ProtectedFile::flush`.
+    let host_flags = (flags & !(O_ACCMODE | O_TRUNC | O_APPEND)) | O_RDONLY;
+    let created = flags & O_CREAT != 0
+        && matches!(
+            host::fstatat(AT_FDCWD, path, AT_SYMLINK_NOFOLLOW),
+            Err(errno) if errno == Errno::ENOENT.as_neg_isize()
+        );
+    let fd = host::open(path, host_flags, mode)?;
+    if flags & O_DIRECTORY != 0 {
+        return Ok(fd);
+    }
+
+    let existing = OPEN_FILES.lock().get(path).and_then(Weak::upgrade);
+    let file = match existing {
+        Some(file) => file,
+        None => match ProtectedFile::load(path.to_string(), fd, created) {
+            Ok(file) => {
+                let file = Arc::new(Mutex::new(file));
+                OPEN_FILES
+                    .lock()
+                    .insert(path.to_string(), Arc::downgrade(&file));
+                file
+            }
+            // Directories are opened as they are
+            Err(errno) if errno == Errno::EISDIR.as_neg_isize() => return Ok(fd),
+            Err(errno) => {
+                let _ = host::close(fd);
+                return Err(errno);
+            }
+        },
+    };
+    if flags & O_TRUNC != 0 && flags & O_ACCMODE != O_RDONLY {
+        let mut file = file.lock();
+        file.data.clear();
+        file.dirty = true;
+    }
+
+    let desc = ProtectedFileDesc {
+        file,
+        pos: Mutex::new(0),
+        append: AtomicBool::new(flags & O_APPEND != 0),
+    };
+    vfs::install(fd, path.to_string(), flags, Box::new(desc));
+    Ok(fd)
+}
+
+/// Get the metadata of the protected file at `path`, with the size of its
+/// plaintext.
+pub fn stat(path: &str) -> Result<Stat, isize> {
+    let mut stat = host::stat(path)?;
+    if stat.mode & S_IFMT != S_IFREG {
+        return Ok(stat);
+    }
+    let open = OPEN_FILES.lock().get(path).and_then(Weak::upgrade);
+    let len = match open {
+        Some(file) => file.lock().data.len(),
+        None => {
+            // The stored size is only known to be right once the file has
+            // been verified
+            let fd = host::open(path, O_RDONLY, 0)?;
+            let file = ProtectedFile::load(path.to_string(), fd, false);
+            host::close(fd)?;
+            file?.data.len()
+        }
+    };
+    stat.size = len as i64;
+    Ok(stat)
+}
+
+/// Change the size of the protected file at `path`.
+pub fn truncate(path: &str, len: usize) -> Result<(), isize> {
+    // Opening the stored file for writing checks that it may be written
+    let fd = host::open(path, O_RDWR, 0)?;
+    let open = OPEN_FILES.lock().get(path).and_then(Weak::upgrade);
+    let result = match open {
+        Some(file) => file.lock().set_len(len),
+        None => ProtectedFile::load(path.to_string(), fd, false).and_then(|mut file| {
+            file.set_len(len)?;
+            file.flush()
+        }),
+    };
+    host::close(fd)?;
+    result
+}
+
+/// Rename the protected file `old` to `new`. The file is encrypted under the
+/// keys of its new path into a temporary file, which then replaces `new`
+/// atomically. Directories are refused with `EXDEV`, as every file beneath
+/// them would have to be encrypted again, so that tools fall back to copying.
+pub fn rename(old: &str, new: &str, flags: u32) -> Result<(), isize> {
+    if flags & RENAME_EXCHANGE != 0 {
+        return Err(Errno::EINVAL.as_neg_isize());
+    }
+    let stat = host::fstatat(AT_FDCWD, old, AT_SYMLINK_NOFOLLOW)?;
+    if stat.mode & S_IFMT != S_IFREG {
+        return Err(Errno::EXDEV.as_neg_isize());
+    }
+    if old == new {
+        return Ok(());
+    }
+    let keys = keys_for(new)?;
+
+    // An open file stays locked, so that no write to it goes missing
+    let open = OPEN_FILES.lock().get(old).and_then(Weak::upgrade);
+    let root = {
+        let mut open_guard;
+        let mut loaded;
+        let file = match &open {
+            Some(open) => {
+                open_guard = open.lock();
+                &mut *open_guard
+            }
+            None => {
+                let fd = host::open(old, O_RDONLY, 0)?;
+                let file = ProtectedFile::load(old.to_string(), fd, false);
+                host::close(fd)?;
+                loaded = file?;
+                &mut loaded
+            }
+        };
+        let (stored, root) = format::encode(&keys, &file.data);
+        let temp = format!("{}.rename~", new);
+        let fd = host::open(&temp, O_WRONLY | O_CREAT | O_EXCL, stat.mode & 0o7777)?;
+        let written = host::write_all(fd, &stored);
+        host::close(fd)?;
+        let renamed = written.and_then(|()| host::renameat(AT_FDCWD, &temp, AT_FDCWD, new, flags));
+        if let Err(errno) = renamed {
+            let _ = host::unlinkat(AT_FDCWD, &temp, 0);
+            return Err(errno);
+        }
+        if let Err(errno) = host::unlinkat(AT_FDCWD, old, 0) {
+            log::error!(
+                "Cannot remove protected file {} after renaming it: {}",
+                old,
+                errno
+            );
+        }
+        file.path = new.to_string();
+        file.dirty = false;
+        root
+    };
+
+    let mut roots = ROOTS.lock();
+    roots.remove(old);
+    roots.insert(new.to_string(), root);
+    drop(roots);
+    let mut open_files = OPEN_FILES.lock();
+    if let Some(replaced) = open_files.remove(new).and_then(|file| file.upgrade()) {
+        replaced.lock().removed = true;
+    }
+    if let Some(open) = open_files.remove(old) {
+        open_files.insert(new.to_string(), open);
+    }
+    Ok(())
+}
+
+/// Forget a protected file that has been removed, so that it is not written
+/// back, and its known version.
+pub fn unlinked(path: &str) {
+    ROOTS.lock().remove(path);
+    if let Some(file) = OPEN_FILES
+        .lock()
+        .remove(path)
+        .and_then(|file| file.upgrade())
+    {
+        file.lock().removed = true;
+    }
+}
@@ -0,0 +1,129 @@
+//! File metadata, and its `struct stat` layout on each architecture.
+//!
+//! [`Stat`] is what file systems and the edge responder report, and is only
+//! laid out as the syscall ABI wants it when it is copied to userspace.
+
+pub use edge_proto::{Stat, Timespec};
+
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFLNK: u32 = 0o120000;
+pub const S_IFSOCK: u32 = 0o140000;
+
+/// Offsets of the fields in `struct stat`.
+#[cfg(target_arch = "x86_64")]
+mod layout {
+    pub const SIZE: usize = 144;
+    pub const ST_DEV: usize = 0;
+    pub const ST_INO: usize = 8;
+    /// `st_nlink` is 64 bits wide, and comes before `st_mode`
+    pub const ST_NLINK: usize = 16;
+    pub const ST_MODE: usize = 24;
+    pub const ST_UID: usize = 28;
+    pub const ST_GID: usize = 32;
+    pub const ST_RDEV: usize = 40;
+    pub const ST_SIZE: usize = 48;
+    pub const ST_BLKSIZE: usize = 56;
+    pub const ST_BLOCKS: usize = 64;
+    pub const ST_ATIME: usize = 72;
+    pub const ST_MTIME: usize = 88;
+    pub const ST_CTIME: usize = 104;
+}
+
+/// Offsets of the fields in `struct stat` (the generic layout).
+#[cfg(target_arch = "riscv64")]
+mod layout {
+    pub const SIZE: usize = 128;
+    pub const ST_DEV: usize = 0;
+    pub const ST_INO: usize = 8;
+    pub const ST_MODE: usize = 16;
+    pub const ST_NLINK: usize = 20;
+    pub const ST_UID: usize = 24;
+    pub const ST_GID: usize = 28;
+    pub const ST_RDEV: usize = 32;
+    pub const ST_SIZE: usize = 48;
+    /// `st_blksize` is 32 bits wide
+    pub const ST_BLKSIZE: usize = 56;
+    pub const ST_BLOCKS: usize = 64;
+    pub const ST_ATIME: usize = 72;
+    pub const ST_MTIME: usize = 88;
+    pub const ST_CTIME: usize = 104;
+}
+
+pub const STAT_SIZE: usize = layout::SIZE;
+
+fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+    buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// Lay the metadata out as this architecture's `struct stat`.
+pub fn to_bytes(stat: &Stat) -> [u8; STAT_SIZE] {
+    use layout::*;
+
+    let mut buf = [0; STAT_SIZE];
+    put(&mut buf, ST_DEV, &stat.dev.to_ne_bytes());
+    put(&mut buf, ST_INO, &stat.ino.to_ne_bytes());
+    put(&mut buf, ST_MODE, &stat.mode.to_ne_bytes());
+    #[cfg(target_arch = "x86_64")]
+    put(&mut buf, ST_NLINK, &u64::from(stat.nlink).to_ne_bytes());
+    #[cfg(target_arch = "riscv64")]
+    put(&mut buf, ST_NLINK, &stat.nlink.to_ne_bytes());
+    put(&mut buf, ST_UID, &stat.uid.to_ne_bytes());
+    put(&mut buf, ST_GID, &stat.gid.to_ne_bytes());
+    put(&mut buf, ST_RDEV, &stat.rdev.to_ne_bytes());
+    put(&mut buf, ST_SIZE, &stat.size.to_ne_bytes());
+    #[cfg(target_arch = "x86_64")]
+    put(&mut buf, ST_BLKSIZE, &stat.blksize.to_ne_bytes());
+    #[cfg(target_arch = "riscv64")]
+    put(&mut buf, ST_BLKSIZE, &(stat.blksize as i32).to_ne_bytes());
+    put(&mut buf, ST_BLOCKS, &stat.blocks.to_ne_bytes());
+    for (offset, time) in [
+        (ST_ATIME, stat.atime),
+        (ST_MTIME, stat.mtime),
+        (ST_CTIME, stat.ctime),
+    ] {
+        put(&mut buf, offset, &time.sec.to_ne_bytes());
+        put(&mut buf, offset + 8, &time.nsec.to_ne_bytes());
+    }
+    buf
+}
+
+/// The size of `struct statx`, which is the same on every architecture.
+pub const STATX_SIZE: usize = 256;
+/// The fields of `struct statx` that are filled in: everything `struct stat`
+/// has, but not the birth time.
+pub const STATX_BASIC_STATS: u32 = 0x7ff;
+
+fn major(dev: u64) -> u32 {
+    (((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff)) as u32
+}
+
+fn minor(dev: u64) -> u32 {
+    ((dev & 0xff) | ((dev >> 12) & !0xff)) as u32
+}
+
+/// Lay the metadata out as `struct statx`.
+pub fn to_statx_bytes(stat: &Stat) -> [u8; STATX_SIZE] {
+    let mut buf = [0; STATX_SIZE];
+    put(&mut buf, 0, &STATX_BASIC_STATS.to_ne_bytes());
+    put(&mut buf, 4, &(stat.blksize as u32).to_ne_bytes());
+    put(&mut buf, 16, &stat.nlink.to_ne_bytes());
+    put(&mut buf, 20, &stat.uid.to_ne_bytes());
+    put(&mut buf, 24, &stat.gid.to_ne_bytes());
+    put(&mut buf, 28, &(stat.mode as u16).to_ne_bytes());
+    put(&mut buf, 32, &stat.ino.to_ne_bytes());
+    put(&mut buf, 40, &stat.size.to_ne_bytes());
+    put(&mut buf, 48, &stat.blocks.to_ne_bytes());
+    // `struct statx_timestamp` has a 32-bit nanosecond field
+    for (offset, time) in [(64, stat.atime), (96, stat.ctime), (112, stat.mtime)] {
+        put(&mut buf, offset, &time.sec.to_ne_bytes());
+        put(&mut buf, offset + 8, &(time.nsec as u32).to_ne_bytes());
+    }
+    put(&mut buf, 128, &major(stat.rdev).to_ne_bytes());
+    put(&mut buf, 132, &minor(stat.rdev).to_ne_bytes());
+    put(&mut buf, 136, &major(stat.dev).to_ne_bytes());
+    put(&mut buf, 140, &minor(stat.dev).to_ne_bytes());
+    buf
+}
@@ -0,0 +1,457 @@
+//! An in-memory file system, whose files never leave the enclave.
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use spin::Mutex;
+
+use super::{
+    host::{O_ACCMODE, O_APPEND, O_CREAT, O_DIRECTORY, O_EXCL, O_RDONLY, O_TRUNC},
+    resize_enclave_file,
+    stat::{Stat, S_IFDIR, S_IFLNK, S_IFMT, S_IFREG},
+    vfs::{self, File, FileSystem, SeekFrom, DT_DIR, DT_LNK, DT_REG},
+};
+use crate::Errno;
+
+/// The device number reported for tmpfs files.
+const TMPFS_DEV: u64 = 0x7e;
+const BLOCK_SIZE: i64 = 0x1000;
+const O_TMPFILE: i32 = 0o20200000;
+const RENAME_NOREPLACE: u32 = 1;
+const RENAME_EXCHANGE: u32 = 2;
+
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+enum Content {
+    File(Vec<u8>),
+    Dir(BTreeMap<String, Arc<Inode>>),
+    /// A symbolic link and its target.
+    Symlink(String),
+}
+
+struct Inode {
+    ino: u64,
+    /// The file type and permission bits.
+    mode: u32,
+    /// The number of directory entries referring to a file that is not a
+    /// directory.
+    links: AtomicU32,
+    content: Mutex<Content>,
+}
+
+impl Inode {
+    fn new(mode: u32, content: Content) -> Arc<Inode> {
+        Arc::new(Inode {
+            ino: NEXT_INO.fetch_add(1, Ordering::Relaxed),
+            mode,
+            links: AtomicU32::new(1),
+            content: Mutex::new(content),
+        })
+    }
+
+    fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    fn dirent_type(&self) -> u8 {
+        match self.mode & S_IFMT {
+            S_IFDIR => DT_DIR,
+            S_IFLNK => DT_LNK,
+            _ => DT_REG,
+        }
+    }
+
+    fn stat(&self) -> Stat {
+        let links = self.links.load(Ordering::Relaxed);
+        let (size, nlink) = match &*self.content.lock() {
+            Content::File(data) => (data.len() as i64, links),
+            Content::Symlink(target) => (target.len() as i64, links),
+            Content::Dir(entries) => {
+                let subdirs = entries.values().filter(|inode| inode.is_dir()).count();
+                (0, 2 + subdirs as u32)
+            }
+        };
+        Stat {
+            dev: TMPFS_DEV,
+            ino: self.ino,
+            mode: self.mode,
+            nlink,
+            size,
+            blksize: BLOCK_SIZE,
+            blocks: (size + 511) / 512,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct TmpFs {
+    root: Arc<Inode>,
+    /// Held while directory entries are created, removed or moved, so that
+    /// a rename sees no other changes between its checks and its updates.
+    namespace: Mutex<()>,
+}
+
+impl TmpFs {
+    pub fn new() -> TmpFs {
+        TmpFs {
+            root: Inode::new(S_IFDIR | 0o1777, Content::Dir(BTreeMap::new())),
+            namespace: Mutex::new(()),
+        }
+    }
+
+    fn lookup(&self, path: &str) -> Result<Arc<Inode>, isize> {
+        let mut inode = Arc::clone(&self.root);
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let child = match &*inode.content.lock() {
+                Content::Dir(entries) => entries.get(name).cloned(),
+                _ => return Err(Errno::ENOTDIR.as_neg_isize()),
+            };
+            inode = child.ok_or(Errno::ENOENT.as_neg_isize())?;
+        }
+        Ok(inode)
+    }
+
+    /// Find the parent directory of `path` and the name of `path` in it.
+    fn lookup_parent<'p>(&self, path: &'p str) -> Result<(Arc<Inode>, &'p str), isize> {
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() {
+            // The mount point itself
+            return Err(Errno::EBUSY.as_neg_isize());
+        }
+        Ok((self.lookup(parent)?, name))
+    }
+
+    /// Create an entry in the directory `parent` with `make`, unless it exists.
+    fn create(
+        parent: &Inode,
+        name: &str,
+        make: impl FnOnce() -> Arc<Inode>,
+    ) -> Result<Arc<Inode>, isize> {
+        match &mut *parent.content.lock() {
+            Content::Dir(entries) if entries.contains_key(name) => {
+                Err(Errno::EEXIST.as_neg_isize())
+            }
+            Content::Dir(entries) => {
+                let inode = make();
+                entries.insert(name.into(), Arc::clone(&inode));
+                Ok(inode)
+            }
+            _ => Err(Errno::ENOTDIR.as_neg_isize()),
+        }
+    }
+
+    /// Run `f` on the entries of the directory `dir`.
+    fn with_entries<R>(
+        dir: &Inode,
+        f: impl FnOnce(&mut BTreeMap<String, Arc<Inode>>) -> R,
+    ) -> Result<R, isize> {
+        match &mut *dir.content.lock() {
+            Content::Dir(entries) => Ok(f(entries)),
+            _ => Err(Errno::ENOTDIR.as_neg_isize()),
+        }
+    }
+
+    /// Check whether the entry `inode` may replace `replaced` in a rename.
+    fn check_replace(inode: &Inode, replaced: &Inode) -> Result<(), isize> {
+        match (inode.is_dir(), &*replaced.content.lock()) {
+            (true, Content::Dir(entries)) if !entries.is_empty() => {
+                Err(Errno::ENOTEMPTY.as_neg_isize())
+            }
+            (true, Content::Dir(_)) => Ok(()),
+            (true, _) => Err(Errno::ENOTDIR.as_neg_isize()),
+            (false, Content::Dir(_)) => Err(Errno::EISDIR.as_neg_isize()),
+            (false, _) => Ok(()),
+        }
+    }
+}
+
+/// Check whether `path` is beneath the directory `dir`.
+fn is_beneath(path: &str, dir: &str) -> bool {
+    path.strip_prefix(dir)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+impl Default for TmpFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for TmpFs {
+    fn open(&self, path: &str, flags: i32, mode: u32) -> Result<Box<dyn File>, isize> {
+        if flags & O_TMPFILE == O_TMPFILE {
+            return Err(Errno::EOPNOTSUPP.as_neg_isize());
+        }
+        let writable = flags & O_ACCMODE != O_RDONLY;
+        let inode = match self.lookup(path) {
+            Ok(_) if flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL => {
+                return Err(Errno::EEXIST.as_neg_isize())
+            }
+            Ok(inode) => inode,
+            Err(errno) if errno == Errno::ENOENT.as_neg_isize() && flags & O_CREAT != 0 => {
+                let _namespace = self.namespace.lock();
+                let (parent, name) = self.lookup_parent(path)?;
+                Self::create(&parent, name, || {
+                    Inode::new(S_IFREG | (mode & 0o7777), Content::File(Vec::new()))
+                })?
+            }
+            Err(errno) => return Err(errno),
+        };
+
+        if inode.mode & S_IFMT == S_IFLNK {
+            // Opened with `O_NOFOLLOW`
+            return Err(Errno::ELOOP.as_neg_isize());
+        } else if inode.is_dir() {
+            if writable {
+                return Err(Errno::EISDIR.as_neg_isize());
+            }
+        } else if flags & O_DIRECTORY != 0 {
+            return Err(Errno::ENOTDIR.as_neg_isize());
+        } else if writable && flags & O_TRUNC != 0 {
+            if let Content::File(data) = &mut *inode.content.lock() {
+                data.clear();
+            }
+        }
+        Ok(Box::new(TmpFile {
+            inode,
+            pos: Mutex::new(0),
+            append: AtomicBool::new(flags & O_APPEND != 0),
+        }))
+    }
+
+    fn mkdir(&self, path: &str, mode: u32) -> Result<(), isize> {
+        let _namespace = self.namespace.lock();
+        let (parent, name) = match self.lookup_parent(path) {
+            Err(errno) if errno == Errno::EBUSY.as_neg_isize() => {
+                return Err(Errno::EEXIST.as_neg_isize())
+            }
+            result => result?,
+        };
+        Self::create(&parent, name, || {
+            Inode::new(S_IFDIR | (mode & 0o7777), Content::Dir(BTreeMap::new()))
+        })
+        .map(|_| ())
+    }
+
+    fn unlink(&self, path: &str, remove_dir: bool) -> Result<(), isize> {
+        let _namespace = self.namespace.lock();
+        let (parent, name) = self.lookup_parent(path)?;
+        let mut parent = parent.content.lock();
+        let entries = match &mut *parent {
+            Content::Dir(entries) => entries,
+            _ => return Err(Errno::ENOTDIR.as_neg_isize()),
+        };
+        let inode = entries.get(name).ok_or(Errno::ENOENT.as_neg_isize())?;
+        match (&*inode.content.lock(), remove_dir) {
+            (Content::Dir(children), true) if !children.is_empty() => {
+                return Err(Errno::ENOTEMPTY.as_neg_isize())
+            }
+            (Content::Dir(_), true) => (),
+            (Content::Dir(_), false) => return Err(Errno::EISDIR.as_neg_isize()),
+            (_, true) => return Err(Errno::ENOTDIR.as_neg_isize()),
+            (_, false) => {
+                inode.links.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+        // Open files keep their inode
+        entries.remove(name);
+        Ok(())
+    }
+
+    fn rename(&self, old: &str, new: &str, flags: u32) -> Result<(), isize> {
+        if flags & !(RENAME_NOREPLACE | RENAME_EXCHANGE) != 0
+            || flags == RENAME_NOREPLACE | RENAME_EXCHANGE
+        {
+            return Err(Errno::EINVAL.as_neg_isize());
+        }
+        let exchange = flags & RENAME_EXCHANGE != 0;
+        let _namespace = self.namespace.lock();
+        let (old_parent, old_name) = self.lookup_parent(old)?;
+        let (new_parent, new_name) = self.lookup_parent(new)?;
+        let inode = Self::with_entries(&old_parent, |entries| entries.get(old_name).cloned())?
+            .ok_or(Errno::ENOENT.as_neg_isize())?;
+        let replaced = Self::with_entries(&new_parent, |entries| entries.get(new_name).cloned())?;
+
+        // A directory cannot be moved beneath itself
+        if is_beneath(new, old) || (exchange && is_beneath(old, new)) {
+            return Err(Errno::EINVAL.as_neg_isize());
+        }
+        match &replaced {
+            // Renaming a file to itself, or to another link to it, does nothing
+            Some(replaced) if Arc::ptr_eq(replaced, &inode) => return Ok(()),
+            Some(_) if flags & RENAME_NOREPLACE != 0 => return Err(Errno::EEXIST.as_neg_isize()),
+            Some(replaced) if !exchange => Self::check_replace(&inode, replaced)?,
+            None if exchange => return Err(Errno::ENOENT.as_neg_isize()),
+            _ => (),
+        }
+
+        // The new name never goes missing in between
+        Self::with_entries(&new_parent, |entries| {
+            entries.insert(new_name.into(), Arc::clone(&inode))
+        })?;
+        match replaced {
+            Some(replaced) if exchange => {
+                Self::with_entries(&old_parent, |entries| {
+                    entries.insert(old_name.into(), replaced)
+                })?;
+            }
+            replaced => {
+                Self::with_entries(&old_parent, |entries| entries.remove(old_name))?;
+                if let Some(replaced) = replaced.filter(|replaced| !replaced.is_dir()) {
+                    replaced.links.fetch_sub(1, Ordering::Relaxed);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn link(&self, old: &str, new: &str) -> Result<(), isize> {
+        let _namespace = self.namespace.lock();
+        let inode = self.lookup(old)?;
+        if inode.is_dir() {
+            return Err(Errno::EPERM.as_neg_isize());
+        }
+        let (parent, name) = match self.lookup_parent(new) {
+            Err(errno) if errno == Errno::EBUSY.as_neg_isize() => {
+                return Err(Errno::EEXIST.as_neg_isize())
+            }
+            result => result?,
+        };
+        Self::create(&parent, name, || Arc::clone(&inode))?;
+        inode.links.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn symlink(&self, target: &str, path: &str) -> Result<(), isize> {
+        let _namespace = self.namespace.lock();
+        let (parent, name) = match self.lookup_parent(path) {
+            Err(errno) if errno == Errno::EBUSY.as_neg_isize() => {
+                return Err(Errno::EEXIST.as_neg_isize())
+            }
+            result => result?,
+        };
+        Self::create(&parent, name, || {
+            Inode::new(S_IFLNK | 0o777, Content::Symlink(target.into()))
+        })
+        .map(|_| ())
+    }
+
+    fn stat(&self, path: &str) -> Result<Stat, isize> {
+        Ok(self.lookup(path)?.stat())
+    }
+
+    fn readlink(&self, path: &str) -> Result<String, isize> {
+        match &*self.lookup(path)?.content.lock() {
+            Content::Symlink(target) => Ok(target.clone()),
+            _ => Err(Errno::EINVAL.as_neg_isize()),
+        }
+    }
+}
+
+struct TmpFile {
+    inode: Arc<Inode>,
+    /// The offset in a file, or the index of the next entry in a directory.
+    pos: Mutex<usize>,
+    append: AtomicBool,
+}
+
+impl TmpFile {
+    /// Write `buf` at `offset`, or at the end if the file is in append mode,
+    /// returning where the write ended.
+    fn write_data(&self, buf: &[u8], offset: usize) -> Result<usize, isize> {
+        let mut content = self.inode.content.lock();
+        let data = match &mut *content {
+            Content::File(data) => data,
+            _ => return Err(Errno::EISDIR.as_neg_isize()),
+        };
+        let start = if self.append.load(Ordering::Relaxed) {
+            data.len()
+        } else {
+            offset
+        };
+        let end = start
+            .checked_add(buf.len())
+            .ok_or(Errno::EFBIG.as_neg_isize())?;
+        if data.len() < end {
+            resize_enclave_file(data, end)?;
+        }
+        data[start..end].copy_from_slice(buf);
+        Ok(end)
+    }
+}
+
+impl File for TmpFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let mut pos = self.pos.lock();
+        let len = self.read_at(buf, *pos)?;
+        *pos += len;
+        Ok(len)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, isize> {
+        let mut pos = self.pos.lock();
+        *pos = self.write_data(buf, *pos)?;
+        Ok(buf.len())
+    }
+
+    fn read_at(&self, buf: &mut [u8], offset: usize) -> Result<usize, isize> {
+        let content = self.inode.content.lock();
+        let data = match &*content {
+            Content::File(data) => data,
+            _ => return Err(Errno::EISDIR.as_neg_isize()),
+        };
+        let start = offset.min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[0..len].copy_from_slice(&data[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, buf: &[u8], offset: usize) -> Result<usize, isize> {
+        // Like on Linux, files in append mode are appended to anyway
+        self.write_data(buf, offset)?;
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(self.inode.stat())
+    }
+
+    fn truncate(&self, len: usize) -> Result<(), isize> {
+        match &mut *self.inode.content.lock() {
+            Content::File(data) => resize_enclave_file(data, len),
+            _ => Err(Errno::EINVAL.as_neg_isize()),
+        }
+    }
+
+    fn set_append(&self, append: bool) {
+        self.append.store(append, Ordering::Relaxed);
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize, isize> {
+        let len = match &*self.inode.content.lock() {
+            Content::File(data) => data.len(),
+            // Only rewinding is meaningful for directories
+            Content::Dir(_) if pos == SeekFrom::Start(0) => 0,
+            _ => return Err(Errno::EINVAL.as_neg_isize()),
+        };
+        pos.apply(&mut self.pos.lock(), len)
+    }
+
+    fn getdents64(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        let content = self.inode.content.lock();
+        let entries = match &*content {
+            Content::Dir(entries) => entries,
+            _ => return Err(Errno::ENOTDIR.as_neg_isize()),
+        };
+        // The parent of a mount point is outside the file system, so `..`
+        // reports the directory itself
+        let dots = [
+            (self.inode.ino, DT_DIR, "."),
+            (self.inode.ino, DT_DIR, ".."),
+        ];
+        let children = entries
+            .iter()
+            .map(|(name, inode)| (inode.ino, inode.dirent_type(), name.as_str()));
+
+        vfs::fill_dirents(buf, &mut self.pos.lock(), dots.into_iter().chain(children))
+    }
+}
@@ -0,0 +1,745 @@
+//! The virtual file system layer.
+//!
+//! File systems implemented inside the enclave are mounted at path prefixes;
+//! operations on paths beneath a mount point are served by its file system,
+//! and all others go to the edge responder. Files opened inside the enclave
+//! are kept in a table of their own, so their reads and writes never cause an
+//! edge call. Their fd numbers are still reserved at the edge responder, which
+//! keeps handing out fd numbers for host files.
+
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, sync::Arc, vec, vec::Vec};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicI32, Ordering},
+};
+use hal::task::Pid;
+use spin::{Mutex, RwLock};
+
+use super::{
+    host::{
+        O_ACCMODE, O_APPEND, O_CLOEXEC, O_CREAT, O_EXCL, O_NOCTTY, O_NONBLOCK, O_RDONLY, O_TRUNC,
+        O_WRONLY,
+    },
+    poll::{POLLIN, POLLOUT, POLLRDNORM, POLLWRNORM},
+    stat::Stat,
+};
+use crate::{limits::MAXSYMLINKS, Errno};
+
+/// A file system served inside the enclave. Paths are relative to its mount
+/// point, normalized and without a leading `/`; the mount point itself is the
+/// empty path.
+pub trait FileSystem: Send + Sync {
+    fn open(&self, path: &str, flags: i32, mode: u32) -> Result<Box<dyn File>, isize>;
+
+    fn mkdir(&self, path: &str, mode: u32) -> Result<(), isize>;
+
+    /// Remove a file, or a directory if `remove_dir` is set.
+    fn unlink(&self, path: &str, remove_dir: bool) -> Result<(), isize>;
+
+    /// Rename `old` to `new` like `renameat2`. `flags` may contain
+    /// `RENAME_NOREPLACE` or `RENAME_EXCHANGE`.
+    fn rename(&self, old: &str, _new: &str, _flags: u32) -> Result<(), isize> {
+        self.stat(old)?;
+        Err(Errno::EACCES.as_neg_isize())
+    }
+
+    /// Create `new` as a hard link to `old`, without following a symbolic
+    /// link at `old`.
+    fn link(&self, old: &str, _new: &str) -> Result<(), isize> {
+        self.stat(old)?;
+        Err(Errno::EACCES.as_neg_isize())
+    }
+
+    /// Create a symbolic link at `path` that points to `target`.
+    fn symlink(&self, _target: &str, path: &str) -> Result<(), isize> {
+        match self.stat(path) {
+            Ok(_) => Err(Errno::EEXIST.as_neg_isize()),
+            Err(_) => Err(Errno::EACCES.as_neg_isize()),
+        }
+    }
+
+    /// Read the target of a symbolic link. Links are followed by
+    /// [`follow_links`], which resolves a relative target against the
+    /// directory of the link, so opening one fails with `ELOOP`.
+    fn readlink(&self, _path: &str) -> Result<String, isize> {
+        Err(Errno::EINVAL.as_neg_isize())
+    }
+
+    /// Change the size of a regular file like `truncate`.
+    fn truncate(&self, path: &str, len: usize) -> Result<(), isize> {
+        self.open(path, O_WRONLY, 0)?.truncate(len)
+    }
+
+    /// Get the metadata of a file without following a symbolic link.
+    fn stat(&self, path: &str) -> Result<Stat, isize> {
+        self.open(path, O_RDONLY, 0)?.stat()
+    }
+
+    /// Check whether a file may be accessed with `mode`, a combination of
+    /// `R_OK`, `W_OK` and `X_OK`.
+    fn access(&self, path: &str, mode: i32) -> Result<(), isize> {
+        check_access(&self.stat(path)?, mode)
+    }
+}
+
+/// Check `mode` of `access` against the permission bits of a file.
+pub fn check_access(stat: &Stat, mode: i32) -> Result<(), isize> {
+    // Tasks run as root, but a file nobody may write is not writable here
+    // either
+    let allowed = (stat.mode | stat.mode >> 3 | stat.mode >> 6) & 0o7;
+    if mode as u32 & !allowed != 0 {
+        return Err(Errno::EACCES.as_neg_isize());
+    }
+    Ok(())
+}
+
+/// An open file description. Files that keep a position update it themselves.
+pub trait File: Send + Sync {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, isize>;
+
+    fn write(&self, buf: &[u8]) -> Result<usize, isize>;
+
+    fn stat(&self) -> Result<Stat, isize>;
+
+    /// Read at `offset` without moving the file position (like `pread`).
+    fn read_at(&self, _buf: &mut [u8], _offset: usize) -> Result<usize, isize> {
+        Err(Errno::ESPIPE.as_neg_isize())
+    }
+
+    /// Write at `offset` without moving the file position (like `pwrite`).
+    fn write_at(&self, _buf: &[u8], _offset: usize) -> Result<usize, isize> {
+        Err(Errno::ESPIPE.as_neg_isize())
+    }
+
+    /// Change the size of the file like `ftruncate`.
+    fn truncate(&self, _len: usize) -> Result<(), isize> {
+        Err(Errno::EINVAL.as_neg_isize())
+    }
+
+    /// Write the file back to where it is stored, like `fsync`.
+    fn sync(&self) -> Result<(), isize> {
+        Ok(())
+    }
+
+    /// Switch append mode on or off, after `fcntl(F_SETFL)`.
+    fn set_append(&self, _append: bool) {}
+
+    /// Switch non-blocking mode on or off, after `fcntl(F_SETFL)`. Files that
+    /// never block ignore it.
+    fn set_nonblocking(&self, _nonblocking: bool) {}
+
+    /// Move the file position, returning the new one.
+    fn seek(&self, _pos: SeekFrom) -> Result<usize, isize> {
+        Err(Errno::ESPIPE.as_neg_isize())
+    }
+
+    /// Fill `buf` with `struct linux_dirent64` records (see [`DirentWriter`]).
+    fn getdents64(&self, _buf: &mut [u8]) -> Result<usize, isize> {
+        Err(Errno::ENOTDIR.as_neg_isize())
+    }
+
+    /// The `POLL*` events the file is ready for. Files that never block are
+    /// always ready to be read and written.
+    fn poll(&self) -> i16 {
+        POLLIN | POLLOUT | POLLRDNORM | POLLWRNORM
+    }
+
+    /// Called when the last fd referring to the file is closed.
+    fn release(&self) -> Result<(), isize> {
+        Ok(())
+    }
+
+    /// The file as [`Any`], for syscalls that only apply to one kind of file.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    Start(u64),
+    Current(i64),
+    End(i64),
+}
+
+impl SeekFrom {
+    /// Apply the seek to the position `pos` in a file of `len` bytes.
+    pub fn apply(self, pos: &mut usize, len: usize) -> Result<usize, isize> {
+        let (base, offset) = match self {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::Current(offset) => (*pos as i64, offset),
+            SeekFrom::End(offset) => (len as i64, offset),
+        };
+        let new_pos = base
+            .checked_add(offset)
+            .filter(|&new_pos| new_pos >= 0)
+            .ok_or(Errno::EINVAL.as_neg_isize())?;
+        *pos = new_pos as usize;
+        Ok(*pos)
+    }
+}
+
+struct OpenFile {
+    /// The absolute path the file was opened at.
+    path: String,
+    /// The open flags, whose status flags may be changed by `F_SETFL`.
+    flags: AtomicI32,
+    file: Box<dyn File>,
+}
+
+impl OpenFile {
+    fn flags(&self) -> i32 {
+        self.flags.load(Ordering::Relaxed)
+    }
+}
+
+type Mount = (String, Arc<dyn FileSystem>);
+
+static MOUNTS: RwLock<Vec<Mount>> = RwLock::new(Vec::new());
+static FILES: Mutex<BTreeMap<(Pid, i32), Arc<OpenFile>>> = Mutex::new(BTreeMap::new());
+
+const SEEK_SET: i32 = 0;
+const SEEK_CUR: i32 = 1;
+const SEEK_END: i32 = 2;
+
+/// The size of the kernel buffer that reads and writes are split into.
+const CHUNK_SIZE: usize = 0x10000;
+
+/// Serve the paths beneath the absolute path `prefix` from `fs`.
+pub fn mount(prefix: &str, fs: Arc<dyn FileSystem>) {
+    MOUNTS.write().push((super::normalize_path(prefix), fs));
+}
+
+/// A path served by a file system inside the enclave.
+struct Resolved {
+    fs: Arc<dyn FileSystem>,
+    /// The absolute path.
+    path: String,
+    /// Where the path relative to the mount point starts.
+    relative_start: usize,
+}
+
+impl Resolved {
+    fn relative(&self) -> &str {
+        &self.path[self.relative_start..]
+    }
+
+    fn same_fs(&self, other: &Resolved) -> bool {
+        // Only the data pointers are compared, as vtables may be duplicated
+        core::ptr::eq(
+            Arc::as_ptr(&self.fs) as *const u8,
+            Arc::as_ptr(&other.fs) as *const u8,
+        )
+    }
+}
+
+/// Resolve `path` against `dir_fd`, and find the file system serving it if
+/// there is one.
+fn resolve(dir_fd: i32, path: &str) -> Result<Option<Resolved>, isize> {
+    if MOUNTS.read().is_empty() {
+        return Ok(None);
+    }
+    Ok(lookup(super::absolute_path(dir_fd, path)?))
+}
+
+/// Find the file system serving the absolute, normalized path `path`.
+fn lookup(path: String) -> Option<Resolved> {
+    let mounts = MOUNTS.read();
+    let mount = mounts
+        .iter()
+        .filter_map(|(prefix, fs)| {
+            let rest = path.strip_prefix(prefix.as_str())?;
+            let relative = match rest.strip_prefix('/') {
+                Some(relative) => relative,
+                None if rest.is_empty() || prefix == "/" => rest,
+                None => return None,
+            };
+            Some((prefix.len(), fs, path.len() - relative.len()))
+        })
+        .max_by_key(|&(prefix_len, ..)| prefix_len);
+    mount.map(|(_, fs, relative_start)| Resolved {
+        fs: Arc::clone(fs),
+        path,
+        relative_start,
+    })
+}
+
+fn current_file(fd: i32) -> Option<Arc<OpenFile>> {
+    let pid = hal::task::current_pid();
+    FILES.lock().get(&(pid, fd)).cloned()
+}
+
+fn into_syscall_result(result: Result<usize, isize>) -> isize {
+    match result {
+        Ok(value) => value as isize,
+        Err(errno) => errno,
+    }
+}
+
+/// Make `fd` refer to `file`, opened at `path` with `flags`. The fd number
+/// must be taken at the edge responder, which [`reserve_fd`] does.
+///
+/// [`reserve_fd`]: super::host::reserve_fd
+pub fn install(fd: i32, path: String, flags: i32, file: Box<dyn File>) {
+    let pid = hal::task::current_pid();
+    let flags = AtomicI32::new(flags);
+    let file = Arc::new(OpenFile { path, flags, file });
+    FILES.lock().insert((pid, fd), file);
+}
+
+/// Find the absolute path an fd served inside the enclave was opened at.
+pub fn fd_path(fd: i32) -> Option<String> {
+    current_file(fd).map(|file| file.path.clone())
+}
+
+/// Open the absolute path `path` for the kernel itself, without an fd, if it
+/// is served inside the enclave.
+pub fn open(path: &str, flags: i32) -> Option<Result<Box<dyn File>, isize>> {
+    let resolved = lookup(super::normalize_path(path))?;
+    Some(resolved.fs.open(resolved.relative(), flags, 0))
+}
+
+/// Follow `path`, relative to `dir_fd`, through the symbolic links served
+/// inside the enclave, returning the absolute path it leads to.
+pub fn follow_links(dir_fd: i32, path: &str) -> Result<String, isize> {
+    if path.is_empty() {
+        return Err(Errno::ENOENT.as_neg_isize());
+    }
+    let mut path = super::absolute_path(dir_fd, path)?;
+    for _ in 0..MAXSYMLINKS {
+        let target = match lookup(path.clone()) {
+            Some(resolved) => resolved.fs.readlink(resolved.relative()),
+            None => return Ok(path),
+        };
+        path = match target {
+            Ok(target) if target.starts_with('/') => super::normalize_path(&target),
+            Ok(target) => {
+                let (dir, _) = path.rsplit_once('/').unwrap_or_default();
+                super::normalize_path(&format!("{}/{}", dir, target))
+            }
+            Err(_) => return Ok(path),
+        };
+    }
+    Err(Errno::ELOOP.as_neg_isize())
+}
+
+/// Open `path`, relative to `dir_fd`, if it is served inside the enclave.
+/// Symbolic links must have been followed with [`follow_links`].
+pub fn openat(dir_fd: i32, path: &str, flags: i32, mode: u32) -> Option<isize> {
+    let resolved = match resolve(dir_fd, path) {
+        Ok(resolved) => resolved?,
+        Err(errno) => return Some(errno),
+    };
+    let result = resolved
+        .fs
+        .open(resolved.relative(), flags, mode)
+        .and_then(|file| {
+            let fd = super::host::reserve_fd(flags & O_CLOEXEC != 0)?;
+            install(fd, resolved.path, flags, file);
+            Ok(fd as usize)
+        });
+    Some(into_syscall_result(result))
+}
+
+pub fn mkdirat(dir_fd: i32, path: &str, mode: u32) -> Option<isize> {
+    let resolved = match resolve(dir_fd, path) {
+        Ok(resolved) => resolved?,
+        Err(errno) => return Some(errno),
+    };
+    let result = resolved.fs.mkdir(resolved.relative(), mode);
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+pub fn unlinkat(dir_fd: i32, path: &str, remove_dir: bool) -> Option<isize> {
+    let resolved = match resolve(dir_fd, path) {
+        Ok(resolved) => resolved?,
+        Err(errno) => return Some(errno),
+    };
+    let result = resolved.fs.unlink(resolved.relative(), remove_dir);
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+/// Resolve the two paths of `renameat2` and `linkat`, served by a single
+/// file system inside the enclave, or both by the host.
+fn resolve_pair(
+    old_dir_fd: i32,
+    old_path: &str,
+    new_dir_fd: i32,
+    new_path: &str,
+) -> Option<Result<(Resolved, Resolved), isize>> {
+    let (old, new) = match (resolve(old_dir_fd, old_path), resolve(new_dir_fd, new_path)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(errno), _) | (_, Err(errno)) => return Some(Err(errno)),
+    };
+    match (old, new) {
+        (None, None) => None,
+        (Some(old), Some(new)) if old.same_fs(&new) => Some(Ok((old, new))),
+        // Like between mount points on Linux
+        _ => Some(Err(Errno::EXDEV.as_neg_isize())),
+    }
+}
+
+/// Rename a path like `renameat2`, if either path is served inside the
+/// enclave.
+pub fn renameat(
+    old_dir_fd: i32,
+    old_path: &str,
+    new_dir_fd: i32,
+    new_path: &str,
+    flags: u32,
+) -> Option<isize> {
+    let result = resolve_pair(old_dir_fd, old_path, new_dir_fd, new_path)?
+        .and_then(|(old, new)| old.fs.rename(old.relative(), new.relative(), flags));
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+/// Create a hard link like `linkat`, if either path is served inside the
+/// enclave. Symbolic links must have been followed with [`follow_links`] if
+/// asked to.
+pub fn linkat(old_dir_fd: i32, old_path: &str, new_dir_fd: i32, new_path: &str) -> Option<isize> {
+    let result = resolve_pair(old_dir_fd, old_path, new_dir_fd, new_path)?
+        .and_then(|(old, new)| old.fs.link(old.relative(), new.relative()));
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+pub fn symlinkat(target: &str, dir_fd: i32, path: &str) -> Option<isize> {
+    let resolved = match resolve(dir_fd, path) {
+        Ok(resolved) => resolved?,
+        Err(errno) => return Some(errno),
+    };
+    let result = resolved.fs.symlink(target, resolved.relative());
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+/// Change the size of the file at the absolute path `path`, if it is served
+/// inside the enclave. Symbolic links must have been followed.
+pub fn truncate(path: &str, len: usize) -> Option<isize> {
+    let resolved = lookup(super::normalize_path(path))?;
+    let result = resolved.fs.truncate(resolved.relative(), len);
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+pub fn ftruncate(fd: i32, len: usize) -> Option<isize> {
+    let file = current_file(fd)?;
+    if file.flags() & O_ACCMODE == O_RDONLY {
+        return Some(Errno::EINVAL.as_neg_isize());
+    }
+    Some(into_syscall_result(file.file.truncate(len).map(|()| 0)))
+}
+
+pub fn fsync(fd: i32) -> Option<isize> {
+    let file = current_file(fd)?;
+    Some(into_syscall_result(file.file.sync().map(|()| 0)))
+}
+
+/// Read from `fd` into the user buffers `iov` (pointer, length), at `offset`
+/// if given, if it is served inside the enclave.
+///
+/// # Safety
+///
+/// The buffers must be writable user memory.
+pub unsafe fn readv(fd: i32, iov: &[(usize, usize)], offset: Option<usize>) -> Option<isize> {
+    let file = current_file(fd)?;
+    if file.flags() & O_ACCMODE == O_WRONLY {
+        return Some(Errno::EBADF.as_neg_isize());
+    }
+    let len = iov.iter().map(|&(_, len)| len).max().unwrap_or(0);
+    let mut buf = vec![0; len.min(CHUNK_SIZE)];
+    let mut total = 0;
+    for &(ptr, len) in iov {
+        let mut done = 0;
+        while done < len {
+            let chunk = (len - done).min(CHUNK_SIZE);
+            let result = match offset {
+                Some(offset) => file.file.read_at(&mut buf[0..chunk], offset + total),
+                None => file.file.read(&mut buf[0..chunk]),
+            };
+            let bytes_read = match result {
+                Ok(bytes_read) => bytes_read,
+                // Report the bytes read before the error, like Linux does
+                Err(errno) if total == 0 => return Some(errno),
+                Err(_) => return Some(total as isize),
+            };
+            hal::mem::copy_to_user(&buf[0..bytes_read], (ptr + done) as *mut u8);
+            done += bytes_read;
+            total += bytes_read;
+            if bytes_read < chunk {
+                return Some(total as isize);
+            }
+        }
+    }
+    Some(total as isize)
+}
+
+/// Write the user buffers `iov` (pointer, length) to `fd`, at `offset` if
+/// given, if it is served inside the enclave.
+///
+/// # Safety
+///
+/// The buffers must be readable user memory.
+pub unsafe fn writev(fd: i32, iov: &[(usize, usize)], offset: Option<usize>) -> Option<isize> {
+    let file = current_file(fd)?;
+    if file.flags() & O_ACCMODE == O_RDONLY {
+        return Some(Errno::EBADF.as_neg_isize());
+    }
+    let len = iov.iter().map(|&(_, len)| len).max().unwrap_or(0);
+    let mut buf = vec![0; len.min(CHUNK_SIZE)];
+    let mut total = 0;
+    for &(ptr, len) in iov {
+        let mut done = 0;
+        while done < len {
+            let chunk = (len - done).min(CHUNK_SIZE);
+            hal::mem::copy_from_user(&mut buf[0..chunk], (ptr + done) as *const u8);
+            let result = match offset {
+                Some(offset) => file.file.write_at(&buf[0..chunk], offset + total),
+                None => file.file.write(&buf[0..chunk]),
+            };
+            let written = match result {
+                Ok(written) => written,
+                Err(errno) if total == 0 => return Some(errno),
+                Err(_) => return Some(total as isize),
+            };
+            done += written;
+            total += written;
+            if written < chunk {
+                return Some(total as isize);
+            }
+        }
+    }
+    Some(total as isize)
+}
+
+/// The file status flags that `F_SETFL` can change.
+const SETFL_FLAGS: i32 = O_APPEND | O_NONBLOCK;
+/// The open flags that `F_GETFL` leaves out, as they only matter when opening.
+const OPEN_ONLY_FLAGS: i32 = O_CREAT | O_EXCL | O_NOCTTY | O_TRUNC | O_CLOEXEC;
+
+/// Get the file status flags of `fd` like `F_GETFL`, if it is served inside
+/// the enclave.
+pub fn status_flags(fd: i32) -> Option<isize> {
+    let file = current_file(fd)?;
+    Some((file.flags() & !OPEN_ONLY_FLAGS) as isize)
+}
+
+/// Change the file status flags of `fd` like `F_SETFL`, if it is served
+/// inside the enclave.
+pub fn set_status_flags(fd: i32, flags: i32) -> Option<isize> {
+    let file = current_file(fd)?;
+    let old_flags = file.flags();
+    let new_flags = (old_flags & !SETFL_FLAGS) | (flags & SETFL_FLAGS);
+    file.flags.store(new_flags, Ordering::Relaxed);
+    if (old_flags ^ new_flags) & O_APPEND != 0 {
+        file.file.set_append(new_flags & O_APPEND != 0);
+    }
+    if (old_flags ^ new_flags) & O_NONBLOCK != 0 {
+        file.file.set_nonblocking(new_flags & O_NONBLOCK != 0);
+    }
+    Some(0)
+}
+
+/// Run an `ioctl` request on `fd`, if it is served inside the enclave. None
+/// of those files is a terminal, so every request fails with `ENOTTY`.
+pub fn ioctl(fd: i32) -> Option<isize> {
+    current_file(fd)?;
+    Some(Errno::ENOTTY.as_neg_isize())
+}
+
+/// Move the file position of `fd` like `lseek`, if it is served inside the
+/// enclave.
+pub fn lseek(fd: i32, offset: i64, whence: i32) -> Option<isize> {
+    let file = current_file(fd)?;
+    let pos = match whence {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as u64),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        // `SEEK_DATA` and `SEEK_HOLE` are not supported
+        _ => return Some(Errno::EINVAL.as_neg_isize()),
+    };
+    Some(into_syscall_result(file.file.seek(pos)))
+}
+
+/// Get the metadata of `path`, relative to `dir_fd`, if it is served inside
+/// the enclave. A symbolic link is not followed.
+pub fn stat_at(dir_fd: i32, path: &str) -> Option<Result<Stat, isize>> {
+    let resolved = match resolve(dir_fd, path) {
+        Ok(resolved) => resolved?,
+        Err(errno) => return Some(Err(errno)),
+    };
+    Some(resolved.fs.stat(resolved.relative()))
+}
+
+pub fn access_at(dir_fd: i32, path: &str, mode: i32) -> Option<isize> {
+    let resolved = match resolve(dir_fd, path) {
+        Ok(resolved) => resolved?,
+        Err(errno) => return Some(errno),
+    };
+    let result = resolved.fs.access(resolved.relative(), mode);
+    Some(into_syscall_result(result.map(|()| 0)))
+}
+
+pub fn readlink_at(dir_fd: i32, path: &str) -> Option<Result<String, isize>> {
+    let resolved = match resolve(dir_fd, path) {
+        Ok(resolved) => resolved?,
+        Err(errno) => return Some(Err(errno)),
+    };
+    Some(resolved.fs.readlink(resolved.relative()))
+}
+
+/// Get the `POLL*` events `fd` is ready for, if it is served inside the
+/// enclave.
+pub fn poll(fd: i32) -> Option<i16> {
+    current_file(fd).map(|file| file.file.poll())
+}
+
+/// Run `f` on the file `fd` refers to, if it is served inside the enclave and
+/// is a `T`.
+pub fn with_file<T: 'static, R>(fd: i32, f: impl FnOnce(&T) -> R) -> Option<R> {
+    let file = current_file(fd)?;
+    let file = file.file.as_any()?.downcast_ref::<T>()?;
+    Some(f(file))
+}
+
+/// Get the metadata of `fd`, if it is served inside the enclave.
+pub fn fstat(fd: i32) -> Option<Result<Stat, isize>> {
+    current_file(fd).map(|file| file.file.stat())
+}
+
+/// List the directory `fd` into the user buffer at `ptr`, if it is served
+/// inside the enclave.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` bytes of writable user memory.
+pub unsafe fn getdents64(fd: i32, ptr: usize, len: usize) -> Option<isize> {
+    let file = current_file(fd)?;
+    let mut buf = vec![0; len.min(CHUNK_SIZE)];
+    let result = file.file.getdents64(&mut buf);
+    if let Ok(filled) = result {
+        hal::mem::copy_to_user(&buf[0..filled], ptr as *mut u8);
+    }
+    Some(into_syscall_result(result))
+}
+
+fn release(file: Arc<OpenFile>) -> Result<(), isize> {
+    if Arc::strong_count(&file) == 1 {
+        file.file.release()
+    } else {
+        Ok(())
+    }
+}
+
+/// Forget `fd` as it is closed. Its fd number must be released at the edge
+/// responder as well.
+pub fn close(fd: i32) -> Result<(), isize> {
+    let pid = hal::task::current_pid();
+    let file = FILES.lock().remove(&(pid, fd));
+    file.map_or(Ok(()), release)
+}
+
+/// Let `dest_fd` refer to the same file as `src_fd`, after `src_fd` has been
+/// duplicated at the edge responder.
+pub fn dup(src_fd: i32, dest_fd: i32) -> Result<(), isize> {
+    // dup2 closes the destination first
+    close(dest_fd)?;
+    if let Some(file) = current_file(src_fd) {
+        let pid = hal::task::current_pid();
+        FILES.lock().insert((pid, dest_fd), file);
+    }
+    Ok(())
+}
+
+/// Share the open files of `parent` with its new child.
+pub fn fork(parent: Pid, child: Pid) {
+    let mut files = FILES.lock();
+    let inherited: Vec<_> = files
+        .range((parent, i32::MIN)..=(parent, i32::MAX))
+        .map(|(&(_, fd), file)| (fd, Arc::clone(file)))
+        .collect();
+    for (fd, file) in inherited {
+        files.insert((child, fd), file);
+    }
+}
+
+/// Close the open files of an exiting process.
+pub fn exit(pid: Pid) {
+    let closed: Vec<_> = {
+        let mut files = FILES.lock();
+        let fds: Vec<_> = files
+            .range((pid, i32::MIN)..=(pid, i32::MAX))
+            .map(|(&key, _)| key)
+            .collect();
+        fds.into_iter()
+            .filter_map(|key| files.remove(&key))
+            .collect()
+    };
+    for file in closed {
+        if let Err(errno) = release(file) {
+            log::error!("Failed to release a file of PID {}: {}", pid, errno);
+        }
+    }
+}
+
+/// Continue listing a directory from its `pos`-th entry, where `entries`
+/// yields the inode number, type and name of each entry.
+pub fn fill_dirents<'n>(
+    buf: &mut [u8],
+    pos: &mut usize,
+    entries: impl Iterator<Item = (u64, u8, &'n str)>,
+) -> Result<usize, isize> {
+    let mut writer = DirentWriter::new(buf);
+    for (ino, kind, name) in entries.skip(*pos) {
+        if !writer.push(ino, *pos as i64 + 1, kind, name) {
+            if writer.is_empty() {
+                return Err(Errno::EINVAL.as_neg_isize());
+            }
+            break;
+        }
+        *pos += 1;
+    }
+    Ok(writer.len())
+}
+
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+pub const DT_LNK: u8 = 10;
+
+/// Fills a buffer with `struct linux_dirent64` records.
+pub struct DirentWriter<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl<'b> DirentWriter<'b> {
+    pub fn new(buf: &'b mut [u8]) -> DirentWriter<'b> {
+        DirentWriter { buf, len: 0 }
+    }
+
+    /// Append an entry, returning `false` if it does not fit. `offset` is the
+    /// position of the next entry.
+    pub fn push(&mut self, ino: u64, offset: i64, kind: u8, name: &str) -> bool {
+        const HEADER_SIZE: usize = 19;
+        let reclen = (HEADER_SIZE + name.len() + 1).next_multiple_of(8);
+        if self.len + reclen > self.buf.len() {
+            return false;
+        }
+        let record = &mut self.buf[self.len..self.len + reclen];
+        record.fill(0);
+        record[0..8].copy_from_slice(&ino.to_ne_bytes());
+        record[8..16].copy_from_slice(&offset.to_ne_bytes());
+        record[16..18].copy_from_slice(&(reclen as u16).to_ne_bytes());
+        record[18] = kind;
+        record[HEADER_SIZE..HEADER_SIZE + name.len()].copy_from_slice(name.as_bytes());
+        self.len += reclen;
+        true
+    }
+
+    /// The bytes filled so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
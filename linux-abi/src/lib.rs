@@ -2,11 +2,14 @@
 
 extern crate alloc;
 
-#[cfg(feature = "multitasking")]
 pub mod elf;
 mod errno;
 pub mod exec;
+pub mod fs;
 pub mod limits;
+#[cfg(feature = "netstack")]
+pub mod net;
+pub mod notify;
 pub mod syscall;
 
 pub use errno::Errno;
@@ -1 +1,10 @@
 pub const PATH_MAX: usize = 4096;
+/// The number of symbolic links followed when resolving a path.
+pub const MAXSYMLINKS: usize = 40;
+/// The maximum number of buffers in a vectored I/O syscall.
+pub const IOV_MAX: usize = 1024;
+/// The maximum number of open fds of a process, as the edge responder allows.
+pub const NOFILE_MAX: usize = 4096;
+/// The maximum size of a file kept in enclave memory (tmpfs and protected
+/// files), which the guest must not be able to grow until the heap runs out.
+pub const ENCLAVE_FILE_MAX: usize = 64 << 20;
@@ -0,0 +1,517 @@
+//! IPv4 sockets served by a network stack inside the enclave.
+//!
+//! With this module, the connections of `AF_INET` sockets, and the data they
+//! carry, never leave the enclave: the host only moves the Ethernet frames of
+//! the stack between a dedicated edge channel and its network link, which is
+//! a TAP device or a peer in the same process. Sockets are files served
+//! inside the enclave, so they are read, written, polled and closed like
+//! other such files.
+
+use alloc::{boxed::Box, format, vec::Vec};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use edge_proto::{EdgeCallReq, EdgeCallResp, SockAddr};
+use netstack::{Endpoint, FrameLink, NetStack, Protocol, SocketId};
+use spin::Mutex;
+
+use crate::{
+    fs::{
+        host::{self, O_CLOEXEC, O_NONBLOCK, O_RDWR},
+        poll::{POLLERR, POLLHUP, POLLIN, POLLOUT, POLLRDNORM, POLLWRNORM},
+        stat::{Stat, S_IFSOCK},
+        vfs::{self, File},
+    },
+    Errno,
+};
+
+const AF_INET: usize = 2;
+const SOCK_STREAM: usize = 1;
+const SOCK_DGRAM: usize = 2;
+const SOCK_TYPE_MASK: usize = 0xf;
+const SOCK_NONBLOCK: usize = O_NONBLOCK as usize;
+const SOCK_CLOEXEC: usize = O_CLOEXEC as usize;
+const IPPROTO_TCP: i32 = 6;
+const IPPROTO_UDP: i32 = 17;
+
+const MSG_PEEK: i32 = 0x2;
+const MSG_DONTWAIT: i32 = 0x40;
+const MSG_NOSIGNAL: i32 = 0x4000;
+
+const SHUT_RD: i32 = 0;
+const SHUT_WR: i32 = 1;
+const SHUT_RDWR: i32 = 2;
+
+const SOL_SOCKET: i32 = 1;
+const SO_REUSEADDR: i32 = 2;
+const SO_TYPE: i32 = 3;
+const SO_ERROR: i32 = 4;
+const SO_ACCEPTCONN: i32 = 30;
+const SO_PROTOCOL: i32 = 38;
+const SO_DOMAIN: i32 = 39;
+const TCP_NODELAY: i32 = 1;
+
+type Stack = NetStack<EdgeLink>;
+
+/// The network stack, which is only there if the host has a network link.
+static STACK: Mutex<Option<Stack>> = Mutex::new(None);
+
+/// The host's network link, reached over the dedicated edge channel.
+struct EdgeLink;
+
+impl FrameLink for EdgeLink {
+    fn transmit(&mut self, frame: &[u8]) {
+        let result = hal::edge::with_dedicated_edge_caller(|caller| {
+            caller.data_zone_mut().unwrap()[0..frame.len()].copy_from_slice(frame);
+            caller
+                .write_header(&EdgeCallReq::NetTransmit {
+                    len: frame.len() as u64,
+                })
+                .unwrap();
+            caller.kick().unwrap();
+            caller.read_header().unwrap().into_result()
+        });
+        if let Err(err) = result {
+            log::warn!("Failed to transmit a frame: {}", err);
+        }
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Option<usize> {
+        hal::edge::with_dedicated_edge_caller(|caller| {
+            caller.write_header(&EdgeCallReq::NetReceive).unwrap();
+            caller.kick().unwrap();
+            // Once no frame is pending, the host watches the link, and
+            // reports it ready with `Notification::IoReady`
+            match caller.read_header().unwrap().into_result() {
+                Ok(EdgeCallResp::OkWithU64(len)) => {
+                    let len = (len as usize).min(buf.len());
+                    buf[0..len].copy_from_slice(&caller.read_data().unwrap()[0..len]);
+                    Some(len)
+                }
+                Ok(_) => None,
+                Err(err) => {
+                    log::warn!("Failed to receive a frame: {}", err);
+                    None
+                }
+            }
+        })
+    }
+}
+
+fn now_ms() -> u64 {
+    hal::time::monotonic_ns() / 1_000_000
+}
+
+/// Bring the network stack up on the host's network link, if it has one.
+pub fn init() {
+    let config = hal::edge::with_edge_caller(|caller| {
+        caller.write_header(&EdgeCallReq::NetConfig).unwrap();
+        caller.kick().unwrap();
+        caller.read_header().unwrap().into_result()
+    });
+    let config = match config {
+        Ok(resp) => resp.into_ok_with_net_config().unwrap(),
+        Err(err) => {
+            log::info!("No network link: {}", err);
+            return;
+        }
+    };
+    let config = netstack::Config {
+        mac: config.mac,
+        addr: config.addr,
+        prefix_len: config.prefix_len,
+        gateway: config.gateway,
+        // Initial sequence numbers and ports must not be guessable
+        seed: hal::rand::random_u64(),
+    };
+    log::info!("Network stack: {:?}", config);
+    *STACK.lock() = Some(NetStack::new(EdgeLink, &config, now_ms()));
+}
+
+/// Do something with the network stack, after letting it receive and send
+/// what it can.
+fn with_stack<R>(f: impl FnOnce(&mut Stack) -> R) -> R {
+    let mut stack = STACK.lock();
+    let stack = stack.as_mut().expect("the network stack is not up");
+    stack.poll(now_ms());
+    let result = f(stack);
+    // Send what `f` queued right away
+    stack.poll(now_ms());
+    result
+}
+
+/// Wake the current task when the network stack has to run its timers (such
+/// as retransmissions), before it waits for host files.
+pub fn wake_for_timers() {
+    let delay = match STACK.lock().as_mut() {
+        Some(stack) => stack.poll_delay(now_ms()),
+        None => return,
+    };
+    if let Some(delay) = delay {
+        hal::time::wake_at(hal::time::monotonic_ns() + delay.max(1) * 1_000_000);
+    }
+}
+
+/// Run `f` on the stack until it no longer fails with `WouldBlock`, waiting
+/// for frames in between, unless `nonblocking` is set.
+fn block_on<R>(
+    nonblocking: bool,
+    mut f: impl FnMut(&mut Stack) -> netstack::Result<R>,
+) -> Result<R, isize> {
+    loop {
+        match with_stack(&mut f) {
+            Err(netstack::Error::WouldBlock) if !nonblocking => {
+                wake_for_timers();
+                host::wait_for_io();
+            }
+            result => return result.map_err(errno_of),
+        }
+    }
+}
+
+fn errno_of(err: netstack::Error) -> isize {
+    use netstack::Error::*;
+
+    match err {
+        WouldBlock => Errno::EAGAIN,
+        InvalidState => Errno::EINVAL,
+        NotSupported => Errno::EOPNOTSUPP,
+        Unaddressable => Errno::EADDRNOTAVAIL,
+        AddrInUse => Errno::EADDRINUSE,
+        NotConnected => Errno::ENOTCONN,
+        IsConnected => Errno::EISCONN,
+        InProgress => Errno::EALREADY,
+        Shutdown => Errno::EPIPE,
+        DestinationRequired => Errno::EDESTADDRREQ,
+        MessageTooLong => Errno::EMSGSIZE,
+        ConnectionRefused => Errno::ECONNREFUSED,
+    }
+    .as_neg_isize()
+}
+
+fn endpoint_of(addr: &SockAddr) -> Result<Endpoint, isize> {
+    match *addr {
+        SockAddr::Inet { addr, port } => Ok(Endpoint { addr, port }),
+        _ => Err(Errno::EAFNOSUPPORT.as_neg_isize()),
+    }
+}
+
+fn sockaddr_of(endpoint: Endpoint) -> SockAddr {
+    SockAddr::Inet {
+        addr: endpoint.addr,
+        port: endpoint.port,
+    }
+}
+
+fn into_syscall_result(result: Result<(), isize>) -> isize {
+    match result {
+        Ok(()) => 0,
+        Err(errno) => errno,
+    }
+}
+
+/// A socket of the network stack, as an open file.
+struct NetSocket {
+    id: SocketId,
+    nonblocking: AtomicBool,
+}
+
+impl File for NetSocket {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, isize> {
+        self.socket().recv(buf, 0).map(|(received, _)| received)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, isize> {
+        self.socket().send(buf, 0, None)
+    }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(Stat {
+            mode: S_IFSOCK | 0o777,
+            nlink: 1,
+            blksize: 0x1000,
+            ..Default::default()
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+
+    fn poll(&self) -> i16 {
+        let readiness = with_stack(|stack| stack.readiness(self.id));
+        let mut events = 0;
+        if readiness.readable {
+            events |= POLLIN | POLLRDNORM;
+        }
+        if readiness.writable {
+            events |= POLLOUT | POLLWRNORM;
+        }
+        if readiness.hangup {
+            events |= POLLHUP;
+        }
+        if readiness.error {
+            events |= POLLERR;
+        }
+        events
+    }
+
+    fn release(&self) -> Result<(), isize> {
+        with_stack(|stack| stack.close(self.id));
+        Ok(())
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+impl NetSocket {
+    fn socket(&self) -> Socket {
+        Socket {
+            id: self.id,
+            nonblocking: self.nonblocking.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Make `id` a socket file at a new fd.
+fn install(id: SocketId, flags: usize) -> Result<i32, isize> {
+    let fd = host::reserve_fd(flags & SOCK_CLOEXEC != 0)?;
+    let nonblocking = flags & SOCK_NONBLOCK != 0;
+    let socket = NetSocket {
+        id,
+        nonblocking: AtomicBool::new(nonblocking),
+    };
+    let flags = O_RDWR | (flags & (SOCK_NONBLOCK | SOCK_CLOEXEC)) as i32;
+    vfs::install(fd, format!("socket:[{}]", fd), flags, Box::new(socket));
+    Ok(fd)
+}
+
+/// Create a socket like `socket`, if it is one of the network stack.
+pub fn socket(domain: usize, ty: usize, protocol: usize) -> Option<isize> {
+    if domain != AF_INET {
+        return None;
+    }
+    if STACK.lock().is_none() {
+        log::warn!("socket: There is no network link for IPv4 sockets");
+        return Some(Errno::EAFNOSUPPORT.as_neg_isize());
+    }
+    let protocol = match (ty & SOCK_TYPE_MASK, protocol as i32) {
+        (SOCK_STREAM, 0 | IPPROTO_TCP) => Protocol::Tcp,
+        (SOCK_DGRAM, 0 | IPPROTO_UDP) => Protocol::Udp,
+        (SOCK_STREAM | SOCK_DGRAM, _) => return Some(Errno::EPROTONOSUPPORT.as_neg_isize()),
+        _ => return Some(Errno::ESOCKTNOSUPPORT.as_neg_isize()),
+    };
+    if ty & !(SOCK_TYPE_MASK | SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+        return Some(Errno::EINVAL.as_neg_isize());
+    }
+    let id = with_stack(|stack| stack.socket(protocol));
+    Some(match install(id, ty) {
+        Ok(fd) => fd as isize,
+        Err(errno) => {
+            with_stack(|stack| stack.close(id));
+            errno
+        }
+    })
+}
+
+/// A socket of the network stack, found at an fd by [`lookup`].
+#[derive(Debug, Clone, Copy)]
+pub struct Socket {
+    id: SocketId,
+    nonblocking: bool,
+}
+
+/// Find the socket at `fd`, if it is one of the network stack.
+pub fn lookup(fd: i32) -> Option<Socket> {
+    vfs::with_file(fd, NetSocket::socket)
+}
+
+impl Socket {
+    pub fn bind(&self, addr: &SockAddr) -> isize {
+        let result = endpoint_of(addr)
+            .and_then(|local| with_stack(|stack| stack.bind(self.id, local)).map_err(errno_of));
+        into_syscall_result(result)
+    }
+
+    pub fn listen(&self, backlog: i32) -> isize {
+        let backlog = backlog.max(0) as usize;
+        into_syscall_result(with_stack(|stack| stack.listen(self.id, backlog)).map_err(errno_of))
+    }
+
+    /// Accept a connection like `accept4`, returning its fd and the address
+    /// of the peer.
+    pub fn accept(&self, flags: usize) -> Result<(i32, SockAddr), isize> {
+        if flags & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+            return Err(Errno::EINVAL.as_neg_isize());
+        }
+        let listening = with_stack(|stack| stack.is_listening(self.id));
+        if !listening {
+            return Err(Errno::EINVAL.as_neg_isize());
+        }
+        let (id, peer) = block_on(self.nonblocking, |stack| stack.accept(self.id))?;
+        match install(id, flags) {
+            Ok(fd) => Ok((fd, sockaddr_of(peer))),
+            Err(errno) => {
+                with_stack(|stack| stack.close(id));
+                Err(errno)
+            }
+        }
+    }
+
+    /// Connect like `connect`, which waits until the connection is
+    /// established unless the socket is non-blocking.
+    pub fn connect(&self, addr: &SockAddr) -> isize {
+        let remote = match endpoint_of(addr) {
+            Ok(remote) => remote,
+            Err(errno) => return errno,
+        };
+        let result = with_stack(|stack| {
+            stack.connect(self.id, remote)?;
+            Ok(stack.protocol(self.id))
+        });
+        match result {
+            Ok(Protocol::Udp) => 0,
+            Ok(Protocol::Tcp) if self.nonblocking => Errno::EINPROGRESS.as_neg_isize(),
+            Ok(Protocol::Tcp) => {
+                let established = block_on(false, |stack| {
+                    if let Some(err) = stack.take_error(self.id) {
+                        return Err(err);
+                    }
+                    match stack.readiness(self.id).writable {
+                        true => Ok(()),
+                        false => Err(netstack::Error::WouldBlock),
+                    }
+                });
+                into_syscall_result(established)
+            }
+            Err(err) => errno_of(err),
+        }
+    }
+
+    fn check_flags(flags: i32) -> Result<bool, isize> {
+        if flags & !(MSG_PEEK | MSG_DONTWAIT | MSG_NOSIGNAL) != 0 {
+            return Err(Errno::EOPNOTSUPP.as_neg_isize());
+        }
+        Ok(flags & MSG_DONTWAIT != 0)
+    }
+
+    /// How much of `len` bytes one `sendto` takes. A stream is sent at most a
+    /// send buffer at once, as on a non-blocking socket, and a datagram must
+    /// fit in the send buffer.
+    pub fn send_len(&self, len: usize) -> Result<usize, isize> {
+        match with_stack(|stack| stack.protocol(self.id)) {
+            Protocol::Tcp => Ok(len.min(netstack::TCP_BUFFER_SIZE)),
+            Protocol::Udp if len > netstack::UDP_BUFFER_SIZE => Err(Errno::EMSGSIZE.as_neg_isize()),
+            Protocol::Udp => Ok(len),
+        }
+    }
+
+    /// How much of `len` bytes one `recvfrom` may return: no more than a
+    /// receive buffer holds.
+    pub fn recv_len(&self, len: usize) -> usize {
+        len.min(netstack::TCP_BUFFER_SIZE.max(netstack::UDP_BUFFER_SIZE))
+    }
+
+    /// Send `data` like `sendto`. Like on Linux, a blocking stream socket
+    /// only returns once all of it is sent, or an error occurs.
+    pub fn send(&self, data: &[u8], flags: i32, addr: Option<&SockAddr>) -> Result<usize, isize> {
+        if flags & MSG_PEEK != 0 {
+            return Err(Errno::EOPNOTSUPP.as_neg_isize());
+        }
+        let nonblocking = Self::check_flags(flags)? || self.nonblocking;
+        let remote = addr.map(endpoint_of).transpose()?;
+        let mut sent = 0;
+        loop {
+            match block_on(nonblocking, |stack| {
+                stack.send(self.id, &data[sent..], remote)
+            }) {
+                Ok(len) => sent += len,
+                Err(_) if sent > 0 => return Ok(sent),
+                Err(errno) => return Err(errno),
+            }
+            if sent == data.len() || nonblocking {
+                return Ok(sent);
+            }
+        }
+    }
+
+    /// Receive into `buf` like `recvfrom`, returning the size received and
+    /// the sender of a datagram.
+    pub fn recv(&self, buf: &mut [u8], flags: i32) -> Result<(usize, SockAddr), isize> {
+        let nonblocking = Self::check_flags(flags)? || self.nonblocking;
+        let peek = flags & MSG_PEEK != 0;
+        let (received, sender) = block_on(nonblocking, |stack| stack.recv(self.id, buf, peek))?;
+        Ok((received, sender.map_or(SockAddr::Unnamed, sockaddr_of)))
+    }
+
+    pub fn shutdown(&self, how: i32) -> isize {
+        let (read, write) = match how {
+            SHUT_RD => (true, false),
+            SHUT_WR => (false, true),
+            SHUT_RDWR => (true, true),
+            _ => return Errno::EINVAL.as_neg_isize(),
+        };
+        into_syscall_result(
+            with_stack(|stack| stack.shutdown(self.id, read, write)).map_err(errno_of),
+        )
+    }
+
+    /// Read a socket option like `getsockopt`, returning its value.
+    pub fn getsockopt(&self, level: i32, name: i32) -> Result<Vec<u8>, isize> {
+        let value = with_stack(|stack| match (level, name) {
+            (SOL_SOCKET, SO_ERROR) => Ok(stack
+                .take_error(self.id)
+                .map_or(0, |err| -errno_of(err) as i32)),
+            (SOL_SOCKET, SO_TYPE) => Ok(match stack.protocol(self.id) {
+                Protocol::Tcp => SOCK_STREAM as i32,
+                Protocol::Udp => SOCK_DGRAM as i32,
+            }),
+            (SOL_SOCKET, SO_PROTOCOL) => Ok(match stack.protocol(self.id) {
+                Protocol::Tcp => IPPROTO_TCP,
+                Protocol::Udp => IPPROTO_UDP,
+            }),
+            (SOL_SOCKET, SO_DOMAIN) => Ok(AF_INET as i32),
+            (SOL_SOCKET, SO_ACCEPTCONN) => Ok(stack.is_listening(self.id) as i32),
+            // Ports are free to be bound again as soon as they are closed
+            (SOL_SOCKET, SO_REUSEADDR) => Ok(1),
+            (IPPROTO_TCP, TCP_NODELAY) => stack
+                .nodelay(self.id)
+                .map(i32::from)
+                .map_err(|_| Errno::ENOPROTOOPT.as_neg_isize()),
+            _ => Err(Errno::ENOPROTOOPT.as_neg_isize()),
+        })?;
+        Ok(value.to_ne_bytes().to_vec())
+    }
+
+    /// Set a socket option like `setsockopt`.
+    pub fn setsockopt(&self, level: i32, name: i32, value: &[u8]) -> isize {
+        let value = match value.get(0..4) {
+            Some(value) => i32::from_ne_bytes(value.try_into().unwrap()),
+            None => return Errno::EINVAL.as_neg_isize(),
+        };
+        let result = with_stack(|stack| match (level, name) {
+            (SOL_SOCKET, SO_REUSEADDR) => Ok(()),
+            (IPPROTO_TCP, TCP_NODELAY) => stack
+                .set_nodelay(self.id, value != 0)
+                .map_err(|_| Errno::ENOPROTOOPT.as_neg_isize()),
+            _ => {
+                log::warn!("setsockopt: Unsupported option {} at level {}", name, level);
+                Err(Errno::ENOPROTOOPT.as_neg_isize())
+            }
+        });
+        into_syscall_result(result)
+    }
+
+    pub fn local_name(&self) -> SockAddr {
+        sockaddr_of(with_stack(|stack| stack.local_endpoint(self.id)))
+    }
+
+    pub fn peer_name(&self) -> Result<SockAddr, isize> {
+        with_stack(|stack| stack.remote_endpoint(self.id))
+            .map(sockaddr_of)
+            .map_err(errno_of)
+    }
+}
@@ -0,0 +1,23 @@
+use edge_proto::notify::Notification;
+
+/// Handle a notification posted by the host. Kernels register this with
+/// `hal::edge::set_notification_handler`.
+pub fn handle_notification(notification: Notification) {
+    match notification {
+        Notification::Shutdown => {
+            log::info!("The host requested a shutdown");
+            hal::exit_enclave(0);
+        }
+        Notification::Signal(signo) => {
+            // Signals cannot be delivered to processes yet, so apply the
+            // default action of the terminating signals forwarded by the host.
+            log::info!("Terminating on signal {} from the host", signo);
+            hal::exit_enclave(128 + signo as usize);
+        }
+        Notification::IoReady => crate::fs::host::io_ready(),
+        Notification::TimerTick => {
+            // Nothing waits for these yet
+            log::trace!("Ignoring host notification {:?}", notification);
+        }
+    }
+}
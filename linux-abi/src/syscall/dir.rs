@@ -1,7 +1,7 @@
 use alloc::{borrow::ToOwned, vec};
 use edge_proto::EdgeCallReq;
 
-use crate::Errno;
+use crate::{fs::vfs, Errno};
 
 use super::SyscallHandler;
 
@@ -19,6 +19,11 @@ unsafe fn syscall_mkdirat(fd: usize, path: usize, mode: usize) -> isize {
     }
     let path = core::str::from_utf8(&path_buf[0..path_len]).expect("path is not valid UTF-8");
 
+    if let Some(result) = vfs::mkdirat(fd as i32, path, mode as u32) {
+        log::trace!("mkdirat(_, {:?}, {:#o}) = {}", path, mode, result);
+        return result;
+    }
+
     let result = hal::edge::with_edge_caller(|caller| {
         caller
             .write_header(&EdgeCallReq::SyscallMkdirAt {
@@ -84,6 +89,11 @@ unsafe fn syscall_chdir(path: usize) -> isize {
 }
 
 unsafe fn syscall_getdents64(fd: usize, buf: usize, size: usize) -> isize {
+    if let Some(result) = vfs::getdents64(fd as i32, buf, size) {
+        log::trace!("getdents64({}, _, {}) = {}", fd, size, result);
+        return result;
+    }
+
     let result = hal::edge::with_edge_caller(|caller| {
         caller
             .write_header(&EdgeCallReq::SyscallGetDents64 {
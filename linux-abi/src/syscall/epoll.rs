@@ -0,0 +1,256 @@
+use alloc::{boxed::Box, collections::BTreeMap, string::ToString, vec, vec::Vec};
+use core::any::Any;
+use edge_proto::PolledFd;
+use hal::time::{monotonic_ns, NSEC_PER_SEC};
+use spin::Mutex;
+
+use super::SyscallHandler;
+use crate::{
+    fs::{
+        host::{self, O_CLOEXEC, O_RDWR},
+        poll::{self, POLLNVAL},
+        stat::Stat,
+        vfs::{self, File},
+    },
+    Errno,
+};
+
+pub const SYSCALL_EPOLL_CREATE: SyscallHandler = SyscallHandler::Syscall1(syscall_epoll_create);
+pub const SYSCALL_EPOLL_CREATE1: SyscallHandler = SyscallHandler::Syscall1(syscall_epoll_create1);
+pub const SYSCALL_EPOLL_CTL: SyscallHandler = SyscallHandler::Syscall4(syscall_epoll_ctl);
+pub const SYSCALL_EPOLL_WAIT: SyscallHandler = SyscallHandler::Syscall4(syscall_epoll_wait);
+pub const SYSCALL_EPOLL_PWAIT: SyscallHandler = SyscallHandler::Syscall6(syscall_epoll_pwait);
+
+const EPOLL_CLOEXEC: i32 = O_CLOEXEC;
+
+const EPOLL_CTL_ADD: usize = 1;
+const EPOLL_CTL_DEL: usize = 2;
+const EPOLL_CTL_MOD: usize = 3;
+
+const EPOLLONESHOT: u32 = 1 << 30;
+/// The bits of an event mask that are flags rather than events: `EPOLLET`,
+/// `EPOLLONESHOT`, `EPOLLWAKEUP` and `EPOLLEXCLUSIVE`.
+const EPOLL_FLAGS: u32 = 0xf000_0000;
+
+/// The path reported for the fd of an epoll instance.
+const EPOLL_PATH: &str = "anon_inode:[eventpoll]";
+
+/// `struct epoll_event`, which is only packed on x86-64.
+#[cfg_attr(target_arch = "x86_64", repr(C, packed))]
+#[cfg_attr(not(target_arch = "x86_64"), repr(C))]
+#[derive(Clone, Copy)]
+struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+/// An epoll instance, whose interest list maps fds to the event masks they
+/// were added with.
+///
+/// Edge-triggered interest (`EPOLLET`) is reported like level-triggered
+/// interest. Callers are woken up more often, but never miss an event, as they
+/// read each file until it would block anyway.
+struct Epoll {
+    interest: Mutex<BTreeMap<i32, EpollEvent>>,
+}
+
+impl Epoll {
+    fn ctl(&self, op: usize, fd: i32, event: Option<EpollEvent>) -> isize {
+        let mut interest = self.interest.lock();
+        match (op, event) {
+            (EPOLL_CTL_ADD, Some(event)) => {
+                if interest.contains_key(&fd) {
+                    return Errno::EEXIST.as_neg_isize();
+                }
+                interest.insert(fd, event);
+            }
+            (EPOLL_CTL_MOD, Some(event)) => match interest.get_mut(&fd) {
+                Some(old_event) => *old_event = event,
+                None => return Errno::ENOENT.as_neg_isize(),
+            },
+            (EPOLL_CTL_DEL, _) => {
+                if interest.remove(&fd).is_none() {
+                    return Errno::ENOENT.as_neg_isize();
+                }
+            }
+            _ => return Errno::EINVAL.as_neg_isize(),
+        }
+        0
+    }
+
+    /// The fds to poll, leaving out the one-shot fds that have fired.
+    fn polled_fds(&self) -> Vec<PolledFd> {
+        self.interest
+            .lock()
+            .iter()
+            .filter(|(_, event)| event.events & !EPOLL_FLAGS != 0)
+            .map(|(&fd, event)| PolledFd {
+                fd,
+                // The events share the values of the `POLL*` flags
+                events: event.events as u16 as i16,
+            })
+            .collect()
+    }
+
+    /// Turn the events the fds were found ready for into at most `max`
+    /// `struct epoll_event`s. Closed fds are dropped from the interest list,
+    /// and one-shot fds that are reported are disabled.
+    fn collect(&self, fds: &[PolledFd], revents: &[i16], max: usize) -> Vec<EpollEvent> {
+        let mut interest = self.interest.lock();
+        let mut events = Vec::new();
+        for (polled, &revents) in fds.iter().zip(revents) {
+            if revents & POLLNVAL != 0 {
+                interest.remove(&polled.fd);
+                continue;
+            }
+            if revents == 0 || events.len() == max {
+                continue;
+            }
+            // The fd may have been removed while the task was waiting
+            if let Some(event) = interest.get_mut(&polled.fd) {
+                events.push(EpollEvent {
+                    events: revents as u16 as u32,
+                    data: event.data,
+                });
+                if event.events & EPOLLONESHOT != 0 {
+                    event.events &= EPOLL_FLAGS;
+                }
+            }
+        }
+        events
+    }
+}
+
+impl File for Epoll {
+    fn read(&self, _buf: &mut [u8]) -> Result<usize, isize> {
+        Err(Errno::EINVAL.as_neg_isize())
+    }
+
+    fn write(&self, _buf: &[u8]) -> Result<usize, isize> {
+        Err(Errno::EINVAL.as_neg_isize())
+    }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(Stat {
+            mode: 0o600,
+            nlink: 1,
+            blksize: 0x1000,
+            ..Default::default()
+        })
+    }
+
+    /// Waiting on an epoll instance through `poll` or another instance is not
+    /// supported, so it is never ready.
+    fn poll(&self) -> i16 {
+        0
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// The error for an `epfd` that is not an epoll instance.
+fn not_epoll(epfd: i32) -> isize {
+    match poll::is_open(epfd) {
+        Ok(true) => Errno::EINVAL.as_neg_isize(),
+        Ok(false) => Errno::EBADF.as_neg_isize(),
+        Err(errno) => errno,
+    }
+}
+
+unsafe fn syscall_epoll_create(size: usize) -> isize {
+    // The size is only a hint, but must be positive
+    if size as i32 <= 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    syscall_epoll_create1(0)
+}
+
+unsafe fn syscall_epoll_create1(flags: usize) -> isize {
+    let flags = flags as i32;
+    if flags & !EPOLL_CLOEXEC != 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let fd = match host::reserve_fd(flags & EPOLL_CLOEXEC != 0) {
+        Ok(fd) => fd,
+        Err(errno) => return errno,
+    };
+    let epoll = Epoll {
+        interest: Mutex::new(BTreeMap::new()),
+    };
+    vfs::install(fd, EPOLL_PATH.to_string(), O_RDWR | flags, Box::new(epoll));
+    fd as isize
+}
+
+unsafe fn syscall_epoll_ctl(epfd: usize, op: usize, fd: usize, event: usize) -> isize {
+    let (epfd, fd) = (epfd as i32, fd as i32);
+    if vfs::with_file(epfd, |_: &Epoll| ()).is_none() {
+        return not_epoll(epfd);
+    }
+    match poll::is_open(fd) {
+        Ok(true) => {}
+        Ok(false) => return Errno::EBADF.as_neg_isize(),
+        Err(errno) => return errno,
+    }
+    if fd == epfd || vfs::with_file(fd, |_: &Epoll| ()).is_some() {
+        log::warn!("epoll_ctl: Nested epoll instances are not supported");
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let event = (op != EPOLL_CTL_DEL).then(|| hal::mem::read_from_user(event as *const EpollEvent));
+    vfs::with_file(epfd, |epoll: &Epoll| epoll.ctl(op, fd, event))
+        .unwrap_or(Errno::EBADF.as_neg_isize())
+}
+
+unsafe fn syscall_epoll_wait(
+    epfd: usize,
+    events: usize,
+    maxevents: usize,
+    timeout: usize,
+) -> isize {
+    let (epfd, maxevents) = (epfd as i32, maxevents as i32);
+    if maxevents <= 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    // A negative timeout waits forever
+    let deadline = u64::try_from(timeout as i32)
+        .ok()
+        .map(|timeout_ms| monotonic_ns().saturating_add(timeout_ms * (NSEC_PER_SEC / 1000)));
+    loop {
+        let fds = match vfs::with_file(epfd, Epoll::polled_fds) {
+            Some(fds) => fds,
+            None => return not_epoll(epfd),
+        };
+        let mut revents = vec![0; fds.len()];
+        let ready = match poll::wait(&fds, &mut revents, deadline) {
+            Ok(ready) => ready,
+            Err(errno) => return errno,
+        };
+        let ready_events = match vfs::with_file(epfd, |epoll: &Epoll| {
+            epoll.collect(&fds, &revents, maxevents as usize)
+        }) {
+            Some(ready_events) => ready_events,
+            None => return Errno::EBADF.as_neg_isize(),
+        };
+        // Only closed fds may have been ready, then wait for the others again
+        if ready_events.is_empty() && ready > 0 {
+            continue;
+        }
+        for (i, event) in ready_events.iter().enumerate() {
+            hal::mem::write_to_user((events as *mut EpollEvent).add(i), *event);
+        }
+        return ready_events.len() as isize;
+    }
+}
+
+/// Signals are never delivered to tasks, so the signal mask is ignored.
+unsafe fn syscall_epoll_pwait(
+    epfd: usize,
+    events: usize,
+    maxevents: usize,
+    timeout: usize,
+    _sigmask: usize,
+    _sigsetsize: usize,
+) -> isize {
+    syscall_epoll_wait(epfd, events, maxevents, timeout)
+}
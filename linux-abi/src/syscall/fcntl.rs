@@ -0,0 +1,108 @@
+use edge_proto::EdgeCallReq;
+
+use super::SyscallHandler;
+use crate::{fs::vfs, Errno};
+
+pub const SYSCALL_FCNTL: SyscallHandler = SyscallHandler::Syscall3(syscall_fcntl);
+pub const SYSCALL_IOCTL: SyscallHandler = SyscallHandler::Syscall3(syscall_ioctl);
+
+const F_DUPFD: i32 = 0;
+const F_GETFD: i32 = 1;
+const F_SETFD: i32 = 2;
+const F_GETFL: i32 = 3;
+const F_SETFL: i32 = 4;
+const F_DUPFD_CLOEXEC: i32 = 1030;
+
+const TCGETS: u32 = 0x5401;
+const TIOCGWINSZ: u32 = 0x5413;
+const FIONREAD: u32 = 0x541b;
+
+/// The size of the structure filled by each `ioctl` request that is passed on
+/// to the host: `struct termios` (as the kernel defines it), `struct winsize`
+/// and `int`. Other requests fail with `ENOTTY`.
+fn ioctl_size(request: u32) -> Option<usize> {
+    match request {
+        TCGETS => Some(36),
+        TIOCGWINSZ => Some(8),
+        FIONREAD => Some(4),
+        _ => None,
+    }
+}
+
+unsafe fn syscall_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let (fd, cmd) = (fd as i32, cmd as i32);
+    // The file status flags of files served inside the enclave are kept here,
+    // while the fd table lives at the edge responder
+    let served = match cmd {
+        F_GETFL => vfs::status_flags(fd),
+        F_SETFL => vfs::set_status_flags(fd, arg as i32),
+        F_DUPFD | F_DUPFD_CLOEXEC | F_GETFD | F_SETFD => None,
+        _ => {
+            log::warn!("fcntl: Unsupported command: {}", cmd);
+            Some(Errno::EINVAL.as_neg_isize())
+        }
+    };
+    if let Some(result) = served {
+        return result;
+    }
+
+    let result = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallFcntl {
+                pid: hal::task::current_pid(),
+                fd,
+                cmd,
+                arg: arg as u64,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+
+        caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
+    });
+    if (cmd == F_DUPFD || cmd == F_DUPFD_CLOEXEC) && result >= 0 {
+        if let Err(errno) = vfs::dup(fd, result as i32) {
+            return errno;
+        }
+    }
+    log::trace!("fcntl({}, {}, {:#x}) = {}", fd, cmd, arg, result);
+    result
+}
+
+unsafe fn syscall_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    let (fd, request) = (fd as i32, request as u32);
+    if let Some(result) = vfs::ioctl(fd) {
+        return result;
+    }
+    let size = match ioctl_size(request) {
+        Some(size) => size,
+        None => {
+            log::warn!("ioctl: Unsupported request: {:#x}", request);
+            return Errno::ENOTTY.as_neg_isize();
+        }
+    };
+
+    let result = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallIoctl {
+                pid: hal::task::current_pid(),
+                fd,
+                request,
+                len: size as u64,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+
+        let result = caller.read_header().unwrap().into_syscall_resp().unwrap() as isize;
+        if result >= 0 {
+            let data = caller.read_data().unwrap();
+            // Only copy what the request is known to fill
+            match data.get(0..size) {
+                Some(data) => hal::mem::copy_to_user(data, arg as *mut u8),
+                None => return Errno::EIO.as_neg_isize(),
+            }
+        }
+        result
+    });
+    log::trace!("ioctl({}, {:#x}, {:#x}) = {}", fd, request, arg, result);
+    result
+}
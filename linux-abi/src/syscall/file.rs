@@ -1,55 +1,218 @@
-use alloc::{borrow::ToOwned, vec};
-use edge_proto::EdgeCallReq;
+use alloc::{borrow::ToOwned, vec, vec::Vec};
+use edge_proto::{DataRegion, EdgeCallReq, EdgeCallResp, MAX_DATA_REGIONS};
 use hal::edge::EDGE_BUFFER_SIZE;
 
 use super::SyscallHandler;
-use crate::{syscall_try, Errno};
+use crate::{
+    fs::{
+        host::{self, AT_FDCWD, O_CLOEXEC, O_NOFOLLOW},
+        protected, vfs,
+    },
+    limits::IOV_MAX,
+    Errno,
+};
 
 pub const SYSCALL_OPENAT: SyscallHandler = SyscallHandler::Syscall4(syscall_openat);
 pub const SYSCALL_READ: SyscallHandler = SyscallHandler::Syscall3(syscall_read);
 pub const SYSCALL_WRITE: SyscallHandler = SyscallHandler::Syscall3(syscall_write);
+pub const SYSCALL_PREAD64: SyscallHandler = SyscallHandler::Syscall4(syscall_pread64);
+pub const SYSCALL_PWRITE64: SyscallHandler = SyscallHandler::Syscall4(syscall_pwrite64);
+pub const SYSCALL_READV: SyscallHandler = SyscallHandler::Syscall3(syscall_readv);
+pub const SYSCALL_WRITEV: SyscallHandler = SyscallHandler::Syscall3(syscall_writev);
+pub const SYSCALL_PREADV: SyscallHandler = SyscallHandler::Syscall4(syscall_preadv);
+pub const SYSCALL_PWRITEV: SyscallHandler = SyscallHandler::Syscall4(syscall_pwritev);
+pub const SYSCALL_LSEEK: SyscallHandler = SyscallHandler::Syscall3(syscall_lseek);
+pub const SYSCALL_TRUNCATE: SyscallHandler = SyscallHandler::Syscall2(syscall_truncate);
+pub const SYSCALL_FTRUNCATE: SyscallHandler = SyscallHandler::Syscall2(syscall_ftruncate);
+pub const SYSCALL_FSYNC: SyscallHandler = SyscallHandler::Syscall1(syscall_fsync);
+pub const SYSCALL_FDATASYNC: SyscallHandler = SyscallHandler::Syscall1(syscall_fdatasync);
 pub const SYSCALL_CLOSE: SyscallHandler = SyscallHandler::Syscall1(syscall_close);
 pub const SYSCALL_DUP: SyscallHandler = SyscallHandler::Syscall1(syscall_dup);
 pub const SYSCALL_DUP3: SyscallHandler = SyscallHandler::Syscall3(syscall_dup3);
-pub const SYSCALL_FSTAT: SyscallHandler = SyscallHandler::Syscall2(syscall_fstat);
 pub const SYSCALL_UNLINKAT: SyscallHandler = SyscallHandler::Syscall3(syscall_unlinkat);
 
-unsafe fn edge_read(fd: usize, buf: &mut [u8]) -> isize {
+/// Read from `fd` into the user buffers `iov` (pointer, length) with a single
+/// edge call, at `offset` if given. The host fills the data zone in place, so
+/// the buffers must fit into it as a whole. Returns `None` if the read would
+/// block.
+unsafe fn edge_readv(fd: i32, iov: &[(usize, usize)], offset: Option<i64>) -> Option<isize> {
     hal::edge::with_edge_caller(|caller| {
-        caller
-            .write_header(&EdgeCallReq::SyscallRead {
-                pid: hal::task::current_pid(),
-                fd: fd as i32,
-                len: buf.len() as u64,
-            })
-            .unwrap();
+        let pid = hal::task::current_pid();
+        let regions = DataRegion::packed(iov.iter().map(|&(_, len)| len));
+        let req = match offset {
+            Some(offset) => EdgeCallReq::SyscallPreadV {
+                pid,
+                fd,
+                regions,
+                offset,
+            },
+            None => EdgeCallReq::SyscallReadV { pid, fd, regions },
+        };
+        caller.write_header(&req).unwrap();
         caller.kick().unwrap();
 
-        let result = caller.read_header().unwrap().into_syscall_resp().unwrap() as isize;
-        if result >= 0 {
-            let data = caller.read_data().unwrap();
-            buf[0..result as usize].copy_from_slice(&data[0..result as usize]);
+        let result = match caller.read_header().unwrap() {
+            EdgeCallResp::WouldBlock => return None,
+            resp => resp.into_syscall_resp().unwrap() as isize,
+        };
+        if result > 0 {
+            // The regions are packed, so the bytes read are contiguous
+            let data = &caller.read_data().unwrap()[0..result as usize];
+            let mut offset = 0;
+            for &(ptr, len) in iov {
+                let len = len.min(data.len() - offset);
+                hal::mem::copy_to_user(&data[offset..offset + len], ptr as *mut u8);
+                offset += len;
+            }
         }
-        result
+        Some(result)
     })
 }
 
-unsafe fn edge_write(fd: usize, buf: &[u8]) -> isize {
+/// Write the user buffers `iov` (pointer, length) to `fd` with a single edge
+/// call, at `offset` if given. The buffers are copied straight into the data
+/// zone, so they must fit into it as a whole.
+unsafe fn edge_writev(fd: i32, iov: &[(usize, usize)], offset: Option<i64>) -> isize {
     hal::edge::with_edge_caller(|caller| {
-        caller
-            .write_header(&EdgeCallReq::SyscallWrite {
-                pid: hal::task::current_pid(),
-                fd: fd as i32,
-                len: buf.len() as u64,
-            })
-            .unwrap();
-        caller.write_data(buf).unwrap();
+        let zone = caller.data_zone_mut().unwrap();
+        let mut zone_offset = 0;
+        for &(ptr, len) in iov {
+            hal::mem::copy_from_user(&mut zone[zone_offset..zone_offset + len], ptr as *const u8);
+            zone_offset += len;
+        }
+
+        let pid = hal::task::current_pid();
+        let regions = DataRegion::packed(iov.iter().map(|&(_, len)| len));
+        let req = match offset {
+            Some(offset) => EdgeCallReq::SyscallPwriteV {
+                pid,
+                fd,
+                regions,
+                offset,
+            },
+            None => EdgeCallReq::SyscallWriteV { pid, fd, regions },
+        };
+        caller.write_header(&req).unwrap();
         caller.kick().unwrap();
 
         caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
     })
 }
 
+/// Split the user buffers `iov` into batches that each fit into the data
+/// zone, splitting buffers that do not fit as a whole. There is always at
+/// least one batch, so that even an empty transfer reaches the host.
+fn edge_batches(iov: &[(usize, usize)]) -> Vec<Vec<(usize, usize)>> {
+    let mut batches = vec![Vec::new()];
+    let mut batch_len = 0;
+    for &(mut ptr, mut len) in iov {
+        while len > 0 {
+            let batch = batches.last_mut().unwrap();
+            if batch_len == EDGE_BUFFER_SIZE || batch.len() == MAX_DATA_REGIONS {
+                batches.push(Vec::new());
+                batch_len = 0;
+                continue;
+            }
+            let chunk = len.min(EDGE_BUFFER_SIZE - batch_len);
+            batch.push((ptr, chunk));
+            batch_len += chunk;
+            ptr += chunk;
+            len -= chunk;
+        }
+    }
+    batches
+}
+
+/// Read from the host file `fd` into the user buffers `iov`, at `offset` if
+/// given, with as few edge calls as the data zone allows.
+unsafe fn host_readv(fd: i32, iov: &[(usize, usize)], offset: Option<i64>) -> isize {
+    let mut total = 0;
+    for batch in edge_batches(iov) {
+        let len: usize = batch.iter().map(|&(_, len)| len).sum();
+        let result = loop {
+            match edge_readv(fd, &batch, offset.map(|offset| offset + total as i64)) {
+                Some(result) => break result,
+                // Wait for the file only while nothing has been read yet
+                None if total == 0 => host::wait_for_io(),
+                None => return total as isize,
+            }
+        };
+        if result < 0 {
+            // Report the bytes read before the error, like Linux does
+            return if total > 0 { total as isize } else { result };
+        }
+        total += result as usize;
+        if (result as usize) < len {
+            break;
+        }
+    }
+    total as isize
+}
+
+/// Write the user buffers `iov` to the host file `fd`, at `offset` if given,
+/// with as few edge calls as the data zone allows.
+unsafe fn host_writev(fd: i32, iov: &[(usize, usize)], offset: Option<i64>) -> isize {
+    let mut total = 0;
+    for batch in edge_batches(iov) {
+        let len: usize = batch.iter().map(|&(_, len)| len).sum();
+        let result = edge_writev(fd, &batch, offset.map(|offset| offset + total as i64));
+        if result < 0 {
+            return if total > 0 { total as isize } else { result };
+        }
+        total += result as usize;
+        if (result as usize) < len {
+            break;
+        }
+    }
+    total as isize
+}
+
+/// Copy the `struct iovec` array at `ptr` from userspace, as (pointer,
+/// length).
+unsafe fn iovecs_from_user(ptr: usize, count: usize) -> Result<Vec<(usize, usize)>, isize> {
+    if count > IOV_MAX {
+        return Err(Errno::EINVAL.as_neg_isize());
+    }
+    let word = core::mem::size_of::<usize>();
+    let mut raw = vec![0u8; count * 2 * word];
+    hal::mem::copy_from_user(&mut raw, ptr as *const u8);
+    let iov: Vec<_> = raw
+        .chunks_exact(2 * word)
+        .map(|iovec| {
+            let field = |index: usize| {
+                usize::from_ne_bytes(iovec[index * word..(index + 1) * word].try_into().unwrap())
+            };
+            (field(0), field(1))
+        })
+        .collect();
+    // The total length must fit into the result
+    iov.iter()
+        .try_fold(0isize, |total, &(_, len)| total.checked_add_unsigned(len))
+        .ok_or(Errno::EINVAL.as_neg_isize())?;
+    Ok(iov)
+}
+
+/// Read or write `iov` at `offset` if given, in the enclave or on the host.
+unsafe fn transfer(fd: usize, iov: &[(usize, usize)], offset: Option<i64>, write: bool) -> isize {
+    let fd = fd as i32;
+    if offset.is_some_and(|offset| offset < 0) {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let vfs_offset = offset.map(|offset| offset as usize);
+    let result = if write {
+        vfs::writev(fd, iov, vfs_offset)
+    } else {
+        vfs::readv(fd, iov, vfs_offset)
+    };
+    result.unwrap_or_else(|| {
+        if write {
+            host_writev(fd, iov, offset)
+        } else {
+            host_readv(fd, iov, offset)
+        }
+    })
+}
+
 unsafe fn syscall_openat(dir_fd: usize, path: usize, flags: usize, mode: usize) -> isize {
     let mut path_buf = vec![0; crate::limits::PATH_MAX];
     let path_len = hal::mem::strncpy_from_user(&mut path_buf, path as *const u8);
@@ -59,12 +222,50 @@ unsafe fn syscall_openat(dir_fd: usize, path: usize, flags: usize, mode: usize)
     }
     let path = core::str::from_utf8(&path_buf[0..path_len]).expect("path is not valid UTF-8");
 
+    // Follow the symbolic links served inside the enclave
+    let (dir_fd, path) = if flags as i32 & O_NOFOLLOW != 0 {
+        (dir_fd as i32, path.to_owned())
+    } else {
+        match vfs::follow_links(dir_fd as i32, path) {
+            Ok(target) => (AT_FDCWD, target),
+            Err(errno) => return errno,
+        }
+    };
+    if let Some(result) = vfs::openat(dir_fd, &path, flags as i32, mode as u32) {
+        log::trace!(
+            "openat({}, {:?}, {}, {:#o}) = {}",
+            dir_fd,
+            path,
+            flags,
+            mode,
+            result
+        );
+        return result;
+    }
+    match protected::protected_path(dir_fd, &path) {
+        Ok(Some(protected_path)) => {
+            let result = match protected::open(&protected_path, flags as i32, mode as u32) {
+                Ok(fd) => fd as isize,
+                Err(errno) => errno,
+            };
+            log::trace!(
+                "openat({}, {:?}, ..) = {} (protected)",
+                dir_fd,
+                path,
+                result
+            );
+            return result;
+        }
+        Ok(None) => (),
+        Err(errno) => return errno,
+    }
+
     let result = hal::edge::with_edge_caller(|caller| {
         caller
             .write_header(&EdgeCallReq::SyscallOpenAt {
                 pid: hal::task::current_pid(),
-                dir_fd: dir_fd as i32,
-                path: path.to_owned(),
+                dir_fd,
+                path: path.clone(),
                 flags: flags as i32,
                 mode: mode as u32,
             })
@@ -84,45 +285,135 @@ unsafe fn syscall_openat(dir_fd: usize, path: usize, flags: usize, mode: usize)
     result
 }
 
-unsafe fn syscall_read(fd: usize, ptr: usize, mut len: usize) -> isize {
-    let mut total_bytes_read = 0;
+unsafe fn syscall_read(fd: usize, ptr: usize, len: usize) -> isize {
+    transfer(fd, &[(ptr, len)], None, false)
+}
+
+unsafe fn syscall_write(fd: usize, ptr: usize, len: usize) -> isize {
+    transfer(fd, &[(ptr, len)], None, true)
+}
+
+unsafe fn syscall_pread64(fd: usize, ptr: usize, len: usize, offset: usize) -> isize {
+    transfer(fd, &[(ptr, len)], Some(offset as i64), false)
+}
 
-    let mut ptr = ptr as *mut u8;
-    let mut buf = vec![0; EDGE_BUFFER_SIZE];
-    while len > EDGE_BUFFER_SIZE {
-        let bytes_read = syscall_try!(edge_read(fd, &mut buf)) as usize;
-        total_bytes_read += bytes_read;
-        hal::mem::copy_to_user(&buf[0..bytes_read], ptr as *mut u8);
-        ptr = ptr.add(bytes_read);
-        len -= bytes_read;
+unsafe fn syscall_pwrite64(fd: usize, ptr: usize, len: usize, offset: usize) -> isize {
+    transfer(fd, &[(ptr, len)], Some(offset as i64), true)
+}
+
+unsafe fn syscall_readv(fd: usize, iov: usize, count: usize) -> isize {
+    match iovecs_from_user(iov, count) {
+        Ok(iov) => transfer(fd, &iov, None, false),
+        Err(errno) => errno,
+    }
+}
+
+unsafe fn syscall_writev(fd: usize, iov: usize, count: usize) -> isize {
+    match iovecs_from_user(iov, count) {
+        Ok(iov) => transfer(fd, &iov, None, true),
+        Err(errno) => errno,
     }
+}
 
-    let bytes_read = syscall_try!(edge_read(fd, &mut buf[0..len])) as usize;
-    total_bytes_read += bytes_read;
-    hal::mem::copy_to_user(&buf[0..bytes_read], ptr as *mut u8);
+// The offset is split into two registers only on 32-bit architectures
+unsafe fn syscall_preadv(fd: usize, iov: usize, count: usize, offset: usize) -> isize {
+    match iovecs_from_user(iov, count) {
+        Ok(iov) => transfer(fd, &iov, Some(offset as i64), false),
+        Err(errno) => errno,
+    }
+}
 
-    total_bytes_read as isize
+unsafe fn syscall_pwritev(fd: usize, iov: usize, count: usize, offset: usize) -> isize {
+    match iovecs_from_user(iov, count) {
+        Ok(iov) => transfer(fd, &iov, Some(offset as i64), true),
+        Err(errno) => errno,
+    }
 }
 
-unsafe fn syscall_write(fd: usize, ptr: usize, mut len: usize) -> isize {
-    let mut bytes_written = 0;
+unsafe fn syscall_lseek(fd: usize, offset: usize, whence: usize) -> isize {
+    let (fd, offset, whence) = (fd as i32, offset as i64, whence as i32);
+    let result = vfs::lseek(fd, offset, whence).unwrap_or_else(|| {
+        hal::edge::with_edge_caller(|caller| {
+            caller
+                .write_header(&EdgeCallReq::SyscallLseek {
+                    pid: hal::task::current_pid(),
+                    fd,
+                    offset,
+                    whence,
+                })
+                .unwrap();
+            caller.kick().unwrap();
 
-    let mut ptr = ptr as *const u8;
-    let mut buf = vec![0; EDGE_BUFFER_SIZE];
-    while len > EDGE_BUFFER_SIZE {
-        hal::mem::copy_from_user(&mut buf, ptr as *const u8);
-        bytes_written += syscall_try!(edge_write(fd, &buf));
-        ptr = ptr.add(EDGE_BUFFER_SIZE);
-        len -= EDGE_BUFFER_SIZE;
+            caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
+        })
+    });
+    log::trace!("lseek({}, {}, {}) = {}", fd, offset, whence, result);
+    result
+}
+
+fn truncate(path: &str, len: usize) -> isize {
+    let path = match vfs::follow_links(AT_FDCWD, path) {
+        Ok(target) => target,
+        Err(errno) => return errno,
+    };
+    if let Some(result) = vfs::truncate(&path, len) {
+        return result;
     }
+    let result = match protected::protected_path(AT_FDCWD, &path) {
+        Ok(Some(protected_path)) => protected::truncate(&protected_path, len),
+        Ok(None) => host::truncate(&path, len as i64),
+        Err(errno) => Err(errno),
+    };
+    result.map_or_else(|errno| errno, |()| 0)
+}
+
+unsafe fn syscall_truncate(path: usize, len: usize) -> isize {
+    let path = match super::path_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let result = if (len as isize) < 0 {
+        Errno::EINVAL.as_neg_isize()
+    } else {
+        truncate(&path, len)
+    };
+    log::trace!("truncate({:?}, {}) = {}", path, len as isize, result);
+    result
+}
 
-    hal::mem::copy_from_user(&mut buf[0..len], ptr as *const u8);
-    bytes_written += syscall_try!(edge_write(fd, &buf[0..len]));
+unsafe fn syscall_ftruncate(fd: usize, len: usize) -> isize {
+    let fd = fd as i32;
+    let result = if (len as isize) < 0 {
+        Errno::EINVAL.as_neg_isize()
+    } else {
+        vfs::ftruncate(fd, len)
+            .unwrap_or_else(|| host::ftruncate(fd, len as i64).map_or_else(|errno| errno, |()| 0))
+    };
+    log::trace!("ftruncate({}, {}) = {}", fd, len as isize, result);
+    result
+}
+
+fn fsync(fd: i32, data_only: bool) -> isize {
+    // Files served inside the enclave have no metadata to keep apart
+    vfs::fsync(fd).unwrap_or_else(|| host::fsync(fd, data_only).map_or_else(|errno| errno, |()| 0))
+}
+
+unsafe fn syscall_fsync(fd: usize) -> isize {
+    let result = fsync(fd as i32, false);
+    log::trace!("fsync({}) = {}", fd, result);
+    result
+}
 
-    bytes_written
+unsafe fn syscall_fdatasync(fd: usize) -> isize {
+    let result = fsync(fd as i32, true);
+    log::trace!("fdatasync({}) = {}", fd, result);
+    result
 }
 
 unsafe fn syscall_close(fd: usize) -> isize {
+    // Files served inside the enclave still hold an fd number at the host
+    let released = vfs::close(fd as i32);
+
     let result = hal::edge::with_edge_caller(|caller| {
         caller
             .write_header(&EdgeCallReq::SyscallClose {
@@ -134,6 +425,10 @@ unsafe fn syscall_close(fd: usize) -> isize {
 
         caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
     });
+    let result = match released {
+        Err(errno) if result == 0 => errno,
+        _ => result,
+    };
     log::trace!("close({}) = {}", fd, result);
     result
 }
@@ -145,12 +440,18 @@ unsafe fn syscall_dup(fd: usize) -> isize {
                 pid: hal::task::current_pid(),
                 src_fd: fd as i32,
                 dest_fd: None,
+                cloexec: false,
             })
             .unwrap();
         caller.kick().unwrap();
 
         caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
     });
+    if result >= 0 {
+        if let Err(errno) = vfs::dup(fd as i32, result as i32) {
+            return errno;
+        }
+    }
     log::trace!("dup({}) = {}", fd, result);
     result
 }
@@ -160,7 +461,7 @@ unsafe fn syscall_dup3(src_fd: usize, dest_fd: usize, flags: usize) -> isize {
         log::error!("dup3: src_fd and dest_fd are the same");
         return Errno::EINVAL.as_neg_isize();
     }
-    if flags != 0 {
+    if flags as i32 & !O_CLOEXEC != 0 {
         log::error!("dup3: Unsupported flags: {}", flags);
         return Errno::EINVAL.as_neg_isize();
     }
@@ -171,38 +472,19 @@ unsafe fn syscall_dup3(src_fd: usize, dest_fd: usize, flags: usize) -> isize {
                 pid: hal::task::current_pid(),
                 src_fd: src_fd as i32,
                 dest_fd: Some(dest_fd as i32),
+                cloexec: flags as i32 & O_CLOEXEC != 0,
             })
             .unwrap();
         caller.kick().unwrap();
 
         caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
     });
-    log::trace!("dup2({}, {}) = {}", src_fd, dest_fd, result);
-    result
-}
-
-unsafe fn syscall_fstat(fd: usize, stat: usize) -> isize {
-    #[cfg(target_arch = "riscv64")]
-    const STAT_SIZE: usize = 128;
-    #[cfg(target_arch = "x86_64")]
-    const STAT_SIZE: usize = 144;
-
-    let result = hal::edge::with_edge_caller(|caller| {
-        caller
-            .write_header(&EdgeCallReq::SyscallFstat {
-                pid: hal::task::current_pid(),
-                fd: fd as i32,
-            })
-            .unwrap();
-        caller.kick().unwrap();
-
-        let result = caller.read_header().unwrap().into_syscall_resp().unwrap() as isize;
-        if result >= 0 {
-            hal::mem::copy_to_user(&caller.read_data().unwrap()[0..STAT_SIZE], stat as *mut u8);
+    if result >= 0 {
+        if let Err(errno) = vfs::dup(src_fd as i32, dest_fd as i32) {
+            return errno;
         }
-        result
-    });
-    log::trace!("fstat({}, _) = {}", fd, result);
+    }
+    log::trace!("dup2({}, {}) = {}", src_fd, dest_fd, result);
     result
 }
 
@@ -214,6 +496,16 @@ unsafe fn syscall_unlinkat(dir_fd: usize, path: usize, flags: usize) -> isize {
         return Errno::EFAULT.as_neg_isize();
     }
     let path = core::str::from_utf8(&path_buf[0..path_len]).expect("path is not valid UTF-8");
+    const AT_REMOVEDIR: usize = 0x200;
+    let remove_dir = flags & AT_REMOVEDIR != 0;
+    if let Some(result) = vfs::unlinkat(dir_fd as i32, path, remove_dir) {
+        log::trace!("unlinkat({}, {:?}, {}) = {}", dir_fd, path, flags, result);
+        return result;
+    }
+    let protected_path = match protected::protected_path(dir_fd as i32, path) {
+        Ok(protected_path) => protected_path,
+        Err(errno) => return errno,
+    };
 
     let result = hal::edge::with_edge_caller(|caller| {
         caller
@@ -228,6 +520,9 @@ unsafe fn syscall_unlinkat(dir_fd: usize, path: usize, flags: usize) -> isize {
 
         caller.read_header().unwrap().into_syscall_resp().unwrap() as isize
     });
+    if let (0, Some(protected_path)) = (result, protected_path) {
+        protected::unlinked(&protected_path);
+    }
     log::trace!("unlinkat({}, {:?}, {}) = {}", dir_fd, path, flags, result);
     result
 }
@@ -0,0 +1,202 @@
+use super::SyscallHandler;
+use crate::{
+    fs::{
+        host::{self, AT_FDCWD, AT_SYMLINK_FOLLOW},
+        protected, vfs,
+    },
+    Errno,
+};
+
+pub const SYSCALL_RENAMEAT2: SyscallHandler = SyscallHandler::Syscall5(syscall_renameat2);
+pub const SYSCALL_RENAMEAT: SyscallHandler = SyscallHandler::Syscall4(syscall_renameat);
+pub const SYSCALL_RENAME: SyscallHandler = SyscallHandler::Syscall2(syscall_rename);
+pub const SYSCALL_LINKAT: SyscallHandler = SyscallHandler::Syscall5(syscall_linkat);
+pub const SYSCALL_LINK: SyscallHandler = SyscallHandler::Syscall2(syscall_link);
+pub const SYSCALL_SYMLINKAT: SyscallHandler = SyscallHandler::Syscall3(syscall_symlinkat);
+pub const SYSCALL_SYMLINK: SyscallHandler = SyscallHandler::Syscall2(syscall_symlink);
+
+const RENAME_NOREPLACE: u32 = 1;
+const RENAME_EXCHANGE: u32 = 2;
+const RENAME_WHITEOUT: u32 = 4;
+
+fn into_syscall_result(result: Result<(), isize>) -> isize {
+    result.map_or_else(|errno| errno, |()| 0)
+}
+
+fn renameat2(
+    old_dir_fd: i32,
+    old_path: &str,
+    new_dir_fd: i32,
+    new_path: &str,
+    flags: u32,
+) -> isize {
+    if flags & !(RENAME_NOREPLACE | RENAME_EXCHANGE | RENAME_WHITEOUT) != 0
+        || flags & (RENAME_NOREPLACE | RENAME_EXCHANGE) == RENAME_NOREPLACE | RENAME_EXCHANGE
+    {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    if let Some(result) = vfs::renameat(old_dir_fd, old_path, new_dir_fd, new_path, flags) {
+        return result;
+    }
+    let result = match (
+        protected::protected_path(old_dir_fd, old_path),
+        protected::protected_path(new_dir_fd, new_path),
+    ) {
+        (Err(errno), _) | (_, Err(errno)) => Err(errno),
+        (Ok(None), Ok(None)) => host::renameat(old_dir_fd, old_path, new_dir_fd, new_path, flags),
+        (Ok(Some(old)), Ok(Some(new))) => protected::rename(&old, &new, flags),
+        // The file would have to be encrypted or decrypted
+        _ => Err(Errno::EXDEV.as_neg_isize()),
+    };
+    into_syscall_result(result)
+}
+
+unsafe fn syscall_renameat2(
+    old_dir_fd: usize,
+    old_path: usize,
+    new_dir_fd: usize,
+    new_path: usize,
+    flags: usize,
+) -> isize {
+    let (old_path, new_path) = match (
+        super::path_from_user(old_path),
+        super::path_from_user(new_path),
+    ) {
+        (Ok(old_path), Ok(new_path)) => (old_path, new_path),
+        (Err(errno), _) | (_, Err(errno)) => return errno,
+    };
+    let result = renameat2(
+        old_dir_fd as i32,
+        &old_path,
+        new_dir_fd as i32,
+        &new_path,
+        flags as u32,
+    );
+    log::trace!(
+        "renameat2({}, {:?}, {}, {:?}, {:#x}) = {}",
+        old_dir_fd as i32,
+        old_path,
+        new_dir_fd as i32,
+        new_path,
+        flags,
+        result
+    );
+    result
+}
+
+unsafe fn syscall_renameat(
+    old_dir_fd: usize,
+    old_path: usize,
+    new_dir_fd: usize,
+    new_path: usize,
+) -> isize {
+    syscall_renameat2(old_dir_fd, old_path, new_dir_fd, new_path, 0)
+}
+
+unsafe fn syscall_rename(old_path: usize, new_path: usize) -> isize {
+    syscall_renameat2(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, 0)
+}
+
+fn linkat(old_dir_fd: i32, old_path: &str, new_dir_fd: i32, new_path: &str, flags: i32) -> isize {
+    // Linking an fd with `AT_EMPTY_PATH` is not supported
+    if flags & !AT_SYMLINK_FOLLOW != 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let followed;
+    let (old_dir_fd, old_path) = if flags & AT_SYMLINK_FOLLOW != 0 {
+        followed = match vfs::follow_links(old_dir_fd, old_path) {
+            Ok(target) => target,
+            Err(errno) => return errno,
+        };
+        (AT_FDCWD, followed.as_str())
+    } else {
+        (old_dir_fd, old_path)
+    };
+    if let Some(result) = vfs::linkat(old_dir_fd, old_path, new_dir_fd, new_path) {
+        return result;
+    }
+    let result = match (
+        protected::protected_path(old_dir_fd, old_path),
+        protected::protected_path(new_dir_fd, new_path),
+    ) {
+        (Err(errno), _) | (_, Err(errno)) => Err(errno),
+        (Ok(None), Ok(None)) => host::linkat(old_dir_fd, old_path, new_dir_fd, new_path, flags),
+        // The keys of a protected file are bound to a single path
+        _ => Err(Errno::EPERM.as_neg_isize()),
+    };
+    into_syscall_result(result)
+}
+
+unsafe fn syscall_linkat(
+    old_dir_fd: usize,
+    old_path: usize,
+    new_dir_fd: usize,
+    new_path: usize,
+    flags: usize,
+) -> isize {
+    let (old_path, new_path) = match (
+        super::path_from_user(old_path),
+        super::path_from_user(new_path),
+    ) {
+        (Ok(old_path), Ok(new_path)) => (old_path, new_path),
+        (Err(errno), _) | (_, Err(errno)) => return errno,
+    };
+    let result = linkat(
+        old_dir_fd as i32,
+        &old_path,
+        new_dir_fd as i32,
+        &new_path,
+        flags as i32,
+    );
+    log::trace!(
+        "linkat({}, {:?}, {}, {:?}, {:#x}) = {}",
+        old_dir_fd as i32,
+        old_path,
+        new_dir_fd as i32,
+        new_path,
+        flags,
+        result
+    );
+    result
+}
+
+unsafe fn syscall_link(old_path: usize, new_path: usize) -> isize {
+    syscall_linkat(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, 0)
+}
+
+fn symlinkat(target: &str, dir_fd: i32, path: &str) -> isize {
+    if target.is_empty() {
+        return Errno::ENOENT.as_neg_isize();
+    }
+    if let Some(result) = vfs::symlinkat(target, dir_fd, path) {
+        return result;
+    }
+    let result = match protected::protected_path(dir_fd, path) {
+        Err(errno) => Err(errno),
+        Ok(None) => host::symlinkat(target, dir_fd, path),
+        // The host would resolve the link, which protected files must not
+        // depend on
+        Ok(Some(_)) => Err(Errno::EPERM.as_neg_isize()),
+    };
+    into_syscall_result(result)
+}
+
+unsafe fn syscall_symlinkat(target: usize, dir_fd: usize, path: usize) -> isize {
+    let (target, path) = match (super::path_from_user(target), super::path_from_user(path)) {
+        (Ok(target), Ok(path)) => (target, path),
+        (Err(errno), _) | (_, Err(errno)) => return errno,
+    };
+    let result = symlinkat(&target, dir_fd as i32, &path);
+    log::trace!(
+        "symlinkat({:?}, {}, {:?}) = {}",
+        target,
+        dir_fd as i32,
+        path,
+        result
+    );
+    result
+}
+
+unsafe fn syscall_symlink(target: usize, path: usize) -> isize {
+    syscall_symlinkat(target, AT_FDCWD as usize, path)
+}
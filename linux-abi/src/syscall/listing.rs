@@ -4,12 +4,42 @@
 use super::*;
 
 pub use dir::{SYSCALL_CHDIR, SYSCALL_GETCWD, SYSCALL_GETDENTS64, SYSCALL_MKDIRAT};
+pub use epoll::{
+    SYSCALL_EPOLL_CREATE, SYSCALL_EPOLL_CREATE1, SYSCALL_EPOLL_CTL, SYSCALL_EPOLL_PWAIT,
+    SYSCALL_EPOLL_WAIT,
+};
+pub use fcntl::{SYSCALL_FCNTL, SYSCALL_IOCTL};
 pub use file::{
-    SYSCALL_CLOSE, SYSCALL_DUP, SYSCALL_DUP3, SYSCALL_FSTAT, SYSCALL_OPENAT, SYSCALL_READ,
-    SYSCALL_UNLINKAT, SYSCALL_WRITE,
+    SYSCALL_CLOSE, SYSCALL_DUP, SYSCALL_DUP3, SYSCALL_FDATASYNC, SYSCALL_FSYNC, SYSCALL_FTRUNCATE,
+    SYSCALL_LSEEK, SYSCALL_OPENAT, SYSCALL_PREAD64, SYSCALL_PREADV, SYSCALL_PWRITE64,
+    SYSCALL_PWRITEV, SYSCALL_READ, SYSCALL_READV, SYSCALL_TRUNCATE, SYSCALL_UNLINKAT,
+    SYSCALL_WRITE, SYSCALL_WRITEV,
+};
+pub use link::{
+    SYSCALL_LINK, SYSCALL_LINKAT, SYSCALL_RENAME, SYSCALL_RENAMEAT, SYSCALL_RENAMEAT2,
+    SYSCALL_SYMLINK, SYSCALL_SYMLINKAT,
 };
 pub use mem::{SYSCALL_MMAP, SYSCALL_MUNMAP};
+pub use poll::{SYSCALL_POLL, SYSCALL_PPOLL, SYSCALL_PSELECT6, SYSCALL_SELECT};
 pub use process::{
     SYSCALL_CLONE, SYSCALL_EXECVE_PRE, SYSCALL_EXIT, SYSCALL_GETPID, SYSCALL_GETPPID,
     SYSCALL_SCHED_YIELD, SYSCALL_WAIT4,
 };
+pub use random::SYSCALL_GETRANDOM;
+pub use sched::{
+    SYSCALL_GETPRIORITY, SYSCALL_SCHED_GETAFFINITY, SYSCALL_SCHED_GETSCHEDULER,
+    SYSCALL_SCHED_SETSCHEDULER, SYSCALL_SETPRIORITY,
+};
+pub use socket::{
+    SYSCALL_ACCEPT, SYSCALL_ACCEPT4, SYSCALL_BIND, SYSCALL_CONNECT, SYSCALL_GETPEERNAME,
+    SYSCALL_GETSOCKNAME, SYSCALL_GETSOCKOPT, SYSCALL_LISTEN, SYSCALL_RECVFROM, SYSCALL_SENDTO,
+    SYSCALL_SETSOCKOPT, SYSCALL_SHUTDOWN, SYSCALL_SOCKET,
+};
+pub use stat::{
+    SYSCALL_FACCESSAT, SYSCALL_FSTAT, SYSCALL_LSTAT, SYSCALL_NEWFSTATAT, SYSCALL_READLINKAT,
+    SYSCALL_STAT, SYSCALL_STATX,
+};
+pub use time::{
+    SYSCALL_CLOCK_GETTIME, SYSCALL_CLOCK_NANOSLEEP, SYSCALL_GETTIMEOFDAY, SYSCALL_NANOSLEEP,
+    SYSCALL_TIME, SYSCALL_TIMES,
+};
@@ -2,11 +2,20 @@ use alloc::{string::String, vec::Vec};
 use hal::task::UserspaceRegs;
 
 mod dir;
+mod epoll;
+mod fcntl;
 mod file;
+mod link;
 pub mod listing;
 mod mem;
+mod poll;
 mod process;
+mod random;
+mod sched;
+mod socket;
+mod stat;
 pub mod tables;
+mod time;
 
 #[derive(Clone, Copy)]
 pub enum SyscallHandler {
@@ -15,6 +24,7 @@ pub enum SyscallHandler {
     Syscall2(unsafe fn(usize, usize) -> isize),
     Syscall3(unsafe fn(usize, usize, usize) -> isize),
     Syscall4(unsafe fn(usize, usize, usize, usize) -> isize),
+    Syscall5(unsafe fn(usize, usize, usize, usize, usize) -> isize),
     Syscall6(unsafe fn(usize, usize, usize, usize, usize, usize) -> isize),
     SyscallClone(unsafe fn(&UserspaceRegs, usize, usize) -> isize),
     SyscallExecvePre(
@@ -22,6 +32,21 @@ pub enum SyscallHandler {
     ),
 }
 
+/// Copy the NUL-terminated path at `ptr` from userspace.
+///
+/// # Safety
+///
+/// `ptr` must point to readable user memory.
+unsafe fn path_from_user(ptr: usize) -> Result<String, isize> {
+    let mut buf = alloc::vec![0; crate::limits::PATH_MAX];
+    let len = hal::mem::strncpy_from_user(&mut buf, ptr as *const u8);
+    if len >= buf.len() {
+        return Err(crate::Errno::ENAMETOOLONG.as_neg_isize());
+    }
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| crate::Errno::EINVAL.as_neg_isize())
+}
+
 #[macro_export]
 macro_rules! syscall_try {
     ($val:expr) => {{
@@ -0,0 +1,221 @@
+use alloc::{vec, vec::Vec};
+use edge_proto::PolledFd;
+use hal::time::{monotonic_ns, NSEC_PER_SEC};
+
+use super::{
+    time::{Timespec, Timeval},
+    SyscallHandler,
+};
+use crate::{
+    fs::poll::{
+        self, POLLERR, POLLHUP, POLLIN, POLLNVAL, POLLOUT, POLLPRI, POLLRDNORM, POLLWRNORM,
+    },
+    limits::NOFILE_MAX,
+    Errno,
+};
+
+pub const SYSCALL_POLL: SyscallHandler = SyscallHandler::Syscall3(syscall_poll);
+pub const SYSCALL_PPOLL: SyscallHandler = SyscallHandler::Syscall5(syscall_ppoll);
+pub const SYSCALL_SELECT: SyscallHandler = SyscallHandler::Syscall5(syscall_select);
+pub const SYSCALL_PSELECT6: SyscallHandler = SyscallHandler::Syscall6(syscall_pselect6);
+
+/// The events that make an fd readable, writable or exceptional for `select`,
+/// as Linux defines them.
+const SELECT_IN: i16 = POLLIN | POLLRDNORM | POLLHUP | POLLERR;
+const SELECT_OUT: i16 = POLLOUT | POLLWRNORM | POLLERR;
+const SELECT_EX: i16 = POLLPRI;
+
+/// The number of fds in a word of an `fd_set`.
+const NFDBITS: usize = u64::BITS as usize;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Pollfd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+/// The deadline of a timeout of `timeout_ns` that starts now.
+fn deadline_after(timeout_ns: u64) -> u64 {
+    monotonic_ns().saturating_add(timeout_ns)
+}
+
+/// The time left until `deadline`, which Linux writes back to the timeout of
+/// `ppoll`, `select` and `pselect6`.
+fn time_left(deadline: u64) -> u64 {
+    deadline.saturating_sub(monotonic_ns())
+}
+
+/// Run `poll` on the `nfds` entries of the array at `ufds`.
+unsafe fn do_poll(ufds: usize, nfds: usize, deadline: Option<u64>) -> isize {
+    if nfds > NOFILE_MAX {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let ufds = ufds as *mut Pollfd;
+    let pollfds: Vec<Pollfd> = (0..nfds)
+        .map(|i| hal::mem::read_from_user(ufds.add(i)))
+        .collect();
+    let fds: Vec<PolledFd> = pollfds
+        .iter()
+        .map(|pollfd| PolledFd {
+            fd: pollfd.fd,
+            events: pollfd.events,
+        })
+        .collect();
+    let mut revents = vec![0; nfds];
+    let ready = match poll::wait(&fds, &mut revents, deadline) {
+        Ok(ready) => ready,
+        Err(errno) => return errno,
+    };
+    for (i, (pollfd, revents)) in pollfds.into_iter().zip(revents).enumerate() {
+        hal::mem::write_to_user(ufds.add(i), Pollfd { revents, ..pollfd });
+    }
+    ready as isize
+}
+
+unsafe fn syscall_poll(ufds: usize, nfds: usize, timeout_ms: usize) -> isize {
+    // A negative timeout waits forever
+    let deadline = u64::try_from(timeout_ms as i32)
+        .ok()
+        .map(|timeout_ms| deadline_after(timeout_ms * (NSEC_PER_SEC / 1000)));
+    do_poll(ufds, nfds, deadline)
+}
+
+/// Signals are never delivered to tasks, so the signal mask is ignored.
+unsafe fn syscall_ppoll(
+    ufds: usize,
+    nfds: usize,
+    tsp: usize,
+    _sigmask: usize,
+    _sigsetsize: usize,
+) -> isize {
+    if tsp == 0 {
+        return do_poll(ufds, nfds, None);
+    }
+    let deadline = match hal::mem::read_from_user(tsp as *const Timespec).to_ns() {
+        Ok(timeout) => deadline_after(timeout),
+        Err(errno) => return errno,
+    };
+    let result = do_poll(ufds, nfds, Some(deadline));
+    hal::mem::write_to_user(tsp as *mut Timespec, Timespec::from_ns(time_left(deadline)));
+    result
+}
+
+/// Read the `fd_set` of `words` words at `ptr`, if it is not null.
+unsafe fn read_fd_set(ptr: usize, words: usize) -> Option<Vec<u64>> {
+    if ptr == 0 {
+        return None;
+    }
+    let ptr = ptr as *const u64;
+    Some(
+        (0..words)
+            .map(|i| hal::mem::read_from_user(ptr.add(i)))
+            .collect(),
+    )
+}
+
+fn is_set(set: &Option<Vec<u64>>, fd: usize) -> bool {
+    set.as_ref()
+        .is_some_and(|set| set[fd / NFDBITS] & (1 << (fd % NFDBITS)) != 0)
+}
+
+/// Run `select` on the sets at `readfds`, `writefds` and `exceptfds`, which
+/// are replaced by the fds found ready.
+unsafe fn do_select(
+    nfds: usize,
+    readfds: usize,
+    writefds: usize,
+    exceptfds: usize,
+    deadline: Option<u64>,
+) -> isize {
+    if (nfds as i32) < 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let nfds = nfds.min(NOFILE_MAX);
+    let words = nfds.div_ceil(NFDBITS);
+    let sets = [
+        (readfds, read_fd_set(readfds, words), SELECT_IN),
+        (writefds, read_fd_set(writefds, words), SELECT_OUT),
+        (exceptfds, read_fd_set(exceptfds, words), SELECT_EX),
+    ];
+
+    let fds: Vec<PolledFd> = (0..nfds)
+        .filter_map(|fd| {
+            let events = sets
+                .iter()
+                .filter(|(_, set, _)| is_set(set, fd))
+                .fold(0, |events, &(.., set_events)| events | set_events);
+            (events != 0).then_some(PolledFd {
+                fd: fd as i32,
+                events,
+            })
+        })
+        .collect();
+    let mut revents = vec![0; fds.len()];
+    if let Err(errno) = poll::wait(&fds, &mut revents, deadline) {
+        return errno;
+    }
+    if revents.iter().any(|&revents| revents & POLLNVAL != 0) {
+        return Errno::EBADF.as_neg_isize();
+    }
+
+    let mut ready = 0;
+    for (ptr, set, set_events) in sets {
+        if set.is_none() {
+            continue;
+        }
+        let mut result = vec![0u64; words];
+        for (polled, &revents) in fds.iter().zip(&revents) {
+            let fd = polled.fd as usize;
+            if is_set(&set, fd) && revents & set_events != 0 {
+                result[fd / NFDBITS] |= 1 << (fd % NFDBITS);
+                ready += 1;
+            }
+        }
+        for (i, word) in result.into_iter().enumerate() {
+            hal::mem::write_to_user((ptr as *mut u64).add(i), word);
+        }
+    }
+    ready
+}
+
+unsafe fn syscall_select(
+    nfds: usize,
+    readfds: usize,
+    writefds: usize,
+    exceptfds: usize,
+    tvp: usize,
+) -> isize {
+    if tvp == 0 {
+        return do_select(nfds, readfds, writefds, exceptfds, None);
+    }
+    let deadline = match hal::mem::read_from_user(tvp as *const Timeval).to_ns() {
+        Ok(timeout) => deadline_after(timeout),
+        Err(errno) => return errno,
+    };
+    let result = do_select(nfds, readfds, writefds, exceptfds, Some(deadline));
+    hal::mem::write_to_user(tvp as *mut Timeval, Timeval::from_ns(time_left(deadline)));
+    result
+}
+
+/// Signals are never delivered to tasks, so the signal mask is ignored.
+unsafe fn syscall_pselect6(
+    nfds: usize,
+    readfds: usize,
+    writefds: usize,
+    exceptfds: usize,
+    tsp: usize,
+    _sigmask: usize,
+) -> isize {
+    if tsp == 0 {
+        return do_select(nfds, readfds, writefds, exceptfds, None);
+    }
+    let deadline = match hal::mem::read_from_user(tsp as *const Timespec).to_ns() {
+        Ok(timeout) => deadline_after(timeout),
+        Err(errno) => return errno,
+    };
+    let result = do_select(nfds, readfds, writefds, exceptfds, Some(deadline));
+    hal::mem::write_to_user(tsp as *mut Timespec, Timespec::from_ns(time_left(deadline)));
+    result
+}
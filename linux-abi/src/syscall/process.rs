@@ -13,6 +13,10 @@ pub const SYSCALL_CLONE: SyscallHandler = SyscallHandler::SyscallClone(syscall_c
 pub const SYSCALL_EXECVE_PRE: SyscallHandler = SyscallHandler::SyscallExecvePre(syscall_execve_pre);
 
 unsafe fn syscall_exit(retval: usize) -> isize {
+    // Release the files served inside the enclave while the host can still be
+    // reached on the process's behalf
+    crate::fs::vfs::exit(hal::task::current_pid());
+
     let current = hal::task::current();
     let mut cur_lock = current.lock();
     let pid = cur_lock.pid;
@@ -134,8 +138,14 @@ unsafe fn syscall_clone(regs: &UserspaceRegs, flags: usize, stack: usize) -> isi
     let pid = task.lock().pid;
     log::debug!("Created a new task with PID = {}", pid);
 
-    // Link parent process and child process
-    task.lock().parent = Arc::downgrade(&current);
+    // Link parent process and child process. The child inherits the
+    // scheduling policy and nice value, but not the statistics.
+    {
+        let mut task_lock = task.lock();
+        task_lock.parent = Arc::downgrade(&current);
+        task_lock.nice = cur_lock.nice;
+        task_lock.policy = cur_lock.policy;
+    }
     cur_lock.wait_queue.add_child(Arc::clone(&task));
 
     // Duplicate PCB at the edge responder side
@@ -149,8 +159,9 @@ unsafe fn syscall_clone(regs: &UserspaceRegs, flags: usize, stack: usize) -> isi
         caller.kick().unwrap();
         assert!(caller.read_header().unwrap().is_ok());
     });
-    // Spawn the new task in the async executor
-    executor::spawn(TaskFuture::new(task));
+    crate::fs::vfs::fork(cur_pid, pid);
+    // Hand the new task over to the scheduler
+    hal::task::spawn(TaskFuture::new(task));
 
     pid as isize
 }
@@ -181,6 +192,7 @@ unsafe fn syscall_execve_pre(
 
     // Read path, argv, envp
     let path = read_string_from_user(path as *const u8)?;
+    let path = crate::fs::absolute_path(crate::fs::host::AT_FDCWD, &path)?;
     let mut argv = Vec::new();
     loop {
         let ptr = hal::mem::read_from_user(argv_ptr as *const *const u8);
@@ -0,0 +1,29 @@
+use super::SyscallHandler;
+use crate::Errno;
+
+pub const SYSCALL_GETRANDOM: SyscallHandler = SyscallHandler::Syscall3(syscall_getrandom);
+
+const GRND_NONBLOCK: usize = 0x1;
+const GRND_RANDOM: usize = 0x2;
+const GRND_INSECURE: usize = 0x4;
+
+/// Linux returns at most this many bytes from one call.
+const MAX_GETRANDOM: usize = (1 << 25) - 1;
+
+/// The generator is seeded inside the enclave before its first use, so none
+/// of the flags changes where the bytes come from or makes the call block.
+unsafe fn syscall_getrandom(buf: usize, len: usize, flags: usize) -> isize {
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM | GRND_INSECURE) != 0
+        || flags & (GRND_RANDOM | GRND_INSECURE) == GRND_RANDOM | GRND_INSECURE
+    {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let len = len.min(MAX_GETRANDOM);
+    let mut chunk = [0u8; 256];
+    for offset in (0..len).step_by(chunk.len()) {
+        let chunk = &mut chunk[0..(len - offset).min(256)];
+        hal::rand::fill_bytes(chunk);
+        hal::mem::copy_to_user(chunk, (buf + offset) as *mut u8);
+    }
+    len as isize
+}
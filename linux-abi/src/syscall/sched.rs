@@ -0,0 +1,119 @@
+use alloc::sync::Arc;
+use hal::task::{Policy, Task, NICE_MAX, NICE_MIN};
+use spin::Mutex;
+
+use super::SyscallHandler;
+use crate::Errno;
+
+pub const SYSCALL_SCHED_SETSCHEDULER: SyscallHandler =
+    SyscallHandler::Syscall3(syscall_sched_setscheduler);
+pub const SYSCALL_SCHED_GETSCHEDULER: SyscallHandler =
+    SyscallHandler::Syscall1(syscall_sched_getscheduler);
+pub const SYSCALL_SCHED_GETAFFINITY: SyscallHandler =
+    SyscallHandler::Syscall3(syscall_sched_getaffinity);
+pub const SYSCALL_SETPRIORITY: SyscallHandler = SyscallHandler::Syscall3(syscall_setpriority);
+pub const SYSCALL_GETPRIORITY: SyscallHandler = SyscallHandler::Syscall2(syscall_getpriority);
+
+const SCHED_FIFO: i32 = 1;
+const SCHED_RR: i32 = 2;
+const SCHED_DEADLINE: i32 = 6;
+const SCHED_RESET_ON_FORK: i32 = 0x4000_0000;
+
+const PRIO_PROCESS: usize = 0;
+
+/// The enclave runs on a single CPU, whose mask fits in a `long`.
+const CPUMASK_SIZE: usize = core::mem::size_of::<u64>();
+
+/// The task `pid`, where 0 stands for the calling task.
+fn find_task(pid: usize) -> Result<Arc<Mutex<Task>>, isize> {
+    match pid as i32 {
+        0 => Ok(hal::task::current()),
+        pid if pid > 0 => hal::task::find(pid).ok_or(Errno::ESRCH.as_neg_isize()),
+        _ => Err(Errno::EINVAL.as_neg_isize()),
+    }
+}
+
+unsafe fn syscall_sched_setscheduler(pid: usize, policy: usize, param: usize) -> isize {
+    let policy = policy as i32;
+    if param == 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    if policy & SCHED_RESET_ON_FORK != 0 {
+        log::warn!("sched_setscheduler: SCHED_RESET_ON_FORK is not supported");
+        return Errno::EINVAL.as_neg_isize();
+    }
+    // `struct sched_param` only holds the static priority, which is always 0
+    // for the policies of the fair scheduler
+    let priority = hal::mem::read_from_user(param as *const i32);
+    let task = match find_task(pid) {
+        Ok(task) => task,
+        Err(errno) => return errno,
+    };
+    let policy = match Policy::from_raw(policy) {
+        Some(policy) => policy,
+        // Real-time tasks could starve everything else, so they are only
+        // allowed as much as to unprivileged users without `RLIMIT_RTPRIO`
+        None if matches!(policy, SCHED_FIFO | SCHED_RR | SCHED_DEADLINE) => {
+            log::warn!("sched_setscheduler: Real-time policy {} is refused", policy);
+            return Errno::EPERM.as_neg_isize();
+        }
+        None => return Errno::EINVAL.as_neg_isize(),
+    };
+    if priority != 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    task.lock().policy = policy;
+    0
+}
+
+unsafe fn syscall_sched_getscheduler(pid: usize) -> isize {
+    match find_task(pid) {
+        Ok(task) => task.lock().policy.raw() as isize,
+        Err(errno) => errno,
+    }
+}
+
+unsafe fn syscall_sched_getaffinity(pid: usize, len: usize, mask: usize) -> isize {
+    // Like Linux, insist on whole `long`s
+    if len < CPUMASK_SIZE || len & (CPUMASK_SIZE - 1) != 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    if let Err(errno) = find_task(pid) {
+        return errno;
+    }
+    hal::mem::write_to_user(mask as *mut u64, 1);
+    CPUMASK_SIZE as isize
+}
+
+unsafe fn syscall_setpriority(which: usize, who: usize, nice: usize) -> isize {
+    if which != PRIO_PROCESS {
+        log::warn!(
+            "setpriority: Only PRIO_PROCESS is supported (got {})",
+            which
+        );
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let task = match find_task(who) {
+        Ok(task) => task,
+        Err(errno) => return errno,
+    };
+    // Everything runs as root in the enclave, so the priority may be raised
+    task.lock().nice = (nice as i32).clamp(NICE_MIN, NICE_MAX);
+    0
+}
+
+/// Returns `20 - nice`, so that the result is never negative. The C library
+/// turns it back into the nice value.
+unsafe fn syscall_getpriority(which: usize, who: usize) -> isize {
+    if which != PRIO_PROCESS {
+        log::warn!(
+            "getpriority: Only PRIO_PROCESS is supported (got {})",
+            which
+        );
+        return Errno::EINVAL.as_neg_isize();
+    }
+    match find_task(who) {
+        Ok(task) => (20 - task.lock().nice) as isize,
+        Err(errno) => errno,
+    }
+}
@@ -0,0 +1,575 @@
+//! Sockets, which are host sockets kept in the fd table at the edge
+//! responder. Once created, they are read, written, polled and closed like
+//! any other host file.
+//!
+//! With the `netstack` feature, IPv4 sockets are served by the network stack
+//! inside the enclave instead (see [`crate::net`]).
+
+use alloc::{string::String, vec, vec::Vec};
+use edge_proto::{EdgeCallReq, EdgeCallResp, PolledFd, SockAddr};
+use hal::edge::EDGE_BUFFER_SIZE;
+
+use super::SyscallHandler;
+#[cfg(feature = "netstack")]
+use crate::net;
+use crate::{
+    fs::{
+        host,
+        poll::{self, POLLOUT},
+    },
+    Errno,
+};
+
+pub const SYSCALL_SOCKET: SyscallHandler = SyscallHandler::Syscall3(syscall_socket);
+pub const SYSCALL_BIND: SyscallHandler = SyscallHandler::Syscall3(syscall_bind);
+pub const SYSCALL_LISTEN: SyscallHandler = SyscallHandler::Syscall2(syscall_listen);
+pub const SYSCALL_ACCEPT: SyscallHandler = SyscallHandler::Syscall3(syscall_accept);
+pub const SYSCALL_ACCEPT4: SyscallHandler = SyscallHandler::Syscall4(syscall_accept4);
+pub const SYSCALL_CONNECT: SyscallHandler = SyscallHandler::Syscall3(syscall_connect);
+pub const SYSCALL_SENDTO: SyscallHandler = SyscallHandler::Syscall6(syscall_sendto);
+pub const SYSCALL_RECVFROM: SyscallHandler = SyscallHandler::Syscall6(syscall_recvfrom);
+pub const SYSCALL_SHUTDOWN: SyscallHandler = SyscallHandler::Syscall2(syscall_shutdown);
+pub const SYSCALL_GETSOCKOPT: SyscallHandler = SyscallHandler::Syscall5(syscall_getsockopt);
+pub const SYSCALL_SETSOCKOPT: SyscallHandler = SyscallHandler::Syscall5(syscall_setsockopt);
+pub const SYSCALL_GETSOCKNAME: SyscallHandler = SyscallHandler::Syscall3(syscall_getsockname);
+pub const SYSCALL_GETPEERNAME: SyscallHandler = SyscallHandler::Syscall3(syscall_getpeername);
+
+const AF_UNSPEC: u16 = 0;
+const AF_UNIX: u16 = 1;
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 10;
+
+const SOL_SOCKET: i32 = 1;
+const SO_ERROR: i32 = 4;
+
+/// The size of `struct sockaddr_storage`, the largest socket address.
+const SOCKADDR_MAX: usize = 128;
+const SOCKADDR_IN_LEN: usize = 16;
+/// The size of `struct sockaddr_in6`, without the `sin6_scope_id` that old
+/// callers leave out.
+const SOCKADDR_IN6_MIN_LEN: usize = 24;
+const SOCKADDR_IN6_LEN: usize = 28;
+
+/// The size of the largest option value the edge responder accepts.
+const MAX_OPTION_LEN: usize = 64;
+
+/// Copy the socket address of `len` bytes at `ptr` from userspace.
+unsafe fn sockaddr_from_user(ptr: usize, len: usize) -> Result<SockAddr, isize> {
+    if !(2..=SOCKADDR_MAX).contains(&len) {
+        return Err(Errno::EINVAL.as_neg_isize());
+    }
+    let mut raw = vec![0u8; len];
+    hal::mem::copy_from_user(&mut raw, ptr as *const u8);
+    match u16::from_ne_bytes([raw[0], raw[1]]) {
+        AF_UNSPEC => Ok(SockAddr::Unnamed),
+        AF_UNIX => {
+            let path = &raw[2..];
+            // Abstract addresses start with a NUL, and are not terminated
+            let path = match path.first() {
+                None => return Ok(SockAddr::Unnamed),
+                Some(0) => path,
+                Some(_) => path.split(|&byte| byte == 0).next().unwrap(),
+            };
+            String::from_utf8(path.to_vec())
+                .map(SockAddr::Unix)
+                .map_err(|_| Errno::EINVAL.as_neg_isize())
+        }
+        AF_INET if len >= SOCKADDR_IN_LEN => Ok(SockAddr::Inet {
+            addr: raw[4..8].try_into().unwrap(),
+            port: u16::from_be_bytes([raw[2], raw[3]]),
+        }),
+        AF_INET6 if len >= SOCKADDR_IN6_MIN_LEN => Ok(SockAddr::Inet6 {
+            addr: raw[8..24].try_into().unwrap(),
+            port: u16::from_be_bytes([raw[2], raw[3]]),
+            flowinfo: u32::from_be_bytes(raw[4..8].try_into().unwrap()),
+            scope_id: raw
+                .get(24..28)
+                .map_or(0, |id| u32::from_ne_bytes(id.try_into().unwrap())),
+        }),
+        AF_INET | AF_INET6 => Err(Errno::EINVAL.as_neg_isize()),
+        family => {
+            log::warn!("Unsupported address family: {}", family);
+            Err(Errno::EAFNOSUPPORT.as_neg_isize())
+        }
+    }
+}
+
+/// Lay `addr` out as a `struct sockaddr_*`. Unnamed addresses only have an
+/// `AF_UNSPEC` family.
+fn sockaddr_bytes(addr: &SockAddr) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(SOCKADDR_IN6_LEN);
+    match addr {
+        SockAddr::Unnamed => raw.extend_from_slice(&AF_UNSPEC.to_ne_bytes()),
+        SockAddr::Unix(path) => {
+            raw.extend_from_slice(&AF_UNIX.to_ne_bytes());
+            raw.extend_from_slice(path.as_bytes());
+            raw.push(0);
+        }
+        SockAddr::Inet { addr, port } => {
+            raw.extend_from_slice(&AF_INET.to_ne_bytes());
+            raw.extend_from_slice(&port.to_be_bytes());
+            raw.extend_from_slice(addr);
+            raw.resize(SOCKADDR_IN_LEN, 0);
+        }
+        SockAddr::Inet6 {
+            addr,
+            port,
+            flowinfo,
+            scope_id,
+        } => {
+            raw.extend_from_slice(&AF_INET6.to_ne_bytes());
+            raw.extend_from_slice(&port.to_be_bytes());
+            raw.extend_from_slice(&flowinfo.to_be_bytes());
+            raw.extend_from_slice(addr);
+            raw.extend_from_slice(&scope_id.to_ne_bytes());
+        }
+    }
+    raw
+}
+
+/// Copy `addr` to the buffer at `ptr`, whose size is at `len_ptr`, if `ptr`
+/// is not null. Like Linux, the address is truncated to the buffer, and its
+/// full size is stored at `len_ptr`.
+unsafe fn sockaddr_to_user(addr: &SockAddr, ptr: usize, len_ptr: usize) -> Result<(), isize> {
+    if ptr == 0 {
+        return Ok(());
+    }
+    let len = hal::mem::read_from_user(len_ptr as *const u32) as i32;
+    if len < 0 {
+        return Err(Errno::EINVAL.as_neg_isize());
+    }
+    let raw = sockaddr_bytes(addr);
+    hal::mem::copy_to_user(&raw[0..raw.len().min(len as usize)], ptr as *mut u8);
+    hal::mem::write_to_user(len_ptr as *mut u32, raw.len() as u32);
+    Ok(())
+}
+
+/// Make a socket call, returning `None` if it would block.
+fn socket_call(req: &EdgeCallReq) -> Result<Option<EdgeCallResp>, isize> {
+    hal::edge::with_edge_caller(|caller| {
+        caller.write_header(req).unwrap();
+        caller.kick().unwrap();
+        let resp = caller
+            .read_header()
+            .unwrap()
+            .into_result()
+            .map_err(host::errno_of)?;
+        Ok((resp != EdgeCallResp::WouldBlock).then_some(resp))
+    })
+}
+
+/// Make a socket call that cannot block, returning its result.
+fn simple_call(req: EdgeCallReq) -> isize {
+    match socket_call(&req) {
+        Ok(Some(resp)) => resp.into_syscall_resp().unwrap() as isize,
+        Ok(None) => Errno::EIO.as_neg_isize(),
+        Err(errno) => errno,
+    }
+}
+
+/// Make a socket call that may block until it succeeds, waiting for the host
+/// to report that the socket is ready.
+fn blocking_call(req: EdgeCallReq) -> Result<EdgeCallResp, isize> {
+    loop {
+        match socket_call(&req)? {
+            Some(resp) => return Ok(resp),
+            None => host::wait_for_io(),
+        }
+    }
+}
+
+unsafe fn syscall_socket(domain: usize, ty: usize, protocol: usize) -> isize {
+    #[cfg(feature = "netstack")]
+    if let Some(result) = net::socket(domain, ty, protocol) {
+        log::trace!("socket({}, {:#x}, {}) = {}", domain, ty, protocol, result);
+        return result;
+    }
+    let result = simple_call(EdgeCallReq::SyscallSocket {
+        pid: hal::task::current_pid(),
+        domain: domain as i32,
+        ty: ty as i32,
+        protocol: protocol as i32,
+    });
+    log::trace!("socket({}, {:#x}, {}) = {}", domain, ty, protocol, result);
+    result
+}
+
+unsafe fn syscall_bind(fd: usize, addr: usize, len: usize) -> isize {
+    let addr = match sockaddr_from_user(addr, len) {
+        Ok(addr) => addr,
+        Err(errno) => return errno,
+    };
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        return socket.bind(&addr);
+    }
+    simple_call(EdgeCallReq::SyscallBind {
+        pid: hal::task::current_pid(),
+        fd: fd as i32,
+        addr,
+    })
+}
+
+unsafe fn syscall_listen(fd: usize, backlog: usize) -> isize {
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        return socket.listen(backlog as i32);
+    }
+    simple_call(EdgeCallReq::SyscallListen {
+        pid: hal::task::current_pid(),
+        fd: fd as i32,
+        backlog: backlog as i32,
+    })
+}
+
+unsafe fn syscall_accept(fd: usize, addr: usize, len_ptr: usize) -> isize {
+    syscall_accept4(fd, addr, len_ptr, 0)
+}
+
+unsafe fn syscall_accept4(fd: usize, addr: usize, len_ptr: usize, flags: usize) -> isize {
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        let (new_fd, peer) = match socket.accept(flags) {
+            Ok(accepted) => accepted,
+            Err(errno) => return errno,
+        };
+        if let Err(errno) = sockaddr_to_user(&peer, addr, len_ptr) {
+            let _ = crate::fs::vfs::close(new_fd);
+            let _ = host::close(new_fd);
+            return errno;
+        }
+        return new_fd as isize;
+    }
+    let resp = blocking_call(EdgeCallReq::SyscallAccept {
+        pid: hal::task::current_pid(),
+        fd: fd as i32,
+        flags: flags as i32,
+    });
+    let (new_fd, peer) = match resp {
+        Ok(resp) => resp.into_ok_with_sock_addr().unwrap(),
+        Err(errno) => return errno,
+    };
+    if let Err(errno) = sockaddr_to_user(&peer, addr, len_ptr) {
+        let _ = host::close(new_fd as i32);
+        return errno;
+    }
+    new_fd as isize
+}
+
+/// Read the pending error of the socket `fd`.
+fn socket_error(fd: i32) -> isize {
+    let error = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallGetSockOpt {
+                pid: hal::task::current_pid(),
+                fd,
+                level: SOL_SOCKET,
+                name: SO_ERROR,
+                len: 4,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        caller
+            .read_header()
+            .unwrap()
+            .into_result()
+            .map_err(host::errno_of)?;
+        let data = caller.read_data().unwrap();
+        Ok(i32::from_ne_bytes(data[0..4].try_into().unwrap()))
+    });
+    match error {
+        Ok(error) => -(error as isize),
+        Err(errno) => errno,
+    }
+}
+
+unsafe fn syscall_connect(fd: usize, addr: usize, len: usize) -> isize {
+    let fd = fd as i32;
+    let addr = match sockaddr_from_user(addr, len) {
+        Ok(addr) => addr,
+        Err(errno) => return errno,
+    };
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd) {
+        return socket.connect(&addr);
+    }
+    let result = socket_call(&EdgeCallReq::SyscallConnect {
+        pid: hal::task::current_pid(),
+        fd,
+        addr,
+    });
+    match result {
+        Ok(Some(resp)) => resp.into_syscall_resp().unwrap() as isize,
+        // The connection completes once the socket becomes writable
+        Ok(None) => {
+            let mut revents = [0];
+            let fds = [PolledFd {
+                fd,
+                events: POLLOUT,
+            }];
+            if let Err(errno) = poll::wait(&fds, &mut revents, None) {
+                return errno;
+            }
+            socket_error(fd)
+        }
+        Err(errno) => errno,
+    }
+}
+
+unsafe fn syscall_sendto(
+    fd: usize,
+    ptr: usize,
+    len: usize,
+    flags: usize,
+    addr: usize,
+    addr_len: usize,
+) -> isize {
+    let addr = match addr {
+        0 => None,
+        addr => match sockaddr_from_user(addr, addr_len) {
+            Ok(addr) => Some(addr),
+            Err(errno) => return errno,
+        },
+    };
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        let len = match socket.send_len(len) {
+            Ok(len) => len,
+            Err(errno) => return errno,
+        };
+        let mut data = vec![0; len];
+        hal::mem::copy_from_user(&mut data, ptr as *const u8);
+        return match socket.send(&data, flags as i32, addr.as_ref()) {
+            Ok(sent) => sent as isize,
+            Err(errno) => errno,
+        };
+    }
+    // Larger messages are sent in part, like on a non-blocking socket
+    let len = len.min(EDGE_BUFFER_SIZE);
+    let req = EdgeCallReq::SyscallSendTo {
+        pid: hal::task::current_pid(),
+        fd: fd as i32,
+        len: len as u64,
+        flags: flags as i32,
+        addr,
+    };
+    loop {
+        let resp = hal::edge::with_edge_caller(|caller| {
+            hal::mem::copy_from_user(
+                &mut caller.data_zone_mut().unwrap()[0..len],
+                ptr as *const u8,
+            );
+            caller.write_header(&req).unwrap();
+            caller.kick().unwrap();
+            caller
+                .read_header()
+                .unwrap()
+                .into_result()
+                .map_err(host::errno_of)
+        });
+        match resp {
+            Ok(EdgeCallResp::WouldBlock) => host::wait_for_io(),
+            Ok(resp) => return resp.into_syscall_resp().unwrap() as isize,
+            Err(errno) => return errno,
+        }
+    }
+}
+
+unsafe fn syscall_recvfrom(
+    fd: usize,
+    ptr: usize,
+    len: usize,
+    flags: usize,
+    addr: usize,
+    len_ptr: usize,
+) -> isize {
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        let mut buf = vec![0; socket.recv_len(len)];
+        let (received, sender) = match socket.recv(&mut buf, flags as i32) {
+            Ok(received) => received,
+            Err(errno) => return errno,
+        };
+        hal::mem::copy_to_user(&buf[0..received], ptr as *mut u8);
+        if let Err(errno) = sockaddr_to_user(&sender, addr, len_ptr) {
+            return errno;
+        }
+        return received as isize;
+    }
+    let len = len.min(EDGE_BUFFER_SIZE);
+    let req = EdgeCallReq::SyscallRecvFrom {
+        pid: hal::task::current_pid(),
+        fd: fd as i32,
+        len: len as u64,
+        flags: flags as i32,
+    };
+    loop {
+        let resp = hal::edge::with_edge_caller(|caller| {
+            caller.write_header(&req).unwrap();
+            caller.kick().unwrap();
+            let resp = caller
+                .read_header()
+                .unwrap()
+                .into_result()
+                .map_err(host::errno_of)?;
+            if let EdgeCallResp::OkWithSockAddr { result, .. } = resp {
+                // `MSG_TRUNC` returns the full size of a datagram
+                let copied = (result as usize).min(len);
+                hal::mem::copy_to_user(&caller.read_data().unwrap()[0..copied], ptr as *mut u8);
+            }
+            Ok(resp)
+        });
+        match resp {
+            Ok(EdgeCallResp::WouldBlock) => host::wait_for_io(),
+            Ok(resp) => {
+                let (received, sender) = resp.into_ok_with_sock_addr().unwrap();
+                // Connected sockets do not report the sender
+                if sender == SockAddr::Unnamed && len_ptr != 0 {
+                    hal::mem::write_to_user(len_ptr as *mut u32, 0);
+                } else if let Err(errno) = sockaddr_to_user(&sender, addr, len_ptr) {
+                    return errno;
+                }
+                return received as isize;
+            }
+            Err(errno) => return errno,
+        }
+    }
+}
+
+unsafe fn syscall_shutdown(fd: usize, how: usize) -> isize {
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        return socket.shutdown(how as i32);
+    }
+    simple_call(EdgeCallReq::SyscallShutdown {
+        pid: hal::task::current_pid(),
+        fd: fd as i32,
+        how: how as i32,
+    })
+}
+
+unsafe fn syscall_getsockopt(
+    fd: usize,
+    level: usize,
+    name: usize,
+    ptr: usize,
+    len_ptr: usize,
+) -> isize {
+    let len = hal::mem::read_from_user(len_ptr as *const u32) as i32;
+    if len < 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let len = (len as usize).min(MAX_OPTION_LEN);
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        return match socket.getsockopt(level as i32, name as i32) {
+            Ok(value) => {
+                let value_len = value.len().min(len);
+                hal::mem::copy_to_user(&value[0..value_len], ptr as *mut u8);
+                hal::mem::write_to_user(len_ptr as *mut u32, value_len as u32);
+                0
+            }
+            Err(errno) => errno,
+        };
+    }
+    let result = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallGetSockOpt {
+                pid: hal::task::current_pid(),
+                fd: fd as i32,
+                level: level as i32,
+                name: name as i32,
+                len: len as u64,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        let value_len = caller
+            .read_header()
+            .unwrap()
+            .into_result()
+            .map_err(host::errno_of)?
+            .into_syscall_resp()
+            .unwrap() as usize;
+        let value_len = value_len.min(len);
+        hal::mem::copy_to_user(&caller.read_data().unwrap()[0..value_len], ptr as *mut u8);
+        Ok(value_len)
+    });
+    match result {
+        Ok(value_len) => {
+            hal::mem::write_to_user(len_ptr as *mut u32, value_len as u32);
+            0
+        }
+        Err(errno) => errno,
+    }
+}
+
+unsafe fn syscall_setsockopt(
+    fd: usize,
+    level: usize,
+    name: usize,
+    ptr: usize,
+    len: usize,
+) -> isize {
+    if len > MAX_OPTION_LEN {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let mut value = vec![0; len];
+    hal::mem::copy_from_user(&mut value, ptr as *const u8);
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        return socket.setsockopt(level as i32, name as i32, &value);
+    }
+    simple_call(EdgeCallReq::SyscallSetSockOpt {
+        pid: hal::task::current_pid(),
+        fd: fd as i32,
+        level: level as i32,
+        name: name as i32,
+        value,
+    })
+}
+
+/// Run `getsockname` or `getpeername` with `req`.
+unsafe fn sock_name(req: EdgeCallReq, addr: usize, len_ptr: usize) -> isize {
+    let name = match socket_call(&req) {
+        Ok(Some(resp)) => resp.into_ok_with_sock_addr().unwrap().1,
+        Ok(None) => return Errno::EIO.as_neg_isize(),
+        Err(errno) => return errno,
+    };
+    match sockaddr_to_user(&name, addr, len_ptr) {
+        Ok(()) => 0,
+        Err(errno) => errno,
+    }
+}
+
+unsafe fn syscall_getsockname(fd: usize, addr: usize, len_ptr: usize) -> isize {
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        return match sockaddr_to_user(&socket.local_name(), addr, len_ptr) {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        };
+    }
+    let req = EdgeCallReq::SyscallGetSockName {
+        pid: hal::task::current_pid(),
+        fd: fd as i32,
+    };
+    sock_name(req, addr, len_ptr)
+}
+
+unsafe fn syscall_getpeername(fd: usize, addr: usize, len_ptr: usize) -> isize {
+    #[cfg(feature = "netstack")]
+    if let Some(socket) = net::lookup(fd as i32) {
+        let result = socket
+            .peer_name()
+            .and_then(|name| sockaddr_to_user(&name, addr, len_ptr));
+        return match result {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        };
+    }
+    let req = EdgeCallReq::SyscallGetPeerName {
+        pid: hal::task::current_pid(),
+        fd: fd as i32,
+    };
+    sock_name(req, addr, len_ptr)
+}
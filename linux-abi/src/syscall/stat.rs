@@ -0,0 +1,187 @@
+use alloc::string::String;
+
+use super::SyscallHandler;
+use crate::{
+    fs::{
+        host::{self, AT_EMPTY_PATH, AT_FDCWD, AT_SYMLINK_NOFOLLOW},
+        protected,
+        stat::{self, Stat},
+        vfs,
+    },
+    Errno,
+};
+
+pub const SYSCALL_FSTAT: SyscallHandler = SyscallHandler::Syscall2(syscall_fstat);
+pub const SYSCALL_NEWFSTATAT: SyscallHandler = SyscallHandler::Syscall4(syscall_newfstatat);
+pub const SYSCALL_STATX: SyscallHandler = SyscallHandler::Syscall5(syscall_statx);
+pub const SYSCALL_STAT: SyscallHandler = SyscallHandler::Syscall2(syscall_stat);
+pub const SYSCALL_LSTAT: SyscallHandler = SyscallHandler::Syscall2(syscall_lstat);
+pub const SYSCALL_FACCESSAT: SyscallHandler = SyscallHandler::Syscall3(syscall_faccessat);
+pub const SYSCALL_READLINKAT: SyscallHandler = SyscallHandler::Syscall4(syscall_readlinkat);
+
+/// Not acted upon, as nothing is automounted.
+const AT_NO_AUTOMOUNT: i32 = 0x800;
+/// `AT_STATX_FORCE_SYNC` and `AT_STATX_DONT_SYNC`, which only matter to
+/// network file systems.
+const AT_STATX_SYNC_TYPE: i32 = 0x6000;
+
+fn fstat(fd: i32) -> Result<Stat, isize> {
+    vfs::fstat(fd).unwrap_or_else(|| host::fstat(fd))
+}
+
+/// Get the metadata of `path` for the `fstatat` family. `flags` may contain
+/// `AT_SYMLINK_NOFOLLOW`, `AT_EMPTY_PATH` and `AT_NO_AUTOMOUNT`.
+fn fstatat(dir_fd: i32, path: &str, flags: i32) -> Result<Stat, isize> {
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH | AT_NO_AUTOMOUNT) != 0 {
+        return Err(Errno::EINVAL.as_neg_isize());
+    }
+    if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        if dir_fd != AT_FDCWD {
+            return fstat(dir_fd);
+        }
+        return fstatat(AT_FDCWD, ".", 0);
+    }
+
+    let followed;
+    let (dir_fd, path) = if flags & AT_SYMLINK_NOFOLLOW == 0 {
+        followed = vfs::follow_links(dir_fd, path)?;
+        (AT_FDCWD, followed.as_str())
+    } else {
+        (dir_fd, path)
+    };
+    if let Some(result) = vfs::stat_at(dir_fd, path) {
+        return result;
+    }
+    if let Some(protected_path) = protected::protected_path(dir_fd, path)? {
+        return protected::stat(&protected_path);
+    }
+    host::fstatat(dir_fd, path, flags & AT_SYMLINK_NOFOLLOW)
+}
+
+unsafe fn syscall_fstat(fd: usize, stat: usize) -> isize {
+    let result = fstat(fd as i32).map(|metadata| {
+        hal::mem::copy_to_user(&stat::to_bytes(&metadata), stat as *mut u8);
+        0
+    });
+    let result = result.unwrap_or_else(|errno| errno);
+    log::trace!("fstat({}, _) = {}", fd, result);
+    result
+}
+
+unsafe fn syscall_newfstatat(dir_fd: usize, path: usize, stat: usize, flags: usize) -> isize {
+    let path = match super::path_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let result = fstatat(dir_fd as i32, &path, flags as i32).map(|metadata| {
+        hal::mem::copy_to_user(&stat::to_bytes(&metadata), stat as *mut u8);
+        0
+    });
+    let result = result.unwrap_or_else(|errno| errno);
+    log::trace!(
+        "newfstatat({}, {:?}, _, {:#x}) = {}",
+        dir_fd as i32,
+        path,
+        flags,
+        result
+    );
+    result
+}
+
+unsafe fn syscall_statx(
+    dir_fd: usize,
+    path: usize,
+    flags: usize,
+    mask: usize,
+    buf: usize,
+) -> isize {
+    let path = match super::path_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    // Every basic field is filled in whatever `mask` asks for, which statx
+    // allows
+    let flags = flags as i32 & !AT_STATX_SYNC_TYPE;
+    let result = fstatat(dir_fd as i32, &path, flags).map(|metadata| {
+        hal::mem::copy_to_user(&stat::to_statx_bytes(&metadata), buf as *mut u8);
+        0
+    });
+    let result = result.unwrap_or_else(|errno| errno);
+    log::trace!(
+        "statx({}, {:?}, {:#x}, {:#x}, _) = {}",
+        dir_fd as i32,
+        path,
+        flags,
+        mask,
+        result
+    );
+    result
+}
+
+unsafe fn syscall_stat(path: usize, stat: usize) -> isize {
+    syscall_newfstatat(AT_FDCWD as usize, path, stat, 0)
+}
+
+unsafe fn syscall_lstat(path: usize, stat: usize) -> isize {
+    syscall_newfstatat(AT_FDCWD as usize, path, stat, AT_SYMLINK_NOFOLLOW as usize)
+}
+
+unsafe fn syscall_faccessat(dir_fd: usize, path: usize, mode: usize) -> isize {
+    let path = match super::path_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let mode = mode as i32;
+    if mode & !0o7 != 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let target = match vfs::follow_links(dir_fd as i32, &path) {
+        Ok(target) => target,
+        Err(errno) => return errno,
+    };
+    let result = vfs::access_at(AT_FDCWD, &target, mode).unwrap_or_else(|| {
+        match host::access(AT_FDCWD, &target, mode) {
+            Ok(()) => 0,
+            Err(errno) => errno,
+        }
+    });
+    log::trace!(
+        "faccessat({}, {:?}, {:#o}) = {}",
+        dir_fd as i32,
+        path,
+        mode,
+        result
+    );
+    result
+}
+
+unsafe fn syscall_readlinkat(dir_fd: usize, path: usize, buf: usize, len: usize) -> isize {
+    let path = match super::path_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    if (len as isize) <= 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let target: Result<String, isize> = if path.is_empty() {
+        Err(Errno::ENOENT.as_neg_isize())
+    } else {
+        vfs::readlink_at(dir_fd as i32, &path)
+            .unwrap_or_else(|| host::readlink(dir_fd as i32, &path))
+    };
+    // The target is truncated to fit, and not NUL-terminated
+    let result = target.map(|target| {
+        let len = target.len().min(len);
+        hal::mem::copy_to_user(&target.as_bytes()[0..len], buf as *mut u8);
+        len as isize
+    });
+    let result = result.unwrap_or_else(|errno| errno);
+    log::trace!(
+        "readlinkat({}, {:?}, _, {}) = {}",
+        dir_fd as i32,
+        path,
+        len,
+        result
+    );
+    result
+}
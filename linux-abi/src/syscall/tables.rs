@@ -9,26 +9,75 @@ use super::{listing::*, SyscallHandler};
 // https://elixir.bootlin.com/linux/latest/source/include/uapi/asm-generic/unistd.h
 pub static TABLE_GENERIC: Map<u32, SyscallHandler> = phf_map! {
     17u32 => SYSCALL_GETCWD,
+    20u32 => SYSCALL_EPOLL_CREATE1,
+    21u32 => SYSCALL_EPOLL_CTL,
+    22u32 => SYSCALL_EPOLL_PWAIT,
     23u32 => SYSCALL_DUP,
     24u32 => SYSCALL_DUP3,
+    25u32 => SYSCALL_FCNTL,
+    29u32 => SYSCALL_IOCTL,
     34u32 => SYSCALL_MKDIRAT,
     35u32 => SYSCALL_UNLINKAT,
+    36u32 => SYSCALL_SYMLINKAT,
+    37u32 => SYSCALL_LINKAT,
+    45u32 => SYSCALL_TRUNCATE,
+    46u32 => SYSCALL_FTRUNCATE,
+    48u32 => SYSCALL_FACCESSAT,
     49u32 => SYSCALL_CHDIR,
     56u32 => SYSCALL_OPENAT,
     57u32 => SYSCALL_CLOSE,
     61u32 => SYSCALL_GETDENTS64,
+    62u32 => SYSCALL_LSEEK,
     63u32 => SYSCALL_READ,
     64u32 => SYSCALL_WRITE,
+    65u32 => SYSCALL_READV,
+    66u32 => SYSCALL_WRITEV,
+    67u32 => SYSCALL_PREAD64,
+    68u32 => SYSCALL_PWRITE64,
+    69u32 => SYSCALL_PREADV,
+    70u32 => SYSCALL_PWRITEV,
+    72u32 => SYSCALL_PSELECT6,
+    73u32 => SYSCALL_PPOLL,
+    78u32 => SYSCALL_READLINKAT,
+    79u32 => SYSCALL_NEWFSTATAT,
     80u32 => SYSCALL_FSTAT,
+    82u32 => SYSCALL_FSYNC,
+    83u32 => SYSCALL_FDATASYNC,
     93u32 => SYSCALL_EXIT,
+    101u32 => SYSCALL_NANOSLEEP,
+    113u32 => SYSCALL_CLOCK_GETTIME,
+    115u32 => SYSCALL_CLOCK_NANOSLEEP,
+    119u32 => SYSCALL_SCHED_SETSCHEDULER,
+    120u32 => SYSCALL_SCHED_GETSCHEDULER,
+    123u32 => SYSCALL_SCHED_GETAFFINITY,
     124u32 => SYSCALL_SCHED_YIELD,
+    140u32 => SYSCALL_SETPRIORITY,
+    141u32 => SYSCALL_GETPRIORITY,
+    153u32 => SYSCALL_TIMES,
+    169u32 => SYSCALL_GETTIMEOFDAY,
     172u32 => SYSCALL_GETPID,
     173u32 => SYSCALL_GETPPID,
+    198u32 => SYSCALL_SOCKET,
+    200u32 => SYSCALL_BIND,
+    201u32 => SYSCALL_LISTEN,
+    202u32 => SYSCALL_ACCEPT,
+    203u32 => SYSCALL_CONNECT,
+    204u32 => SYSCALL_GETSOCKNAME,
+    205u32 => SYSCALL_GETPEERNAME,
+    206u32 => SYSCALL_SENDTO,
+    207u32 => SYSCALL_RECVFROM,
+    208u32 => SYSCALL_SETSOCKOPT,
+    209u32 => SYSCALL_GETSOCKOPT,
+    210u32 => SYSCALL_SHUTDOWN,
     215u32 => SYSCALL_MUNMAP,
     220u32 => SYSCALL_CLONE,
     221u32 => SYSCALL_EXECVE_PRE,
     222u32 => SYSCALL_MMAP,
+    242u32 => SYSCALL_ACCEPT4,
     260u32 => SYSCALL_WAIT4,
+    276u32 => SYSCALL_RENAMEAT2,
+    278u32 => SYSCALL_GETRANDOM,
+    291u32 => SYSCALL_STATX,
 };
 
 // https://blog.rchapman.org/posts/Linux_System_Call_Table_for_x86_64/
@@ -36,22 +85,82 @@ pub static TABLE_X86_64: Map<u32, SyscallHandler> = phf_map! {
     0u32 => SYSCALL_READ,
     1u32 => SYSCALL_WRITE,
     3u32 => SYSCALL_CLOSE,
+    4u32 => SYSCALL_STAT,
     5u32 => SYSCALL_FSTAT,
+    6u32 => SYSCALL_LSTAT,
+    7u32 => SYSCALL_POLL,
+    8u32 => SYSCALL_LSEEK,
     9u32 => SYSCALL_MMAP,
     11u32 => SYSCALL_MUNMAP,
+    16u32 => SYSCALL_IOCTL,
+    17u32 => SYSCALL_PREAD64,
+    18u32 => SYSCALL_PWRITE64,
+    19u32 => SYSCALL_READV,
+    20u32 => SYSCALL_WRITEV,
+    23u32 => SYSCALL_SELECT,
     24u32 => SYSCALL_SCHED_YIELD,
     32u32 => SYSCALL_DUP,
+    35u32 => SYSCALL_NANOSLEEP,
     39u32 => SYSCALL_GETPID,
+    41u32 => SYSCALL_SOCKET,
+    42u32 => SYSCALL_CONNECT,
+    43u32 => SYSCALL_ACCEPT,
+    44u32 => SYSCALL_SENDTO,
+    45u32 => SYSCALL_RECVFROM,
+    48u32 => SYSCALL_SHUTDOWN,
+    49u32 => SYSCALL_BIND,
+    50u32 => SYSCALL_LISTEN,
+    51u32 => SYSCALL_GETSOCKNAME,
+    52u32 => SYSCALL_GETPEERNAME,
+    54u32 => SYSCALL_SETSOCKOPT,
+    55u32 => SYSCALL_GETSOCKOPT,
     56u32 => SYSCALL_CLONE,
     59u32 => SYSCALL_EXECVE_PRE,
     60u32 => SYSCALL_EXIT,
     61u32 => SYSCALL_WAIT4,
+    72u32 => SYSCALL_FCNTL,
+    74u32 => SYSCALL_FSYNC,
+    75u32 => SYSCALL_FDATASYNC,
+    76u32 => SYSCALL_TRUNCATE,
+    77u32 => SYSCALL_FTRUNCATE,
     79u32 => SYSCALL_GETCWD,
     80u32 => SYSCALL_CHDIR,
+    82u32 => SYSCALL_RENAME,
+    86u32 => SYSCALL_LINK,
+    88u32 => SYSCALL_SYMLINK,
+    96u32 => SYSCALL_GETTIMEOFDAY,
+    100u32 => SYSCALL_TIMES,
     110u32 => SYSCALL_GETPPID,
+    140u32 => SYSCALL_GETPRIORITY,
+    141u32 => SYSCALL_SETPRIORITY,
+    144u32 => SYSCALL_SCHED_SETSCHEDULER,
+    145u32 => SYSCALL_SCHED_GETSCHEDULER,
+    201u32 => SYSCALL_TIME,
+    204u32 => SYSCALL_SCHED_GETAFFINITY,
+    213u32 => SYSCALL_EPOLL_CREATE,
     217u32 => SYSCALL_GETDENTS64,
+    228u32 => SYSCALL_CLOCK_GETTIME,
+    230u32 => SYSCALL_CLOCK_NANOSLEEP,
+    232u32 => SYSCALL_EPOLL_WAIT,
+    233u32 => SYSCALL_EPOLL_CTL,
     257u32 => SYSCALL_OPENAT,
     258u32 => SYSCALL_MKDIRAT,
+    262u32 => SYSCALL_NEWFSTATAT,
     263u32 => SYSCALL_UNLINKAT,
+    264u32 => SYSCALL_RENAMEAT,
+    265u32 => SYSCALL_LINKAT,
+    266u32 => SYSCALL_SYMLINKAT,
+    267u32 => SYSCALL_READLINKAT,
+    269u32 => SYSCALL_FACCESSAT,
+    270u32 => SYSCALL_PSELECT6,
+    271u32 => SYSCALL_PPOLL,
+    281u32 => SYSCALL_EPOLL_PWAIT,
+    288u32 => SYSCALL_ACCEPT4,
+    291u32 => SYSCALL_EPOLL_CREATE1,
     292u32 => SYSCALL_DUP3,
+    295u32 => SYSCALL_PREADV,
+    296u32 => SYSCALL_PWRITEV,
+    316u32 => SYSCALL_RENAMEAT2,
+    318u32 => SYSCALL_GETRANDOM,
+    332u32 => SYSCALL_STATX,
 };
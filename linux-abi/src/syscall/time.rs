@@ -0,0 +1,198 @@
+use hal::time::{monotonic_ns, realtime_ns, NSEC_PER_SEC};
+
+use super::SyscallHandler;
+use crate::Errno;
+
+pub const SYSCALL_CLOCK_GETTIME: SyscallHandler = SyscallHandler::Syscall2(syscall_clock_gettime);
+pub const SYSCALL_CLOCK_NANOSLEEP: SyscallHandler =
+    SyscallHandler::Syscall4(syscall_clock_nanosleep);
+pub const SYSCALL_GETTIMEOFDAY: SyscallHandler = SyscallHandler::Syscall2(syscall_gettimeofday);
+pub const SYSCALL_NANOSLEEP: SyscallHandler = SyscallHandler::Syscall2(syscall_nanosleep);
+pub const SYSCALL_TIME: SyscallHandler = SyscallHandler::Syscall1(syscall_time);
+pub const SYSCALL_TIMES: SyscallHandler = SyscallHandler::Syscall1(syscall_times);
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+const CLOCK_THREAD_CPUTIME_ID: usize = 3;
+const CLOCK_MONOTONIC_RAW: usize = 4;
+const CLOCK_REALTIME_COARSE: usize = 5;
+const CLOCK_MONOTONIC_COARSE: usize = 6;
+const CLOCK_BOOTTIME: usize = 7;
+
+const TIMER_ABSTIME: usize = 1;
+
+/// The unit of `clock_t` (`USER_HZ`).
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct Timespec {
+    sec: i64,
+    nsec: i64,
+}
+
+impl Timespec {
+    pub(super) fn from_ns(ns: u64) -> Timespec {
+        Timespec {
+            sec: (ns / NSEC_PER_SEC) as i64,
+            nsec: (ns % NSEC_PER_SEC) as i64,
+        }
+    }
+
+    pub(super) fn to_ns(self) -> Result<u64, isize> {
+        if self.sec < 0 || !(0..NSEC_PER_SEC as i64).contains(&self.nsec) {
+            return Err(Errno::EINVAL.as_neg_isize());
+        }
+        Ok((self.sec as u64)
+            .saturating_mul(NSEC_PER_SEC)
+            .saturating_add(self.nsec as u64))
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct Timeval {
+    sec: i64,
+    usec: i64,
+}
+
+impl Timeval {
+    pub(super) fn from_ns(ns: u64) -> Timeval {
+        Timeval {
+            sec: (ns / NSEC_PER_SEC) as i64,
+            usec: (ns % NSEC_PER_SEC / 1000) as i64,
+        }
+    }
+
+    pub(super) fn to_ns(self) -> Result<u64, isize> {
+        if self.sec < 0 || !(0..1_000_000).contains(&self.usec) {
+            return Err(Errno::EINVAL.as_neg_isize());
+        }
+        Ok((self.sec as u64)
+            .saturating_mul(NSEC_PER_SEC)
+            .saturating_add(self.usec as u64 * 1000))
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Timezone {
+    minuteswest: i32,
+    dsttime: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Tms {
+    utime: i64,
+    stime: i64,
+    cutime: i64,
+    cstime: i64,
+}
+
+fn clock_ns(clock: usize) -> Result<u64, isize> {
+    match clock {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => Ok(realtime_ns()),
+        // The enclave never suspends, so the boot time is the monotonic time
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => {
+            Ok(monotonic_ns())
+        }
+        // Every process has a single thread
+        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => {
+            Ok(hal::task::current().lock().cpu_time_ns())
+        }
+        _ => {
+            log::warn!("clock_gettime: Unsupported clock: {}", clock);
+            Err(Errno::EINVAL.as_neg_isize())
+        }
+    }
+}
+
+unsafe fn syscall_clock_gettime(clock: usize, tp: usize) -> isize {
+    match clock_ns(clock) {
+        Ok(ns) => {
+            hal::mem::write_to_user(tp as *mut Timespec, Timespec::from_ns(ns));
+            0
+        }
+        Err(errno) => errno,
+    }
+}
+
+unsafe fn syscall_gettimeofday(tv: usize, tz: usize) -> isize {
+    let now = realtime_ns();
+    if tv != 0 {
+        hal::mem::write_to_user(tv as *mut Timeval, Timeval::from_ns(now));
+    }
+    if tz != 0 {
+        // The enclave keeps UTC
+        let tz_value = Timezone {
+            minuteswest: 0,
+            dsttime: 0,
+        };
+        hal::mem::write_to_user(tz as *mut Timezone, tz_value);
+    }
+    0
+}
+
+unsafe fn syscall_time(tloc: usize) -> isize {
+    let now = (realtime_ns() / NSEC_PER_SEC) as i64;
+    if tloc != 0 {
+        hal::mem::write_to_user(tloc as *mut i64, now);
+    }
+    now as isize
+}
+
+/// Sleeps are never interrupted, as there are no signal handlers, so the
+/// remaining time is never written back.
+unsafe fn syscall_nanosleep(req: usize, _rem: usize) -> isize {
+    let duration = match hal::mem::read_from_user(req as *const Timespec).to_ns() {
+        Ok(duration) => duration,
+        Err(errno) => return errno,
+    };
+    hal::time::sleep_until(monotonic_ns().saturating_add(duration));
+    0
+}
+
+unsafe fn syscall_clock_nanosleep(clock: usize, flags: usize, req: usize, _rem: usize) -> isize {
+    if flags & !TIMER_ABSTIME != 0 {
+        return Errno::EINVAL.as_neg_isize();
+    }
+    let time = match hal::mem::read_from_user(req as *const Timespec).to_ns() {
+        Ok(time) => time,
+        Err(errno) => return errno,
+    };
+    let now = match clock {
+        CLOCK_REALTIME => realtime_ns(),
+        CLOCK_MONOTONIC | CLOCK_BOOTTIME => monotonic_ns(),
+        _ => {
+            log::warn!("clock_nanosleep: Unsupported clock: {}", clock);
+            return Errno::EINVAL.as_neg_isize();
+        }
+    };
+    // The real-time clock moves in lockstep with the monotonic clock, so an
+    // absolute deadline on either turns into the same monotonic deadline
+    let duration = if flags & TIMER_ABSTIME != 0 {
+        time.saturating_sub(now)
+    } else {
+        time
+    };
+    hal::time::sleep_until(monotonic_ns().saturating_add(duration));
+    0
+}
+
+unsafe fn syscall_times(buf: usize) -> isize {
+    const NSEC_PER_CLOCK_TICK: u64 = NSEC_PER_SEC / CLOCK_TICKS_PER_SEC;
+    if buf != 0 {
+        // Kernel and user mode are not told apart, and children's times are
+        // not collected
+        let tms = Tms {
+            utime: (hal::task::current().lock().cpu_time_ns() / NSEC_PER_CLOCK_TICK) as i64,
+            stime: 0,
+            cutime: 0,
+            cstime: 0,
+        };
+        hal::mem::write_to_user(buf as *mut Tms, tms);
+    }
+    (monotonic_ns() / NSEC_PER_CLOCK_TICK) as isize
+}
@@ -0,0 +1,1938 @@
+//! Exercises the syscall handlers against an in-process edge responder.
+
+use std::{
+    io::Write,
+    sync::{Arc, Mutex, MutexGuard, Once},
+};
+
+use edge_proto::{notify::Notification, DataRegion, EdgeCallReq, EdgeCallResp};
+
+use linux_abi::syscall::{listing::*, SyscallHandler};
+
+const AT_FDCWD: usize = -100isize as usize;
+const O_RDONLY: usize = 0;
+const O_WRONLY: usize = 0o1;
+const O_RDWR: usize = 0o2;
+const O_CREAT: usize = 0o100;
+const O_TRUNC: usize = 0o1000;
+const O_APPEND: usize = 0o2000;
+const O_NONBLOCK: usize = 0o4000;
+const O_DIRECTORY: usize = 0o200000;
+const O_NOFOLLOW: usize = 0o400000;
+const O_CLOEXEC: usize = 0o2000000;
+const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+const AT_EMPTY_PATH: usize = 0x1000;
+const AT_SYMLINK_FOLLOW: usize = 0x400;
+const RENAME_NOREPLACE: usize = 1;
+const R_OK: usize = 4;
+const SEEK_SET: usize = 0;
+const SEEK_CUR: usize = 1;
+const SEEK_END: usize = 2;
+const W_OK: usize = 2;
+
+const EPERM: isize = -1;
+const ENOENT: isize = -2;
+const ESRCH: isize = -3;
+const EIO: isize = -5;
+const EBADF: isize = -9;
+const EAGAIN: isize = -11;
+const EINVAL: isize = -22;
+const EISDIR: isize = -21;
+const EACCES: isize = -13;
+const EEXIST: isize = -17;
+const EXDEV: isize = -18;
+const ENOTTY: isize = -25;
+const EFBIG: isize = -27;
+const EROFS: isize = -30;
+const ENOTEMPTY: isize = -39;
+const ELOOP: isize = -40;
+const EBADMSG: isize = -74;
+const SECRET: &str = "secret.txt";
+const MAX_FILE_SIZE: usize = 0x800_000;
+const POLICY: &str = "
+read-only /ro # except for scratch
+writable /ro/scratch
+deny /ro/hidden
+max-file-size 8388608
+";
+
+static SETUP: Once = Once::new();
+// The edge caller is not reentrant, so tests must not run concurrently.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn host_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("linux-abi-loopback-{}", std::process::id()))
+}
+
+fn setup() -> MutexGuard<'static, ()> {
+    SETUP.call_once(|| {
+        // The guest root sits next to a file the guest must not reach.
+        let root = host_dir().join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(host_dir().join(SECRET), "secret").unwrap();
+        std::fs::create_dir_all(root.join("ro/scratch")).unwrap();
+        std::fs::create_dir_all(root.join("ro/hidden")).unwrap();
+        std::fs::write(root.join("ro/existing.txt"), "read-only").unwrap();
+        std::fs::write(root.join("ro/hidden/hidden.txt"), "hidden").unwrap();
+        std::fs::create_dir_all(root.join("protected")).unwrap();
+
+        hal::arch::loopback::set_guest_root(root.to_str().unwrap());
+        hal::arch::loopback::set_fs_policy(POLICY);
+        hal::arch::loopback::initialize_edge_caller();
+        hal::time::init();
+        linux_abi::fs::init();
+        let image = linux_abi::fs::initramfs::InitramFs::new(Box::leak(initramfs_image()))
+            .expect("the test archive is invalid");
+        linux_abi::fs::vfs::mount("/initramfs", Arc::new(image));
+        let task = hal::task::Task::create(
+            hal::task::TaskMmStruct::new(hal::vm::UserAddressSpace, 0..0),
+            &Default::default(),
+        );
+        assert_eq!(task.lock().pid, 1, "init must match the responder's PCB");
+        hal::arch::loopback::set_current_task(&task);
+        // Keep the PCB alive for the rest of the process.
+        std::mem::forget(Arc::clone(&task));
+    });
+    SERIAL.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+unsafe fn invoke(handler: SyscallHandler, args: &[usize]) -> isize {
+    let arg = |i: usize| args.get(i).copied().unwrap_or(0);
+    match handler {
+        SyscallHandler::Syscall0(f) => f(),
+        SyscallHandler::Syscall1(f) => f(arg(0)),
+        SyscallHandler::Syscall2(f) => f(arg(0), arg(1)),
+        SyscallHandler::Syscall3(f) => f(arg(0), arg(1), arg(2)),
+        SyscallHandler::Syscall4(f) => f(arg(0), arg(1), arg(2), arg(3)),
+        SyscallHandler::Syscall5(f) => f(arg(0), arg(1), arg(2), arg(3), arg(4)),
+        SyscallHandler::Syscall6(f) => f(arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)),
+        _ => panic!("unsupported syscall handler"),
+    }
+}
+
+fn openat(path: &str, flags: usize, mode: usize) -> isize {
+    openat_dir(AT_FDCWD, path, flags, mode)
+}
+
+fn openat_dir(dir_fd: usize, path: &str, flags: usize, mode: usize) -> isize {
+    let path = format!("{}\0", path);
+    unsafe {
+        invoke(
+            SYSCALL_OPENAT,
+            &[dir_fd, path.as_ptr() as usize, flags, mode],
+        )
+    }
+}
+
+fn close(fd: isize) {
+    assert_eq!(unsafe { invoke(SYSCALL_CLOSE, &[fd as usize]) }, 0);
+}
+
+#[test]
+fn write_then_read_back() {
+    let _guard = setup();
+    let data = b"hello from the loopback edge";
+
+    let fd = openat("/rw.txt", O_WRONLY | O_CREAT | O_TRUNC, 0o644);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let written = unsafe {
+        invoke(
+            SYSCALL_WRITE,
+            &[fd as usize, data.as_ptr() as usize, data.len()],
+        )
+    };
+    assert_eq!(written, data.len() as isize);
+    close(fd);
+
+    let fd = openat("/rw.txt", O_RDONLY, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let mut buf = [0u8; 64];
+    let read = unsafe {
+        invoke(
+            SYSCALL_READ,
+            &[fd as usize, buf.as_mut_ptr() as usize, buf.len()],
+        )
+    };
+    assert_eq!(&buf[0..read as usize], data);
+    close(fd);
+}
+
+#[test]
+fn large_transfer_spans_several_edge_calls() {
+    let _guard = setup();
+    let data: Vec<u8> = (0..hal::edge::EDGE_BUFFER_SIZE * 2 + 123)
+        .map(|i| i as u8)
+        .collect();
+
+    let fd = openat("/large.bin", O_WRONLY | O_CREAT | O_TRUNC, 0o644);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let written = unsafe {
+        invoke(
+            SYSCALL_WRITE,
+            &[fd as usize, data.as_ptr() as usize, data.len()],
+        )
+    };
+    assert_eq!(written, data.len() as isize);
+    close(fd);
+
+    let fd = openat("/large.bin", O_RDONLY, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    // Ask for more than the file holds, so the last chunk comes back short.
+    let mut buf = vec![0u8; data.len() + 1000];
+    let read = unsafe {
+        invoke(
+            SYSCALL_READ,
+            &[fd as usize, buf.as_mut_ptr() as usize, buf.len()],
+        )
+    };
+    assert_eq!(read, data.len() as isize);
+    assert!(buf[0..data.len()] == data[..]);
+    close(fd);
+}
+
+/// Flatten buffers into the `struct iovec` array the syscalls take.
+fn iovecs(buffers: &[(*const u8, usize)]) -> Vec<usize> {
+    buffers
+        .iter()
+        .flat_map(|&(ptr, len)| [ptr as usize, len])
+        .collect()
+}
+
+fn lseek(fd: isize, offset: isize, whence: usize) -> isize {
+    unsafe { invoke(SYSCALL_LSEEK, &[fd as usize, offset as usize, whence]) }
+}
+
+#[test]
+fn vectored_and_positional_io() {
+    let _guard = setup();
+    // More buffers than one edge call carries, and one larger than the data
+    // zone
+    let small: Vec<Vec<u8>> = (0..100u8).map(|i| vec![i; 3]).collect();
+    let large: Vec<u8> = (0..hal::edge::EDGE_BUFFER_SIZE + 77)
+        .map(|i| (i * 7) as u8)
+        .collect();
+    let mut data: Vec<u8> = small.concat();
+    data.extend(&large);
+    let mut buffers: Vec<_> = small.iter().map(|buf| (buf.as_ptr(), buf.len())).collect();
+    buffers.push((large.as_ptr(), large.len()));
+    let iov = iovecs(&buffers);
+
+    for path in [
+        "/vectored.bin",
+        "/tmp/vectored.bin",
+        "/protected/vectored.bin",
+    ] {
+        let fd = openat(path, O_RDWR | O_CREAT | O_TRUNC, 0o644);
+        assert!(fd >= 0, "openat failed: {}", fd);
+        let args = [fd as usize, iov.as_ptr() as usize, buffers.len()];
+        let written = unsafe { invoke(SYSCALL_WRITEV, &args) };
+        assert_eq!(written, data.len() as isize, "{}", path);
+        assert_eq!(lseek(fd, 0, SEEK_CUR), data.len() as isize);
+
+        // Positional I/O leaves the file position alone
+        let mut buf = [0u8; 10];
+        let args = [fd as usize, buf.as_mut_ptr() as usize, buf.len(), 299];
+        assert_eq!(unsafe { invoke(SYSCALL_PREAD64, &args) }, 10);
+        assert_eq!(buf[..], data[299..309]);
+        let patch = b"XY";
+        let args = [fd as usize, patch.as_ptr() as usize, patch.len(), 1];
+        assert_eq!(unsafe { invoke(SYSCALL_PWRITE64, &args) }, 2);
+        data[1..3].copy_from_slice(patch);
+        assert_eq!(lseek(fd, 0, SEEK_CUR), data.len() as isize);
+
+        assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+        let mut head = [0u8; 4];
+        let mut rest = vec![0u8; data.len()];
+        let iov = iovecs(&[
+            (head.as_mut_ptr(), head.len()),
+            (rest.as_mut_ptr(), rest.len()),
+        ]);
+        let read = unsafe { invoke(SYSCALL_READV, &[fd as usize, iov.as_ptr() as usize, 2]) };
+        assert_eq!(read, data.len() as isize);
+        assert_eq!(head[..], data[0..4]);
+        assert!(rest[0..data.len() - 4] == data[4..]);
+
+        let mut tail = [0u8; 8];
+        let iov = iovecs(&[(tail.as_mut_ptr(), tail.len())]);
+        let offset = data.len() - 5;
+        let args = [fd as usize, iov.as_ptr() as usize, 1, offset];
+        assert_eq!(unsafe { invoke(SYSCALL_PREADV, &args) }, 5);
+        assert_eq!(tail[0..5], data[offset..]);
+        assert_eq!(lseek(fd, -2, SEEK_END), data.len() as isize - 2);
+        assert_eq!(lseek(fd, -1, SEEK_SET), EINVAL);
+        assert_eq!(lseek(fd, 0, 42), EINVAL);
+        close(fd);
+    }
+}
+
+#[test]
+fn edge_calls_while_another_channel_is_held() {
+    let _guard = setup();
+    let data = b"written through a second channel";
+
+    let fd = openat("/nested.txt", O_WRONLY | O_CREAT | O_TRUNC, 0o644);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    // Hold one channel; the write must be served by another one.
+    let written = hal::edge::with_edge_caller(|_held| unsafe {
+        invoke(
+            SYSCALL_WRITE,
+            &[fd as usize, data.as_ptr() as usize, data.len()],
+        )
+    });
+    assert_eq!(written, data.len() as isize);
+    close(fd);
+}
+
+#[test]
+#[should_panic(expected = "shared edge channels are held")]
+fn edge_calls_while_every_channel_is_held() {
+    let _guard = setup();
+    // Each level holds a channel until the next one is served
+    fn hold() {
+        hal::edge::with_edge_caller(|_held| hold());
+    }
+    hold();
+}
+
+#[test]
+fn openat_missing_file() {
+    let _guard = setup();
+    assert_eq!(openat("/no-such-file", O_RDONLY, 0), ENOENT);
+}
+
+/// List the entries of the directory at `path`, sorted by name.
+fn list_dir(path: &str) -> Vec<String> {
+    let fd = openat(path, O_RDONLY | O_DIRECTORY, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let mut buf = [0u8; 1024];
+    let len = unsafe {
+        invoke(
+            SYSCALL_GETDENTS64,
+            &[fd as usize, buf.as_mut_ptr() as usize, buf.len()],
+        )
+    };
+    assert!(len > 0, "getdents64 failed: {}", len);
+    close(fd);
+
+    // struct linux_dirent64 { u64 d_ino; i64 d_off; u16 d_reclen; u8 d_type; char d_name[]; }
+    let mut names = Vec::new();
+    let mut pos = 0;
+    while pos < len as usize {
+        let reclen = u16::from_ne_bytes([buf[pos + 16], buf[pos + 17]]) as usize;
+        let name = &buf[pos + 19..pos + reclen];
+        let name = &name[0..name.iter().position(|&b| b == 0).unwrap()];
+        names.push(String::from_utf8(name.to_vec()).unwrap());
+        pos += reclen;
+    }
+    names.sort();
+    names
+}
+
+#[test]
+fn getdents64_lists_directory() {
+    let _guard = setup();
+
+    let dir = b"/listing\0";
+    let result = unsafe { invoke(SYSCALL_MKDIRAT, &[AT_FDCWD, dir.as_ptr() as usize, 0o755]) };
+    assert!(result == 0 || result == -17, "mkdirat failed: {}", result); // EEXIST
+    for name in ["a", "b"] {
+        close(openat(
+            &format!("/listing/{}", name),
+            O_WRONLY | O_CREAT,
+            0o644,
+        ));
+    }
+
+    assert_eq!(list_dir("/listing"), [".", "..", "a", "b"]);
+}
+
+#[test]
+fn dotdot_stays_in_the_guest_root() {
+    let _guard = setup();
+    for path in ["/../secret.txt", "../secret.txt", "/a/../../../secret.txt"] {
+        assert_eq!(openat(path, O_RDONLY, 0), ENOENT, "{} escaped", path);
+    }
+
+    let dir = openat("/", O_RDONLY | O_DIRECTORY, 0);
+    assert!(dir >= 0, "openat failed: {}", dir);
+    assert_eq!(
+        openat_dir(dir as usize, "../secret.txt", O_RDONLY, 0),
+        ENOENT
+    );
+    close(dir);
+}
+
+#[test]
+fn symlinks_stay_in_the_guest_root() {
+    let _guard = setup();
+    let root = host_dir().join("root");
+    let secret = host_dir().join(SECRET);
+    for (link, target) in [
+        ("relative-link", std::path::Path::new("../secret.txt")),
+        ("absolute-link", &secret),
+        ("parent-link", std::path::Path::new("..")),
+    ] {
+        let _ = std::fs::remove_file(root.join(link));
+        std::os::unix::fs::symlink(target, root.join(link)).unwrap();
+    }
+
+    for path in [
+        "/relative-link",
+        "/absolute-link",
+        "/parent-link/secret.txt",
+    ] {
+        assert_eq!(openat(path, O_RDONLY, 0), ENOENT, "{} escaped", path);
+    }
+    // Files are not created through dangling links while a policy is set.
+    assert_eq!(openat("/relative-link", O_WRONLY | O_CREAT, 0o644), ELOOP);
+    assert_eq!(std::fs::read_to_string(&secret).unwrap(), "secret");
+
+    let dir = openat("/parent-link", O_RDONLY | O_DIRECTORY, 0);
+    assert!(dir >= 0, "openat failed: {}", dir);
+    let path = b"secret.txt\0";
+    let result = unsafe { invoke(SYSCALL_UNLINKAT, &[dir as usize, path.as_ptr() as usize, 0]) };
+    assert_eq!(result, ENOENT);
+    close(dir);
+    assert!(secret.exists());
+}
+
+fn mkdirat(path: &str) -> isize {
+    let path = format!("{}\0", path);
+    unsafe { invoke(SYSCALL_MKDIRAT, &[AT_FDCWD, path.as_ptr() as usize, 0o755]) }
+}
+
+fn unlinkat(path: &str) -> isize {
+    let path = format!("{}\0", path);
+    unsafe { invoke(SYSCALL_UNLINKAT, &[AT_FDCWD, path.as_ptr() as usize, 0]) }
+}
+
+#[test]
+fn policy_keeps_read_only_paths_unmodified() {
+    let _guard = setup();
+    let fd = openat("/ro/existing.txt", O_RDONLY, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    close(fd);
+
+    assert_eq!(openat("/ro/existing.txt", O_WRONLY, 0), EROFS);
+    assert_eq!(openat("/ro/new.txt", O_WRONLY | O_CREAT, 0o644), EROFS);
+    assert_eq!(mkdirat("/ro/new-dir"), EROFS);
+    assert_eq!(unlinkat("/ro/existing.txt"), EROFS);
+
+    let fd = openat("/ro/scratch/new.txt", O_WRONLY | O_CREAT, 0o644);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    close(fd);
+    assert_eq!(unlinkat("/ro/scratch/new.txt"), 0);
+}
+
+#[test]
+fn policy_applies_to_symlink_targets() {
+    let _guard = setup();
+    let root = host_dir().join("root");
+    for (link, target) in [
+        ("ro/scratch/existing-link", "/ro/existing.txt"),
+        ("hidden-link", "/ro/hidden"),
+    ] {
+        let _ = std::fs::remove_file(root.join(link));
+        std::os::unix::fs::symlink(target, root.join(link)).unwrap();
+    }
+
+    assert_eq!(openat("/ro/hidden/hidden.txt", O_RDONLY, 0), EACCES);
+    assert_eq!(openat("/hidden-link/hidden.txt", O_RDONLY, 0), EACCES);
+    assert_eq!(openat("/ro/scratch/existing-link", O_WRONLY, 0), EROFS);
+    assert_eq!(
+        std::fs::read_to_string(root.join("ro/existing.txt")).unwrap(),
+        "read-only"
+    );
+}
+
+#[test]
+fn policy_caps_the_file_size() {
+    let _guard = setup();
+    let data = vec![0x5au8; MAX_FILE_SIZE + 100];
+
+    let fd = openat("/capped.bin", O_WRONLY | O_CREAT | O_TRUNC, 0o644);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let write =
+        |len: usize| unsafe { invoke(SYSCALL_WRITE, &[fd as usize, data.as_ptr() as usize, len]) };
+    assert_eq!(write(data.len()), MAX_FILE_SIZE as isize);
+    assert_eq!(write(1), EFBIG);
+    close(fd);
+}
+
+fn write_file(path: &str, data: &[u8]) {
+    let fd = openat(path, O_WRONLY | O_CREAT | O_TRUNC, 0o644);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let written = unsafe {
+        invoke(
+            SYSCALL_WRITE,
+            &[fd as usize, data.as_ptr() as usize, data.len()],
+        )
+    };
+    assert_eq!(written, data.len() as isize);
+    close(fd);
+}
+
+#[test]
+fn protected_files_reach_the_host_encrypted() {
+    let _guard = setup();
+    let data: Vec<u8> = b"top secret ".repeat(1000);
+    write_file("/protected/secret.txt", &data);
+
+    let stored = std::fs::read(host_dir().join("root/protected/secret.txt")).unwrap();
+    assert!(!stored.windows(11).any(|window| window == b"top secret "));
+
+    let fd = openat("/protected/../protected/secret.txt", O_RDONLY, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let stat = fstat(fd);
+    assert_eq!(
+        i64::from_ne_bytes(stat[48..56].try_into().unwrap()),
+        data.len() as i64
+    );
+    let mut buf = vec![0u8; data.len() + 10];
+    let read = unsafe {
+        invoke(
+            SYSCALL_READ,
+            &[fd as usize, buf.as_mut_ptr() as usize, buf.len()],
+        )
+    };
+    assert_eq!(&buf[0..read as usize], data);
+    close(fd);
+}
+
+#[test]
+fn protected_files_detect_tampering() {
+    let _guard = setup();
+    write_file("/protected/tampered.txt", b"original contents");
+
+    let host_path = host_dir().join("root/protected/tampered.txt");
+    let mut stored = std::fs::read(&host_path).unwrap();
+    let last = stored.len() - 1;
+    stored[last] ^= 1;
+    std::fs::write(&host_path, stored).unwrap();
+    assert_eq!(openat("/protected/tampered.txt", O_RDONLY, 0), EIO);
+}
+
+#[test]
+fn protected_files_detect_rollback() {
+    let _guard = setup();
+    write_file("/protected/rollback.txt", b"version 1");
+    let host_path = host_dir().join("root/protected/rollback.txt");
+    let old = std::fs::read(&host_path).unwrap();
+    write_file("/protected/rollback.txt", b"version 2");
+
+    std::fs::write(&host_path, old).unwrap();
+    assert_eq!(openat("/protected/rollback.txt", O_RDONLY, 0), EIO);
+}
+
+#[test]
+fn protected_files_detect_truncation() {
+    let _guard = setup();
+    // As after a restart, when the enclave knows no version of the file
+    let host_path = host_dir().join("root/protected/truncated.txt");
+    std::fs::write(&host_path, b"").unwrap();
+    assert_eq!(openat("/protected/truncated.txt", O_RDONLY, 0), EIO);
+    assert_eq!(
+        openat("/protected/truncated.txt", O_RDWR | O_CREAT, 0o644),
+        EIO
+    );
+
+    std::fs::remove_file(&host_path).unwrap();
+    write_file("/protected/truncated.txt", b"");
+    assert_eq!(read_file("/protected/truncated.txt"), b"");
+}
+
+fn fstat(fd: isize) -> [u8; 144] {
+    let mut stat = [0u8; 144];
+    assert_eq!(
+        unsafe { invoke(SYSCALL_FSTAT, &[fd as usize, stat.as_mut_ptr() as usize]) },
+        0
+    );
+    stat
+}
+
+fn newfstatat(path: &str, flags: usize) -> Result<[u8; 144], isize> {
+    let path = format!("{}\0", path);
+    let mut stat = [0u8; 144];
+    let args = [
+        AT_FDCWD,
+        path.as_ptr() as usize,
+        stat.as_mut_ptr() as usize,
+        flags,
+    ];
+    match unsafe { invoke(SYSCALL_NEWFSTATAT, &args) } {
+        0 => Ok(stat),
+        errno => Err(errno),
+    }
+}
+
+/// The `st_mode` and `st_size` of a `struct stat`.
+fn mode_and_size(stat: &[u8; 144]) -> (u32, i64) {
+    (
+        u32::from_ne_bytes(stat[24..28].try_into().unwrap()),
+        i64::from_ne_bytes(stat[48..56].try_into().unwrap()),
+    )
+}
+
+fn faccessat(path: &str, mode: usize) -> isize {
+    let path = format!("{}\0", path);
+    unsafe { invoke(SYSCALL_FACCESSAT, &[AT_FDCWD, path.as_ptr() as usize, mode]) }
+}
+
+fn readlinkat(path: &str, len: usize) -> Result<String, isize> {
+    let path = format!("{}\0", path);
+    let mut buf = vec![0u8; len];
+    let args = [
+        AT_FDCWD,
+        path.as_ptr() as usize,
+        buf.as_mut_ptr() as usize,
+        len,
+    ];
+    match unsafe { invoke(SYSCALL_READLINKAT, &args) } {
+        len if len >= 0 => Ok(String::from_utf8(buf[0..len as usize].to_vec()).unwrap()),
+        errno => Err(errno),
+    }
+}
+
+#[test]
+fn stat_family_on_host_files() {
+    let _guard = setup();
+    let root = host_dir().join("root");
+    let _ = std::fs::remove_file(root.join("ro/link"));
+    std::os::unix::fs::symlink("existing.txt", root.join("ro/link")).unwrap();
+
+    let stat = newfstatat("/ro/link", 0).unwrap();
+    assert_eq!(mode_and_size(&stat).1, 9);
+    let ino = std::os::unix::fs::MetadataExt::ino(
+        &std::fs::metadata(root.join("ro/existing.txt")).unwrap(),
+    );
+    assert_eq!(u64::from_ne_bytes(stat[8..16].try_into().unwrap()), ino);
+    let (mode, _) = mode_and_size(&newfstatat("/ro/link", AT_SYMLINK_NOFOLLOW).unwrap());
+    assert_eq!(mode & 0o170000, 0o120000);
+    assert_eq!(readlinkat("/ro/link", 64).as_deref(), Ok("existing.txt"));
+    assert_eq!(readlinkat("/ro/link", 5).as_deref(), Ok("exist"));
+    assert_eq!(readlinkat("/ro/existing.txt", 64), Err(EINVAL));
+
+    // An fd and the path it was opened at agree
+    let fd = openat("/ro/existing.txt", O_RDONLY, 0);
+    assert_eq!(fstat(fd), stat);
+    let empty = [0u8];
+    let mut by_fd = [0u8; 144];
+    let args = [
+        fd as usize,
+        empty.as_ptr() as usize,
+        by_fd.as_mut_ptr() as usize,
+        AT_EMPTY_PATH,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_NEWFSTATAT, &args) }, 0);
+    assert_eq!(by_fd, stat);
+    close(fd);
+
+    assert_eq!(newfstatat("/ro/missing", 0), Err(ENOENT));
+    assert_eq!(newfstatat("/ro/hidden/hidden.txt", 0), Err(EACCES));
+    assert_eq!(newfstatat("/relative-link", 0), Err(ENOENT));
+    assert_eq!(faccessat("/ro/existing.txt", R_OK), 0);
+    assert_eq!(faccessat("/ro/existing.txt", W_OK), EROFS);
+    assert_eq!(faccessat("/ro/scratch", W_OK), 0);
+}
+
+#[test]
+fn stat_family_on_enclave_files() {
+    let _guard = setup();
+    let (mode, _) = mode_and_size(&newfstatat("/proc/self", AT_SYMLINK_NOFOLLOW).unwrap());
+    assert_eq!(mode & 0o170000, 0o120000);
+    let (mode, _) = mode_and_size(&newfstatat("/proc/self", 0).unwrap());
+    assert_eq!(mode & 0o170000, 0o040000);
+    assert_eq!(readlinkat("/proc/self", 64).as_deref(), Ok("/proc/1"));
+    assert_eq!(faccessat("/proc/cpuinfo", W_OK), EACCES);
+    assert_eq!(faccessat("/initramfs/bin/init", R_OK), 0);
+    assert_eq!(faccessat("/initramfs/bin/init", W_OK), EROFS);
+
+    // Protected files report the size of their plaintext
+    write_file("/protected/stat.txt", b"plaintext");
+    let (_, size) = mode_and_size(&newfstatat("/protected/stat.txt", 0).unwrap());
+    assert_eq!(size, 9);
+
+    // struct statx: stx_mode, then st_rdev as major and minor
+    let path = b"/dev/null\0";
+    let mut statx = [0u8; 256];
+    let args = [
+        AT_FDCWD,
+        path.as_ptr() as usize,
+        0,
+        0x7ff,
+        statx.as_mut_ptr() as usize,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_STATX, &args) }, 0);
+    assert_eq!(
+        u16::from_ne_bytes(statx[28..30].try_into().unwrap()),
+        0o020666
+    );
+    assert_eq!(u32::from_ne_bytes(statx[128..132].try_into().unwrap()), 1);
+    assert_eq!(u32::from_ne_bytes(statx[132..136].try_into().unwrap()), 3);
+}
+
+#[test]
+fn tmpfs_files_stay_in_the_enclave() {
+    let _guard = setup();
+    let data = b"scratch data".repeat(10000);
+    assert_eq!(mkdirat("/tmp/work"), 0);
+    write_file("/tmp/work/scratch.bin", &data);
+    assert!(!host_dir().join("root/tmp").exists());
+    assert_eq!(list_dir("/tmp/work"), [".", "..", "scratch.bin"]);
+
+    let dir_fd = openat("/tmp/work", O_RDONLY | O_DIRECTORY, 0);
+    assert!(dir_fd >= 0, "openat failed: {}", dir_fd);
+    let fd = openat_dir(dir_fd as usize, "../work/scratch.bin", O_RDONLY, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    close(dir_fd);
+    let stat = fstat(fd);
+    // st_mode and st_size
+    assert_eq!(
+        u32::from_ne_bytes(stat[24..28].try_into().unwrap()) & 0o170000,
+        0o100000
+    );
+    assert_eq!(
+        i64::from_ne_bytes(stat[48..56].try_into().unwrap()),
+        data.len() as i64
+    );
+    let mut buf = vec![0u8; data.len() + 10];
+    let read = unsafe {
+        invoke(
+            SYSCALL_READ,
+            &[fd as usize, buf.as_mut_ptr() as usize, buf.len()],
+        )
+    };
+    assert_eq!(&buf[0..read as usize], data);
+    close(fd);
+
+    assert_eq!(unlinkat("/tmp/work"), EISDIR);
+    assert_eq!(unlinkat("/tmp/work/scratch.bin"), 0);
+    assert_eq!(openat("/tmp/work/scratch.bin", O_RDONLY, 0), ENOENT);
+    assert_eq!(list_dir("/tmp/work"), [".", ".."]);
+}
+
+#[test]
+fn enclave_files_do_not_grow_past_the_cap() {
+    let _guard = setup();
+    let data = b"x";
+    for path in ["/tmp/huge.bin", "/protected/huge.bin"] {
+        let fd = openat(path, O_RDWR | O_CREAT | O_TRUNC, 0o644);
+        assert!(fd >= 0, "openat failed: {}", fd);
+        let args = [fd as usize, data.as_ptr() as usize, data.len(), 1 << 50];
+        assert_eq!(
+            unsafe { invoke(SYSCALL_PWRITE64, &args) },
+            EFBIG,
+            "{}",
+            path
+        );
+        assert_eq!(lseek(fd, 1 << 50, SEEK_SET), 1 << 50);
+        let args = [fd as usize, data.as_ptr() as usize, data.len()];
+        assert_eq!(unsafe { invoke(SYSCALL_WRITE, &args) }, EFBIG, "{}", path);
+        assert_eq!(i64::from_ne_bytes(fstat(fd)[48..56].try_into().unwrap()), 0);
+        close(fd);
+        assert_eq!(unlinkat(path), 0);
+    }
+}
+
+fn read_file(path: &str) -> Vec<u8> {
+    let fd = openat(path, O_RDONLY, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let mut buf = vec![0u8; 4096];
+    let read = unsafe {
+        invoke(
+            SYSCALL_READ,
+            &[fd as usize, buf.as_mut_ptr() as usize, buf.len()],
+        )
+    };
+    assert!(read >= 0, "read failed: {}", read);
+    close(fd);
+    buf.truncate(read as usize);
+    buf
+}
+
+fn renameat2(old: &str, new: &str, flags: usize) -> isize {
+    let (old, new) = (format!("{}\0", old), format!("{}\0", new));
+    let args = [
+        AT_FDCWD,
+        old.as_ptr() as usize,
+        AT_FDCWD,
+        new.as_ptr() as usize,
+        flags,
+    ];
+    unsafe { invoke(SYSCALL_RENAMEAT2, &args) }
+}
+
+fn linkat(old: &str, new: &str, flags: usize) -> isize {
+    let (old, new) = (format!("{}\0", old), format!("{}\0", new));
+    let args = [
+        AT_FDCWD,
+        old.as_ptr() as usize,
+        AT_FDCWD,
+        new.as_ptr() as usize,
+        flags,
+    ];
+    unsafe { invoke(SYSCALL_LINKAT, &args) }
+}
+
+fn symlinkat(target: &str, path: &str) -> isize {
+    let (target, path) = (format!("{}\0", target), format!("{}\0", path));
+    let args = [target.as_ptr() as usize, AT_FDCWD, path.as_ptr() as usize];
+    unsafe { invoke(SYSCALL_SYMLINKAT, &args) }
+}
+
+fn truncate(path: &str, len: usize) -> isize {
+    let path = format!("{}\0", path);
+    unsafe { invoke(SYSCALL_TRUNCATE, &[path.as_ptr() as usize, len]) }
+}
+
+/// The `st_nlink` of a `struct stat`.
+fn nlink(stat: &[u8; 144]) -> u64 {
+    u64::from_ne_bytes(stat[16..24].try_into().unwrap())
+}
+
+#[test]
+fn rename_truncate_and_sync() {
+    let _guard = setup();
+    for dir in ["/renames", "/tmp/renames", "/protected/renames"] {
+        assert_eq!(mkdirat(dir), 0);
+        let (a, b) = (format!("{}/a", dir), format!("{}/b", dir));
+        write_file(&a, b"new contents");
+        write_file(&b, b"old contents");
+
+        assert_eq!(renameat2(&a, &b, RENAME_NOREPLACE), EEXIST, "{}", dir);
+        assert_eq!(renameat2(&a, &b, 0), 0, "{}", dir);
+        assert_eq!(read_file(&b), b"new contents");
+        assert_eq!(newfstatat(&a, 0), Err(ENOENT));
+        assert_eq!(renameat2(&b, &b, 0), 0);
+
+        assert_eq!(truncate(&b, 3), 0);
+        assert_eq!(read_file(&b), b"new");
+        assert_eq!(truncate(&b, 5), 0);
+        assert_eq!(read_file(&b), b"new\0\0");
+
+        let fd = openat(&b, O_RDWR, 0);
+        assert!(fd >= 0, "openat failed: {}", fd);
+        assert_eq!(unsafe { invoke(SYSCALL_FTRUNCATE, &[fd as usize, 1]) }, 0);
+        assert_eq!(unsafe { invoke(SYSCALL_FSYNC, &[fd as usize]) }, 0);
+        assert_eq!(unsafe { invoke(SYSCALL_FDATASYNC, &[fd as usize]) }, 0);
+        close(fd);
+        assert_eq!(read_file(&b), b"n");
+        let fd = openat(&b, O_RDONLY, 0);
+        assert_eq!(
+            unsafe { invoke(SYSCALL_FTRUNCATE, &[fd as usize, 0]) },
+            EINVAL
+        );
+        close(fd);
+    }
+
+    assert_eq!(renameat2("/tmp/renames/b", "/renames/c", 0), EXDEV);
+    assert_eq!(renameat2("/renames/b", "/protected/renames/c", 0), EXDEV);
+    assert_eq!(
+        renameat2("/ro/existing.txt", "/ro/scratch/moved.txt", 0),
+        EROFS
+    );
+    assert_eq!(truncate("/ro/existing.txt", 0), EROFS);
+    assert_eq!(truncate("/renames/b", MAX_FILE_SIZE + 1), EFBIG);
+    assert_eq!(truncate("/proc/cpuinfo", 0), EACCES);
+}
+
+#[test]
+fn enclave_files_are_not_truncated_past_the_cap() {
+    let _guard = setup();
+    for path in ["/tmp/truncated.bin", "/protected/truncated.bin"] {
+        write_file(path, b"kept");
+        assert_eq!(truncate(path, 1 << 50), EFBIG, "{}", path);
+        let fd = openat(path, O_RDWR, 0);
+        assert!(fd >= 0, "openat failed: {}", fd);
+        assert_eq!(
+            unsafe { invoke(SYSCALL_FTRUNCATE, &[fd as usize, 1 << 50]) },
+            EFBIG,
+            "{}",
+            path
+        );
+        close(fd);
+        assert_eq!(read_file(path), b"kept");
+        assert_eq!(unlinkat(path), 0);
+    }
+}
+
+#[test]
+fn links_on_host_and_enclave_files() {
+    let _guard = setup();
+    for dir in ["/links", "/tmp/links"] {
+        assert_eq!(mkdirat(dir), 0);
+        let (file, link) = (format!("{}/file", dir), format!("{}/link", dir));
+        let (symlink, hard) = (format!("{}/symlink", dir), format!("{}/hard", dir));
+        write_file(&file, b"linked");
+
+        assert_eq!(linkat(&file, &link, 0), 0, "{}", dir);
+        assert_eq!(linkat(&file, &link, 0), EEXIST);
+        assert_eq!(nlink(&newfstatat(&file, 0).unwrap()), 2);
+        assert_eq!(symlinkat("file", &symlink), 0, "{}", dir);
+        assert_eq!(readlinkat(&symlink, 64).as_deref(), Ok("file"));
+        assert_eq!(read_file(&symlink), b"linked");
+        let (mode, _) = mode_and_size(&newfstatat(&symlink, AT_SYMLINK_NOFOLLOW).unwrap());
+        assert_eq!(mode & 0o170000, 0o120000);
+
+        // A hard link to a symbolic link, or to where it leads
+        assert_eq!(linkat(&symlink, &hard, AT_SYMLINK_FOLLOW), 0);
+        assert_eq!(nlink(&newfstatat(&file, 0).unwrap()), 3);
+        assert_eq!(unlinkat(&file), 0);
+        assert_eq!(unlinkat(&link), 0);
+        assert_eq!(read_file(&hard), b"linked");
+        assert_eq!(openat(&symlink, O_RDONLY, 0), ENOENT);
+        assert_eq!(openat(&symlink, O_RDONLY | O_NOFOLLOW, 0), ELOOP);
+    }
+
+    // Directories move as a whole, but not beneath themselves
+    assert_eq!(mkdirat("/tmp/links/dir"), 0);
+    assert_eq!(mkdirat("/tmp/links/dir/sub"), 0);
+    assert_eq!(
+        renameat2("/tmp/links/dir", "/tmp/links/dir/sub/dir", 0),
+        EINVAL
+    );
+    assert_eq!(mkdirat("/tmp/links/other"), 0);
+    assert_eq!(
+        renameat2("/tmp/links/other", "/tmp/links/dir", 0),
+        ENOTEMPTY
+    );
+    assert_eq!(renameat2("/tmp/links/dir", "/tmp/links/moved", 0), 0);
+    assert_eq!(list_dir("/tmp/links/moved"), [".", "..", "sub"]);
+
+    write_file("/protected/linked.txt", b"protected");
+    assert_eq!(linkat("/protected/linked.txt", "/protected/link", 0), EPERM);
+    assert_eq!(symlinkat("linked.txt", "/protected/symlink"), EPERM);
+    assert_eq!(symlinkat("target", "/initramfs/link"), EROFS);
+}
+
+#[test]
+fn protected_files_move_while_open() {
+    let _guard = setup();
+    write_file("/protected/replaced.txt", b"replaced");
+    let replaced = openat("/protected/replaced.txt", O_RDWR, 0);
+    assert!(replaced >= 0, "openat failed: {}", replaced);
+    let fd = openat("/protected/moving.txt", O_RDWR | O_CREAT | O_TRUNC, 0o644);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let write = |fd: isize, data: &[u8]| unsafe {
+        invoke(
+            SYSCALL_WRITE,
+            &[fd as usize, data.as_ptr() as usize, data.len()],
+        )
+    };
+    assert_eq!(write(fd, b"written "), 8);
+
+    assert_eq!(
+        renameat2("/protected/moving.txt", "/protected/replaced.txt", 0),
+        0
+    );
+    assert!(!host_dir().join("root/protected/moving.txt").exists());
+    assert_eq!(write(fd, b"before and after"), 16);
+    close(fd);
+    // The replaced file is not written back over the renamed one
+    assert_eq!(write(replaced, b"stale"), 5);
+    close(replaced);
+    assert_eq!(
+        read_file("/protected/replaced.txt"),
+        b"written before and after"
+    );
+}
+
+#[test]
+fn procfs_describes_enclave_tasks() {
+    let _guard = setup();
+    let listing = list_dir("/proc");
+    for entry in ["1", "cpuinfo", "self"] {
+        assert!(listing.iter().any(|name| name == entry), "{:?}", listing);
+    }
+    assert_eq!(list_dir("/proc/self"), [".", "..", "exe", "maps", "status"]);
+    let status = String::from_utf8(read_file("/proc/self/status")).unwrap();
+    assert!(status.contains("\nPid:\t1\n"), "{}", status);
+    assert!(!read_file("/proc/cpuinfo").is_empty());
+    assert_eq!(openat("/proc/cpuinfo", O_WRONLY, 0), EACCES);
+    assert_eq!(openat("/proc/2/status", O_RDONLY, 0), ENOENT);
+
+    // The executable's link leads back to the guest root
+    assert_eq!(openat("/proc/self/exe", O_RDONLY, 0), ENOENT);
+    hal::task::find(1).unwrap().lock().mm.exe = "/ro/existing.txt".into();
+    assert_eq!(read_file("/proc/1/exe"), b"read-only");
+    assert_eq!(openat("/proc/1/exe", O_RDONLY | O_NOFOLLOW, 0), ELOOP);
+    hal::task::find(1).unwrap().lock().mm.exe.clear();
+}
+
+#[test]
+fn devfs_devices_are_served_in_the_enclave() {
+    let _guard = setup();
+    assert_eq!(
+        list_dir("/dev"),
+        [
+            ".",
+            "..",
+            "attestation",
+            "null",
+            "random",
+            "seal",
+            "tty",
+            "unseal",
+            "urandom",
+            "zero"
+        ]
+    );
+    assert_eq!(read_file("/dev/null"), b"");
+    assert_eq!(read_file("/dev/zero"), [0; 4096]);
+    write_file("/dev/null", b"discarded");
+
+    let fd = openat("/dev/zero", O_RDONLY, 0);
+    let stat = fstat(fd);
+    close(fd);
+    // st_mode and st_rdev
+    assert_eq!(
+        u32::from_ne_bytes(stat[24..28].try_into().unwrap()),
+        0o020666
+    );
+    assert_eq!(u64::from_ne_bytes(stat[40..48].try_into().unwrap()), 0x105);
+    assert_eq!(openat("/dev/sda", O_WRONLY | O_CREAT, 0o644), EACCES);
+}
+
+#[test]
+fn random_bytes_come_from_the_enclave() {
+    let _guard = setup();
+    let getrandom = |buf: &mut [u8], flags: usize| unsafe {
+        invoke(
+            SYSCALL_GETRANDOM,
+            &[buf.as_mut_ptr() as usize, buf.len(), flags],
+        )
+    };
+    // More than one request of the generator
+    let (mut first, mut second) = (vec![0u8; 70000], vec![0u8; 70000]);
+    assert_eq!(getrandom(&mut first, 0), 70000);
+    assert_eq!(getrandom(&mut second, 0x1), 70000);
+    assert_ne!(first, second);
+    assert!(first[65536..].iter().any(|&byte| byte != 0));
+    // GRND_RANDOM and GRND_INSECURE cannot be combined
+    assert_eq!(getrandom(&mut first, 0x6), EINVAL);
+    assert_eq!(getrandom(&mut first, 0x8), EINVAL);
+
+    let (random, urandom) = (read_file("/dev/random"), read_file("/dev/urandom"));
+    assert_eq!((random.len(), urandom.len()), (4096, 4096));
+    assert_ne!(random, urandom);
+}
+
+#[test]
+fn attestation_reports_bind_user_data() {
+    let _guard = setup();
+    let fd = openat("/dev/attestation", O_RDWR, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let read_report = || {
+        let mut report = vec![0u8; 4096];
+        let len = unsafe {
+            invoke(
+                SYSCALL_READ,
+                &[fd as usize, report.as_mut_ptr() as usize, report.len()],
+            )
+        };
+        assert!(len > 0, "read failed: {}", len);
+        report.truncate(len as usize);
+        report
+    };
+
+    let evidence = attestation_verifier::verify_simulated(&read_report()).unwrap();
+    assert!(evidence.binds(b""));
+    let args = [fd as usize, b"nonce".as_ptr() as usize, 5];
+    assert_eq!(unsafe { invoke(SYSCALL_WRITE, &args) }, 5);
+    let report = read_report();
+    let evidence = attestation_verifier::verify_simulated(&report).unwrap();
+    assert_eq!(evidence.measurement, [0; 32]);
+    assert!(evidence.binds(b"nonce"));
+    // The report is read like a file
+    let mut rest = [0u8; 16];
+    let args = [fd as usize, rest.as_mut_ptr() as usize, rest.len()];
+    assert_eq!(unsafe { invoke(SYSCALL_READ, &args) }, 0);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read_report(), report);
+
+    let data = [0x55u8; 65];
+    let args = [fd as usize, data.as_ptr() as usize, data.len()];
+    assert_eq!(unsafe { invoke(SYSCALL_WRITE, &args) }, EINVAL);
+    close(fd);
+}
+
+/// Write `input` to the device at `path`, in two parts, and read back all of
+/// its output.
+fn transform_device(path: &str, input: &[u8]) -> Result<Vec<u8>, isize> {
+    let fd = openat(path, O_RDWR, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    for part in [&input[..input.len() / 2], &input[input.len() / 2..]] {
+        let args = [fd as usize, part.as_ptr() as usize, part.len()];
+        assert_eq!(unsafe { invoke(SYSCALL_WRITE, &args) }, part.len() as isize);
+    }
+    let mut output = Vec::new();
+    let result = loop {
+        let mut buf = [0u8; 100];
+        let args = [fd as usize, buf.as_mut_ptr() as usize, buf.len()];
+        match unsafe { invoke(SYSCALL_READ, &args) } {
+            0 => break Ok(output),
+            len if len < 0 => break Err(len),
+            len => output.extend(&buf[..len as usize]),
+        }
+    };
+    close(fd);
+    result
+}
+
+#[test]
+fn sealed_blobs_are_opened_in_the_enclave() {
+    let _guard = setup();
+    let secret = b"the key to everything, and then some more bytes".repeat(10);
+    let blob = transform_device("/dev/seal", &secret).unwrap();
+    assert!(!blob.windows(secret.len()).any(|window| window == secret));
+    assert_eq!(transform_device("/dev/unseal", &blob), Ok(secret.clone()));
+    // Each blob is sealed under a fresh nonce
+    assert_ne!(transform_device("/dev/seal", &secret).unwrap(), blob);
+
+    let mut tampered = blob.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert_eq!(transform_device("/dev/unseal", &tampered), Err(EBADMSG));
+    assert_eq!(
+        transform_device("/dev/unseal", &blob[..blob.len() - 1]),
+        Err(EBADMSG)
+    );
+    assert_eq!(transform_device("/dev/unseal", b""), Err(EBADMSG));
+    let empty = transform_device("/dev/seal", b"").unwrap();
+    assert_eq!(transform_device("/dev/unseal", &empty), Ok(Vec::new()));
+}
+
+/// Append a `newc` cpio entry to `image`.
+fn cpio_entry(image: &mut Vec<u8>, ino: u32, mode: u32, name: &str, data: &[u8]) {
+    let fields = [ino, mode, 0, 0, 1, 0, data.len() as u32, 0, 0, 0, 0];
+    image.extend(b"070701");
+    for field in fields.into_iter().chain([name.len() as u32 + 1, 0]) {
+        image.extend(format!("{:08X}", field).as_bytes());
+    }
+    image.extend(name.as_bytes());
+    image.push(0);
+    image.resize(image.len().next_multiple_of(4), 0);
+    image.extend(data);
+    image.resize(image.len().next_multiple_of(4), 0);
+}
+
+fn initramfs_image() -> Box<[u8]> {
+    let mut image = Vec::new();
+    cpio_entry(&mut image, 1, 0o040755, "bin", b"");
+    cpio_entry(&mut image, 2, 0o100755, "bin/init", b"\x7fELF init");
+    cpio_entry(&mut image, 3, 0o120777, "bin/sh", b"init");
+    // The parent directory is not in the archive
+    cpio_entry(&mut image, 4, 0o100644, "etc/motd", b"hello");
+    cpio_entry(&mut image, 0, 0, "TRAILER!!!", b"");
+    image.into_boxed_slice()
+}
+
+#[test]
+fn initramfs_serves_files_read_only() {
+    let _guard = setup();
+    assert_eq!(list_dir("/initramfs"), [".", "..", "bin", "etc"]);
+    // Symlinks are skipped
+    assert_eq!(list_dir("/initramfs/bin"), [".", "..", "init"]);
+
+    let fd = openat("/initramfs/etc/../bin/init", O_RDONLY, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let stat = fstat(fd);
+    // st_mode and st_size
+    assert_eq!(
+        u32::from_ne_bytes(stat[24..28].try_into().unwrap()),
+        0o100755
+    );
+    assert_eq!(i64::from_ne_bytes(stat[48..56].try_into().unwrap()), 9);
+    let mut buf = [0u8; 64];
+    let read = unsafe {
+        invoke(
+            SYSCALL_READ,
+            &[fd as usize, buf.as_mut_ptr() as usize, buf.len()],
+        )
+    };
+    assert_eq!(&buf[0..read as usize], b"\x7fELF init");
+    close(fd);
+
+    assert_eq!(openat("/initramfs/etc/motd", O_WRONLY, 0), EROFS);
+    assert_eq!(openat("/initramfs/new", O_WRONLY | O_CREAT, 0o644), EROFS);
+    assert_eq!(mkdirat("/initramfs/etc/new"), EROFS);
+    assert_eq!(unlinkat("/initramfs/etc/motd"), EROFS);
+    assert_eq!(openat("/initramfs/missing", O_RDONLY, 0), ENOENT);
+}
+
+const F_DUPFD: usize = 0;
+const F_GETFD: usize = 1;
+const F_SETFD: usize = 2;
+const F_GETFL: usize = 3;
+const F_SETFL: usize = 4;
+const F_DUPFD_CLOEXEC: usize = 1030;
+const TCGETS: usize = 0x5401;
+const FIONREAD: usize = 0x541b;
+
+fn fcntl(fd: isize, cmd: usize, arg: usize) -> isize {
+    unsafe { invoke(SYSCALL_FCNTL, &[fd as usize, cmd, arg]) }
+}
+
+#[test]
+fn fcntl_on_host_and_enclave_files() {
+    let _guard = setup();
+    for path in ["/fcntl.txt", "/tmp/fcntl.txt"] {
+        write_file(path, b"start");
+        let fd = openat(path, O_WRONLY | O_CLOEXEC, 0);
+        assert!(fd >= 0, "openat failed: {}", fd);
+        assert_eq!(fcntl(fd, F_GETFD, 0), 1, "{}", path);
+        assert_eq!(fcntl(fd, F_SETFD, 0), 0);
+        assert_eq!(fcntl(fd, F_GETFD, 0), 0);
+        assert_eq!(fcntl(fd, F_GETFL, 0) as usize & (O_APPEND | 0o3), O_WRONLY);
+
+        // Status flags are shared by duplicated fds, `FD_CLOEXEC` is not
+        assert_eq!(fcntl(fd, F_SETFL, O_APPEND | O_NONBLOCK), 0);
+        let dup = fcntl(fd, F_DUPFD_CLOEXEC, 100);
+        assert!(dup >= 100, "F_DUPFD_CLOEXEC failed: {}", dup);
+        assert_eq!(fcntl(dup, F_GETFD, 0), 1);
+        let flags = fcntl(dup, F_GETFL, 0) as usize;
+        assert_eq!(
+            flags & (O_APPEND | O_NONBLOCK),
+            O_APPEND | O_NONBLOCK,
+            "{}",
+            path
+        );
+        assert_eq!(lseek(dup, 0, SEEK_SET), 0);
+        let data = b"+end";
+        let written = unsafe {
+            invoke(
+                SYSCALL_WRITE,
+                &[dup as usize, data.as_ptr() as usize, data.len()],
+            )
+        };
+        assert_eq!(written, data.len() as isize);
+        assert_eq!(read_file(path), b"start+end", "{}", path);
+
+        assert_eq!(fcntl(fd, F_SETFL, 0), 0);
+        assert_eq!(fcntl(dup, F_GETFL, 0) as usize & O_APPEND, 0);
+        assert_eq!(fcntl(fd, F_DUPFD, 1 << 20), EINVAL);
+        close(dup);
+        close(fd);
+    }
+
+    let fd = openat("/fcntl.txt", O_RDONLY, 0);
+    let dup = unsafe { invoke(SYSCALL_DUP3, &[fd as usize, 50, O_CLOEXEC]) };
+    assert_eq!(dup, 50);
+    assert_eq!(fcntl(dup, F_GETFD, 0), 1);
+    assert_eq!(fcntl(fd, F_GETFD, 0), 0);
+    assert_eq!(
+        unsafe { invoke(SYSCALL_DUP3, &[fd as usize, 51, O_APPEND]) },
+        EINVAL
+    );
+    close(dup);
+    close(fd);
+}
+
+#[test]
+fn ioctl_is_limited_to_a_whitelist() {
+    let _guard = setup();
+    write_file("/ioctl.txt", b"0123456789");
+    let fd = openat("/ioctl.txt", O_RDONLY, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    assert_eq!(lseek(fd, 4, SEEK_SET), 4);
+
+    // The host fills in the structure: the bytes left to read
+    let mut pending = [0xffu8; 8];
+    let ioctl = |fd: isize, request: usize, arg: *mut u8| unsafe {
+        invoke(SYSCALL_IOCTL, &[fd as usize, request, arg as usize])
+    };
+    assert_eq!(ioctl(fd, FIONREAD, pending.as_mut_ptr()), 0);
+    assert_eq!(pending, [6, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+
+    let mut termios = [0u8; 64];
+    assert_eq!(ioctl(fd, TCGETS, termios.as_mut_ptr()), ENOTTY);
+    // Requests that are not whitelisted never reach the host
+    assert_eq!(ioctl(fd, 0x5402, termios.as_mut_ptr()), ENOTTY);
+    close(fd);
+
+    write_file("/tmp/ioctl.txt", b"enclave");
+    let fd = openat("/tmp/ioctl.txt", O_RDONLY, 0);
+    assert_eq!(ioctl(fd, FIONREAD, pending.as_mut_ptr()), ENOTTY);
+    close(fd);
+}
+
+const SCHED_OTHER: usize = 0;
+const SCHED_FIFO: usize = 1;
+const SCHED_BATCH: usize = 3;
+const PRIO_PROCESS: usize = 0;
+
+fn sched_setscheduler(pid: usize, policy: usize, priority: i32) -> isize {
+    let param = priority;
+    unsafe {
+        invoke(
+            SYSCALL_SCHED_SETSCHEDULER,
+            &[pid, policy, &param as *const i32 as usize],
+        )
+    }
+}
+
+fn getpriority(who: usize) -> isize {
+    unsafe { invoke(SYSCALL_GETPRIORITY, &[PRIO_PROCESS, who]) }
+}
+
+fn setpriority(who: usize, nice: isize) -> isize {
+    unsafe { invoke(SYSCALL_SETPRIORITY, &[PRIO_PROCESS, who, nice as usize]) }
+}
+
+#[test]
+fn scheduling_policy_and_priority() {
+    let _guard = setup();
+    let getscheduler = |pid| unsafe { invoke(SYSCALL_SCHED_GETSCHEDULER, &[pid]) };
+    assert_eq!(getscheduler(0), SCHED_OTHER as isize);
+    assert_eq!(sched_setscheduler(1, SCHED_BATCH, 0), 0);
+    assert_eq!(getscheduler(0), SCHED_BATCH as isize);
+    assert_eq!(sched_setscheduler(0, SCHED_FIFO, 1), EPERM);
+    assert_eq!(sched_setscheduler(0, SCHED_OTHER, 1), EINVAL);
+    assert_eq!(sched_setscheduler(0, 42, 0), EINVAL);
+    assert_eq!(sched_setscheduler(2, SCHED_OTHER, 0), ESRCH);
+    assert_eq!(sched_setscheduler(0, SCHED_OTHER, 0), 0);
+
+    // The raw syscall reports `20 - nice`, and nice values are clamped
+    assert_eq!(getpriority(0), 20);
+    assert_eq!(setpriority(0, 5), 0);
+    assert_eq!(getpriority(1), 15);
+    assert_eq!(setpriority(0, 100), 0);
+    assert_eq!(getpriority(0), 1);
+    assert_eq!(setpriority(0, -100), 0);
+    assert_eq!(getpriority(0), 40);
+    assert_eq!(setpriority(2, 0), ESRCH);
+    assert_eq!(setpriority(0, 0), 0);
+
+    let mut mask = [0u64; 16];
+    let getaffinity = |len: usize, mask: &mut [u64]| unsafe {
+        invoke(
+            SYSCALL_SCHED_GETAFFINITY,
+            &[0, len, mask.as_mut_ptr() as usize],
+        )
+    };
+    assert_eq!(getaffinity(128, &mut mask), 8);
+    assert_eq!(mask, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(getaffinity(4, &mut mask), EINVAL);
+
+    let status = String::from_utf8(read_file("/proc/self/status")).unwrap();
+    assert!(
+        status.contains("\nvoluntary_ctxt_switches:\t0\n"),
+        "{}",
+        status
+    );
+}
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+const CLOCK_THREAD_CPUTIME_ID: usize = 3;
+const TIMER_ABSTIME: usize = 1;
+
+fn clock_gettime(clock: usize) -> Result<[i64; 2], isize> {
+    let mut tp = [0i64; 2];
+    match unsafe { invoke(SYSCALL_CLOCK_GETTIME, &[clock, tp.as_mut_ptr() as usize]) } {
+        0 => Ok(tp),
+        errno => Err(errno),
+    }
+}
+
+fn clock_nanosleep(clock: usize, flags: usize, time: [i64; 2]) -> isize {
+    unsafe {
+        invoke(
+            SYSCALL_CLOCK_NANOSLEEP,
+            &[clock, flags, time.as_ptr() as usize, 0],
+        )
+    }
+}
+
+#[test]
+fn clocks_follow_the_counter() {
+    let _guard = setup();
+    let before = clock_gettime(CLOCK_MONOTONIC).unwrap();
+    let realtime_before = clock_gettime(CLOCK_REALTIME).unwrap();
+    hal::arch::loopback::advance_clock(1_500_000_000);
+    let after = clock_gettime(CLOCK_MONOTONIC).unwrap();
+    let realtime_after = clock_gettime(CLOCK_REALTIME).unwrap();
+    let elapsed =
+        |from: [i64; 2], to: [i64; 2]| (to[0] - from[0]) * 1_000_000_000 + to[1] - from[1];
+    assert_eq!(elapsed(before, after), 1_500_000_000);
+    assert_eq!(elapsed(realtime_before, realtime_after), 1_500_000_000);
+
+    // The wall clock was only taken from the host at boot
+    let host = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    assert!(
+        (realtime_after[0] - host).abs() < 60,
+        "{:?}",
+        realtime_after
+    );
+    let mut tv = [0i64; 2];
+    assert_eq!(
+        unsafe { invoke(SYSCALL_GETTIMEOFDAY, &[tv.as_mut_ptr() as usize, 0]) },
+        0
+    );
+    assert_eq!(tv, [realtime_after[0], realtime_after[1] / 1000]);
+    let mut tloc = 0i64;
+    let time = unsafe { invoke(SYSCALL_TIME, &[&mut tloc as *mut i64 as usize]) };
+    assert_eq!((time as i64, tloc), (realtime_after[0], realtime_after[0]));
+
+    // `clock_t` counts hundredths of a second since boot
+    let mut tms = [-1i64; 4];
+    let ticks = unsafe { invoke(SYSCALL_TIMES, &[tms.as_mut_ptr() as usize]) };
+    assert_eq!(ticks as i64, elapsed([0, 0], after) / 10_000_000);
+    assert_eq!(tms, [0; 4]);
+    assert_eq!(clock_gettime(CLOCK_THREAD_CPUTIME_ID), Ok([0, 0]));
+    assert_eq!(clock_gettime(42), Err(EINVAL));
+
+    // Sleeps that are already over return at once
+    let zero = [0i64; 2];
+    assert_eq!(
+        unsafe { invoke(SYSCALL_NANOSLEEP, &[zero.as_ptr() as usize, 0]) },
+        0
+    );
+    assert_eq!(clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, before), 0);
+    assert_eq!(
+        clock_nanosleep(CLOCK_REALTIME, TIMER_ABSTIME, realtime_before),
+        0
+    );
+    assert_eq!(
+        clock_nanosleep(CLOCK_MONOTONIC, 0, [0, 1_000_000_000]),
+        EINVAL
+    );
+    assert_eq!(clock_nanosleep(CLOCK_MONOTONIC, 0, [-1, 0]), EINVAL);
+    assert_eq!(clock_nanosleep(CLOCK_MONOTONIC, 2, zero), EINVAL);
+    assert_eq!(clock_nanosleep(CLOCK_THREAD_CPUTIME_ID, 0, zero), EINVAL);
+}
+
+/// Run `entry` in init as a task of the scheduler, until it returns.
+fn run_as_init(entry: impl FnOnce() + Send + 'static) {
+    let init = hal::task::current();
+    let stats = init.lock().stats;
+    hal::arch::loopback::set_task_entry(&init, entry);
+    hal::task::spawn(hal::task::TaskFuture::new(Arc::clone(&init)));
+    hal::task::run_until_idle();
+    // Init goes on serving the other tests from the test thread
+    let mut init = init.lock();
+    init.exit_status = None;
+    init.stats = stats;
+}
+
+/// Call `f` on another host thread once init has blocked.
+fn once_init_blocks(f: impl FnOnce() + Send + 'static) -> std::thread::JoinHandle<()> {
+    let init = hal::task::current();
+    std::thread::spawn(move || {
+        while !init.lock().waiting {
+            std::thread::yield_now();
+        }
+        f();
+    })
+}
+
+#[test]
+fn sleeping_tasks_wait_for_the_clock() {
+    let _guard = setup();
+    let woken_at = Arc::new(Mutex::new(None));
+    let advanced = once_init_blocks(|| hal::arch::loopback::advance_clock(1_000));
+    let start = clock_gettime(CLOCK_MONOTONIC).unwrap();
+    let result = Arc::clone(&woken_at);
+    run_as_init(move || {
+        assert_eq!(clock_nanosleep(CLOCK_MONOTONIC, 0, [0, 1_000]), 0);
+        *result.lock().unwrap() = clock_gettime(CLOCK_MONOTONIC).ok();
+    });
+    advanced.join().unwrap();
+    let woken_at = woken_at.lock().unwrap().expect("the task did not finish");
+    let elapsed = (woken_at[0] - start[0]) * 1_000_000_000 + woken_at[1] - start[1];
+    assert_eq!(elapsed, 1_000);
+}
+
+#[test]
+fn reads_from_empty_pipes_do_not_block_the_host() {
+    let _guard = setup();
+    let fifo = host_dir().join("root/fifo");
+    let _ = std::fs::remove_file(&fifo);
+    let status = std::process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Opening a FIFO for reading only returns at once if it does not block
+    let fd = openat("/fifo", O_RDONLY | O_NONBLOCK, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let mut writer = std::fs::OpenOptions::new().write(true).open(&fifo).unwrap();
+    let mut buf = [0u8; 16];
+    let read = |buf: &mut [u8]| unsafe {
+        invoke(
+            SYSCALL_READ,
+            &[fd as usize, buf.as_mut_ptr() as usize, buf.len()],
+        )
+    };
+    assert_eq!(read(&mut buf), EAGAIN);
+
+    // In blocking mode, the host leaves the waiting to the enclave
+    assert_eq!(fcntl(fd, F_SETFL, 0), 0);
+    let resp = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallReadV {
+                pid: 1,
+                fd: fd as i32,
+                regions: DataRegion::packed([buf.len()]),
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        caller.read_header().unwrap()
+    });
+    assert_eq!(resp, EdgeCallResp::WouldBlock);
+
+    // Ready pipes are read right away, up to the end of the file
+    writer.write_all(b"ping").unwrap();
+    assert_eq!(read(&mut buf), 4);
+    assert_eq!(&buf[0..4], b"ping");
+    drop(writer);
+    assert_eq!(read(&mut buf), 0);
+    close(fd);
+}
+
+#[test]
+fn blocking_reads_park_until_the_host_has_data() {
+    let _guard = setup();
+    hal::edge::set_notification_handler(linux_abi::notify::handle_notification);
+    let fifo = host_dir().join("root/blocking-fifo");
+    let _ = std::fs::remove_file(&fifo);
+    let status = std::process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let fd = openat("/blocking-fifo", O_RDONLY | O_NONBLOCK, 0);
+    assert!(fd >= 0, "openat failed: {}", fd);
+    let mut writer = std::fs::OpenOptions::new().write(true).open(&fifo).unwrap();
+    assert_eq!(fcntl(fd, F_SETFL, 0), 0);
+
+    // The host only writes once the reader has parked
+    let written = once_init_blocks(move || {
+        writer.write_all(b"ping").unwrap();
+        hal::arch::loopback::post_notification(Notification::IoReady);
+    });
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let result = Arc::clone(&received);
+    run_as_init(move || {
+        let mut buf = [0u8; 16];
+        let read = unsafe {
+            invoke(
+                SYSCALL_READ,
+                &[fd as usize, buf.as_mut_ptr() as usize, buf.len()],
+            )
+        };
+        assert_eq!(read, 4);
+        result.lock().unwrap().extend_from_slice(&buf[..4]);
+    });
+    written.join().unwrap();
+    assert_eq!(*received.lock().unwrap(), b"ping");
+    close(fd);
+}
+
+const POLLIN: i16 = 0x1;
+const POLLOUT: i16 = 0x4;
+const POLLNVAL: i16 = 0x20;
+const EPOLL_CTL_ADD: usize = 1;
+const EPOLL_CTL_DEL: usize = 2;
+const EPOLL_CTL_MOD: usize = 3;
+const EPOLLONESHOT: u32 = 1 << 30;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Pollfd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+fn poll_now(fds: &mut [Pollfd]) -> isize {
+    unsafe { invoke(SYSCALL_POLL, &[fds.as_mut_ptr() as usize, fds.len(), 0]) }
+}
+
+fn epoll_ctl(epfd: isize, op: usize, fd: isize, events: u32, data: u64) -> isize {
+    let event = EpollEvent { events, data };
+    let args = [epfd as usize, op, fd as usize, &event as *const _ as usize];
+    unsafe { invoke(SYSCALL_EPOLL_CTL, &args) }
+}
+
+fn epoll_wait_now(epfd: isize, events: &mut [EpollEvent]) -> isize {
+    let args = [epfd as usize, events.as_mut_ptr() as usize, events.len(), 0];
+    unsafe { invoke(SYSCALL_EPOLL_WAIT, &args) }
+}
+
+#[test]
+fn readiness_of_host_and_enclave_files() {
+    let _guard = setup();
+    let fifo = host_dir().join("root/poll-fifo");
+    let _ = std::fs::remove_file(&fifo);
+    let status = std::process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let fifo_fd = openat("/poll-fifo", O_RDONLY | O_NONBLOCK, 0);
+    assert!(fifo_fd >= 0, "openat failed: {}", fifo_fd);
+    let mut writer = std::fs::OpenOptions::new().write(true).open(&fifo).unwrap();
+    write_file("/tmp/poll.txt", b"ready");
+    let tmp_fd = openat("/tmp/poll.txt", O_RDWR, 0);
+    assert!(tmp_fd >= 0, "openat failed: {}", tmp_fd);
+
+    // Enclave files are always ready, empty pipes are not, negative fds are
+    // ignored and closed ones are reported
+    let pollfd = |fd: isize, events| Pollfd {
+        fd: fd as i32,
+        events,
+        revents: 0,
+    };
+    let mut fds = [
+        pollfd(fifo_fd, POLLIN),
+        pollfd(tmp_fd, POLLIN | POLLOUT),
+        pollfd(-1, POLLIN),
+        pollfd(999, POLLIN),
+    ];
+    assert_eq!(poll_now(&mut fds), 2);
+    let revents: Vec<i16> = fds.iter().map(|fd| fd.revents).collect();
+    assert_eq!(revents, [0, POLLIN | POLLOUT, 0, POLLNVAL]);
+
+    writer.write_all(b"ping").unwrap();
+    let mut fds = [pollfd(fifo_fd, POLLIN)];
+    assert_eq!(poll_now(&mut fds), 1);
+    assert_eq!(fds[0].revents, POLLIN);
+
+    // select() only counts the sets the fds are ready for
+    let mut readfds = [0u64; 1];
+    let mut writefds = [0u64; 1];
+    readfds[0] |= 1 << fifo_fd;
+    writefds[0] |= 1 << tmp_fd;
+    let mut timeout = [0i64; 2];
+    let args = [
+        tmp_fd.max(fifo_fd) as usize + 1,
+        readfds.as_mut_ptr() as usize,
+        writefds.as_mut_ptr() as usize,
+        0,
+        timeout.as_mut_ptr() as usize,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_SELECT, &args) }, 2);
+    assert_eq!(readfds[0], 1 << fifo_fd);
+    assert_eq!(writefds[0], 1 << tmp_fd);
+
+    // epoll reports the data registered with each ready fd
+    let epfd = unsafe { invoke(SYSCALL_EPOLL_CREATE1, &[O_CLOEXEC]) };
+    assert!(epfd >= 0, "epoll_create1 failed: {}", epfd);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, fifo_fd, POLLIN as u32, 7), 0);
+    assert_eq!(
+        epoll_ctl(epfd, EPOLL_CTL_ADD, fifo_fd, POLLIN as u32, 7),
+        EEXIST
+    );
+    assert_eq!(
+        epoll_ctl(epfd, EPOLL_CTL_MOD, tmp_fd, POLLIN as u32, 8),
+        ENOENT
+    );
+    assert_eq!(
+        epoll_ctl(epfd, EPOLL_CTL_ADD, epfd, POLLIN as u32, 9),
+        EINVAL
+    );
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, 999, POLLIN as u32, 9), EBADF);
+    let mut events = [EpollEvent::default(); 4];
+    assert_eq!(epoll_wait_now(epfd, &mut events), 1);
+    let (ready, data) = (events[0].events, events[0].data);
+    assert_eq!((ready, data), (POLLIN as u32, 7));
+
+    // Drained pipes are no longer ready, and one-shot fds fire once
+    let mut buf = [0u8; 16];
+    let args = [fifo_fd as usize, buf.as_mut_ptr() as usize, buf.len()];
+    assert_eq!(unsafe { invoke(SYSCALL_READ, &args) }, 4);
+    assert_eq!(epoll_wait_now(epfd, &mut events), 0);
+    let oneshot = POLLOUT as u32 | EPOLLONESHOT;
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, tmp_fd, oneshot, 8), 0);
+    assert_eq!(epoll_wait_now(epfd, &mut events), 1);
+    assert_eq!({ events[0].data }, 8);
+    assert_eq!(epoll_wait_now(epfd, &mut events), 0);
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_DEL, tmp_fd, 0, 0), 0);
+
+    // Only epoll instances can be waited on
+    let args = [
+        tmp_fd as usize,
+        events.as_mut_ptr() as usize,
+        events.len(),
+        0,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_EPOLL_WAIT, &args) }, EINVAL);
+    close(epfd);
+    close(tmp_fd);
+    close(fifo_fd);
+}
+
+const AF_UNIX: usize = 1;
+const AF_INET: usize = 2;
+const AF_PACKET: usize = 17;
+const SOCK_STREAM: usize = 1;
+const SOCK_DGRAM: usize = 2;
+const SOCK_RAW: usize = 3;
+const SOL_SOCKET: usize = 1;
+const SO_REUSEADDR: usize = 2;
+const SO_LINGER: usize = 13;
+const SHUT_WR: usize = 1;
+const EAFNOSUPPORT: isize = -97;
+const ENOTSOCK: isize = -88;
+const ENOPROTOOPT: isize = -92;
+const EINPROGRESS: isize = -115;
+
+fn socket(domain: usize, ty: usize) -> isize {
+    unsafe { invoke(SYSCALL_SOCKET, &[domain, ty, 0]) }
+}
+
+/// A `struct sockaddr_in` for 127.0.0.1 and `port`.
+fn sockaddr_in(port: u16) -> [u8; 16] {
+    let mut addr = [0u8; 16];
+    addr[0..2].copy_from_slice(&(AF_INET as u16).to_ne_bytes());
+    addr[2..4].copy_from_slice(&port.to_be_bytes());
+    addr[4..8].copy_from_slice(&[127, 0, 0, 1]);
+    addr
+}
+
+fn bind(fd: isize, addr: &[u8]) -> isize {
+    let args = [fd as usize, addr.as_ptr() as usize, addr.len()];
+    unsafe { invoke(SYSCALL_BIND, &args) }
+}
+
+/// Run `getsockname` or `getpeername` on `fd`, returning the address.
+fn sock_name(handler: SyscallHandler, fd: isize) -> Vec<u8> {
+    let mut addr = [0u8; 128];
+    let mut len = addr.len() as u32;
+    let args = [
+        fd as usize,
+        addr.as_mut_ptr() as usize,
+        &mut len as *mut u32 as usize,
+    ];
+    assert_eq!(unsafe { invoke(handler, &args) }, 0);
+    addr[0..len as usize].to_vec()
+}
+
+fn port_of(addr: &[u8]) -> u16 {
+    u16::from_be_bytes([addr[2], addr[3]])
+}
+
+fn sendto(fd: isize, data: &[u8], addr: Option<&[u8]>) -> isize {
+    let (addr, len) = addr.map_or((0, 0), |addr| (addr.as_ptr() as usize, addr.len()));
+    let args = [
+        fd as usize,
+        data.as_ptr() as usize,
+        data.len(),
+        0,
+        addr,
+        len,
+    ];
+    unsafe { invoke(SYSCALL_SENDTO, &args) }
+}
+
+/// Receive into `buf`, returning the result and the sender's address.
+fn recvfrom(fd: isize, buf: &mut [u8]) -> (isize, Vec<u8>) {
+    let mut addr = [0u8; 128];
+    let mut len = addr.len() as u32;
+    let args = [
+        fd as usize,
+        buf.as_mut_ptr() as usize,
+        buf.len(),
+        0,
+        addr.as_mut_ptr() as usize,
+        &mut len as *mut u32 as usize,
+    ];
+    let result = unsafe { invoke(SYSCALL_RECVFROM, &args) };
+    (result, addr[0..len as usize].to_vec())
+}
+
+fn getsockopt_int(fd: isize, level: usize, name: usize) -> Result<i32, isize> {
+    let mut value = 0i32;
+    let mut len = 4u32;
+    let args = [
+        fd as usize,
+        level,
+        name,
+        &mut value as *mut i32 as usize,
+        &mut len as *mut u32 as usize,
+    ];
+    match unsafe { invoke(SYSCALL_GETSOCKOPT, &args) } {
+        0 => Ok(value),
+        errno => Err(errno),
+    }
+}
+
+#[test]
+#[cfg_attr(
+    feature = "netstack",
+    ignore = "IPv4 sockets are served in the enclave"
+)]
+fn tcp_sockets_through_the_host() {
+    let _guard = setup();
+    let listener = socket(AF_INET, SOCK_STREAM | O_NONBLOCK | O_CLOEXEC);
+    assert!(listener >= 0, "socket failed: {}", listener);
+    let one = 1i32;
+    let args = [
+        listener as usize,
+        SOL_SOCKET,
+        SO_REUSEADDR,
+        &one as *const i32 as usize,
+        4,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_SETSOCKOPT, &args) }, 0);
+    assert_eq!(getsockopt_int(listener, SOL_SOCKET, SO_REUSEADDR), Ok(1));
+    assert_eq!(bind(listener, &sockaddr_in(0)), 0);
+    assert_eq!(
+        unsafe { invoke(SYSCALL_LISTEN, &[listener as usize, 8]) },
+        0
+    );
+    let local = sock_name(SYSCALL_GETSOCKNAME, listener);
+    let port = port_of(&local);
+    assert_ne!(port, 0);
+    assert_eq!(local, sockaddr_in(port));
+
+    // Connections over the host loopback complete right away
+    let client = socket(AF_INET, SOCK_STREAM | O_NONBLOCK);
+    let addr = sockaddr_in(port);
+    let args = [client as usize, addr.as_ptr() as usize, addr.len()];
+    let connected = unsafe { invoke(SYSCALL_CONNECT, &args) };
+    assert!(connected == 0 || connected == EINPROGRESS);
+    let mut fds = [Pollfd {
+        fd: client as i32,
+        events: POLLOUT,
+        revents: 0,
+    }];
+    assert_eq!(poll_now(&mut fds), 1);
+    assert_eq!(sock_name(SYSCALL_GETPEERNAME, client), sockaddr_in(port));
+
+    let mut peer = [0u8; 16];
+    let mut peer_len = peer.len() as u32;
+    let args = [
+        listener as usize,
+        peer.as_mut_ptr() as usize,
+        &mut peer_len as *mut u32 as usize,
+        O_CLOEXEC,
+    ];
+    let conn = unsafe { invoke(SYSCALL_ACCEPT4, &args) };
+    assert!(conn >= 0, "accept4 failed: {}", conn);
+    assert_eq!(peer_len, 16);
+    assert_eq!(peer[4..8], [127, 0, 0, 1]);
+    assert_eq!(sock_name(SYSCALL_GETSOCKNAME, client), peer);
+
+    // Sockets carry data both through the socket calls and like files
+    let mut buf = [0u8; 16];
+    assert_eq!(sendto(client, b"hello", None), 5);
+    assert_eq!(recvfrom(conn, &mut buf), (5, Vec::new()));
+    assert_eq!(&buf[0..5], b"hello");
+    let args = [conn as usize, b"world".as_ptr() as usize, 5];
+    assert_eq!(unsafe { invoke(SYSCALL_WRITE, &args) }, 5);
+    let args = [client as usize, buf.as_mut_ptr() as usize, buf.len()];
+    assert_eq!(unsafe { invoke(SYSCALL_READ, &args) }, 5);
+    assert_eq!(&buf[0..5], b"world");
+
+    // The peer sees the end of the stream after a shutdown
+    let args = [client as usize, SHUT_WR];
+    assert_eq!(unsafe { invoke(SYSCALL_SHUTDOWN, &args) }, 0);
+    assert_eq!(recvfrom(conn, &mut buf).0, 0);
+
+    // Only ordinary sockets and options that cannot block are allowed
+    assert_eq!(socket(AF_INET, SOCK_RAW), EPERM);
+    assert_eq!(socket(AF_PACKET, SOCK_DGRAM), EAFNOSUPPORT);
+    assert_eq!(
+        getsockopt_int(conn, SOL_SOCKET, SO_LINGER),
+        Err(ENOPROTOOPT)
+    );
+    let tmp_fd = openat("/tmp/socket.txt", O_RDWR | O_CREAT, 0o644);
+    let args = [tmp_fd as usize, 1];
+    assert_eq!(unsafe { invoke(SYSCALL_LISTEN, &args) }, ENOTSOCK);
+    close(tmp_fd);
+
+    close(conn);
+    close(client);
+    close(listener);
+}
+
+#[test]
+#[cfg_attr(
+    feature = "netstack",
+    ignore = "IPv4 sockets are served in the enclave"
+)]
+fn datagram_and_unix_sockets_through_the_host() {
+    let _guard = setup();
+    let sender = socket(AF_INET, SOCK_DGRAM);
+    let receiver = socket(AF_INET, SOCK_DGRAM);
+    assert_eq!(bind(sender, &sockaddr_in(0)), 0);
+    assert_eq!(bind(receiver, &sockaddr_in(0)), 0);
+    let sender_addr = sock_name(SYSCALL_GETSOCKNAME, sender);
+    let receiver_addr = sock_name(SYSCALL_GETSOCKNAME, receiver);
+
+    // A receive that would block is left to the enclave to wait for
+    let resp = hal::edge::with_edge_caller(|caller| {
+        caller
+            .write_header(&EdgeCallReq::SyscallRecvFrom {
+                pid: 1,
+                fd: receiver as i32,
+                len: 16,
+                flags: 0,
+            })
+            .unwrap();
+        caller.kick().unwrap();
+        caller.read_header().unwrap()
+    });
+    assert_eq!(resp, EdgeCallResp::WouldBlock);
+
+    assert_eq!(sendto(sender, b"datagram", Some(&receiver_addr)), 8);
+    let mut buf = [0u8; 16];
+    assert_eq!(recvfrom(receiver, &mut buf), (8, sender_addr));
+    assert_eq!(&buf[0..8], b"datagram");
+    close(sender);
+    close(receiver);
+
+    // Unix sockets are bound at guest paths, and named by them
+    let _ = std::fs::remove_file(host_dir().join("root/tmp-socket"));
+    let unix = socket(AF_UNIX, SOCK_STREAM);
+    let mut addr = (AF_UNIX as u16).to_ne_bytes().to_vec();
+    addr.extend_from_slice(b"/tmp-socket\0");
+    assert_eq!(bind(unix, &addr), 0);
+    assert!(host_dir().join("root/tmp-socket").exists());
+    assert_eq!(sock_name(SYSCALL_GETSOCKNAME, unix), addr);
+    close(unix);
+
+    // The abstract namespace is shared with the host
+    let unix = socket(AF_UNIX, SOCK_STREAM);
+    let mut addr = (AF_UNIX as u16).to_ne_bytes().to_vec();
+    addr.extend_from_slice(b"\0abstract");
+    assert_eq!(bind(unix, &addr), EACCES);
+    close(unix);
+}
+
+static RECEIVED: Mutex<Vec<Notification>> = Mutex::new(Vec::new());
+
+fn record_notification(notification: Notification) {
+    RECEIVED.lock().unwrap().push(notification);
+}
+
+#[test]
+fn host_notifications_reach_the_handler() {
+    let _guard = setup();
+    hal::edge::set_notification_handler(record_notification);
+
+    hal::arch::loopback::post_notification(Notification::TimerTick);
+    hal::arch::loopback::post_notification(Notification::Signal(2));
+    hal::arch::loopback::post_notification(Notification::TimerTick);
+    hal::edge::poll_notifications();
+    assert_eq!(
+        *RECEIVED.lock().unwrap(),
+        [Notification::TimerTick, Notification::Signal(2)]
+    );
+
+    // Taken notifications are not delivered again.
+    hal::edge::poll_notifications();
+    assert_eq!(RECEIVED.lock().unwrap().len(), 2);
+}
@@ -0,0 +1,411 @@
+//! Exercises the in-enclave network stack against a peer stack on the other
+//! end of the host's network link, in the same process.
+
+#![cfg(feature = "netstack")]
+
+use std::{
+    os::unix::net::UnixDatagram,
+    sync::{Arc, Mutex, MutexGuard, Once, OnceLock},
+};
+
+use edge_proto::NetConfig;
+use linux_abi::syscall::{listing::*, SyscallHandler};
+use netstack::{Endpoint, FrameLink, NetStack, Protocol};
+
+const O_NONBLOCK: usize = 0o4000;
+const AF_INET: usize = 2;
+const SOCK_STREAM: usize = 1;
+const SOCK_DGRAM: usize = 2;
+const SOL_SOCKET: usize = 1;
+const SO_ERROR: usize = 4;
+const IPPROTO_TCP: usize = 6;
+const TCP_NODELAY: usize = 1;
+const POLLIN: i16 = 0x1;
+const POLLOUT: i16 = 0x4;
+const MSG_DONTWAIT: usize = 0x40;
+const S_IFMT: u32 = 0o170000;
+const S_IFSOCK: u32 = 0o140000;
+
+const EAGAIN: isize = -11;
+const EMSGSIZE: isize = -90;
+const EADDRINUSE: isize = -98;
+const ECONNREFUSED: isize = -111;
+const EINPROGRESS: isize = -115;
+
+const GUEST: [u8; 4] = [10, 0, 0, 2];
+const PEER: [u8; 4] = [10, 0, 0, 1];
+
+static SETUP: Once = Once::new();
+static SERIAL: Mutex<()> = Mutex::new(());
+static PEER_STACK: OnceLock<Mutex<NetStack<PeerLink>>> = OnceLock::new();
+
+/// The peer's end of the host's network link.
+struct PeerLink(UnixDatagram);
+
+impl FrameLink for PeerLink {
+    fn transmit(&mut self, frame: &[u8]) {
+        let _ = self.0.send(frame);
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Option<usize> {
+        self.0.recv(buf).ok()
+    }
+}
+
+fn setup() -> MutexGuard<'static, ()> {
+    SETUP.call_once(|| {
+        let root = std::env::temp_dir().join(format!("linux-abi-netstack-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        hal::arch::loopback::set_guest_root(root.to_str().unwrap());
+        let link = hal::arch::loopback::attach_frame_link(NetConfig {
+            mac: [2, 0, 0, 0, 0, 2],
+            addr: GUEST,
+            prefix_len: 24,
+            gateway: None,
+        });
+        link.set_nonblocking(true).unwrap();
+        hal::arch::loopback::initialize_edge_caller();
+        hal::time::init();
+        linux_abi::fs::init();
+        linux_abi::net::init();
+
+        let config = netstack::Config {
+            mac: [2, 0, 0, 0, 0, 1],
+            addr: PEER,
+            prefix_len: 24,
+            gateway: None,
+            seed: 1,
+        };
+        let peer = NetStack::new(PeerLink(link), &config, 0);
+        assert!(PEER_STACK.set(Mutex::new(peer)).is_ok());
+
+        let task = hal::task::Task::create(
+            hal::task::TaskMmStruct::new(hal::vm::UserAddressSpace, 0..0),
+            &Default::default(),
+        );
+        assert_eq!(task.lock().pid, 1, "init must match the responder's PCB");
+        hal::arch::loopback::set_current_task(&task);
+        std::mem::forget(Arc::clone(&task));
+    });
+    SERIAL.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn peer() -> MutexGuard<'static, NetStack<PeerLink>> {
+    PEER_STACK.get().unwrap().lock().unwrap()
+}
+
+unsafe fn invoke(handler: SyscallHandler, args: &[usize]) -> isize {
+    let arg = |i: usize| args.get(i).copied().unwrap_or(0);
+    match handler {
+        SyscallHandler::Syscall1(f) => f(arg(0)),
+        SyscallHandler::Syscall2(f) => f(arg(0), arg(1)),
+        SyscallHandler::Syscall3(f) => f(arg(0), arg(1), arg(2)),
+        SyscallHandler::Syscall4(f) => f(arg(0), arg(1), arg(2), arg(3)),
+        SyscallHandler::Syscall5(f) => f(arg(0), arg(1), arg(2), arg(3), arg(4)),
+        SyscallHandler::Syscall6(f) => f(arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)),
+        _ => panic!("unsupported syscall handler"),
+    }
+}
+
+#[repr(C)]
+struct Pollfd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+fn poll_now(fd: isize, events: i16) -> i16 {
+    let mut fds = [Pollfd {
+        fd: fd as i32,
+        events,
+        revents: 0,
+    }];
+    let result = unsafe { invoke(SYSCALL_POLL, &[fds.as_mut_ptr() as usize, 1, 0]) };
+    assert!(result >= 0, "poll failed: {}", result);
+    fds[0].revents
+}
+
+/// Let frames go back and forth between the guest's stack, which runs when
+/// `fd` is polled, and the peer's.
+fn pump(fd: isize) {
+    for now_ms in 0..16 {
+        peer().poll(now_ms);
+        poll_now(fd, POLLIN);
+    }
+}
+
+fn close(fd: isize) {
+    assert_eq!(unsafe { invoke(SYSCALL_CLOSE, &[fd as usize]) }, 0);
+}
+
+fn socket(ty: usize) -> isize {
+    let fd = unsafe { invoke(SYSCALL_SOCKET, &[AF_INET, ty | O_NONBLOCK, 0]) };
+    assert!(fd >= 0, "socket failed: {}", fd);
+    fd
+}
+
+fn sockaddr_in(addr: [u8; 4], port: u16) -> [u8; 16] {
+    let mut raw = [0u8; 16];
+    raw[0..2].copy_from_slice(&(AF_INET as u16).to_ne_bytes());
+    raw[2..4].copy_from_slice(&port.to_be_bytes());
+    raw[4..8].copy_from_slice(&addr);
+    raw
+}
+
+fn bind(fd: isize, port: u16) -> isize {
+    let addr = sockaddr_in([0; 4], port);
+    unsafe { invoke(SYSCALL_BIND, &[fd as usize, addr.as_ptr() as usize, 16]) }
+}
+
+fn connect(fd: isize, addr: [u8; 4], port: u16) -> isize {
+    let addr = sockaddr_in(addr, port);
+    unsafe { invoke(SYSCALL_CONNECT, &[fd as usize, addr.as_ptr() as usize, 16]) }
+}
+
+fn read(fd: isize, buf: &mut [u8]) -> isize {
+    let args = [fd as usize, buf.as_mut_ptr() as usize, buf.len()];
+    unsafe { invoke(SYSCALL_READ, &args) }
+}
+
+fn write(fd: isize, data: &[u8]) -> isize {
+    let args = [fd as usize, data.as_ptr() as usize, data.len()];
+    unsafe { invoke(SYSCALL_WRITE, &args) }
+}
+
+fn getsockopt_int(fd: isize, level: usize, name: usize) -> isize {
+    let mut value = 0i32;
+    let mut len = 4u32;
+    let args = [
+        fd as usize,
+        level,
+        name,
+        &mut value as *mut i32 as usize,
+        &mut len as *mut u32 as usize,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_GETSOCKOPT, &args) }, 0);
+    value as isize
+}
+
+/// Run `getsockname` or `getpeername` on `fd`.
+fn sock_name(handler: SyscallHandler, fd: isize) -> Vec<u8> {
+    let mut addr = [0u8; 32];
+    let mut len = addr.len() as u32;
+    let args = [
+        fd as usize,
+        addr.as_mut_ptr() as usize,
+        &mut len as *mut u32 as usize,
+    ];
+    assert_eq!(unsafe { invoke(handler, &args) }, 0);
+    addr[0..len as usize].to_vec()
+}
+
+#[test]
+fn tcp_connections_stay_in_the_enclave() {
+    let _guard = setup();
+    let listener = socket(SOCK_STREAM);
+    assert_eq!(bind(listener, 80), 0);
+    assert_eq!(
+        unsafe { invoke(SYSCALL_LISTEN, &[listener as usize, 4]) },
+        0
+    );
+    assert_eq!(
+        sock_name(SYSCALL_GETSOCKNAME, listener),
+        sockaddr_in([0; 4], 80)
+    );
+    let other = socket(SOCK_STREAM);
+    assert_eq!(bind(other, 80), EADDRINUSE);
+    close(other);
+
+    let mut peer_addr = [0u8; 16];
+    let mut peer_len = 16u32;
+    let accept_args = [
+        listener as usize,
+        peer_addr.as_mut_ptr() as usize,
+        &mut peer_len as *mut u32 as usize,
+        O_NONBLOCK,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_ACCEPT4, &accept_args) }, EAGAIN);
+
+    let client = peer().socket(Protocol::Tcp);
+    peer()
+        .connect(
+            client,
+            Endpoint {
+                addr: GUEST,
+                port: 80,
+            },
+        )
+        .unwrap();
+    pump(listener);
+    assert_eq!(poll_now(listener, POLLIN), POLLIN);
+    let conn = unsafe { invoke(SYSCALL_ACCEPT4, &accept_args) };
+    assert!(conn >= 0, "accept failed: {}", conn);
+    let client_port = peer().local_endpoint(client).port;
+    assert_eq!(peer_addr, sockaddr_in(PEER, client_port));
+    assert_eq!(
+        sock_name(SYSCALL_GETPEERNAME, conn),
+        sockaddr_in(PEER, client_port)
+    );
+
+    let mut stat = [0u8; 144];
+    let args = [conn as usize, stat.as_mut_ptr() as usize];
+    assert_eq!(unsafe { invoke(SYSCALL_FSTAT, &args) }, 0);
+    let mode = u32::from_ne_bytes(stat[24..28].try_into().unwrap());
+    assert_eq!(mode & S_IFMT, S_IFSOCK);
+
+    // Data flows both ways, through the stack in the enclave
+    let mut buf = [0u8; 16];
+    assert_eq!(read(conn, &mut buf), EAGAIN);
+    assert_eq!(peer().send(client, b"hello", None), Ok(5));
+    pump(conn);
+    assert_eq!(read(conn, &mut buf), 5);
+    assert_eq!(&buf[0..5], b"hello");
+    assert_eq!(write(conn, b"world"), 5);
+    pump(conn);
+    assert_eq!(peer().recv(client, &mut buf, false), Ok((5, None)));
+    assert_eq!(&buf[0..5], b"world");
+
+    let one = 1i32;
+    let args = [
+        conn as usize,
+        IPPROTO_TCP,
+        TCP_NODELAY,
+        &one as *const i32 as usize,
+        4,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_SETSOCKOPT, &args) }, 0);
+    assert_eq!(getsockopt_int(conn, IPPROTO_TCP, TCP_NODELAY), 1);
+
+    // The peer closing its end is the end of the stream
+    peer().shutdown(client, false, true).unwrap();
+    pump(conn);
+    assert_eq!(read(conn, &mut buf), 0);
+    close(conn);
+    close(listener);
+    pump(listener);
+    peer().close(client);
+}
+
+#[test]
+fn tcp_connections_from_the_enclave() {
+    let _guard = setup();
+    let server = peer().socket(Protocol::Tcp);
+    peer()
+        .bind(
+            server,
+            Endpoint {
+                addr: PEER,
+                port: 7,
+            },
+        )
+        .unwrap();
+    peer().listen(server, 1).unwrap();
+
+    let client = socket(SOCK_STREAM);
+    assert_eq!(connect(client, PEER, 7), EINPROGRESS);
+    pump(client);
+    assert_eq!(poll_now(client, POLLOUT), POLLOUT);
+    assert_eq!(getsockopt_int(client, SOL_SOCKET, SO_ERROR), 0);
+    assert_eq!(sock_name(SYSCALL_GETPEERNAME, client), sockaddr_in(PEER, 7));
+    let (conn, _) = peer().accept(server).unwrap();
+    assert_eq!(write(client, b"ping"), 4);
+    pump(client);
+    let mut buf = [0u8; 16];
+    assert_eq!(peer().recv(conn, &mut buf, false), Ok((4, None)));
+    // A send takes no more than the send buffer, whatever the length asked
+    let data = vec![0x5a; netstack::TCP_BUFFER_SIZE];
+    let args = [
+        client as usize,
+        data.as_ptr() as usize,
+        1 << 40,
+        MSG_DONTWAIT,
+        0,
+        0,
+    ];
+    let sent = unsafe { invoke(SYSCALL_SENDTO, &args) };
+    assert!(
+        sent > 0 && sent as usize <= netstack::TCP_BUFFER_SIZE,
+        "{}",
+        sent
+    );
+    close(client);
+    pump(client);
+    peer().close(conn);
+    peer().close(server);
+
+    // Nothing listens on the port, so the peer resets the connection
+    let refused = socket(SOCK_STREAM);
+    assert_eq!(connect(refused, PEER, 8), EINPROGRESS);
+    pump(refused);
+    assert_eq!(getsockopt_int(refused, SOL_SOCKET, SO_ERROR), -ECONNREFUSED);
+    close(refused);
+}
+
+#[test]
+fn udp_datagrams_stay_in_the_enclave() {
+    let _guard = setup();
+    let guest = socket(SOCK_DGRAM);
+    assert_eq!(bind(guest, 53), 0);
+    let peer_socket = peer().socket(Protocol::Udp);
+    peer()
+        .bind(
+            peer_socket,
+            Endpoint {
+                addr: PEER,
+                port: 5353,
+            },
+        )
+        .unwrap();
+
+    let mut buf = [0u8; 16];
+    let mut sender = [0u8; 16];
+    let mut sender_len = 16u32;
+    let recv_args = [
+        guest as usize,
+        buf.as_mut_ptr() as usize,
+        buf.len(),
+        0,
+        sender.as_mut_ptr() as usize,
+        &mut sender_len as *mut u32 as usize,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_RECVFROM, &recv_args) }, EAGAIN);
+
+    let to_guest = Endpoint {
+        addr: GUEST,
+        port: 53,
+    };
+    assert_eq!(peer().send(peer_socket, b"query", Some(to_guest)), Ok(5));
+    pump(guest);
+    // The length asked does not size the kernel's buffer
+    let mut huge_args = recv_args;
+    huge_args[2] = 1 << 40;
+    assert_eq!(unsafe { invoke(SYSCALL_RECVFROM, &huge_args) }, 5);
+    assert_eq!(&buf[0..5], b"query");
+    assert_eq!(peer().send(peer_socket, b"query", Some(to_guest)), Ok(5));
+    pump(guest);
+    assert_eq!(unsafe { invoke(SYSCALL_RECVFROM, &recv_args) }, 5);
+    assert_eq!(&buf[0..5], b"query");
+    assert_eq!(sender, sockaddr_in(PEER, 5353));
+
+    let args = [
+        guest as usize,
+        b"answer".as_ptr() as usize,
+        6,
+        0,
+        sender.as_ptr() as usize,
+        16,
+    ];
+    assert_eq!(unsafe { invoke(SYSCALL_SENDTO, &args) }, 6);
+    let mut oversized = args;
+    oversized[2] = netstack::UDP_BUFFER_SIZE + 1;
+    assert_eq!(unsafe { invoke(SYSCALL_SENDTO, &oversized) }, EMSGSIZE);
+    pump(guest);
+    let mut buf = [0u8; 16];
+    assert_eq!(
+        peer().recv(peer_socket, &mut buf, false),
+        Ok((6, Some(to_guest)))
+    );
+    assert_eq!(&buf[0..6], b"answer");
+    close(guest);
+    peer().close(peer_socket);
+}
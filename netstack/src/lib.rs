@@ -0,0 +1,24 @@
+//! A TCP/IP stack for the enclave kernel, so that connection state and
+//! payloads stay inside the enclave: only Ethernet frames leave it, over a
+//! [`FrameLink`] to the host.
+//!
+//! This wraps [smoltcp] with the socket semantics that the Linux ABI needs,
+//! such as listening sockets with a backlog, ephemeral ports and shutdown.
+
+#![no_std]
+
+extern crate alloc;
+#[cfg(test)]
+extern crate std;
+
+mod link;
+mod stack;
+
+#[cfg(test)]
+mod test;
+
+pub use link::{FrameLink, MAX_FRAME_LEN};
+pub use stack::{
+    Config, Endpoint, Error, NetStack, Protocol, Readiness, Result, SocketId, MAX_BACKLOG,
+    TCP_BUFFER_SIZE, UDP_BUFFER_SIZE,
+};
@@ -0,0 +1,77 @@
+use alloc::{vec, vec::Vec};
+use smoltcp::{
+    phy::{Device, DeviceCapabilities, Medium},
+    time::Instant,
+};
+
+/// The largest Ethernet frame sent or received, without the frame check
+/// sequence.
+pub const MAX_FRAME_LEN: usize = 1514;
+
+/// A link that carries raw Ethernet frames between the stack and the host.
+pub trait FrameLink {
+    /// Send `frame`. A frame that cannot be sent right away may be dropped,
+    /// like a network card would.
+    fn transmit(&mut self, frame: &[u8]);
+
+    /// Take the next frame received into `buf`, returning its length, or
+    /// `None` if no frame is pending.
+    fn receive(&mut self, buf: &mut [u8]) -> Option<usize>;
+}
+
+/// A [`FrameLink`] as a smoltcp device.
+pub(crate) struct LinkDevice<L>(pub L);
+
+impl<L: FrameLink> Device for LinkDevice<L> {
+    type RxToken<'a>
+        = RxFrame
+    where
+        L: 'a;
+    type TxToken<'a>
+        = TxFrame<'a, L>
+    where
+        L: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(RxFrame, TxFrame<'_, L>)> {
+        let mut frame = vec![0; MAX_FRAME_LEN];
+        let len = self.0.receive(&mut frame)?;
+        frame.truncate(len);
+        Some((RxFrame(frame), TxFrame(&mut self.0)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<TxFrame<'_, L>> {
+        Some(TxFrame(&mut self.0))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = MAX_FRAME_LEN;
+        caps
+    }
+}
+
+pub(crate) struct RxFrame(Vec<u8>);
+
+impl smoltcp::phy::RxToken for RxFrame {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.0)
+    }
+}
+
+pub(crate) struct TxFrame<'a, L>(&'a mut L);
+
+impl<L: FrameLink> smoltcp::phy::TxToken for TxFrame<'_, L> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let result = f(&mut frame);
+        self.0.transmit(&frame);
+        result
+    }
+}
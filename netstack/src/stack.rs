@@ -0,0 +1,792 @@
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use core::ops::RangeInclusive;
+use smoltcp::{
+    iface::{self, Interface, SocketHandle, SocketSet},
+    socket::{tcp, udp},
+    time::{Duration, Instant},
+    wire::{
+        EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpEndpoint, IpListenEndpoint,
+        Ipv4Address,
+    },
+};
+
+use crate::link::{FrameLink, LinkDevice};
+
+/// The size of the receive and of the send buffer of each TCP socket.
+pub const TCP_BUFFER_SIZE: usize = 0x10000;
+/// The number of datagrams each UDP socket buffers in each direction, and
+/// their total size.
+const UDP_PACKETS: usize = 32;
+pub const UDP_BUFFER_SIZE: usize = 0x10000;
+/// The most connections a listening socket keeps pending.
+pub const MAX_BACKLOG: usize = 16;
+/// The ports that sockets are bound to implicitly, as on Linux.
+const EPHEMERAL_PORTS: RangeInclusive<u16> = 32768..=60999;
+/// How long a connection may take to be established.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(75);
+
+/// The configuration of the stack's only interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub mac: [u8; 6],
+    pub addr: [u8; 4],
+    pub prefix_len: u8,
+    pub gateway: Option<[u8; 4]>,
+    /// The seed of the initial sequence numbers of TCP connections, which
+    /// should be unpredictable.
+    pub seed: u64,
+}
+
+/// An IPv4 address and port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Endpoint {
+    pub addr: [u8; 4],
+    pub port: u16,
+}
+
+impl Endpoint {
+    const UNSPECIFIED: [u8; 4] = [0; 4];
+
+    fn listen_endpoint(self) -> IpListenEndpoint {
+        IpListenEndpoint {
+            addr: (self.addr != Self::UNSPECIFIED).then(|| ip_address(self.addr)),
+            port: self.port,
+        }
+    }
+}
+
+impl From<IpEndpoint> for Endpoint {
+    fn from(endpoint: IpEndpoint) -> Endpoint {
+        let IpAddress::Ipv4(addr) = endpoint.addr;
+        Endpoint {
+            addr: addr.octets(),
+            port: endpoint.port,
+        }
+    }
+}
+
+impl From<Endpoint> for IpEndpoint {
+    fn from(endpoint: Endpoint) -> IpEndpoint {
+        IpEndpoint::new(ip_address(endpoint.addr), endpoint.port)
+    }
+}
+
+fn ip_address(addr: [u8; 4]) -> IpAddress {
+    IpAddress::Ipv4(Ipv4Address::from(addr))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The operation would block.
+    WouldBlock,
+    /// The socket is not in a state that allows the operation.
+    InvalidState,
+    /// The protocol of the socket does not support the operation.
+    NotSupported,
+    /// The address is not one of the interface.
+    Unaddressable,
+    /// The local address is used by another socket.
+    AddrInUse,
+    /// The socket is not connected.
+    NotConnected,
+    /// The socket is already connected.
+    IsConnected,
+    /// The socket is already connecting.
+    InProgress,
+    /// The socket no longer sends, as it was shut down.
+    Shutdown,
+    /// A datagram is sent without a destination.
+    DestinationRequired,
+    /// A datagram is too large for the send buffer.
+    MessageTooLong,
+    /// The peer refused the connection, or did not answer in time.
+    ConnectionRefused,
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// What a socket is ready for, like the events of `poll`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Readiness {
+    pub readable: bool,
+    pub writable: bool,
+    pub hangup: bool,
+    pub error: bool,
+}
+
+/// Identifies a socket of a [`NetStack`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SocketId(u32);
+
+enum Socket {
+    Tcp(TcpSocket),
+    Listener(Listener),
+    Udp(UdpSocket),
+}
+
+struct TcpSocket {
+    handle: SocketHandle,
+    /// The address the socket is bound to, explicitly or by connecting.
+    local: Option<Endpoint>,
+    connecting: bool,
+    established: bool,
+    read_shut: bool,
+    error: Option<Error>,
+}
+
+impl TcpSocket {
+    fn new(handle: SocketHandle) -> TcpSocket {
+        TcpSocket {
+            handle,
+            local: None,
+            connecting: false,
+            established: false,
+            read_shut: false,
+            error: None,
+        }
+    }
+
+    /// Take note of the state changes of the connection since the last call.
+    fn refresh(&mut self, socket: &mut tcp::Socket) {
+        let state = socket.state();
+        if !matches!(
+            state,
+            tcp::State::Closed | tcp::State::Listen | tcp::State::SynSent | tcp::State::SynReceived
+        ) && !self.established
+        {
+            self.established = true;
+            socket.set_timeout(None);
+        }
+        if self.connecting && !matches!(state, tcp::State::SynSent | tcp::State::SynReceived) {
+            self.connecting = false;
+            if !self.established {
+                self.error = Some(Error::ConnectionRefused);
+            }
+        }
+    }
+}
+
+/// A listening socket, which is several smoltcp sockets listening on the
+/// same address, each of which becomes a connection as a peer connects.
+struct Listener {
+    local: Endpoint,
+    backlog: Vec<SocketHandle>,
+}
+
+struct UdpSocket {
+    handle: SocketHandle,
+    remote: Option<Endpoint>,
+    read_shut: bool,
+}
+
+/// A TCP/IP stack with a single interface on a [`FrameLink`].
+///
+/// The stack only makes progress when [`NetStack::poll`] is called, which
+/// should happen whenever frames may have been received, and after the
+/// delay returned by [`NetStack::poll_delay`].
+pub struct NetStack<L> {
+    iface: Interface,
+    device: LinkDevice<L>,
+    sockets: SocketSet<'static>,
+    entries: BTreeMap<SocketId, Socket>,
+    /// Connections that are closing after their socket was closed.
+    closing: Vec<SocketHandle>,
+    config: Config,
+    next_id: u32,
+    next_port: u16,
+}
+
+impl<L: FrameLink> NetStack<L> {
+    pub fn new(link: L, config: &Config, now_ms: u64) -> NetStack<L> {
+        let mut device = LinkDevice(link);
+        let mut iface_config =
+            iface::Config::new(HardwareAddress::Ethernet(EthernetAddress(config.mac)));
+        iface_config.random_seed = config.seed;
+        let mut iface = Interface::new(
+            iface_config,
+            &mut device,
+            Instant::from_millis(now_ms as i64),
+        );
+        iface.update_ip_addrs(|addrs| {
+            addrs
+                .push(IpCidr::new(ip_address(config.addr), config.prefix_len))
+                .unwrap();
+        });
+        if let Some(gateway) = config.gateway {
+            iface
+                .routes_mut()
+                .add_default_ipv4_route(Ipv4Address::from(gateway))
+                .unwrap();
+        }
+        NetStack {
+            iface,
+            device,
+            sockets: SocketSet::new(Vec::new()),
+            entries: BTreeMap::new(),
+            closing: Vec::new(),
+            config: *config,
+            next_id: 0,
+            // Spread the ports of different runs, like Linux does
+            next_port: *EPHEMERAL_PORTS.start()
+                + (config.seed % EPHEMERAL_PORTS.len() as u64) as u16,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Receive the pending frames, and send what the sockets have to send.
+    pub fn poll(&mut self, now_ms: u64) {
+        let now = Instant::from_millis(now_ms as i64);
+        self.iface.poll(now, &mut self.device, &mut self.sockets);
+
+        for entry in self.entries.values_mut() {
+            if let Socket::Tcp(tcp) = entry {
+                tcp.refresh(self.sockets.get_mut::<tcp::Socket>(tcp.handle));
+            }
+        }
+        let sockets = &mut self.sockets;
+        self.closing.retain(|&handle| {
+            let state = sockets.get::<tcp::Socket>(handle).state();
+            let closed = matches!(state, tcp::State::Closed | tcp::State::TimeWait);
+            if closed {
+                sockets.remove(handle);
+            }
+            !closed
+        });
+    }
+
+    /// The time in milliseconds after which [`NetStack::poll`] must be called
+    /// again even if no frame is received, if any.
+    pub fn poll_delay(&mut self, now_ms: u64) -> Option<u64> {
+        let now = Instant::from_millis(now_ms as i64);
+        self.iface
+            .poll_delay(now, &self.sockets)
+            .map(|delay| delay.total_millis())
+    }
+
+    pub fn socket(&mut self, protocol: Protocol) -> SocketId {
+        let socket = match protocol {
+            Protocol::Tcp => Socket::Tcp(TcpSocket::new(self.add_tcp_socket())),
+            Protocol::Udp => {
+                let buffer = || {
+                    udp::PacketBuffer::new(
+                        vec![udp::PacketMetadata::EMPTY; UDP_PACKETS],
+                        vec![0; UDP_BUFFER_SIZE],
+                    )
+                };
+                let handle = self.sockets.add(udp::Socket::new(buffer(), buffer()));
+                Socket::Udp(UdpSocket {
+                    handle,
+                    remote: None,
+                    read_shut: false,
+                })
+            }
+        };
+        let id = SocketId(self.next_id);
+        self.next_id += 1;
+        self.entries.insert(id, socket);
+        id
+    }
+
+    fn add_tcp_socket(&mut self) -> SocketHandle {
+        let buffer = || tcp::SocketBuffer::new(vec![0; TCP_BUFFER_SIZE]);
+        self.sockets.add(tcp::Socket::new(buffer(), buffer()))
+    }
+
+    fn entry(&self, id: SocketId) -> &Socket {
+        self.entries.get(&id).expect("no such socket")
+    }
+
+    fn entry_mut(&mut self, id: SocketId) -> &mut Socket {
+        self.entries.get_mut(&id).expect("no such socket")
+    }
+
+    pub fn protocol(&self, id: SocketId) -> Protocol {
+        match self.entry(id) {
+            Socket::Tcp(_) | Socket::Listener(_) => Protocol::Tcp,
+            Socket::Udp(_) => Protocol::Udp,
+        }
+    }
+
+    pub fn is_listening(&self, id: SocketId) -> bool {
+        matches!(self.entries.get(&id), Some(Socket::Listener(_)))
+    }
+
+    /// The port of the socket of `protocol` that is bound to it, if any.
+    fn local_ports(&self, protocol: Protocol) -> impl Iterator<Item = u16> + '_ {
+        self.entries
+            .values()
+            .filter_map(move |entry| match (entry, protocol) {
+                (Socket::Tcp(tcp), Protocol::Tcp) => tcp.local.map(|local| local.port),
+                (Socket::Listener(listener), Protocol::Tcp) => Some(listener.local.port),
+                (Socket::Udp(udp), Protocol::Udp) => {
+                    let port = self.sockets.get::<udp::Socket>(udp.handle).endpoint().port;
+                    (port != 0).then_some(port)
+                }
+                _ => None,
+            })
+    }
+
+    fn ephemeral_port(&mut self, protocol: Protocol) -> Result<u16> {
+        let used: Vec<u16> = self.local_ports(protocol).collect();
+        for _ in EPHEMERAL_PORTS {
+            let port = self.next_port;
+            self.next_port = if port == *EPHEMERAL_PORTS.end() {
+                *EPHEMERAL_PORTS.start()
+            } else {
+                port + 1
+            };
+            if !used.contains(&port) {
+                return Ok(port);
+            }
+        }
+        Err(Error::AddrInUse)
+    }
+
+    /// Check that `local` may be bound to by a socket of `protocol`, taking
+    /// an ephemeral port for port 0.
+    fn claim(&mut self, protocol: Protocol, mut local: Endpoint) -> Result<Endpoint> {
+        if local.addr != Endpoint::UNSPECIFIED && local.addr != self.config.addr {
+            return Err(Error::Unaddressable);
+        }
+        if local.port == 0 {
+            local.port = self.ephemeral_port(protocol)?;
+        } else if self.local_ports(protocol).any(|port| port == local.port) {
+            return Err(Error::AddrInUse);
+        }
+        Ok(local)
+    }
+
+    pub fn bind(&mut self, id: SocketId, local: Endpoint) -> Result<()> {
+        let protocol = self.protocol(id);
+        let bound = match self.entry(id) {
+            Socket::Tcp(tcp) => tcp.local.is_some(),
+            Socket::Listener(_) => true,
+            Socket::Udp(udp) => self.sockets.get::<udp::Socket>(udp.handle).is_open(),
+        };
+        if bound {
+            return Err(Error::InvalidState);
+        }
+        let local = self.claim(protocol, local)?;
+        match self.entries.get_mut(&id).unwrap() {
+            Socket::Tcp(tcp) => tcp.local = Some(local),
+            Socket::Udp(udp) => self
+                .sockets
+                .get_mut::<udp::Socket>(udp.handle)
+                .bind(local.listen_endpoint())
+                .map_err(|_| Error::Unaddressable)?,
+            Socket::Listener(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Bind a UDP socket to an ephemeral port if it is not bound yet.
+    fn autobind_udp(&mut self, id: SocketId) -> Result<()> {
+        let handle = match self.entry(id) {
+            Socket::Udp(udp) => udp.handle,
+            _ => return Ok(()),
+        };
+        if self.sockets.get::<udp::Socket>(handle).is_open() {
+            return Ok(());
+        }
+        self.bind(id, Endpoint::default())
+    }
+
+    /// Start listening for connections, keeping up to `backlog` of them
+    /// pending.
+    pub fn listen(&mut self, id: SocketId, backlog: usize) -> Result<()> {
+        let (handle, local) = match self.entry(id) {
+            Socket::Tcp(tcp) if !tcp.connecting && !tcp.established => (tcp.handle, tcp.local),
+            Socket::Tcp(_) => return Err(Error::InvalidState),
+            // Only the backlog would change
+            Socket::Listener(_) => return Ok(()),
+            Socket::Udp(_) => return Err(Error::NotSupported),
+        };
+        let local = match local {
+            Some(local) => local,
+            None => self.claim(Protocol::Tcp, Endpoint::default())?,
+        };
+        let mut handles = vec![handle];
+        handles.extend((1..backlog.clamp(1, MAX_BACKLOG)).map(|_| self.add_tcp_socket()));
+        for &handle in &handles {
+            self.sockets
+                .get_mut::<tcp::Socket>(handle)
+                .listen(local.listen_endpoint())
+                .map_err(|_| Error::Unaddressable)?;
+        }
+        *self.entry_mut(id) = Socket::Listener(Listener {
+            local,
+            backlog: handles,
+        });
+        Ok(())
+    }
+
+    /// Take a connection from a listening socket, returning its socket and
+    /// the peer's address.
+    pub fn accept(&mut self, id: SocketId) -> Result<(SocketId, Endpoint)> {
+        let listener = match self.entries.get_mut(&id).expect("no such socket") {
+            Socket::Listener(listener) => listener,
+            _ => return Err(Error::InvalidState),
+        };
+        let mut accepted = None;
+        for (i, &handle) in listener.backlog.iter().enumerate() {
+            let socket = self.sockets.get_mut::<tcp::Socket>(handle);
+            match socket.state() {
+                tcp::State::Listen | tcp::State::SynReceived => {}
+                // The peer gave up before the connection was accepted
+                tcp::State::Closed | tcp::State::TimeWait => {
+                    socket.abort();
+                    socket
+                        .listen(listener.local.listen_endpoint())
+                        .map_err(|_| Error::Unaddressable)?;
+                }
+                _ => {
+                    accepted = Some(i);
+                    break;
+                }
+            }
+        }
+        let i = accepted.ok_or(Error::WouldBlock)?;
+        let local = listener.local;
+        let handle = self.add_tcp_socket();
+        self.sockets
+            .get_mut::<tcp::Socket>(handle)
+            .listen(local.listen_endpoint())
+            .map_err(|_| Error::Unaddressable)?;
+        let listener = match self.entry_mut(id) {
+            Socket::Listener(listener) => listener,
+            _ => unreachable!(),
+        };
+        let handle = core::mem::replace(&mut listener.backlog[i], handle);
+
+        let socket = self.sockets.get::<tcp::Socket>(handle);
+        let remote = socket.remote_endpoint().unwrap().into();
+        let mut tcp = TcpSocket::new(handle);
+        tcp.local = socket.local_endpoint().map(Endpoint::from);
+        tcp.established = true;
+        let new_id = SocketId(self.next_id);
+        self.next_id += 1;
+        self.entries.insert(new_id, Socket::Tcp(tcp));
+        Ok((new_id, remote))
+    }
+
+    /// Connect to `remote`. A TCP connection is only being established once
+    /// this returns: the socket becomes writable once it is, or reports an
+    /// error through [`NetStack::take_error`].
+    pub fn connect(&mut self, id: SocketId, remote: Endpoint) -> Result<()> {
+        match self.entry(id) {
+            Socket::Tcp(tcp) if tcp.connecting => return Err(Error::InProgress),
+            Socket::Tcp(tcp) if tcp.established => return Err(Error::IsConnected),
+            Socket::Tcp(_) => {}
+            Socket::Listener(_) => return Err(Error::InvalidState),
+            Socket::Udp(_) => {
+                self.autobind_udp(id)?;
+                match self.entry_mut(id) {
+                    Socket::Udp(udp) => udp.remote = Some(remote),
+                    _ => unreachable!(),
+                }
+                return Ok(());
+            }
+        }
+        let local = match self.entry(id) {
+            Socket::Tcp(tcp) => tcp.local,
+            _ => unreachable!(),
+        };
+        let local = match local {
+            Some(local) => local,
+            None => self.claim(Protocol::Tcp, Endpoint::default())?,
+        };
+        let tcp = match self.entries.get_mut(&id).unwrap() {
+            Socket::Tcp(tcp) => tcp,
+            _ => unreachable!(),
+        };
+        let socket = self.sockets.get_mut::<tcp::Socket>(tcp.handle);
+        socket
+            .connect(
+                self.iface.context(),
+                IpEndpoint::from(remote),
+                local.listen_endpoint(),
+            )
+            .map_err(|err| match err {
+                tcp::ConnectError::InvalidState => Error::InvalidState,
+                tcp::ConnectError::Unaddressable => Error::Unaddressable,
+            })?;
+        socket.set_timeout(Some(CONNECT_TIMEOUT));
+        tcp.local = Some(local);
+        tcp.connecting = true;
+        Ok(())
+    }
+
+    /// Send `data`, to `remote` if the socket is not connected, returning how
+    /// much of it was sent.
+    pub fn send(&mut self, id: SocketId, data: &[u8], remote: Option<Endpoint>) -> Result<usize> {
+        self.autobind_udp(id)?;
+        match self.entries.get_mut(&id).expect("no such socket") {
+            Socket::Tcp(tcp) => {
+                let socket = self.sockets.get_mut::<tcp::Socket>(tcp.handle);
+                tcp.refresh(socket);
+                if let Some(err) = tcp.error.take() {
+                    return Err(err);
+                }
+                if tcp.connecting {
+                    return Err(Error::WouldBlock);
+                }
+                if !socket.may_send() {
+                    return Err(if tcp.established {
+                        Error::Shutdown
+                    } else {
+                        Error::NotConnected
+                    });
+                }
+                match socket.send_slice(data) {
+                    Ok(0) if !data.is_empty() => Err(Error::WouldBlock),
+                    Ok(sent) => Ok(sent),
+                    Err(tcp::SendError::InvalidState) => Err(Error::NotConnected),
+                }
+            }
+            Socket::Listener(_) => Err(Error::NotConnected),
+            Socket::Udp(udp) => {
+                let remote = remote.or(udp.remote).ok_or(Error::DestinationRequired)?;
+                let socket = self.sockets.get_mut::<udp::Socket>(udp.handle);
+                if data.len() > socket.payload_send_capacity() {
+                    return Err(Error::MessageTooLong);
+                }
+                match socket.send_slice(data, IpEndpoint::from(remote)) {
+                    Ok(()) => Ok(data.len()),
+                    Err(udp::SendError::BufferFull) => Err(Error::WouldBlock),
+                    Err(udp::SendError::Unaddressable) => Err(Error::Unaddressable),
+                }
+            }
+        }
+    }
+
+    /// Receive into `buf`, leaving the data queued if `peek` is set. Returns
+    /// the size received, which is 0 at the end of a stream, and the sender
+    /// of a datagram.
+    pub fn recv(
+        &mut self,
+        id: SocketId,
+        buf: &mut [u8],
+        peek: bool,
+    ) -> Result<(usize, Option<Endpoint>)> {
+        match self.entries.get_mut(&id).expect("no such socket") {
+            Socket::Tcp(tcp) => {
+                let socket = self.sockets.get_mut::<tcp::Socket>(tcp.handle);
+                tcp.refresh(socket);
+                if let Some(err) = tcp.error.take() {
+                    return Err(err);
+                }
+                if tcp.read_shut {
+                    return Ok((0, None));
+                }
+                if tcp.connecting {
+                    return Err(Error::WouldBlock);
+                }
+                if !tcp.established {
+                    return Err(Error::NotConnected);
+                }
+                let received = if peek {
+                    socket.peek_slice(buf)
+                } else {
+                    socket.recv_slice(buf)
+                };
+                match received {
+                    Ok(0) if !buf.is_empty() && socket.may_recv() => Err(Error::WouldBlock),
+                    Ok(received) => Ok((received, None)),
+                    Err(tcp::RecvError::Finished | tcp::RecvError::InvalidState) => Ok((0, None)),
+                }
+            }
+            Socket::Listener(_) => Err(Error::NotConnected),
+            Socket::Udp(udp) => {
+                if udp.read_shut {
+                    return Ok((0, None));
+                }
+                let socket = self.sockets.get_mut::<udp::Socket>(udp.handle);
+                loop {
+                    let received = if peek {
+                        socket.peek().map(|(data, meta)| (data, *meta))
+                    } else {
+                        socket.recv()
+                    };
+                    let (data, meta) = received.map_err(|_| Error::WouldBlock)?;
+                    let sender = Endpoint::from(meta.endpoint);
+                    // A connected socket only receives from its peer
+                    if udp.remote.is_some_and(|remote| remote != sender) {
+                        if peek {
+                            socket.recv().unwrap();
+                        }
+                        continue;
+                    }
+                    // The rest of a datagram that does not fit is dropped
+                    let len = data.len().min(buf.len());
+                    buf[0..len].copy_from_slice(&data[0..len]);
+                    return Ok((len, Some(sender)));
+                }
+            }
+        }
+    }
+
+    /// Shut the receiving and/or sending side of a connection down.
+    pub fn shutdown(&mut self, id: SocketId, read: bool, write: bool) -> Result<()> {
+        match self.entries.get_mut(&id).expect("no such socket") {
+            Socket::Tcp(tcp) => {
+                let socket = self.sockets.get_mut::<tcp::Socket>(tcp.handle);
+                tcp.refresh(socket);
+                if !tcp.established && !tcp.connecting {
+                    return Err(Error::NotConnected);
+                }
+                tcp.read_shut |= read;
+                if write {
+                    socket.close();
+                }
+                Ok(())
+            }
+            Socket::Listener(_) => Err(Error::NotConnected),
+            Socket::Udp(udp) if udp.remote.is_some() => {
+                udp.read_shut |= read;
+                Ok(())
+            }
+            Socket::Udp(_) => Err(Error::NotConnected),
+        }
+    }
+
+    /// Close a socket. A connection is shut down gracefully in the
+    /// background, while the connections a listener did not accept are
+    /// reset.
+    pub fn close(&mut self, id: SocketId) {
+        match self.entries.remove(&id).expect("no such socket") {
+            Socket::Tcp(tcp) => {
+                self.sockets.get_mut::<tcp::Socket>(tcp.handle).close();
+                self.closing.push(tcp.handle);
+            }
+            Socket::Listener(listener) => {
+                for handle in listener.backlog {
+                    self.sockets.get_mut::<tcp::Socket>(handle).abort();
+                    self.closing.push(handle);
+                }
+            }
+            Socket::Udp(udp) => {
+                self.sockets.remove(udp.handle);
+            }
+        }
+    }
+
+    /// The address a socket is bound to, which is unspecified if it is not.
+    pub fn local_endpoint(&self, id: SocketId) -> Endpoint {
+        match self.entry(id) {
+            Socket::Tcp(tcp) => {
+                let socket = self.sockets.get::<tcp::Socket>(tcp.handle);
+                socket
+                    .local_endpoint()
+                    .map(Endpoint::from)
+                    .or(tcp.local)
+                    .unwrap_or_default()
+            }
+            Socket::Listener(listener) => listener.local,
+            Socket::Udp(udp) => {
+                let endpoint = self.sockets.get::<udp::Socket>(udp.handle).endpoint();
+                Endpoint {
+                    addr: endpoint.addr.map_or(Endpoint::UNSPECIFIED, |addr| {
+                        let IpAddress::Ipv4(addr) = addr;
+                        addr.octets()
+                    }),
+                    port: endpoint.port,
+                }
+            }
+        }
+    }
+
+    /// The address of the peer of a connected socket.
+    pub fn remote_endpoint(&self, id: SocketId) -> Result<Endpoint> {
+        match self.entry(id) {
+            Socket::Tcp(tcp) if tcp.established => {
+                let socket = self.sockets.get::<tcp::Socket>(tcp.handle);
+                socket
+                    .remote_endpoint()
+                    .map(Endpoint::from)
+                    .ok_or(Error::NotConnected)
+            }
+            Socket::Udp(udp) => udp.remote.ok_or(Error::NotConnected),
+            _ => Err(Error::NotConnected),
+        }
+    }
+
+    /// Take the error that a connection attempt ended with, if any.
+    pub fn take_error(&mut self, id: SocketId) -> Option<Error> {
+        match self.entries.get_mut(&id).expect("no such socket") {
+            Socket::Tcp(tcp) => {
+                tcp.refresh(self.sockets.get_mut::<tcp::Socket>(tcp.handle));
+                tcp.error.take()
+            }
+            _ => None,
+        }
+    }
+
+    pub fn readiness(&mut self, id: SocketId) -> Readiness {
+        match self.entries.get_mut(&id).expect("no such socket") {
+            Socket::Tcp(tcp) => {
+                let socket = self.sockets.get_mut::<tcp::Socket>(tcp.handle);
+                tcp.refresh(socket);
+                let finished = tcp.established && !socket.may_recv();
+                // Like on Linux, a socket that is not connected is hung up
+                let hangup = (finished && !socket.may_send())
+                    || (!tcp.connecting && socket.state() == tcp::State::Closed);
+                let error = tcp.error.is_some();
+                Readiness {
+                    readable: socket.can_recv() || finished || tcp.read_shut || error,
+                    writable: socket.can_send() || hangup || error,
+                    hangup,
+                    error,
+                }
+            }
+            Socket::Listener(listener) => Readiness {
+                readable: listener.backlog.iter().any(|&handle| {
+                    let socket = self.sockets.get::<tcp::Socket>(handle);
+                    !matches!(
+                        socket.state(),
+                        tcp::State::Listen | tcp::State::SynReceived | tcp::State::Closed
+                    )
+                }),
+                ..Default::default()
+            },
+            Socket::Udp(udp) => {
+                let socket = self.sockets.get::<udp::Socket>(udp.handle);
+                Readiness {
+                    readable: socket.can_recv() || udp.read_shut,
+                    writable: socket.can_send(),
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    /// Turn Nagle's algorithm off or on for a TCP socket, like `TCP_NODELAY`.
+    pub fn set_nodelay(&mut self, id: SocketId, nodelay: bool) -> Result<()> {
+        let handle = self.tcp_handle(id)?;
+        self.sockets
+            .get_mut::<tcp::Socket>(handle)
+            .set_nagle_enabled(!nodelay);
+        Ok(())
+    }
+
+    pub fn nodelay(&self, id: SocketId) -> Result<bool> {
+        let handle = self.tcp_handle(id)?;
+        Ok(!self.sockets.get::<tcp::Socket>(handle).nagle_enabled())
+    }
+
+    fn tcp_handle(&self, id: SocketId) -> Result<SocketHandle> {
+        match self.entry(id) {
+            Socket::Tcp(tcp) => Ok(tcp.handle),
+            Socket::Listener(listener) => Ok(listener.backlog[0]),
+            Socket::Udp(_) => Err(Error::NotSupported),
+        }
+    }
+}
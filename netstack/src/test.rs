@@ -0,0 +1,281 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc, vec, vec::Vec};
+
+use crate::{Config, Endpoint, Error, FrameLink, NetStack, Protocol};
+
+type Queue = Rc<RefCell<VecDeque<Vec<u8>>>>;
+
+/// One end of an in-memory Ethernet cable.
+struct Wire {
+    tx: Queue,
+    rx: Queue,
+}
+
+impl FrameLink for Wire {
+    fn transmit(&mut self, frame: &[u8]) {
+        self.tx.borrow_mut().push_back(frame.to_vec());
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let frame = self.rx.borrow_mut().pop_front()?;
+        buf[0..frame.len()].copy_from_slice(&frame);
+        Some(frame.len())
+    }
+}
+
+const A: [u8; 4] = [10, 0, 0, 1];
+const B: [u8; 4] = [10, 0, 0, 2];
+
+fn config(addr: [u8; 4], seed: u64) -> Config {
+    Config {
+        mac: [2, 0, 0, 0, 0, addr[3]],
+        addr,
+        prefix_len: 24,
+        gateway: None,
+        seed,
+    }
+}
+
+struct Pair {
+    a: NetStack<Wire>,
+    b: NetStack<Wire>,
+    queues: [Queue; 2],
+    now_ms: u64,
+}
+
+impl Pair {
+    fn new() -> Pair {
+        let queues: [Queue; 2] = Default::default();
+        let wire = |tx: &Queue, rx: &Queue| Wire {
+            tx: tx.clone(),
+            rx: rx.clone(),
+        };
+        Pair {
+            a: NetStack::new(wire(&queues[0], &queues[1]), &config(A, 1), 0),
+            b: NetStack::new(wire(&queues[1], &queues[0]), &config(B, 2), 0),
+            queues,
+            now_ms: 0,
+        }
+    }
+
+    /// Poll both stacks until no frame is in flight.
+    fn pump(&mut self) {
+        loop {
+            self.now_ms += 1;
+            self.a.poll(self.now_ms);
+            self.b.poll(self.now_ms);
+            if self.queues.iter().all(|queue| queue.borrow().is_empty()) {
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn tcp_connection() {
+    let mut pair = Pair::new();
+    let listener = pair.b.socket(Protocol::Tcp);
+    pair.b
+        .bind(listener, Endpoint { addr: B, port: 80 })
+        .unwrap();
+    pair.b.listen(listener, 4).unwrap();
+    assert_eq!(pair.b.accept(listener), Err(Error::WouldBlock));
+
+    let client = pair.a.socket(Protocol::Tcp);
+    pair.a
+        .connect(client, Endpoint { addr: B, port: 80 })
+        .unwrap();
+    assert_eq!(
+        pair.a.connect(client, Endpoint { addr: B, port: 80 }),
+        Err(Error::InProgress)
+    );
+    assert!(!pair.a.readiness(client).writable);
+    pair.pump();
+    assert!(pair.a.readiness(client).writable);
+    assert_eq!(pair.a.take_error(client), None);
+
+    assert!(pair.b.readiness(listener).readable);
+    let (server, peer) = pair.b.accept(listener).unwrap();
+    assert_eq!(peer, pair.a.local_endpoint(client));
+    assert_eq!(peer.addr, A);
+    assert!((32768..=60999).contains(&peer.port));
+    assert_eq!(pair.b.remote_endpoint(server), Ok(peer));
+    assert_eq!(
+        pair.b.local_endpoint(server),
+        Endpoint { addr: B, port: 80 }
+    );
+
+    let mut buf = [0; 16];
+    assert_eq!(pair.b.recv(server, &mut buf, false), Err(Error::WouldBlock));
+    assert_eq!(pair.a.send(client, b"hello", None), Ok(5));
+    pair.pump();
+    assert!(pair.b.readiness(server).readable);
+    assert_eq!(pair.b.recv(server, &mut buf, true), Ok((5, None)));
+    assert_eq!(pair.b.recv(server, &mut buf, false), Ok((5, None)));
+    assert_eq!(&buf[0..5], b"hello");
+
+    pair.a.shutdown(client, false, true).unwrap();
+    assert_eq!(pair.a.send(client, b"late", None), Err(Error::Shutdown));
+    pair.pump();
+    assert_eq!(pair.b.recv(server, &mut buf, false), Ok((0, None)));
+    assert_eq!(pair.b.send(server, b"bye", None), Ok(3));
+    pair.pump();
+    assert_eq!(pair.a.recv(client, &mut buf, false), Ok((3, None)));
+    assert_eq!(&buf[0..3], b"bye");
+
+    pair.b.close(server);
+    pair.a.close(client);
+    pair.pump();
+    pair.b.close(listener);
+    pair.pump();
+}
+
+#[test]
+fn refused_connection() {
+    let mut pair = Pair::new();
+    let client = pair.a.socket(Protocol::Tcp);
+    pair.a
+        .connect(client, Endpoint { addr: B, port: 81 })
+        .unwrap();
+    pair.pump();
+    let readiness = pair.a.readiness(client);
+    assert!(readiness.error && readiness.writable);
+    assert_eq!(pair.a.take_error(client), Some(Error::ConnectionRefused));
+    assert_eq!(pair.a.take_error(client), None);
+    assert_eq!(pair.a.send(client, b"x", None), Err(Error::NotConnected));
+}
+
+#[test]
+fn udp_datagrams() {
+    let mut pair = Pair::new();
+    let server = pair.b.socket(Protocol::Udp);
+    pair.b
+        .bind(
+            server,
+            Endpoint {
+                addr: [0; 4],
+                port: 53,
+            },
+        )
+        .unwrap();
+
+    let client = pair.a.socket(Protocol::Udp);
+    assert_eq!(
+        pair.a.send(client, b"query", None),
+        Err(Error::DestinationRequired)
+    );
+    assert_eq!(
+        pair.a.send(
+            client,
+            &vec![0; 0x20000],
+            Some(Endpoint { addr: B, port: 53 })
+        ),
+        Err(Error::MessageTooLong)
+    );
+    pair.a
+        .connect(client, Endpoint { addr: B, port: 53 })
+        .unwrap();
+    assert_eq!(pair.a.send(client, b"query", None), Ok(5));
+    pair.pump();
+
+    let mut buf = [0; 3];
+    let (len, sender) = pair.b.recv(server, &mut buf, false).unwrap();
+    assert_eq!(&buf[0..len], b"que");
+    let sender = sender.unwrap();
+    assert_eq!(sender.addr, A);
+    assert_eq!(sender.port, pair.a.local_endpoint(client).port);
+    assert_eq!(pair.b.recv(server, &mut buf, false), Err(Error::WouldBlock));
+
+    // A connected socket drops the datagrams of other senders
+    let other = pair.b.socket(Protocol::Udp);
+    assert_eq!(pair.b.send(other, b"spam", Some(sender)), Ok(4));
+    assert_eq!(pair.b.send(server, b"answer", Some(sender)), Ok(6));
+    pair.pump();
+    let mut buf = [0; 16];
+    assert_eq!(
+        pair.a.recv(client, &mut buf, false),
+        Ok((6, Some(Endpoint { addr: B, port: 53 })))
+    );
+    assert_eq!(&buf[0..6], b"answer");
+    assert_eq!(pair.a.recv(client, &mut buf, false), Err(Error::WouldBlock));
+}
+
+#[test]
+fn local_addresses() {
+    let mut pair = Pair::new();
+    let first = pair.a.socket(Protocol::Tcp);
+    pair.a
+        .bind(
+            first,
+            Endpoint {
+                addr: A,
+                port: 8080,
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        pair.a.bind(
+            first,
+            Endpoint {
+                addr: A,
+                port: 8081
+            }
+        ),
+        Err(Error::InvalidState)
+    );
+
+    let second = pair.a.socket(Protocol::Tcp);
+    assert_eq!(
+        pair.a.bind(
+            second,
+            Endpoint {
+                addr: [0; 4],
+                port: 8080
+            }
+        ),
+        Err(Error::AddrInUse)
+    );
+    assert_eq!(
+        pair.a.bind(
+            second,
+            Endpoint {
+                addr: B,
+                port: 8081
+            }
+        ),
+        Err(Error::Unaddressable)
+    );
+    // The port is only taken for TCP
+    let udp = pair.a.socket(Protocol::Udp);
+    pair.a
+        .bind(
+            udp,
+            Endpoint {
+                addr: A,
+                port: 8080,
+            },
+        )
+        .unwrap();
+
+    pair.a.listen(second, 1).unwrap();
+    let port = pair.a.local_endpoint(second).port;
+    assert!((32768..=60999).contains(&port));
+    assert_ne!(port, 8080);
+    pair.a.close(first);
+    let third = pair.a.socket(Protocol::Tcp);
+    pair.a
+        .bind(
+            third,
+            Endpoint {
+                addr: A,
+                port: 8080,
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        pair.a.local_endpoint(third),
+        Endpoint {
+            addr: A,
+            port: 8080
+        }
+    );
+}
@@ -3,7 +3,6 @@
 
 use hal::{
     arch::sgx::{frame::UserspaceRegs, vm::UserAddressSpace},
-    edge::EdgeFile,
     task::{Task, TaskFuture, TaskMmStruct, VmArea},
     vm::AddressSpace,
 };
@@ -11,7 +10,6 @@ use sgx_types::sgx_status_t;
 
 extern crate alloc;
 
-mod elf;
 mod heap;
 mod klog;
 mod panic;
@@ -40,6 +38,11 @@ pub extern "C" fn rt_main(utm_base: *mut u8, utm_size: usize) -> sgx_status_t {
 
     // Log system
     klog::klog_init().expect("failed to initialize klog module");
+    hal::edge::set_notification_handler(linux_abi::notify::handle_notification);
+    hal::time::init();
+    linux_abi::fs::init();
+    #[cfg(feature = "netstack")]
+    linux_abi::net::init();
 
     log::debug!("SGX TEE OS is running!");
     log::debug!(
@@ -69,9 +72,11 @@ pub extern "C" fn rt_main(utm_base: *mut u8, utm_size: usize) -> sgx_status_t {
     let mut mm = TaskMmStruct::new(addr_space, user_stack_begin..user_stack_begin + 0x4000);
 
     // Load sgx-init as an ELF file
-    let mut edge_file = elf::EdgeElfFile(EdgeFile::open("sgx-init"));
-    let elf_file = elf_loader::ElfFile::new(&mut edge_file, elf_loader::arch::X86_64);
-    elf_file.load_allocated(&mut edge_file, |ptr, size| {
+    let init_path = linux_abi::exec::init_path("sgx-init");
+    let mut exec_file = linux_abi::elf::ExecFile::open(&init_path).expect("failed to open sgx-init");
+    mm.exe = linux_abi::fs::normalize_path(&init_path);
+    let elf_file = elf_loader::ElfFile::new(&mut exec_file, elf_loader::arch::X86_64);
+    elf_file.load_allocated(&mut exec_file, |ptr, size| {
         let placement = ptr as usize + rsrv_base as usize;
         log::debug!(
             "ELF loader: mapping ({:?} + {:#X}) -> {:#X}",
@@ -111,8 +116,8 @@ pub extern "C" fn rt_main(utm_base: *mut u8, utm_size: usize) -> sgx_status_t {
     );
 
     let task_future = TaskFuture::new(task);
-    executor::spawn(task_future);
-    executor::run_until_idle();
+    hal::task::spawn(task_future);
+    hal::task::run_until_idle();
 
     log::debug!("All kernel tasks have exited");
     sgx_status_t::SGX_SUCCESS
@@ -35,6 +35,9 @@ pub unsafe extern "C" fn handle_syscall(
         Some(SyscallHandler::Syscall4(f)) => {
             result = f(arg0, arg1, arg2, arg3);
         }
+        Some(SyscallHandler::Syscall5(f)) => {
+            result = f(arg0, arg1, arg2, arg3, arg4);
+        }
         Some(SyscallHandler::Syscall6(f)) => {
             result = f(arg0, arg1, arg2, arg3, arg4, arg5);
         }
@@ -15,4 +15,12 @@ fn main() {
     for lib in sgx_libs {
         println!("cargo:rustc-link-lib=dylib={}{}", lib, lib_suffix);
     }
+
+    // Quotes are made by the quoting enclave of the DCAP library, which has no
+    // SIM mode
+    println!("cargo:rustc-check-cfg=cfg(sgx_hw)");
+    if sgx_mode == "HW" {
+        println!("cargo:rustc-cfg=sgx_hw");
+        println!("cargo:rustc-link-lib=dylib=sgx_dcap_ql");
+    }
 }
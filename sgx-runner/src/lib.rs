@@ -1,8 +1,10 @@
 mod ocall;
+mod quote;
 
 use std::sync::Mutex;
 
-use edge_proto::server::SharedMemEdgeStream;
+use anyhow::Context;
+use edge_proto::{channel::EdgeMemLayout, server::SharedMemEdgeStream};
 use sgx_types::*;
 use sgx_urts::SgxEnclave;
 
@@ -17,13 +19,14 @@ extern "C" {
     ) -> sgx_status_t;
 }
 
+const EDGE_MEM_LAYOUT: EdgeMemLayout = EdgeMemLayout::new(
+    kconfig::EDGE_CHANNELS,
+    kconfig::EDGE_HEADER_SIZE,
+    kconfig::EDGE_BUFFER_SIZE,
+);
+
 lazy_static::lazy_static! {
-    static ref EDGE_MEM: Mutex<SharedMemEdgeStream> = Mutex::new(SharedMemEdgeStream::new(
-        core::ptr::null_mut(),
-        0,
-        core::ptr::null_mut(),
-        0,
-    ));
+    static ref EDGE_STREAMS: Mutex<Vec<SharedMemEdgeStream>> = Mutex::new(Vec::new());
 }
 
 fn init_enclave() -> SgxResult<SgxEnclave> {
@@ -56,12 +59,16 @@ pub fn main() -> anyhow::Result<()> {
 
     let edge_mem = unsafe { libc::malloc(kconfig::EDGE_MEM_SIZE) } as *mut u8;
     log::debug!("Shared memory allocated, address = {:?}", edge_mem);
-    *EDGE_MEM.lock().unwrap() = SharedMemEdgeStream::new(
-        edge_mem,
-        0x1_000,
-        unsafe { edge_mem.add(0x1_000) },
-        kconfig::EDGE_BUFFER_SIZE,
-    );
+    *EDGE_STREAMS.lock().unwrap() = (0..EDGE_MEM_LAYOUT.channels)
+        .map(|channel| unsafe {
+            SharedMemEdgeStream::for_channel(&EDGE_MEM_LAYOUT, edge_mem, channel)
+        })
+        .collect();
+    // The edge memory is never freed
+    unsafe {
+        edge_responder::notify::set_notification_area(EDGE_MEM_LAYOUT.notification_area(edge_mem));
+    }
+    edge_responder::notify::start_notifier().context("start notifier")?;
 
     // let mut kernel_file = File::open("sgx-rt.bin").expect("failed to open the bin");
     // let edge_mem_ref:&mut [u8]=core::slice::from_raw_parts_mut(edge_mem as *mut u8,SHARED_MEM_SIZE);
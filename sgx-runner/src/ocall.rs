@@ -1,8 +1,13 @@
+use sgx_types::{sgx_report_t, sgx_status_t, sgx_target_info_t};
+
 #[no_mangle]
 unsafe extern "C" fn ocall_edge_kick() {
-    if let Err(err) = edge_responder::handle_edge_call(&mut *crate::EDGE_MEM.lock().unwrap()) {
-        log::error!("Failed to handle edge call: {:#}", err);
-        std::process::exit(1);
+    let mut edge_streams = crate::EDGE_STREAMS.lock().unwrap();
+    for edge_stream in edge_streams.iter_mut().filter(|s| s.take_doorbell()) {
+        if let Err(err) = edge_responder::handle_edge_call(edge_stream) {
+            log::error!("Failed to handle edge call: {:#}", err);
+            std::process::exit(1);
+        }
     }
 }
 
@@ -25,3 +30,31 @@ unsafe extern "C" fn ocall_switch_gs_base(new_gs_base: usize) -> usize {
 
     old_gs_base
 }
+
+#[no_mangle]
+unsafe extern "C" fn ocall_get_qe_target_info(target_info: *mut sgx_target_info_t) -> sgx_status_t {
+    match crate::quote::qe_target_info() {
+        Ok(info) => {
+            *target_info = info;
+            sgx_status_t::SGX_SUCCESS
+        }
+        Err(status) => status,
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn ocall_get_quote(
+    report: *const sgx_report_t,
+    quote: *mut u8,
+    quote_size: u32,
+    quote_len: *mut u32,
+) -> sgx_status_t {
+    let quote = std::slice::from_raw_parts_mut(quote, quote_size as usize);
+    match crate::quote::quote(&*report, quote) {
+        Ok(len) => {
+            *quote_len = len;
+            sgx_status_t::SGX_SUCCESS
+        }
+        Err(status) => status,
+    }
+}
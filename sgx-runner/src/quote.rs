@@ -0,0 +1,76 @@
+//! Quotes of the enclave's reports, made by the quoting enclave (QE) of the
+//! DCAP library. They need SGX hardware, so they are not available in SIM
+//! mode.
+
+use sgx_types::{sgx_report_t, sgx_status_t, sgx_target_info_t};
+
+#[cfg(sgx_hw)]
+mod dcap {
+    use sgx_types::{sgx_report_t, sgx_target_info_t};
+
+    /// A `quote3_error_t`, which is `SGX_QL_SUCCESS` (0) on success.
+    pub type Quote3Error = u32;
+
+    extern "C" {
+        pub fn sgx_qe_get_target_info(target_info: *mut sgx_target_info_t) -> Quote3Error;
+        pub fn sgx_qe_get_quote_size(quote_size: *mut u32) -> Quote3Error;
+        pub fn sgx_qe_get_quote(
+            report: *const sgx_report_t,
+            quote_size: u32,
+            quote: *mut u8,
+        ) -> Quote3Error;
+    }
+}
+
+#[cfg(sgx_hw)]
+fn check(error: dcap::Quote3Error, what: &str) -> Result<(), sgx_status_t> {
+    if error != 0 {
+        log::error!("Failed to {}: {:#x}", what, error);
+        return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+    }
+    Ok(())
+}
+
+/// The target info of the QE, which the enclave's reports must be made for.
+#[cfg(sgx_hw)]
+pub fn qe_target_info() -> Result<sgx_target_info_t, sgx_status_t> {
+    let mut target_info = sgx_target_info_t::default();
+    check(
+        unsafe { dcap::sgx_qe_get_target_info(&mut target_info) },
+        "get the QE's target info",
+    )?;
+    Ok(target_info)
+}
+
+/// Have the QE sign `report` into `quote`, returning the quote's size.
+#[cfg(sgx_hw)]
+pub fn quote(report: &sgx_report_t, quote: &mut [u8]) -> Result<u32, sgx_status_t> {
+    let mut size = 0;
+    check(
+        unsafe { dcap::sgx_qe_get_quote_size(&mut size) },
+        "get the quote size",
+    )?;
+    if size as usize > quote.len() {
+        log::error!(
+            "A quote of {} bytes does not fit the enclave's buffer",
+            size
+        );
+        return Err(sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+    check(
+        unsafe { dcap::sgx_qe_get_quote(report, size, quote.as_mut_ptr()) },
+        "get a quote",
+    )?;
+    Ok(size)
+}
+
+#[cfg(not(sgx_hw))]
+pub fn qe_target_info() -> Result<sgx_target_info_t, sgx_status_t> {
+    log::warn!("There is no quoting enclave in SIM mode");
+    Err(sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED)
+}
+
+#[cfg(not(sgx_hw))]
+pub fn quote(_report: &sgx_report_t, _quote: &mut [u8]) -> Result<u32, sgx_status_t> {
+    Err(sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED)
+}
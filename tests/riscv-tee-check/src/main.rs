@@ -0,0 +1,168 @@
+#![no_std]
+#![no_main]
+
+//! Checks the kernel's TEE services on Keystone, which only work with the
+//! security monitor.
+//!
+//! The report read from `/dev/attestation` must bind the nonce written to it.
+//! It is saved to `attestation-report.bin` on the host, to be checked with
+//! the `attestation-verifier` crate against the device's public key. Data
+//! sealed through `/dev/seal` must come back from `/dev/unseal`.
+
+core::arch::global_asm!(
+    r#"
+    .section .text.entry
+    .global _start
+
+_start:
+    j  main
+    "#
+);
+
+const OPENAT: usize = 56;
+const CLOSE: usize = 57;
+const READ: usize = 63;
+const WRITE: usize = 64;
+const EXIT: usize = 93;
+
+const AT_FDCWD: usize = -100isize as usize;
+const O_WRONLY: usize = 0o1;
+const O_RDWR: usize = 0o2;
+const O_CREAT: usize = 0o100;
+const O_TRUNC: usize = 0o1000;
+
+/// The size of the security monitor's `struct report`.
+const REPORT_SIZE: usize = (64 + 8 + 1024 + 64) + (64 + 32 + 64) + 32;
+/// Where the data is in the report: after the enclave's measurement and the
+/// data length.
+const REPORT_DATA_OFFSET: usize = 64 + 8;
+const NONCE: [u8; 64] = [0x5a; 64];
+static SECRET: &[u8] = b"sealed by riscv-tee-check";
+
+static mut BUF: [u8; 2048] = [0; 2048];
+
+unsafe fn syscall(nr: usize, arg0: usize, arg1: usize, arg2: usize, arg3: usize) -> isize {
+    let result;
+    core::arch::asm!(
+        "ecall",
+        in("a7") nr,
+        inlateout("a0") arg0 => result,
+        in("a1") arg1,
+        in("a2") arg2,
+        in("a3") arg3,
+    );
+    result
+}
+
+fn print(msg: &str) {
+    unsafe {
+        syscall(WRITE, 1, msg.as_ptr() as usize, msg.len(), 0);
+    }
+}
+
+fn exit(code: usize) -> ! {
+    unsafe {
+        syscall(EXIT, code, 0, 0, 0);
+    }
+    unreachable!()
+}
+
+fn fail(msg: &str) -> ! {
+    print("tee-check: ");
+    print(msg);
+    print("\n");
+    exit(1)
+}
+
+fn check(result: isize, msg: &str) -> usize {
+    if result < 0 {
+        fail(msg);
+    }
+    result as usize
+}
+
+fn open(path: &str, flags: usize) -> usize {
+    check(
+        unsafe { syscall(OPENAT, AT_FDCWD, path.as_ptr() as usize, flags, 0o644) },
+        "open failed",
+    )
+}
+
+/// Write `input` to the device at `path` and read its output into `output`,
+/// returning the size of the output.
+fn transform(path: &str, input: &[u8], output: &mut [u8]) -> usize {
+    let fd = open(path, O_RDWR);
+    let written = check(
+        unsafe { syscall(WRITE, fd, input.as_ptr() as usize, input.len(), 0) },
+        "write failed",
+    );
+    if written != input.len() {
+        fail("short write");
+    }
+    let mut len = 0;
+    loop {
+        let buf = &mut output[len..];
+        let read = check(
+            unsafe { syscall(READ, fd, buf.as_mut_ptr() as usize, buf.len(), 0) },
+            "read failed",
+        );
+        if read == 0 {
+            break;
+        }
+        len += read;
+    }
+    check(unsafe { syscall(CLOSE, fd, 0, 0, 0) }, "close failed");
+    len
+}
+
+fn attestation(buf: &mut [u8]) {
+    let len = transform("/dev/attestation\0", &NONCE, buf);
+    if len != REPORT_SIZE {
+        fail("the report does not have the monitor's size");
+    }
+    if buf[REPORT_DATA_OFFSET..][..NONCE.len()] != NONCE {
+        fail("the report does not bind the nonce");
+    }
+
+    let fd = open("attestation-report.bin\0", O_WRONLY | O_CREAT | O_TRUNC);
+    check(
+        unsafe { syscall(WRITE, fd, buf.as_ptr() as usize, len, 0) },
+        "write failed",
+    );
+    check(unsafe { syscall(CLOSE, fd, 0, 0, 0) }, "close failed");
+    print("tee-check: attestation ok\n");
+}
+
+fn sealing(buf: &mut [u8]) {
+    let (blob, data) = buf.split_at_mut(1024);
+    let blob_len = transform("/dev/seal\0", SECRET, blob);
+    if blob[..blob_len]
+        .windows(SECRET.len())
+        .any(|window| window == SECRET)
+    {
+        fail("the blob holds the data in clear");
+    }
+    let data_len = transform("/dev/unseal\0", &blob[..blob_len], data);
+    if data[..data_len] != *SECRET {
+        fail("unsealing does not give the data back");
+    }
+    print("tee-check: sealing ok\n");
+}
+
+#[no_mangle]
+extern "C" fn main() {
+    // The user stack is tiny, so the buffer is static
+    let buf = unsafe { &mut *core::ptr::addr_of_mut!(BUF) };
+    attestation(buf);
+    sealing(buf);
+    exit(0);
+}
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("wfi");
+        }
+    }
+}
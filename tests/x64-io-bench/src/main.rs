@@ -0,0 +1,163 @@
+#![no_std]
+#![no_main]
+
+//! Measures file I/O throughput through the edge call interface.
+//!
+//! A file is written and read back in large chunks, and the elapsed TSC
+//! cycles of each phase are printed. Run it on two builds to compare them.
+
+core::arch::global_asm!(
+    r#"
+    .section .text.entry
+    .global _start
+
+_start:
+    jmp     main
+    "#
+);
+
+const READ: usize = 0;
+const WRITE: usize = 1;
+const CLOSE: usize = 3;
+const MMAP: usize = 9;
+const EXIT: usize = 60;
+const OPENAT: usize = 257;
+
+const AT_FDCWD: usize = -100isize as usize;
+const O_RDONLY: usize = 0;
+const O_WRONLY: usize = 0o1;
+const O_CREAT: usize = 0o100;
+const O_TRUNC: usize = 0o1000;
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const MAP_PRIVATE: usize = 0x2;
+const MAP_ANONYMOUS: usize = 0x20;
+
+static PATH: &str = "io-bench.dat\0";
+const CHUNK_SIZE: usize = 0x100_000;
+const TOTAL_SIZE: usize = 0x1_000_000;
+
+unsafe fn syscall(
+    nr: usize,
+    arg0: usize,
+    arg1: usize,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+    arg5: usize,
+) -> isize {
+    let result;
+    core::arch::asm!(
+        "syscall",
+        inout("rax") nr => result,
+        in("rdi") arg0,
+        in("rsi") arg1,
+        in("rdx") arg2,
+        in("r10") arg3,
+        in("r8") arg4,
+        in("r9") arg5,
+        out("rcx") _,
+        out("r11") _,
+    );
+    result
+}
+
+fn check(result: isize) -> usize {
+    if result < 0 {
+        print("io-bench: syscall failed\n");
+        exit(1);
+    }
+    result as usize
+}
+
+fn print(msg: &str) {
+    unsafe {
+        syscall(WRITE, 1, msg.as_ptr() as usize, msg.len(), 0, 0, 0);
+    }
+}
+
+fn print_num(mut num: u64) {
+    let mut buf = [0u8; 20];
+    let mut pos = buf.len();
+    loop {
+        pos -= 1;
+        buf[pos] = b'0' + (num % 10) as u8;
+        num /= 10;
+        if num == 0 {
+            break;
+        }
+    }
+    print(core::str::from_utf8(&buf[pos..]).unwrap());
+}
+
+fn exit(code: usize) -> ! {
+    unsafe {
+        syscall(EXIT, code, 0, 0, 0, 0, 0);
+    }
+    unreachable!()
+}
+
+fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+fn report(phase: &str, cycles: u64) {
+    print(phase);
+    print(": ");
+    print_num(TOTAL_SIZE as u64);
+    print(" bytes in ");
+    print_num(cycles);
+    print(" cycles (");
+    print_num(TOTAL_SIZE as u64 * 1000 / cycles.max(1));
+    print(" bytes per 1000 cycles)\n");
+}
+
+/// Issue `nr` (read or write) on the buffer until `TOTAL_SIZE` bytes are
+/// transferred, returning the elapsed cycles.
+fn transfer(nr: usize, flags: usize, buf: usize) -> u64 {
+    let fd =
+        check(unsafe { syscall(OPENAT, AT_FDCWD, PATH.as_ptr() as usize, flags, 0o644, 0, 0) });
+    let start = rdtsc();
+    let mut done = 0;
+    while done < TOTAL_SIZE {
+        let len = check(unsafe { syscall(nr, fd, buf, CHUNK_SIZE, 0, 0, 0) });
+        if len == 0 {
+            print("io-bench: unexpected end of file\n");
+            exit(1);
+        }
+        done += len;
+    }
+    let cycles = rdtsc() - start;
+    check(unsafe { syscall(CLOSE, fd, 0, 0, 0, 0, 0) });
+    cycles
+}
+
+#[no_mangle]
+extern "C" fn main() {
+    // The user stack is tiny, so the transfer buffer comes from mmap
+    let buf = check(unsafe {
+        syscall(
+            MMAP,
+            0,
+            CHUNK_SIZE,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE | MAP_ANONYMOUS,
+            -1isize as usize,
+            0,
+        )
+    });
+
+    let cycles = transfer(WRITE, O_WRONLY | O_CREAT | O_TRUNC, buf);
+    report("write", cycles);
+    let cycles = transfer(READ, O_RDONLY, buf);
+    report("read", cycles);
+
+    exit(0);
+}
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    unsafe {
+        core::arch::asm!("ud2", options(noreturn));
+    }
+}
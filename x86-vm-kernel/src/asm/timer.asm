@@ -0,0 +1,61 @@
+    .macro SAVE_SCRATCH
+    push    rax
+    push    rcx
+    push    rdx
+    push    rsi
+    push    rdi
+    push    r8
+    push    r9
+    push    r10
+    push    r11
+    .endm
+
+    .macro RESTORE_SCRATCH
+    pop     r11
+    pop     r10
+    pop     r9
+    pop     r8
+    pop     rdi
+    pop     rsi
+    pop     rdx
+    pop     rcx
+    pop     rax
+    .endm
+
+    .text
+    .global timer_entry
+
+timer_entry:
+    # the kernel itself is never preempted, so it keeps its stack
+    test    qword ptr [rsp + 0x08], 3
+    jz      timer_from_kernel
+
+    # The CPU pushed the interrupt frame onto the interrupt stack shared by
+    # all tasks. Move it to the task's kernel stack, so that the task can be
+    # switched out before it returns to user mode.
+    push    rax
+    mov     rax, rsp
+    swapgs
+    mov     rsp, gs:[0]
+    push    qword ptr [rax + 0x28]  # ss
+    push    qword ptr [rax + 0x20]  # rsp
+    push    qword ptr [rax + 0x18]  # rflags
+    push    qword ptr [rax + 0x10]  # cs
+    push    qword ptr [rax + 0x08]  # rip
+    mov     rax, [rax]
+
+    SAVE_SCRATCH
+    mov     edi, 1
+    call    handle_timer
+    RESTORE_SCRATCH
+
+    # load user GS base
+    swapgs
+    iretq
+
+timer_from_kernel:
+    SAVE_SCRATCH
+    xor     edi, edi
+    call    handle_timer
+    RESTORE_SCRATCH
+    iretq
@@ -1,12 +1,16 @@
 use alloc::{string::String, vec::Vec};
+use edge_proto::caller::EdgeCallError;
 use hal::{
     arch::x86_vm::gdt,
     task::UserspaceRegs,
     vm::{AddressSpace, ClonableAddressSpace, UserAddressSpace},
 };
-use x86_64::{PhysAddr, VirtAddr};
+use x86_64::{
+    registers::rflags::{self, RFlags},
+    PhysAddr, VirtAddr,
+};
 
-pub fn do_execve(path: String, argv: Vec<String>, envp: Vec<String>) {
+pub fn do_execve(path: String, argv: Vec<String>, envp: Vec<String>) -> Result<(), EdgeCallError> {
     log::debug!(
         "execve: Replacing current task with {}, argv = {:?}, envp = {:?}",
         path,
@@ -30,12 +34,13 @@ pub fn do_execve(path: String, argv: Vec<String>, envp: Vec<String>) {
                 );
             }
         },
-    );
+    )?;
 
     let userspace_regs = UserspaceRegs {
         ss: gdt::USER_DATA_SEL.0 as usize,
         rsp: exec_data.user_sp,
-        rflags: x86_64::registers::rflags::read_raw() as usize,
+        // Syscalls run with interrupts disabled, but user mode must not
+        rflags: (rflags::read() | RFlags::INTERRUPT_FLAG).bits() as usize,
         cs: gdt::USER_CODE_SEL.0 as usize,
         rip: exec_data.entry,
         ..Default::default()
@@ -44,4 +49,5 @@ pub fn do_execve(path: String, argv: Vec<String>, envp: Vec<String>) {
     hal::task::current()
         .lock()
         .replace(exec_data.mm, &userspace_regs);
+    Ok(())
 }
@@ -41,7 +41,7 @@ impl SyscallFrame {
             r15: self.r15,
             rip: self.rcx, // `syscall` convention
             cs: gdt::USER_CODE_SEL.0 as usize,
-            rflags: self.rflags,
+            rflags: self.r11, // `syscall` convention
             rsp: hal::task::current().lock().tls.foreign_sp,
             ss: gdt::USER_DATA_SEL.0 as usize,
             // TODO: process user GS
@@ -1,16 +1,50 @@
-use hal::dbg_print;
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use hal::arch::x86_vm::gdt;
+use x86_64::{structures::idt::InterruptDescriptorTable, VirtAddr};
 
 use super::pic::{InterruptIndex, PICS};
 
-extern "x86-interrupt" fn timer_int(_stack_frame: InterruptStackFrame) {
-    dbg_print!(".");
-    unsafe {
-        PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
+core::arch::global_asm!(include_str!("../asm/timer.asm"));
+
+extern "C" {
+    fn timer_entry();
+}
+
+/// Called by `timer_entry` with interrupts disabled. A task interrupted in
+/// user mode is charged the tick, and switched out once its time slice has
+/// run out; the kernel itself is never preempted.
+#[no_mangle]
+unsafe extern "C" fn handle_timer(from_user: bool) {
+    PICS.lock()
+        .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
+
+    if from_user {
+        gdt::enter_kernel();
+        if hal::task::tick() {
+            preempt();
+        }
+        gdt::enter_user();
     }
 }
 
+/// The user's x87, MMX and SSE registers, as saved by `fxsave64`.
+#[repr(C, align(16))]
+struct FxArea([u8; 512]);
+
+/// Switch out the current task, whose time slice has run out. Its general
+/// purpose registers are on its kernel stack, but the registers that only
+/// user mode uses would be overwritten by other tasks.
+unsafe fn preempt() {
+    let mut fx_area = FxArea([0; 512]);
+    core::arch::asm!("fxsave64 [{}]", in(reg) fx_area.0.as_mut_ptr());
+    hal::task::yield_to_sched();
+    core::arch::asm!("fxrstor64 [{}]", in(reg) fx_area.0.as_ptr());
+}
+
 pub fn load_idt_entries(idt: &mut InterruptDescriptorTable) {
-    idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_int);
+    // `timer_entry` picks the stack itself, which an `extern "x86-interrupt"`
+    // handler cannot do
+    unsafe {
+        idt[InterruptIndex::Timer.as_usize()]
+            .set_handler_addr(VirtAddr::new(timer_entry as usize as u64));
+    }
 }
@@ -1,8 +1,10 @@
 pub mod devices;
 pub mod idt;
 pub mod pic;
+pub mod pit;
 
 pub fn init() {
     idt::IDT.load();
     pic::init();
+    pit::init();
 }
@@ -0,0 +1,21 @@
+use hal::cfg::TIMER_HZ;
+use x86_64::instructions::port::Port;
+
+/// The frequency of the PIT's input clock.
+const PIT_FREQ: usize = 1_193_182;
+
+const PIT_CHANNEL_0: u16 = 0x40;
+const PIT_COMMAND: u16 = 0x43;
+/// Channel 0, low then high byte of the divisor, rate generator mode.
+const CHANNEL_0_RATE_GENERATOR: u8 = 0b0011_0100;
+
+/// Make the PIT raise the timer interrupt `TIMER_HZ` times per second.
+pub fn init() {
+    let divisor = (PIT_FREQ / TIMER_HZ) as u16;
+    unsafe {
+        Port::new(PIT_COMMAND).write(CHANNEL_0_RATE_GENERATOR);
+        let mut channel_0 = Port::new(PIT_CHANNEL_0);
+        channel_0.write(divisor as u8);
+        channel_0.write((divisor >> 8) as u8);
+    }
+}
@@ -46,6 +46,11 @@ fn start_kernel(boot_info: &'static mut BootInfo) -> ! {
         mem_region.end as usize,
     );
     log::debug!("Edge memory and heap initialized at {:?}", mem_region);
+    hal::edge::set_notification_handler(linux_abi::notify::handle_notification);
+    hal::time::init();
+    linux_abi::fs::init();
+    #[cfg(feature = "netstack")]
+    linux_abi::net::init();
 
     hal::arch::x86_vm::arch_init();
     syscall::init();
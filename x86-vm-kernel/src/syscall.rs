@@ -1,7 +1,7 @@
 use hal::arch::x86_vm::gdt;
 use linux_abi::syscall::tables::TABLE_X86_64 as SYSCALL_TABLE;
 use linux_abi::syscall::SyscallHandler;
-use x86_64::VirtAddr;
+use x86_64::{registers::rflags::RFlags, VirtAddr};
 
 use crate::frame::SyscallFrame;
 
@@ -42,6 +42,9 @@ unsafe extern "C" fn handle_syscall(frame: *mut SyscallFrame) {
         Some(SyscallHandler::Syscall4(f)) => {
             result = f(arg0, arg1, arg2, arg3);
         }
+        Some(SyscallHandler::Syscall5(f)) => {
+            result = f(arg0, arg1, arg2, arg3, arg4);
+        }
         Some(SyscallHandler::Syscall6(f)) => {
             result = f(arg0, arg1, arg2, arg3, arg4, arg5);
         }
@@ -50,12 +53,17 @@ unsafe extern "C" fn handle_syscall(frame: *mut SyscallFrame) {
         }
         Some(SyscallHandler::SyscallExecvePre(f)) => {
             match f(arg0, arg1, arg2) {
-                Ok((path, argv, envp)) => {
-                    crate::exec::do_execve(path, argv, envp);
-                    hal::task::yield_to_sched();
-                    unreachable!()
-                    // no need to update result value
-                }
+                Ok((path, argv, envp)) => match crate::exec::do_execve(path, argv, envp) {
+                    Ok(()) => {
+                        hal::task::yield_to_sched();
+                        unreachable!()
+                        // no need to update result value
+                    }
+                    Err(err) => {
+                        log::warn!("execve: Failed to load the executable: {}", err);
+                        result = -(err.errno() as isize);
+                    }
+                },
                 Err(err) => {
                     // execve failed, return error code
                     result = err;
@@ -84,6 +92,8 @@ pub fn init() {
 
     // configure syscall handler address
     msr::LStar::write(VirtAddr::from_ptr(syscall_entry as *const ()));
+    // disable interrupts in syscalls, as the kernel is never preempted
+    msr::SFMask::write(RFlags::INTERRUPT_FLAG);
 
     // set IA32_EFER.SCE = 1
     unsafe {
@@ -14,7 +14,7 @@ pub fn enter_user_mode() {
     // exec x86-vm-init
     let exec_data = linux_abi::elf::exec_within(
         addr_space,
-        "x86-vm-init",
+        &linux_abi::exec::init_path("x86-vm-init"),
         linux_abi::exec::INIT_ARGV,
         linux_abi::exec::INIT_ENVP,
         elf_loader::arch::X86_64,
@@ -27,7 +27,8 @@ pub fn enter_user_mode() {
                 );
             }
         },
-    );
+    )
+    .expect("failed to load x86-vm-init");
 
     // construct a task
     let userspace_regs = UserspaceRegs {
@@ -42,8 +43,8 @@ pub fn enter_user_mode() {
     let task_future = TaskFuture::new(task);
 
     // enter user mode
-    executor::spawn(task_future);
-    executor::run_until_idle();
+    hal::task::spawn(task_future);
+    hal::task::run_until_idle();
 }
 
 #[no_mangle]
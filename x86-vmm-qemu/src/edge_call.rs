@@ -5,6 +5,7 @@ use std::{
 
 use anyhow::Context;
 use edge_proto::{
+    channel::EdgeMemLayout,
     server::{EdgeStream, SharedMemEdgeStream},
     EdgeCallReq, EdgeCallResp,
 };
@@ -17,6 +18,12 @@ use nix::{
     },
 };
 
+const EDGE_MEM_LAYOUT: EdgeMemLayout = EdgeMemLayout::new(
+    kconfig::EDGE_CHANNELS,
+    kconfig::EDGE_HEADER_SIZE,
+    kconfig::EDGE_BUFFER_SIZE,
+);
+
 pub struct EdgeCallServer {
     sock: UnixListener,
 }
@@ -24,7 +31,7 @@ pub struct EdgeCallServer {
 struct EdgeCallClient {
     socket: UnixStream,
     edge_mem: *mut u8,
-    edge_stream: SharedMemEdgeStream,
+    edge_streams: Vec<SharedMemEdgeStream>,
 }
 
 impl EdgeCallClient {
@@ -55,21 +62,45 @@ impl EdgeCallClient {
 
         nix::unistd::close(ram_fd).context("close shm_fd")?;
 
-        let edge_stream = SharedMemEdgeStream::new(
-            edge_mem,
-            0x1_000,
-            unsafe { edge_mem.add(0x1_000) },
-            kconfig::EDGE_BUFFER_SIZE,
-        );
+        let edge_streams = (0..EDGE_MEM_LAYOUT.channels)
+            .map(|channel| unsafe {
+                SharedMemEdgeStream::for_channel(&EDGE_MEM_LAYOUT, edge_mem, channel)
+            })
+            .collect();
+        unsafe {
+            edge_responder::notify::set_notification_area(
+                EDGE_MEM_LAYOUT.notification_area(edge_mem),
+            );
+        }
+        edge_responder::notify::start_notifier().context("start notifier")?;
         Ok(EdgeCallClient {
             socket,
             edge_mem,
-            edge_stream,
+            edge_streams,
         })
     }
 
-    pub fn edge_stream(&mut self) -> &mut SharedMemEdgeStream {
-        &mut self.edge_stream
+    /// Handle the edge calls of all channels that have been kicked. Returns
+    /// whether the guest has shut down the edge call stream.
+    pub fn serve_kicked_channels(&mut self) -> anyhow::Result<bool> {
+        let mut shutdown = false;
+        for stream in self.edge_streams.iter_mut() {
+            if !stream.take_doorbell() {
+                continue;
+            }
+            let req = stream.read_header().compat().context("read header")?;
+            if req == EdgeCallReq::StreamShutdown {
+                log::info!("Edge call client signaled exit");
+                stream
+                    .write_header(&EdgeCallResp::Ok)
+                    .compat()
+                    .context("write header")?;
+                shutdown = true;
+                continue;
+            }
+            edge_responder::handle_edge_call_req(stream, req).context("handle edge call")?;
+        }
+        Ok(shutdown)
     }
 
     pub fn wait(&mut self) -> anyhow::Result<()> {
@@ -89,7 +120,8 @@ impl EdgeCallClient {
 impl Drop for EdgeCallClient {
     fn drop(&mut self) {
         unsafe {
-            nix::sys::mman::munmap(self.edge_mem.cast(), 0x4_000).unwrap();
+            edge_responder::notify::set_notification_area(std::ptr::null());
+            nix::sys::mman::munmap(self.edge_mem.cast(), kconfig::UTM_SIZE).unwrap();
         }
     }
 }
@@ -112,24 +144,13 @@ impl EdgeCallServer {
                 .context("create edge call client")?;
             loop {
                 client.wait().context("wait for guest")?;
-                let req = client
-                    .edge_stream()
-                    .read_header()
-                    .compat()
-                    .context("read header")?;
-                if req == EdgeCallReq::StreamShutdown {
-                    log::info!("Edge call client signaled exit");
-                    client
-                        .edge_stream()
-                        .write_header(&EdgeCallResp::Ok)
-                        .compat()
-                        .context("write header")?;
-                    client.kick().context("kick guest")?;
+                let shutdown = client
+                    .serve_kicked_channels()
+                    .context("serve edge channels")?;
+                client.kick().context("kick guest")?;
+                if shutdown {
                     break;
                 }
-                edge_responder::handle_edge_call_req(client.edge_stream(), req)
-                    .context("handle edge call")?;
-                client.kick().context("kick guest")?;
             }
         }
         Ok(())